@@ -0,0 +1,168 @@
+/// Winrates split by whether the game was played out or abandoned early (`--abandoned-output`)
+///
+/// A game forfeited or lost to inactivity in its first few turns says more about the player's
+/// connection than the species, so a species that shows up in many of them can have a
+/// misleading winrate. This shows how much each species' winrate depends on those games;
+/// `--exclude-abandoned` leaves them out of the main outputs altogether.
+use crate::filter::{self, BattleFilter};
+use crate::pass;
+use crate::stats::{format_float, Output};
+use crate::table::{self, Block, Table};
+use indexmap::IndexMap;
+use itertools::Itertools;
+use std::io;
+use std::path::PathBuf;
+
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+struct Record {
+    games: u32,
+    wins: u32,
+}
+
+impl Record {
+    fn winrate(&self) -> Option<f64> {
+        if self.games == 0 {
+            None
+        } else {
+            Some(self.wins as f64 / self.games as f64 * 100.0)
+        }
+    }
+}
+
+#[derive(Default)]
+struct AbandonRow {
+    played_out: Record,
+    abandoned: Record,
+}
+
+pub struct AbandonReport {
+    /// Species:row
+    rows: IndexMap<String, AbandonRow>,
+}
+
+impl AbandonReport {
+    pub fn new<'a>(
+        days: impl Iterator<Item = &'a [PathBuf]>,
+        min_elo: u64,
+        filter: &BattleFilter,
+    ) -> io::Result<Self> {
+        let mut report = AbandonReport {
+            rows: IndexMap::new(),
+        };
+        let mut scan = pass::Scan::new(min_elo, filter);
+        for files in days {
+            let battles = scan.day(files, |json| {
+                (filter::is_abandoned_early(json), pass::sides(json))
+            })?;
+            for (abandoned, sides) in battles {
+                for (_, won, species) in sides {
+                    for species in species {
+                        report.add(species, abandoned, won);
+                    }
+                }
+            }
+        }
+        report.sort();
+        Ok(report)
+    }
+
+    fn add(&mut self, species: String, abandoned: bool, won: bool) {
+        let row = self.rows.entry(species).or_default();
+        let record = if abandoned {
+            &mut row.abandoned
+        } else {
+            &mut row.played_out
+        };
+        record.games += 1;
+        record.wins += won as u32;
+    }
+
+    /// Species with the largest share of abandoned games first
+    fn sort(&mut self) {
+        self.rows.sort_by(|a_species, a, b_species, b| {
+            let share = |row: &AbandonRow| {
+                row.abandoned.games as f64 / (row.abandoned.games + row.played_out.games) as f64
+            };
+            share(b)
+                .partial_cmp(&share(a))
+                .unwrap()
+                .then_with(|| a_species.cmp(b_species))
+        });
+    }
+}
+
+impl Output for AbandonReport {
+    fn to_csv(&mut self) -> String {
+        Itertools::intersperse(
+            self.rows.iter().map(|(species, row)| {
+                let mut fields = vec![species.clone()];
+                for record in [row.played_out, row.abandoned] {
+                    fields.push(record.games.to_string());
+                    fields.push(record.winrate().map_or(String::new(), format_float));
+                }
+                fields.join(",")
+            }),
+            String::from("\n"),
+        )
+        .collect()
+    }
+
+    fn to_human_readable(&mut self) -> String {
+        table::blocks_to_text(&self.to_blocks())
+    }
+
+    fn to_blocks(&mut self) -> Vec<Block> {
+        let abandoned_title = format!(
+            "Winrate when abandoned (<= {} turns)",
+            filter::EARLY_ABANDON_TURNS
+        );
+        let mut table = Table::new(&[
+            "Pokemon",
+            "Winrate when played out",
+            "Games",
+            &abandoned_title,
+            "Games",
+        ]);
+        for (species, row) in &self.rows {
+            let mut cells = vec![species.clone()];
+            for record in [row.played_out, row.abandoned] {
+                cells.push(
+                    record
+                        .winrate()
+                        .map_or(String::from("-"), |w| format!("{}%", format_float(w))),
+                );
+                cells.push(record.games.to_string());
+            }
+            table.add_row(cells);
+        }
+        vec![Block::Table(table)]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_abandon_report() {
+        let mut report = AbandonReport {
+            rows: IndexMap::new(),
+        };
+        for (species, abandoned, won) in [
+            ("Blissey", false, true),
+            ("Blissey", false, false),
+            ("Dragapult", true, true),
+            ("Dragapult", false, false),
+            ("Mew", true, false),
+        ] {
+            report.add(species.to_string(), abandoned, won);
+        }
+        report.sort();
+        assert_eq!(
+            report.to_csv(),
+            "Mew,0,,1,0.000000\n\
+             Dragapult,1,0.000000,1,100.000000\n\
+             Blissey,2,50.000000,0,"
+        );
+    }
+}
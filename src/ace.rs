@@ -0,0 +1,217 @@
+/// Team winrates by each team's "ace" (`--ace-output`)
+///
+/// Players often ask whether getting a particular Pokémon wins you the game. That question is
+/// about the team's best member rather than every member, so this picks one ace per team and
+/// gives the winrate of teams by their ace. By default the ace is the team's highest-level
+/// member; with `--ace-species`, it's whichever team member comes first in the given list.
+use crate::filter::BattleFilter;
+use crate::pass;
+use crate::stats::{format_float, Output, Stats};
+use crate::table::{self, Block, Table};
+use indexmap::IndexMap;
+use itertools::Itertools;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Teams whose highest level is shared by several members
+const TIED: &str = "(tied)";
+/// Teams with no member on the `--ace-species` list
+const NO_ACE: &str = "(none)";
+
+/// How a team's ace is chosen
+pub enum AceRule {
+    /// The highest-level member
+    HighestLevel,
+    /// The first member found in this list, in the list's order
+    Species(Vec<String>),
+}
+
+impl AceRule {
+    /// Reads a list of species, one per line, most important first
+    pub fn read_species(path: &Path) -> io::Result<Self> {
+        Ok(AceRule::Species(
+            fs::read_to_string(path)?
+                .lines()
+                .map(str::trim)
+                .filter(|line| !line.is_empty() && !line.starts_with('#'))
+                .map(Stats::normalize_species)
+                .collect(),
+        ))
+    }
+
+    /// The ace of a team given as (species, level)
+    fn ace(&self, team: &[(String, u32)]) -> String {
+        match self {
+            AceRule::HighestLevel => {
+                let max = team.iter().map(|(_, level)| *level).max().unwrap_or(0);
+                let mut highest = team.iter().filter(|(_, level)| *level == max);
+                match (highest.next(), highest.next()) {
+                    (Some((species, _)), None) => species.clone(),
+                    _ => String::from(TIED),
+                }
+            }
+            AceRule::Species(list) => list
+                .iter()
+                .find(|ace| team.iter().any(|(species, _)| species == *ace))
+                .cloned()
+                .unwrap_or_else(|| String::from(NO_ACE)),
+        }
+    }
+}
+
+/// Each side's (won, ace)
+fn aces(json: &str, rule: &AceRule) -> Vec<(bool, String)> {
+    Stats::sides(json)
+        .iter()
+        .zip(Stats::results_by_side(0, json))
+        .filter(|(_, results)| !results.is_empty())
+        .map(|(side, results)| {
+            let team_path = format!("{}team", side);
+            let team = gjson::get(json, &team_path);
+            let team = results
+                .iter()
+                .zip(team.array())
+                .map(|(result, member)| {
+                    let level = member.get("level");
+                    let level = if level.exists() { level.u32() } else { 100 };
+                    (result.species.clone(), level)
+                })
+                .collect::<Vec<_>>();
+            (results[0].won, rule.ace(&team))
+        })
+        .collect()
+}
+
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+struct Record {
+    games: u32,
+    wins: u32,
+}
+
+impl Record {
+    fn winrate(&self) -> f64 {
+        self.wins as f64 / self.games as f64 * 100.0
+    }
+
+    fn deviations(&self) -> f64 {
+        (self.winrate() - 50.0) * (self.games as f64).sqrt() / 50.0
+    }
+}
+
+pub struct AceReport {
+    /// Ace:record
+    rows: IndexMap<String, Record>,
+}
+
+impl AceReport {
+    pub fn new<'a>(
+        days: impl Iterator<Item = &'a [PathBuf]>,
+        min_elo: u64,
+        filter: &BattleFilter,
+        rule: &AceRule,
+    ) -> io::Result<Self> {
+        let mut report = AceReport {
+            rows: IndexMap::new(),
+        };
+        let mut scan = pass::Scan::new(min_elo, filter);
+        for files in days {
+            let battles = scan.day(files, |json| aces(json, rule))?;
+            for (won, ace) in battles.into_iter().flatten() {
+                report.add(ace, won);
+            }
+        }
+        report.sort();
+        Ok(report)
+    }
+
+    fn add(&mut self, ace: String, won: bool) {
+        let record = self.rows.entry(ace).or_default();
+        record.games += 1;
+        record.wins += won as u32;
+    }
+
+    /// Most deviations above 50% first
+    fn sort(&mut self) {
+        self.rows.sort_by(|a_ace, a, b_ace, b| {
+            b.deviations()
+                .partial_cmp(&a.deviations())
+                .unwrap()
+                .then_with(|| a_ace.cmp(b_ace))
+        });
+    }
+}
+
+impl Output for AceReport {
+    fn to_csv(&mut self) -> String {
+        Itertools::intersperse(
+            self.rows.iter().map(|(ace, record)| {
+                [
+                    ace.clone(),
+                    record.games.to_string(),
+                    record.wins.to_string(),
+                    format_float(record.winrate()),
+                    format_float(record.deviations()),
+                ]
+                .join(",")
+            }),
+            String::from("\n"),
+        )
+        .collect()
+    }
+
+    fn to_human_readable(&mut self) -> String {
+        table::blocks_to_text(&self.to_blocks())
+    }
+
+    fn to_blocks(&mut self) -> Vec<Block> {
+        let mut table = Table::new(&["Ace", "Deviations", "Team winrate", "Games", "Wins"]);
+        for (ace, record) in &self.rows {
+            table.add_row(vec![
+                ace.clone(),
+                format_float(record.deviations()),
+                format!("{}%", format_float(record.winrate())),
+                record.games.to_string(),
+                record.wins.to_string(),
+            ]);
+        }
+        vec![Block::Table(table)]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_aces() {
+        let json = r#"{"winner":"A","p1":"A","p2":"B","p1team":[{"species":"Mew","level":84},{"species":"Ditto","level":100}],"p2team":[{"species":"Blissey","level":88},{"species":"Pikachu-Alola","level":88}]}"#;
+        assert_eq!(
+            aces(json, &AceRule::HighestLevel),
+            vec![(true, String::from("Ditto")), (false, String::from(TIED))]
+        );
+
+        let rule = AceRule::Species(vec![String::from("Pikachu"), String::from("Mew")]);
+        assert_eq!(
+            aces(json, &rule),
+            vec![
+                (true, String::from("Mew")),
+                (false, String::from("Pikachu"))
+            ]
+        );
+        let rule = AceRule::Species(vec![String::from("Kingambit")]);
+        assert_eq!(aces(json, &rule)[0].1, NO_ACE);
+
+        let mut report = AceReport {
+            rows: IndexMap::new(),
+        };
+        for (won, ace) in aces(json, &AceRule::HighestLevel) {
+            report.add(ace, won);
+        }
+        report.sort();
+        assert_eq!(
+            report.to_csv(),
+            "Ditto,1,1,100.000000,1.000000\n(tied),1,0,0.000000,-1.000000"
+        );
+    }
+}
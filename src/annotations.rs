@@ -0,0 +1,136 @@
+/// Splitting species' stats at known changes (`--annotations`)
+///
+/// When a species' level or sets change partway through the input, its overall winrate mixes
+/// two different Pokémon. An annotations file lists those changes, one `species,day,label` line
+/// each (e.g. `Mew,2024-03-01,level 84 to 82`), and the main outputs then count the species
+/// separately before the first change and after each one, as e.g. `Mew (before 2024-03-01)`
+/// and `Mew (since 2024-03-01: level 84 to 82)`. Reports that take a second pass aren't split.
+use crate::stats::{parse_day, GameResult, Stats};
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+#[derive(Clone, Debug, PartialEq)]
+struct Annotation {
+    /// Start of the day, in seconds since the Unix epoch
+    start: u64,
+    day: String,
+    label: String,
+}
+
+#[derive(Debug, Default, PartialEq)]
+pub struct Annotations {
+    /// Species:its annotations, earliest first
+    by_species: HashMap<String, Vec<Annotation>>,
+}
+
+impl Annotations {
+    /// Reads `species,day,label` lines; blank lines and lines starting with `#` are skipped
+    pub fn read(path: &Path) -> io::Result<Self> {
+        Annotations::parse(&fs::read_to_string(path)?).map_err(|e| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{}: {}", path.display(), e),
+            )
+        })
+    }
+
+    fn parse(file: &str) -> Result<Self, String> {
+        let mut annotations = Annotations::default();
+        for (idx, line) in file.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let mut fields = line.splitn(3, ',').map(str::trim);
+            let (species, day, label) = match (fields.next(), fields.next(), fields.next()) {
+                (Some(species), Some(day), Some(label)) => (species, day, label),
+                _ => return Err(format!("line {} isn't species,day,label", idx + 1)),
+            };
+            let start = parse_day(day)
+                .ok_or_else(|| format!("line {} has an invalid day '{}'", idx + 1, day))?;
+            annotations
+                .by_species
+                .entry(Stats::normalize_species(species))
+                .or_default()
+                .push(Annotation {
+                    start,
+                    day: day.to_string(),
+                    label: label.to_string(),
+                });
+        }
+        for list in annotations.by_species.values_mut() {
+            list.sort_by_key(|annotation| annotation.start);
+        }
+        Ok(annotations)
+    }
+
+    /// The name a species is counted under on `day`, if it has annotations and the day is a
+    /// YYYY-MM-DD directory
+    fn segment(&self, species: &str, day: &str) -> Option<String> {
+        let list = self.by_species.get(species)?;
+        let start = parse_day(day)?;
+        Some(
+            match list
+                .iter()
+                .rev()
+                .find(|annotation| annotation.start <= start)
+            {
+                Some(annotation) => {
+                    format!(
+                        "{} (since {}: {})",
+                        species, annotation.day, annotation.label
+                    )
+                }
+                None => format!("{} (before {})", species, list[0].day),
+            },
+        )
+    }
+
+    /// Renames annotated species in the results of a battle from the day directory `day`
+    pub fn apply(&self, day: &str, results: &mut [GameResult]) {
+        for result in results {
+            if let Some(segment) = self.segment(&result.species, day) {
+                result.species = segment;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_annotations() {
+        let annotations = Annotations::parse(
+            "# species,day,label\n\
+             Mew,2024-03-01,level 84 to 82\n\
+             Mew,2024-02-01,new set\n\
+             Toxtricity-Low-Key,2024-02-15,level change\n",
+        )
+        .unwrap();
+        assert_eq!(
+            annotations.segment("Mew", "2024-01-31"),
+            Some(String::from("Mew (before 2024-02-01)"))
+        );
+        assert_eq!(
+            annotations.segment("Mew", "2024-02-01"),
+            Some(String::from("Mew (since 2024-02-01: new set)"))
+        );
+        assert_eq!(
+            annotations.segment("Mew", "2024-03-10"),
+            Some(String::from("Mew (since 2024-03-01: level 84 to 82)"))
+        );
+        assert_eq!(
+            annotations.segment("Toxtricity", "2024-02-20"),
+            Some(String::from("Toxtricity (since 2024-02-15: level change)"))
+        );
+        assert_eq!(annotations.segment("Mew", "latest"), None);
+        assert_eq!(annotations.segment("Ditto", "2024-03-10"), None);
+
+        assert!(Annotations::parse("Mew,2024-03-01").is_err());
+        assert!(Annotations::parse("Mew,March 1st,level change").is_err());
+    }
+}
@@ -0,0 +1,253 @@
+/// Experimental: winrates of team archetypes found by clustering teams (`--archetype-output`)
+///
+/// Teams are clustered with k-modes over their species: each cluster has a prototype of the
+/// species on at least half its teams (or just its most common species, if none are), and every
+/// team joins the cluster whose prototype it shares the most species with. Random Battles teams
+/// are random, so clusters are loose; this is a first look at which team shapes do well rather
+/// than a definitive grouping.
+use crate::filter::BattleFilter;
+use crate::pass;
+use crate::stats::{format_float, Output};
+use crate::table::{self, Block, Table};
+use indexmap::IndexSet;
+use itertools::Itertools;
+use std::io;
+use std::path::PathBuf;
+
+/// Most species in a prototype
+const PROTOTYPE_SIZE: usize = 6;
+/// Clustering stops after this many rounds even if teams are still moving between clusters
+const MAX_ITERATIONS: usize = 20;
+
+/// A team, as sorted species indices, and whether it won
+type Team = (Vec<usize>, bool);
+
+/// Clusters `teams` (with species indices below `num_species`) into at most `k` clusters,
+/// returning each team's cluster and each cluster's prototype
+fn cluster(teams: &[Team], num_species: usize, k: usize) -> (Vec<usize>, Vec<Vec<usize>>) {
+    // start from k distinct teams spread evenly through the input, so runs are reproducible
+    let mut prototypes: Vec<Vec<usize>> = vec![];
+    for (team, _) in teams.iter().step_by((teams.len() / k.max(1)).max(1)) {
+        if prototypes.len() < k && !prototypes.contains(team) {
+            prototypes.push(team.clone());
+        }
+    }
+
+    let mut assignments = vec![usize::MAX; teams.len()];
+    for _ in 0..MAX_ITERATIONS {
+        let masks = prototypes
+            .iter()
+            .map(|prototype| {
+                let mut mask = vec![false; num_species];
+                for species in prototype {
+                    mask[*species] = true;
+                }
+                mask
+            })
+            .collect::<Vec<_>>();
+
+        let mut changed = false;
+        for ((team, _), assignment) in teams.iter().zip(assignments.iter_mut()) {
+            let best = masks
+                .iter()
+                .enumerate()
+                .max_by_key(|(idx, mask)| {
+                    let shared = team.iter().filter(|species| mask[**species]).count();
+                    // prefer earlier clusters on ties
+                    (shared, std::cmp::Reverse(*idx))
+                })
+                .map_or(0, |(idx, _)| idx);
+            if *assignment != best {
+                *assignment = best;
+                changed = true;
+            }
+        }
+        if !changed {
+            break;
+        }
+
+        // each prototype becomes its cluster's mode; empty clusters keep theirs
+        for (idx, prototype) in prototypes.iter_mut().enumerate() {
+            let mut counts = vec![0u32; num_species];
+            let mut members = 0;
+            for ((team, _), _) in teams
+                .iter()
+                .zip(&assignments)
+                .filter(|(_, cluster)| **cluster == idx)
+            {
+                members += 1;
+                for species in team {
+                    counts[*species] += 1;
+                }
+            }
+            if members == 0 {
+                continue;
+            }
+            let by_count = (0..num_species)
+                .sorted_by(|a, b| counts[*b].cmp(&counts[*a]).then(a.cmp(b)))
+                .collect::<Vec<_>>();
+            let majority = by_count
+                .iter()
+                .take_while(|species| counts[**species] * 2 >= members)
+                .count();
+            let mut mode = by_count[..majority.clamp(1, PROTOTYPE_SIZE)].to_vec();
+            mode.sort_unstable();
+            *prototype = mode;
+        }
+    }
+
+    (assignments, prototypes)
+}
+
+struct ArchetypeRow {
+    prototype: Vec<String>,
+    teams: u32,
+    wins: u32,
+}
+
+impl ArchetypeRow {
+    fn winrate(&self) -> f64 {
+        self.wins as f64 / self.teams as f64 * 100.0
+    }
+
+    /// Standard deviations from 50%, as for species
+    fn deviations(&self) -> f64 {
+        (self.winrate() - 50.0) * (self.teams as f64).sqrt() / 50.0
+    }
+}
+
+pub struct ArchetypeReport {
+    rows: Vec<ArchetypeRow>,
+}
+
+impl ArchetypeReport {
+    /// Reads every team in the input and clusters them into at most `k` archetypes
+    pub fn new<'a>(
+        days: impl Iterator<Item = &'a [PathBuf]>,
+        min_elo: u64,
+        filter: &BattleFilter,
+        k: usize,
+    ) -> io::Result<Self> {
+        let mut species = IndexSet::new();
+        let mut teams = vec![];
+        let mut scan = pass::Scan::new(min_elo, filter);
+        for files in days {
+            for sides in scan.day(files, pass::sides)? {
+                for (_, won, team) in sides {
+                    let mut team = team
+                        .into_iter()
+                        .map(|s| species.insert_full(s).0)
+                        .collect::<Vec<_>>();
+                    team.sort_unstable();
+                    team.dedup();
+                    teams.push((team, won));
+                }
+            }
+        }
+        Ok(Self::from_teams(&teams, &species, k))
+    }
+
+    fn from_teams(teams: &[Team], species: &IndexSet<String>, k: usize) -> Self {
+        let (assignments, prototypes) = cluster(teams, species.len(), k);
+        let mut rows = prototypes
+            .iter()
+            .map(|prototype| ArchetypeRow {
+                prototype: prototype.iter().map(|s| species[*s].clone()).collect(),
+                teams: 0,
+                wins: 0,
+            })
+            .collect::<Vec<_>>();
+        for ((_, won), cluster) in teams.iter().zip(assignments) {
+            rows[cluster].teams += 1;
+            rows[cluster].wins += *won as u32;
+        }
+
+        rows.retain(|row| row.teams > 0);
+        rows.sort_by(|a, b| {
+            b.deviations()
+                .partial_cmp(&a.deviations())
+                .unwrap()
+                .then_with(|| a.prototype.cmp(&b.prototype))
+        });
+        Self { rows }
+    }
+}
+
+impl Output for ArchetypeReport {
+    fn to_csv(&mut self) -> String {
+        Itertools::intersperse(
+            self.rows.iter().map(|row| {
+                [
+                    row.prototype.join(" / "),
+                    row.teams.to_string(),
+                    row.wins.to_string(),
+                    format_float(row.winrate()),
+                    format_float(row.deviations()),
+                ]
+                .join(",")
+            }),
+            String::from("\n"),
+        )
+        .collect()
+    }
+
+    fn to_human_readable(&mut self) -> String {
+        table::blocks_to_text(&self.to_blocks())
+    }
+
+    fn to_blocks(&mut self) -> Vec<Block> {
+        let mut table = Table::new(&["Archetype", "Deviations", "Winrate", "Teams", "Wins"]);
+        for row in &self.rows {
+            table.add_row(vec![
+                row.prototype.join(", "),
+                format_float(row.deviations()),
+                format!("{}%", format_float(row.winrate())),
+                row.teams.to_string(),
+                row.wins.to_string(),
+            ]);
+        }
+        vec![Block::Table(table)]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_archetypes() {
+        let species = [
+            "Blissey",
+            "Chansey",
+            "Toxapex",
+            "Dragapult",
+            "Weavile",
+            "Zeraora",
+        ]
+        .iter()
+        .map(|s| s.to_string())
+        .collect::<IndexSet<_>>();
+        // defensive teams (0-2) mostly lose; offensive teams (3-5) mostly win
+        let teams = vec![
+            (vec![0, 1, 2], false),
+            (vec![3, 4, 5], true),
+            (vec![0, 1, 2], false),
+            (vec![3, 4, 5], true),
+            (vec![0, 1, 3], true),
+            (vec![2, 4, 5], false),
+        ];
+
+        assert_eq!(
+            ArchetypeReport::from_teams(&teams, &species, 2).to_csv(),
+            "Dragapult / Weavile / Zeraora,3,2,66.666667,0.577350\n\
+             Blissey / Chansey / Toxapex,3,1,33.333333,-0.577350"
+        );
+        // there can't be more clusters than distinct teams
+        assert_eq!(
+            ArchetypeReport::from_teams(&teams[..2], &species, 5)
+                .rows
+                .len(),
+            2
+        );
+    }
+}
@@ -0,0 +1,280 @@
+/// A compact binary stats file with an index, for fast lookups (`--artifact-output`, `lookup`)
+///
+/// Looking up one species in a CSV means reading the whole file. An artifact instead starts
+/// with a fixed-size header and a species index sorted by name, so `lookup` binary searches
+/// the index with a handful of small reads and then reads just that species' sections.
+///
+/// Layout (all integers little-endian):
+/// - header (`HEADER_SIZE` bytes): magic, version, species count, log analysis count, offset
+///   of the names section, and the offset and length of the comma-separated analysis names
+///   within it
+/// - index: per species, sorted by name, the offset and length of its name within the names
+///   section
+/// - records: per species, in index order, `RECORD_SIZE` bytes of games, wins, long games,
+///   long wins, rated games, rating sum, weighted games, and weighted wins
+/// - log counts: per species, in index order, a u32 count for each log analysis
+/// - names
+use crate::log::LogAnalysis;
+use crate::stats::{format_float, FinalStats, PokemonStats, Stats};
+use itertools::Itertools;
+use std::convert::TryInto;
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+const MAGIC: &[u8; 4] = b"RBWA";
+const VERSION: u32 = 1;
+const HEADER_SIZE: u64 = 32;
+const INDEX_ENTRY_SIZE: u64 = 8;
+const RECORD_SIZE: u64 = 5 * 4 + 3 * 8;
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
+
+/// Writes `stats` as an artifact
+pub fn write(stats: &Stats, out: &mut impl Write) -> io::Result<()> {
+    let mut species = stats.records().collect::<Vec<_>>();
+    species.sort_by(|a, b| a.0.cmp(b.0));
+    let analyses = stats.log_stats.analyses.iter().join(",");
+
+    let count = species.len() as u64;
+    let analysis_count = stats.log_stats.analyses.len() as u64;
+    let names_offset =
+        HEADER_SIZE + count * (INDEX_ENTRY_SIZE + RECORD_SIZE) + count * analysis_count * 4;
+
+    let mut names = analyses.clone().into_bytes();
+    let mut index = vec![];
+    for (name, _) in &species {
+        index.extend((names.len() as u32).to_le_bytes());
+        index.extend((name.len() as u32).to_le_bytes());
+        names.extend(name.as_bytes());
+    }
+
+    out.write_all(MAGIC)?;
+    out.write_all(&VERSION.to_le_bytes())?;
+    out.write_all(&(count as u32).to_le_bytes())?;
+    out.write_all(&(analysis_count as u32).to_le_bytes())?;
+    out.write_all(&names_offset.to_le_bytes())?;
+    out.write_all(&0u32.to_le_bytes())?;
+    out.write_all(&(analyses.len() as u32).to_le_bytes())?;
+    out.write_all(&index)?;
+    for (_, s) in &species {
+        for n in [s.games, s.wins, s.long_games, s.long_wins, s.rated_games] {
+            out.write_all(&n.to_le_bytes())?;
+        }
+        for n in [s.rating_sum, s.weighted_games, s.weighted_wins] {
+            out.write_all(&n.to_le_bytes())?;
+        }
+    }
+    for (name, _) in &species {
+        let counts = stats.log_stats.counts.get(*name);
+        for idx in 0..analysis_count as usize {
+            let count = counts.and_then(|c| c.get(idx)).copied().unwrap_or(0);
+            out.write_all(&count.to_le_bytes())?;
+        }
+    }
+    out.write_all(&names)
+}
+
+/// One species' entry in an artifact
+#[derive(Debug)]
+pub struct Entry {
+    pub species: String,
+    stats: PokemonStats,
+    /// Counts for each log analysis in the artifact
+    log_counts: Vec<(LogAnalysis, u32)>,
+}
+
+impl Entry {
+    pub fn to_human_readable(&self) -> String {
+        let FinalStats {
+            winrate,
+            deviations,
+        } = self.stats.final_stats();
+        let mut lines = vec![
+            format!("Pokemon: {}", self.species),
+            format!("Games: {}", self.stats.games),
+            format!("Wins: {}", self.stats.wins),
+            format!("Winrate: {}%", format_float(winrate)),
+            format!("Deviations: {}", format_float(deviations)),
+        ];
+        for (analysis, count) in &self.log_counts {
+            lines.push(format!("{}: {}", analysis, count));
+        }
+        lines.join("\n") + "\n"
+    }
+}
+
+/// An artifact opened for lookups; only the header is read up front
+pub struct Artifact {
+    file: File,
+    count: u64,
+    analyses: Vec<LogAnalysis>,
+    names_offset: u64,
+}
+
+impl Artifact {
+    pub fn open(path: &Path) -> io::Result<Self> {
+        let mut file = File::open(path)?;
+        let mut header = [0u8; HEADER_SIZE as usize];
+        file.read_exact(&mut header)?;
+        if &header[0..4] != MAGIC {
+            return Err(invalid("not a stats artifact"));
+        }
+        let u32_at = |at: usize| u32::from_le_bytes(header[at..at + 4].try_into().unwrap());
+        if u32_at(4) != VERSION {
+            return Err(invalid("unsupported stats artifact version"));
+        }
+        let mut artifact = Artifact {
+            file,
+            count: u32_at(8) as u64,
+            analyses: vec![],
+            names_offset: u64::from_le_bytes(header[16..24].try_into().unwrap()),
+        };
+        let analyses = artifact.read_name(u32_at(24), u32_at(28))?;
+        if u32_at(12) > 0 {
+            artifact.analyses = analyses
+                .split(',')
+                .map(|a| a.parse::<LogAnalysis>())
+                .collect::<Result<_, _>>()
+                .map_err(|e| invalid(&e))?;
+        }
+        Ok(artifact)
+    }
+
+    fn read_at(&mut self, offset: u64, buf: &mut [u8]) -> io::Result<()> {
+        self.file.seek(SeekFrom::Start(offset))?;
+        self.file.read_exact(buf)
+    }
+
+    fn read_name(&mut self, offset: u32, len: u32) -> io::Result<String> {
+        let mut name = vec![0u8; len as usize];
+        self.read_at(self.names_offset + offset as u64, &mut name)?;
+        String::from_utf8(name).map_err(|_| invalid("species name isn't UTF-8"))
+    }
+
+    fn name_at(&mut self, idx: u64) -> io::Result<String> {
+        let mut entry = [0u8; INDEX_ENTRY_SIZE as usize];
+        self.read_at(HEADER_SIZE + idx * INDEX_ENTRY_SIZE, &mut entry)?;
+        let offset = u32::from_le_bytes(entry[0..4].try_into().unwrap());
+        let len = u32::from_le_bytes(entry[4..8].try_into().unwrap());
+        self.read_name(offset, len)
+    }
+
+    /// Looks up a species by its exact (normalized) name
+    pub fn lookup(&mut self, species: &str) -> io::Result<Option<Entry>> {
+        let (mut low, mut high) = (0, self.count);
+        while low < high {
+            let mid = (low + high) / 2;
+            match self.name_at(mid)?.as_str().cmp(species) {
+                std::cmp::Ordering::Less => low = mid + 1,
+                std::cmp::Ordering::Greater => high = mid,
+                std::cmp::Ordering::Equal => return self.entry_at(mid).map(Some),
+            }
+        }
+        Ok(None)
+    }
+
+    fn entry_at(&mut self, idx: u64) -> io::Result<Entry> {
+        let records_offset = HEADER_SIZE + self.count * INDEX_ENTRY_SIZE;
+        let mut record = [0u8; RECORD_SIZE as usize];
+        self.read_at(records_offset + idx * RECORD_SIZE, &mut record)?;
+        let u32_at = |at: usize| u32::from_le_bytes(record[at..at + 4].try_into().unwrap());
+        let f64_at = |at: usize| f64::from_le_bytes(record[at..at + 8].try_into().unwrap());
+        let stats = PokemonStats {
+            games: u32_at(0),
+            wins: u32_at(4),
+            long_games: u32_at(8),
+            long_wins: u32_at(12),
+            rated_games: u32_at(16),
+            rating_sum: f64_at(20),
+            weighted_games: f64_at(28),
+            weighted_wins: f64_at(36),
+        };
+
+        let analysis_count = self.analyses.len() as u64;
+        let mut counts = vec![0u8; (analysis_count * 4) as usize];
+        let counts_offset = records_offset + self.count * RECORD_SIZE;
+        self.read_at(counts_offset + idx * analysis_count * 4, &mut counts)?;
+        let log_counts = self
+            .analyses
+            .iter()
+            .zip(counts.chunks(4))
+            .map(|(a, c)| (*a, u32::from_le_bytes(c.try_into().unwrap())))
+            .collect();
+
+        Ok(Entry {
+            species: self.name_at(idx)?,
+            stats,
+            log_counts,
+        })
+    }
+}
+
+/// Prints a species' entry from the artifact at `path`, for the `lookup` subcommand
+pub fn print_lookup(path: &Path, species: &str) -> io::Result<()> {
+    let species = Stats::normalize_species(species);
+    match Artifact::open(path)?.lookup(&species)? {
+        Some(entry) => print!("{}", entry.to_human_readable()),
+        None => eprintln!("{} isn't in {}", species, path.display()),
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::log::LogStats;
+    use crate::stats::GameResult;
+
+    #[test]
+    fn test_artifact_roundtrip() {
+        let mut stats = Stats::new();
+        stats.log_stats = LogStats::new(&[LogAnalysis::Leads, LogAnalysis::KOs]);
+        for (species, won) in [
+            ("Mew", true),
+            ("Ditto", false),
+            ("Mew", false),
+            ("Blissey", true),
+        ] {
+            stats.add_game_results(vec![GameResult {
+                species: species.to_string(),
+                won,
+                turns: 30,
+                rating: Some(1500.0),
+            }]);
+        }
+        stats
+            .log_stats
+            .counts
+            .insert(String::from("Mew"), vec![2, 5]);
+
+        let path = std::env::temp_dir().join(format!(
+            "randbats-winrates-artifact-test-{}",
+            std::process::id()
+        ));
+        let mut file = File::create(&path).unwrap();
+        write(&stats, &mut file).unwrap();
+        drop(file);
+
+        let mut artifact = Artifact::open(&path).unwrap();
+        let mew = artifact.lookup("Mew").unwrap().unwrap();
+        assert_eq!(
+            mew.to_human_readable(),
+            "Pokemon: Mew\nGames: 2\nWins: 1\nWinrate: 50.000000%\nDeviations: 0.000000\n\
+             leads: 2\nkos: 5\n"
+        );
+        assert_eq!(mew.stats.rating_sum, 3000.0);
+        assert_eq!(
+            artifact.lookup("Ditto").unwrap().unwrap().log_counts[0].1,
+            0
+        );
+        assert_eq!(
+            artifact.lookup("Blissey").unwrap().unwrap().species,
+            "Blissey"
+        );
+        assert!(artifact.lookup("Pikachu").unwrap().is_none());
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,365 @@
+/// Balance suggestions for set maintainers (`--balance-output`)
+///
+/// Species that are far from a 50% winrate, with enough games to be sure of it, are listed
+/// with a concrete suggestion: when every set does badly (or well), the species' level is the
+/// problem; otherwise, it's the worst (or best) set. A set here is a distinct combination of
+/// item, ability, and moves, leaving out whichever of those the battle's generation lacks.
+use crate::filter::BattleFilter;
+use crate::pass;
+use crate::stats::{format_float, Output};
+use crate::table::{self, Block, Table};
+use indexmap::IndexMap;
+use itertools::Itertools;
+use std::io;
+use std::path::PathBuf;
+
+/// Sets need this fraction of the minimum games for a species before they're judged
+const SET_GAMES_FRACTION: u32 = 10;
+
+/// Thresholds for suggesting a change
+#[derive(Copy, Clone, Debug)]
+pub struct Thresholds {
+    /// Species with fewer games than this aren't judged
+    pub min_games: u32,
+    /// Species at least this many standard deviations from 50% get a suggestion
+    pub deviations: f64,
+}
+
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+struct Record {
+    games: u32,
+    wins: u32,
+}
+
+impl Record {
+    fn add(&mut self, won: bool) {
+        self.games += 1;
+        self.wins += won as u32;
+    }
+
+    fn winrate(&self) -> f64 {
+        self.wins as f64 / self.games as f64 * 100.0
+    }
+
+    fn deviations(&self) -> f64 {
+        (self.winrate() - 50.0) * (self.games as f64).sqrt() / 50.0
+    }
+}
+
+#[derive(Debug, Default)]
+struct SpeciesRecord {
+    overall: Record,
+    /// Level:games at it
+    levels: IndexMap<u32, u32>,
+    /// Set:record
+    sets: IndexMap<String, Record>,
+}
+
+/// Items were added in generation 2, and abilities in generation 3
+const FIRST_ITEM_GEN: u32 = 2;
+const FIRST_ABILITY_GEN: u32 = 3;
+
+/// A team member's level, if known, and its set: its item and ability where its generation
+/// has them, then its moves. In unknown generations, whichever fields are present are used.
+fn level_and_set(member: &gjson::Value, generation: Option<u32>) -> (Option<u32>, String) {
+    let level = member.get("level");
+    let mut set = vec![];
+    for (key, first_gen) in [("item", FIRST_ITEM_GEN), ("ability", FIRST_ABILITY_GEN)] {
+        let value = member.get(key);
+        match generation {
+            Some(gen) if gen < first_gen => {}
+            // a set without an item (or ability) is its own set, not a malformed one
+            Some(_) if value.str().is_empty() => set.push(format!("no {}", key)),
+            _ if !value.str().is_empty() => set.push(value.str().to_string()),
+            _ => {}
+        }
+    }
+    set.push(
+        member
+            .get("moves")
+            .array()
+            .iter()
+            .map(|m| m.str().to_string())
+            .sorted()
+            .join("/"),
+    );
+    (
+        if level.exists() {
+            Some(level.u32())
+        } else {
+            None
+        },
+        set.join(" | "),
+    )
+}
+
+struct Suggestion {
+    species: String,
+    level: Option<u32>,
+    record: Record,
+    text: String,
+}
+
+pub struct BalanceReport {
+    suggestions: Vec<Suggestion>,
+}
+
+impl BalanceReport {
+    pub fn new<'a>(
+        days: impl Iterator<Item = &'a [PathBuf]>,
+        min_elo: u64,
+        filter: &BattleFilter,
+        thresholds: Thresholds,
+    ) -> io::Result<Self> {
+        let mut species: IndexMap<String, SpeciesRecord> = IndexMap::new();
+        let mut scan = pass::Scan::new(min_elo, filter);
+        for files in days {
+            let battles = scan.day(files, |json| {
+                let generation = pass::generation(json);
+                pass::team_members(json, filter, |member| level_and_set(member, generation))
+            })?;
+            for battle in battles {
+                for (name, won, (level, moveset)) in battle {
+                    let record = species.entry(name).or_default();
+                    record.overall.add(won);
+                    if let Some(level) = level {
+                        *record.levels.entry(level).or_insert(0) += 1;
+                    }
+                    record.sets.entry(moveset).or_default().add(won);
+                }
+            }
+        }
+        Ok(Self::from_records(species, thresholds))
+    }
+
+    fn from_records(species: IndexMap<String, SpeciesRecord>, thresholds: Thresholds) -> Self {
+        let min_set_games = (thresholds.min_games / SET_GAMES_FRACTION).max(1);
+        let mut suggestions = species
+            .into_iter()
+            .filter_map(|(species, record)| {
+                let deviations = record.overall.deviations();
+                if record.overall.games < thresholds.min_games
+                    || deviations.abs() < thresholds.deviations
+                {
+                    return None;
+                }
+                let underperforms = deviations < 0.0;
+                // the most common level, in case it changed during the period
+                let level = record
+                    .levels
+                    .iter()
+                    .max_by_key(|(level, games)| (**games, std::cmp::Reverse(**level)))
+                    .map(|(level, _)| *level);
+
+                let judged_sets = record
+                    .sets
+                    .iter()
+                    .filter(|(_, set)| set.games >= min_set_games)
+                    .collect::<Vec<_>>();
+                let all_sets_agree = judged_sets
+                    .iter()
+                    .all(|(_, set)| (set.winrate() < 50.0) == underperforms);
+                let (verb, level_change) = if underperforms {
+                    ("underperforms", "+1")
+                } else {
+                    ("overperforms", "-1")
+                };
+                let text = if judged_sets.len() <= 1 || all_sets_agree {
+                    let scope = if judged_sets.len() > 1 {
+                        " across all sets"
+                    } else {
+                        ""
+                    };
+                    format!("{}{}; consider {} level", verb, scope, level_change)
+                } else {
+                    // the set furthest in the same direction as the species
+                    let (moveset, set) = judged_sets
+                        .iter()
+                        .min_by(|a, b| {
+                            let (a, b) = (a.1.winrate(), b.1.winrate());
+                            let ordering = a.partial_cmp(&b).unwrap();
+                            if underperforms {
+                                ordering
+                            } else {
+                                ordering.reverse()
+                            }
+                        })
+                        .unwrap();
+                    format!(
+                        "{} mainly with {} ({}% in {} games); consider changing that set",
+                        verb,
+                        moveset,
+                        format_float(set.winrate()),
+                        set.games
+                    )
+                };
+
+                Some(Suggestion {
+                    species,
+                    level,
+                    record: record.overall,
+                    text,
+                })
+            })
+            .collect::<Vec<_>>();
+
+        suggestions.sort_by(|a, b| {
+            b.record
+                .deviations()
+                .abs()
+                .partial_cmp(&a.record.deviations().abs())
+                .unwrap()
+                .then_with(|| a.species.cmp(&b.species))
+        });
+        Self { suggestions }
+    }
+}
+
+impl Suggestion {
+    fn level(&self) -> String {
+        self.level.map_or(String::from("?"), |l| l.to_string())
+    }
+}
+
+impl Output for BalanceReport {
+    fn to_csv(&mut self) -> String {
+        Itertools::intersperse(
+            self.suggestions.iter().map(|s| {
+                [
+                    s.species.clone(),
+                    s.level(),
+                    format_float(s.record.winrate()),
+                    s.record.games.to_string(),
+                    format_float(s.record.deviations()),
+                    s.text.clone(),
+                ]
+                .join(",")
+            }),
+            String::from("\n"),
+        )
+        .collect()
+    }
+
+    fn to_human_readable(&mut self) -> String {
+        table::blocks_to_text(&self.to_blocks())
+    }
+
+    fn to_blocks(&mut self) -> Vec<Block> {
+        let mut table = Table::new(&[
+            "Pokemon",
+            "Level",
+            "Winrate",
+            "Games",
+            "Deviations",
+            "Suggestion",
+        ]);
+        for s in &self.suggestions {
+            table.add_row(vec![
+                s.species.clone(),
+                s.level(),
+                format!("{}%", format_float(s.record.winrate())),
+                s.record.games.to_string(),
+                format_float(s.record.deviations()),
+                s.text.clone(),
+            ]);
+        }
+        vec![Block::Table(table)]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn species_record(level: u32, sets: &[(&str, u32, u32)]) -> SpeciesRecord {
+        let mut record = SpeciesRecord::default();
+        for (moveset, games, wins) in sets {
+            record.overall.games += games;
+            record.overall.wins += wins;
+            *record.levels.entry(level).or_insert(0) += games;
+            record.sets.insert(
+                moveset.to_string(),
+                Record {
+                    games: *games,
+                    wins: *wins,
+                },
+            );
+        }
+        record
+    }
+
+    #[test]
+    fn test_sets_by_generation() {
+        fn member(json: &str) -> gjson::Value<'_> {
+            gjson::parse(json)
+        }
+        // generation 1 has no items or abilities
+        assert_eq!(
+            level_and_set(
+                &member(
+                    r#"{"species":"Tauros","level":74,"moves":["bodyslam","hyperbeam","earthquake","blizzard"]}"#
+                ),
+                Some(1)
+            ),
+            (
+                Some(74),
+                String::from("blizzard/bodyslam/earthquake/hyperbeam")
+            )
+        );
+        // generation 2 has items but no abilities
+        assert_eq!(
+            level_and_set(
+                &member(
+                    r#"{"species":"Snorlax","item":"Leftovers","ability":"No Ability","moves":["rest","curse"]}"#
+                ),
+                Some(2)
+            ),
+            (None, String::from("Leftovers | curse/rest"))
+        );
+        // from generation 3, a missing item is part of the set
+        assert_eq!(
+            level_and_set(
+                &member(r#"{"species":"Jirachi","ability":"Serene Grace","moves":["ironhead"]}"#),
+                Some(4)
+            ),
+            (None, String::from("no item | Serene Grace | ironhead"))
+        );
+        assert_eq!(
+            level_and_set(
+                &member(r#"{"species":"Mew","item":"Leftovers","moves":[]}"#),
+                None
+            ),
+            (None, String::from("Leftovers | "))
+        );
+    }
+
+    #[test]
+    fn test_suggestions() {
+        let mut species = IndexMap::new();
+        species.insert(
+            String::from("Blissey"),
+            species_record(82, &[("a", 100, 30), ("b", 100, 35)]),
+        );
+        species.insert(
+            String::from("Dragapult"),
+            species_record(78, &[("a", 100, 80), ("b", 100, 45)]),
+        );
+        // too few games to judge
+        species.insert(String::from("Mew"), species_record(80, &[("a", 10, 0)]));
+        // close enough to 50%
+        species.insert(
+            String::from("Ditto"),
+            species_record(100, &[("a", 200, 99)]),
+        );
+
+        let thresholds = Thresholds {
+            min_games: 100,
+            deviations: 3.0,
+        };
+        assert_eq!(
+            BalanceReport::from_records(species, thresholds).to_csv(),
+            "Blissey,82,32.500000,200,-4.949747,underperforms across all sets; consider +1 level\n\
+             Dragapult,78,62.500000,200,3.535534,overperforms mainly with a (80.000000% in 100 games); consider changing that set"
+        );
+    }
+}
@@ -0,0 +1,282 @@
+/// Heuristics for spotting ladder bots before the main analysis
+///
+/// Bots give away two things: they play far more games in a day than a person could, and
+/// they take almost exactly the same amount of time over every turn.
+use crate::filter::to_id;
+use crate::status::status;
+use crate::validate::{check_battle, read_battle};
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+use std::thread;
+
+/// Players with more games than this in a single day are flagged
+const MAX_DAILY_GAMES: u32 = 300;
+/// Players need at least this many games before their timing is judged
+const MIN_TIMED_GAMES: u32 = 20;
+/// Flag players when at least this fraction of their games have stereotyped timing
+const STEREOTYPED_FRACTION: f64 = 0.8;
+/// A battle needs at least this many turn intervals for its timing to be judged
+const MIN_TURN_INTERVALS: usize = 10;
+/// Turn lengths varying less than this (standard deviation over mean) count as stereotyped
+const MAX_TIMING_VARIATION: f64 = 0.1;
+
+#[derive(Copy, Clone, Debug, Default)]
+struct Activity {
+    /// Most games played on any one day
+    max_daily_games: u32,
+    games: u32,
+    stereotyped_games: u32,
+}
+
+/// Whether a battle's turns all took about the same time, going by its `|t:|` timestamps
+fn has_stereotyped_timing(json: &str) -> bool {
+    let log = gjson::get(json, "log");
+    let timestamps = log
+        .array()
+        .iter()
+        .filter_map(|line| line.str().strip_prefix("|t:|")?.parse::<f64>().ok())
+        .collect::<Vec<_>>();
+    let intervals = timestamps
+        .windows(2)
+        .map(|pair| pair[1] - pair[0])
+        .collect::<Vec<_>>();
+    if intervals.len() < MIN_TURN_INTERVALS {
+        return false;
+    }
+
+    let mean = intervals.iter().sum::<f64>() / intervals.len() as f64;
+    if mean <= 0.0 {
+        return false;
+    }
+    let variance =
+        intervals.iter().map(|i| (i - mean).powi(2)).sum::<f64>() / intervals.len() as f64;
+    variance.sqrt() / mean < MAX_TIMING_VARIATION
+}
+
+/// The players in one day's battles, with their game counts and how many had stereotyped timing
+#[derive(Debug, Default)]
+struct DayScan {
+    players: HashMap<String, (u32, u32)>,
+    /// The players in each battle with stereotyped timing, since the timing belongs to the whole
+    /// battle rather than to either side
+    stereotyped_battles: Vec<Vec<String>>,
+}
+
+impl DayScan {
+    fn add_battle(&mut self, json: &str) {
+        let stereotyped = has_stereotyped_timing(json);
+        let ids = ["p1", "p2", "p3", "p4"]
+            .iter()
+            .map(|side| gjson::get(json, side))
+            .filter(|player| player.exists())
+            .map(|player| to_id(player.str()))
+            .collect::<Vec<_>>();
+        for id in &ids {
+            let entry = self.players.entry(id.clone()).or_insert((0, 0));
+            entry.0 += 1;
+            entry.1 += stereotyped as u32;
+        }
+        if stereotyped {
+            self.stereotyped_battles.push(ids);
+        }
+    }
+
+    fn merge(&mut self, other: DayScan) {
+        for (id, (games, stereotyped)) in other.players {
+            let entry = self.players.entry(id).or_insert((0, 0));
+            entry.0 += games;
+            entry.1 += stereotyped;
+        }
+        self.stereotyped_battles.extend(other.stereotyped_battles);
+    }
+}
+
+fn scan_day(files: &[PathBuf]) -> DayScan {
+    let threads = thread::available_parallelism().map_or(1, |n| n.get());
+    let chunk_size = (files.len() / threads).max(1);
+
+    thread::scope(|scope| {
+        let scanners = files
+            .chunks(chunk_size)
+            .map(|chunk| {
+                scope.spawn(move || {
+                    let mut scan = DayScan::default();
+                    for path in chunk {
+                        // the main analysis reports files that can't be read or analyzed
+                        let json = match read_battle(path) {
+                            Ok(json) => json,
+                            Err(_) => continue,
+                        };
+                        if check_battle(&json).is_ok() {
+                            scan.add_battle(&json);
+                        }
+                    }
+                    scan
+                })
+            })
+            .collect::<Vec<_>>();
+
+        let mut scan = DayScan::default();
+        for scanner in scanners {
+            scan.merge(scanner.join().unwrap());
+        }
+        scan
+    })
+}
+
+impl Activity {
+    fn has_stereotyped_timing(&self) -> bool {
+        self.games >= MIN_TIMED_GAMES
+            && self.stereotyped_games as f64 / self.games as f64 >= STEREOTYPED_FRACTION
+    }
+}
+
+/// Picks out the players who look like bots, and why
+///
+/// A battle's timing can come from either side: someone who keeps getting matched against a
+/// bot plays a lot of stereotyped games too. So players are first judged on all their games,
+/// and then again without the stereotyped games against opponents who were also flagged, which
+/// clears the bot's opponent but not the bot (as long as it plays other people too).
+fn judge(
+    mut activity: HashMap<String, Activity>,
+    stereotyped_battles: &[Vec<String>],
+) -> Vec<(String, String)> {
+    let candidates = activity
+        .iter()
+        .filter(|(_, player)| player.has_stereotyped_timing())
+        .map(|(id, _)| id.clone())
+        .collect::<HashSet<_>>();
+    for ids in stereotyped_battles {
+        for id in ids {
+            let ambiguous = ids
+                .iter()
+                .any(|other| other != id && candidates.contains(other));
+            if ambiguous {
+                let player = activity.get_mut(id).unwrap();
+                player.games -= 1;
+                player.stereotyped_games -= 1;
+            }
+        }
+    }
+
+    let mut suspects = activity
+        .into_iter()
+        .filter_map(|(id, player)| {
+            let reason = if player.max_daily_games > MAX_DAILY_GAMES {
+                format!("{} games in one day", player.max_daily_games)
+            } else if player.has_stereotyped_timing() {
+                format!(
+                    "{} of {} games against unflagged opponents with near-constant turn times",
+                    player.stereotyped_games, player.games
+                )
+            } else {
+                return None;
+            };
+            Some((id, reason))
+        })
+        .collect::<Vec<_>>();
+    suspects.sort();
+    suspects
+}
+
+/// Scans every day's battles and returns the IDs of players who look like bots,
+/// printing why each was flagged
+pub fn find_suspected_bots<'a>(days: impl Iterator<Item = &'a [PathBuf]>) -> HashSet<String> {
+    let mut activity: HashMap<String, Activity> = HashMap::new();
+    let mut stereotyped_battles = vec![];
+    for files in days {
+        let scan = scan_day(files);
+        for (id, (games, stereotyped)) in scan.players {
+            let player = activity.entry(id).or_default();
+            player.max_daily_games = player.max_daily_games.max(games);
+            player.games += games;
+            player.stereotyped_games += stereotyped;
+        }
+        stereotyped_battles.extend(scan.stereotyped_battles);
+    }
+
+    let suspects = judge(activity, &stereotyped_battles);
+    for (id, reason) in &suspects {
+        status!("Suspected bot: {} ({})", id, reason);
+    }
+    suspects.into_iter().map(|(id, _)| id).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn battle_with_times(times: &[u32]) -> String {
+        battle_between("A", "B", times)
+    }
+
+    fn battle_between(p1: &str, p2: &str, times: &[u32]) -> String {
+        let log = times
+            .iter()
+            .map(|t| format!("\"|t:|{}\"", t))
+            .collect::<Vec<_>>();
+        format!(
+            r#"{{"p1":"{}","p2":"{}","log":[{}]}}"#,
+            p1,
+            p2,
+            log.join(",")
+        )
+    }
+
+    #[test]
+    fn test_stereotyped_timing() {
+        let steady = (0..12).map(|i| 1000 + i * 5).collect::<Vec<_>>();
+        assert!(has_stereotyped_timing(&battle_with_times(&steady)));
+
+        let human = [0, 4, 30, 35, 80, 81, 95, 140, 146, 200, 230, 231];
+        assert!(!has_stereotyped_timing(&battle_with_times(&human)));
+
+        // too short to judge
+        assert!(!has_stereotyped_timing(&battle_with_times(&[0, 5, 10])));
+    }
+
+    #[test]
+    fn test_bot_and_opponent() {
+        let steady = (0..12).map(|i| 1000 + i * 5).collect::<Vec<_>>();
+        let human = [0, 4, 30, 35, 80, 81, 95, 140, 146, 200, 230, 231];
+
+        // the bot plays one person over and over, and a few games each against other people
+        let mut scan = DayScan::default();
+        for _ in 0..25 {
+            scan.add_battle(&battle_between("Annika", "Steady Bot", &steady));
+        }
+        for n in 0..25 {
+            scan.add_battle(&battle_between(
+                "Steady Bot",
+                &format!("Player {}", n),
+                &steady,
+            ));
+        }
+        for n in 0..3 {
+            scan.add_battle(&battle_between("Annika", &format!("Player {}", n), &human));
+        }
+
+        let activity = scan
+            .players
+            .iter()
+            .map(|(id, &(games, stereotyped_games))| {
+                let player = Activity {
+                    max_daily_games: games,
+                    games,
+                    stereotyped_games,
+                };
+                (id.clone(), player)
+            })
+            .collect();
+        let suspects = judge(activity, &scan.stereotyped_battles);
+        assert_eq!(
+            suspects,
+            vec![(
+                String::from("steadybot"),
+                String::from(
+                    "25 of 25 games against unflagged opponents with near-constant turn times"
+                )
+            )]
+        );
+    }
+}
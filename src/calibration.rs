@@ -0,0 +1,172 @@
+/// How often the higher-rated player wins, by rating gap (`--calibration-output`)
+///
+/// If the ratings mean anything, the higher-rated player should win more often as the gap
+/// grows, roughly as the Elo formula predicts. A flat curve suggests the rating field (or the
+/// elo cutoff built on it) isn't measuring skill. Only rated two-player battles with a winner
+/// and unequal ratings count.
+use crate::filter::BattleFilter;
+use crate::pass;
+use crate::stats::{format_float, Output, Stats};
+use crate::table::{self, Block, Table};
+use itertools::Itertools;
+use std::io;
+use std::path::PathBuf;
+
+/// Width of each rating gap bucket
+const BUCKET_WIDTH: u32 = 50;
+/// Gaps of at least this much share the last bucket
+const MAX_BUCKETED_GAP: u32 = 400;
+
+/// The rating gap and whether the higher-rated player won
+fn rating_gap(json: &str) -> Option<(f64, bool)> {
+    let sides = Stats::results_by_side(0, json);
+    let (p1, p2) = match sides.as_slice() {
+        [p1, p2] if !p1.is_empty() && !p2.is_empty() => (&p1[0], &p2[0]),
+        _ => return None,
+    };
+    let (r1, r2) = (p1.rating?, p2.rating?);
+    if p1.won == p2.won || r1 == r2 {
+        return None;
+    }
+    Some(((r1 - r2).abs(), (r1 > r2) == p1.won))
+}
+
+/// The Elo formula's chance that the higher-rated player wins, as a percentage
+pub(crate) fn expected_winrate(gap: f64) -> f64 {
+    100.0 / (1.0 + 10f64.powf(-gap / 400.0))
+}
+
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+struct Bucket {
+    games: u32,
+    higher_rated_wins: u32,
+    /// Sum of the gaps, for their mean
+    gap_sum: f64,
+}
+
+impl Bucket {
+    fn winrate(&self) -> f64 {
+        self.higher_rated_wins as f64 / self.games as f64 * 100.0
+    }
+
+    fn expected(&self) -> f64 {
+        expected_winrate(self.gap_sum / self.games as f64)
+    }
+}
+
+pub struct CalibrationReport {
+    buckets: Vec<Bucket>,
+}
+
+impl CalibrationReport {
+    pub fn new<'a>(
+        days: impl Iterator<Item = &'a [PathBuf]>,
+        min_elo: u64,
+        filter: &BattleFilter,
+    ) -> io::Result<Self> {
+        let mut report = Self::empty();
+        let mut scan = pass::Scan::new(min_elo, filter);
+        for files in days {
+            for (gap, higher_won) in scan.day(files, rating_gap)?.into_iter().flatten() {
+                report.add(gap, higher_won);
+            }
+        }
+        Ok(report)
+    }
+
+    fn empty() -> Self {
+        CalibrationReport {
+            buckets: vec![Bucket::default(); (MAX_BUCKETED_GAP / BUCKET_WIDTH + 1) as usize],
+        }
+    }
+
+    fn add(&mut self, gap: f64, higher_won: bool) {
+        let idx = ((gap as u32).min(MAX_BUCKETED_GAP) / BUCKET_WIDTH) as usize;
+        let bucket = &mut self.buckets[idx];
+        bucket.games += 1;
+        bucket.higher_rated_wins += higher_won as u32;
+        bucket.gap_sum += gap;
+    }
+
+    /// (label, bucket) for each bucket with games in it
+    fn rows(&self) -> impl Iterator<Item = (String, &Bucket)> {
+        self.buckets
+            .iter()
+            .enumerate()
+            .filter(|(_, bucket)| bucket.games > 0)
+            .map(|(idx, bucket)| {
+                let low = idx as u32 * BUCKET_WIDTH;
+                let label = if low >= MAX_BUCKETED_GAP {
+                    format!("{}+", low)
+                } else {
+                    format!("{}-{}", low, low + BUCKET_WIDTH - 1)
+                };
+                (label, bucket)
+            })
+    }
+}
+
+impl Output for CalibrationReport {
+    fn to_csv(&mut self) -> String {
+        Itertools::intersperse(
+            self.rows().map(|(label, bucket)| {
+                [
+                    label,
+                    bucket.games.to_string(),
+                    format_float(bucket.winrate()),
+                    format_float(bucket.expected()),
+                ]
+                .join(",")
+            }),
+            String::from("\n"),
+        )
+        .collect()
+    }
+
+    fn to_human_readable(&mut self) -> String {
+        table::blocks_to_text(&self.to_blocks())
+    }
+
+    fn to_blocks(&mut self) -> Vec<Block> {
+        let mut table = Table::new(&[
+            "Rating gap",
+            "Higher-rated winrate",
+            "Elo expectation",
+            "Games",
+        ]);
+        for (label, bucket) in self.rows() {
+            table.add_row(vec![
+                label,
+                format!("{}%", format_float(bucket.winrate())),
+                format!("{}%", format_float(bucket.expected())),
+                bucket.games.to_string(),
+            ]);
+        }
+        vec![Block::Table(table)]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_calibration() {
+        let json = r#"{"winner":"B","p1":"A","p2":"B","p1team":[{"species":"Mew"}],"p2team":[{"species":"Ditto"}],"p1rating":{"elo":1300},"p2rating":{"elo":1420}}"#;
+        assert_eq!(rating_gap(json), Some((120.0, true)));
+        let unrated = r#"{"winner":"B","p1":"A","p2":"B","p1team":[{"species":"Mew"}],"p2team":[{"species":"Ditto"}],"p1rating":{"elo":1300}}"#;
+        assert_eq!(rating_gap(unrated), None);
+
+        let mut report = CalibrationReport::empty();
+        report.add(120.0, true);
+        report.add(130.0, false);
+        report.add(0.5, true);
+        report.add(900.0, true);
+        assert_eq!(
+            report.to_csv(),
+            "0-49,1,100.000000,50.071956\n\
+             100-149,2,50.000000,67.250964\n\
+             400+,1,100.000000,99.440803"
+        );
+    }
+}
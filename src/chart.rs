@@ -0,0 +1,229 @@
+/// Charts of the top and bottom of the rankings (`--chart-output` and `--svg-output`)
+///
+/// Both show the species at each end of the rankings, leaving out provisional species. The
+/// Vega-Lite spec charts deviations, with the data inline, so it can be rendered by the Vega
+/// editor or any notebook without a plotting script; the SVG charts winrates around 50% and is
+/// an image that monthly reports can include as-is.
+use crate::fetch::json_string;
+use crate::html::escape_html;
+use crate::matchup::color;
+use crate::stats::{format_float, PokemonStats, Stats};
+
+const SCHEMA: &str = "https://vega.github.io/schema/vega-lite/v5.json";
+
+/// SVG layout, in pixels
+const LABEL_WIDTH: usize = 140;
+const PLOT_WIDTH: usize = 400;
+const BAR_HEIGHT: usize = 18;
+/// Room for the winrate after the longest bar, and for the axis labels below the plot
+const MARGIN: usize = 50;
+
+/// The x coordinate of a winrate in the SVG
+fn x(winrate: f64) -> f64 {
+    LABEL_WIDTH as f64 + winrate / 100.0 * PLOT_WIDTH as f64
+}
+
+impl Stats {
+    /// The `n` established species with the most deviations, then the `n` with the fewest, each
+    /// with its group ("top" or "bottom"); a species is only in one group. The stats must be
+    /// sorted.
+    fn extremes(&self, n: usize) -> Vec<(&String, &PokemonStats, &'static str)> {
+        let established = self
+            .pokemon
+            .iter()
+            .filter(|(_, stats)| !self.is_provisional(stats))
+            .collect::<Vec<_>>();
+        let bottom_start = established.len().saturating_sub(n).max(n);
+        established
+            .iter()
+            .take(n)
+            .map(|(species, stats)| (*species, *stats, "top"))
+            .chain(
+                established
+                    .iter()
+                    .skip(bottom_start)
+                    .map(|(species, stats)| (*species, *stats, "bottom")),
+            )
+            .collect()
+    }
+
+    /// The spec for the `n` species with the most deviations and the `n` with the fewest
+    pub fn to_vega_lite(&mut self, n: usize) -> String {
+        self.sort();
+        let values = self
+            .extremes(n)
+            .into_iter()
+            .map(|(species, stats, group)| {
+                let fstats = stats.final_stats();
+                format!(
+                    "{{\"species\":{},\"deviations\":{},\"winrate\":{},\"games\":{},\"group\":\"{}\"}}",
+                    json_string(self.display_name(species)),
+                    format_float(fstats.deviations),
+                    format_float(fstats.winrate),
+                    stats.games,
+                    group
+                )
+            })
+            .collect::<Vec<_>>();
+
+        format!(
+            r#"{{
+  "$schema": "{}",
+  "description": "Deviations of the top and bottom {} species",
+  "data": {{"values": [
+    {}
+  ]}},
+  "mark": "bar",
+  "encoding": {{
+    "y": {{"field": "species", "type": "nominal", "sort": "-x", "title": "Pokémon"}},
+    "x": {{"field": "deviations", "type": "quantitative", "title": "Deviations"}},
+    "color": {{"field": "group", "type": "nominal", "scale": {{"domain": ["top", "bottom"]}}, "title": null}},
+    "tooltip": [
+      {{"field": "species", "type": "nominal"}},
+      {{"field": "deviations", "type": "quantitative", "format": ".2f"}},
+      {{"field": "winrate", "type": "quantitative", "format": ".2f"}},
+      {{"field": "games", "type": "quantitative"}}
+    ]
+  }}
+}}
+"#,
+            SCHEMA,
+            n,
+            values.join(",\n    ")
+        )
+    }
+
+    /// An SVG bar chart of the winrates of the `n` species with the most deviations and the `n`
+    /// with the fewest, with the two groups apart and bars drawn from 50%
+    pub fn to_svg_chart(&mut self, n: usize) -> String {
+        self.sort();
+        let rows = self.extremes(n);
+        let gap = rows.iter().any(|(_, _, group)| *group == "bottom")
+            && rows.iter().any(|(_, _, group)| *group == "top");
+        let plot_height = (rows.len() + gap as usize) * BAR_HEIGHT;
+        let (width, height) = (LABEL_WIDTH + PLOT_WIDTH + MARGIN, plot_height + MARGIN);
+
+        let mut svg = vec![
+            format!(
+                "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\" \
+                 font-family=\"sans-serif\" font-size=\"11\">",
+                width, height
+            ),
+            format!(
+                "<rect width=\"{}\" height=\"{}\" fill=\"white\"/>",
+                width, height
+            ),
+        ];
+        for tick in (0..=100).step_by(25) {
+            let tick_x = x(tick as f64);
+            svg.push(format!(
+                "<line x1=\"{0}\" y1=\"0\" x2=\"{0}\" y2=\"{1}\" stroke=\"{2}\"/>",
+                tick_x,
+                plot_height,
+                if tick == 50 { "#000000" } else { "#dddddd" }
+            ));
+            svg.push(format!(
+                "<text x=\"{}\" y=\"{}\" text-anchor=\"middle\">{}%</text>",
+                tick_x,
+                plot_height + 14,
+                tick
+            ));
+        }
+
+        let mut position = 0;
+        let mut previous_group = "top";
+        for (species, stats, group) in rows {
+            if group != previous_group {
+                position += 1;
+                previous_group = group;
+            }
+            let winrate = stats.final_stats().winrate;
+            let name = escape_html(self.display_name(species));
+            let top = position * BAR_HEIGHT;
+            svg.push(format!(
+                "<text x=\"{}\" y=\"{}\" text-anchor=\"end\">{}</text>",
+                LABEL_WIDTH - 4,
+                top + BAR_HEIGHT * 3 / 4,
+                name
+            ));
+            svg.push(format!(
+                "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"{}\">\
+                 <title>{}: {:.1}% in {} games</title></rect>",
+                x(winrate.min(50.0)),
+                top + 2,
+                (x(winrate) - x(50.0)).abs(),
+                BAR_HEIGHT - 4,
+                color(Some(winrate)),
+                name,
+                winrate,
+                stats.games
+            ));
+            svg.push(format!(
+                "<text x=\"{}\" y=\"{}\">{:.1}%</text>",
+                x(winrate.max(50.0)) + 4.0,
+                top + BAR_HEIGHT * 3 / 4,
+                winrate
+            ));
+            position += 1;
+        }
+        svg.push(String::from("</svg>"));
+        svg.join("\n") + "\n"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::stats::{GameResult, Stats};
+
+    #[test]
+    fn test_vega_lite() {
+        let result = |species: &str, won| GameResult {
+            species: species.to_string(),
+            won,
+            turns: 10,
+            rating: None,
+        };
+        let mut stats = Stats::new();
+        stats.add_game_results(vec![result("Mew", true), result("Ditto", false)]);
+        stats.add_game_results(vec![result("Mew", true), result("Smeargle", true)]);
+
+        let spec = stats.to_vega_lite(1);
+        assert!(spec.contains("\"$schema\": \"https://vega.github.io/schema/vega-lite/v5.json\""));
+        assert!(spec.contains(
+            "{\"species\":\"Mew\",\"deviations\":1.414214,\"winrate\":100.000000,\"games\":2,\"group\":\"top\"},\n    \
+             {\"species\":\"Ditto\",\"deviations\":-1.000000,\"winrate\":0.000000,\"games\":1,\"group\":\"bottom\"}\n"
+        ));
+        assert!(!spec.contains("Smeargle"));
+
+        // with fewer species than bars, each is shown once
+        assert_eq!(stats.to_vega_lite(5).matches("\"species\":\"").count(), 3);
+    }
+
+    #[test]
+    fn test_svg_chart() {
+        let result = |species: &str, won| GameResult {
+            species: species.to_string(),
+            won,
+            turns: 10,
+            rating: None,
+        };
+        let mut stats = Stats::new();
+        stats.add_game_results(vec![result("Mew", true), result("Ditto", false)]);
+        stats.add_game_results(vec![result("Mew", true), result("Smeargle", true)]);
+
+        let svg = stats.to_svg_chart(1);
+        assert!(svg
+            .starts_with("<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"590\" height=\"104\""));
+        // Mew's bar runs right from 50% at the top, and Ditto's left from it after a gap
+        assert!(svg.contains(
+            "<rect x=\"340\" y=\"2\" width=\"200\" height=\"14\" fill=\"#4575b4\">\
+             <title>Mew: 100.0% in 2 games</title></rect>"
+        ));
+        assert!(svg.contains(
+            "<rect x=\"140\" y=\"38\" width=\"200\" height=\"14\" fill=\"#d73027\">\
+             <title>Ditto: 0.0% in 1 games</title></rect>"
+        ));
+        assert!(!svg.contains("Smeargle"));
+        assert!(svg.trim_end().ends_with("</svg>"));
+    }
+}
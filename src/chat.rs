@@ -0,0 +1,70 @@
+/// Compact output for Pokémon Showdown chat (`--code-output`)
+///
+/// A message starting with `!code` is shown in a monospace block, so the top of the rankings
+/// can be pasted straight into a chat room. Chat messages are short, so this leaves out the
+/// borders, provisional species, and every column but the essentials, and caps the number of
+/// rows; the first line says how many battles the rankings cover and at what elo cutoff.
+use crate::stats::Stats;
+use crate::table::Table;
+
+impl Stats {
+    pub fn to_chat_code(&mut self, max_rows: usize, min_elo: u64) -> String {
+        self.sort();
+
+        let established = self
+            .pokemon
+            .iter()
+            .filter(|(_, stats)| !self.is_provisional(stats))
+            .collect::<Vec<_>>();
+        let shown = &established[..established.len().min(max_rows)];
+
+        let mut table = Table::new(&["#", "Pokemon", "Winrate", "Games"]);
+        for ((species, stats), rank) in shown.iter().zip(self.ranks(shown)) {
+            table.add_row(vec![
+                rank.to_string(),
+                self.display_name(species).to_string(),
+                format!("{:.1}%", stats.final_stats().winrate),
+                self.number_format.format(stats.games),
+            ]);
+        }
+        if let Some(width) = self.name_width {
+            table.truncate_column(1, width);
+        }
+
+        format!(
+            "!code {} battles analyzed, elo cutoff {}, top {} of {} species\n{}",
+            self.number_format.format(self.battles),
+            min_elo,
+            shown.len(),
+            established.len(),
+            table.to_compact()
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::stats::{GameResult, Stats};
+
+    #[test]
+    fn test_chat_code() {
+        let result = |species: &str, won| GameResult {
+            species: species.to_string(),
+            won,
+            turns: 10,
+            rating: None,
+        };
+        let mut stats = Stats::new();
+        stats.add_game_results(vec![result("Mew", true), result("Ditto", false)]);
+        stats.add_game_results(vec![result("Mew", true), result("Rotom-Fan", false)]);
+        stats.add_game_results(vec![result("Ditto", true), result("Rotom-Fan", false)]);
+
+        assert_eq!(
+            stats.to_chat_code(2, 1300),
+            "!code 3 battles analyzed, elo cutoff 1300, top 2 of 3 species\n\
+             # Pokemon Winrate Games\n\
+             1 Mew     100.0%  2\n\
+             2 Ditto   50.0%   2"
+        );
+    }
+}
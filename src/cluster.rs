@@ -0,0 +1,491 @@
+/// Coordinator/worker mode, for splitting a run across machines without shared storage
+///
+/// The coordinator reads the battles and hands them out over TCP in work units; each worker
+/// analyzes a unit and replies with the state (see `state.rs`) of the `Stats` it built.
+/// Units whose worker disconnects or errors are handed to the next worker that asks.
+///
+/// Every message is a frame: a little-endian `u64` length followed by that many bytes.
+/// On connecting, a worker sends the shared token in one frame; the coordinator hangs up
+/// unless it matches, and otherwise replies with one config frame. Each unit is then a frame
+/// holding the number of battles, followed by one frame per battle; a unit of zero battles
+/// means there's no more work.
+use crate::filter::{BattleFilter, FormatKind, Timer};
+use crate::hash::Fnv1a;
+use crate::log::{LogAnalysis, LogStats};
+use crate::stats::{Stats, StatsError};
+use crate::status::status;
+use crate::validate;
+use itertools::Itertools;
+use std::collections::{HashSet, VecDeque};
+use std::convert::TryInto;
+use std::hash::Hasher;
+use std::io::{self, Read, Write};
+use std::net::{TcpListener, TcpStream, ToSocketAddrs};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use std::time::Duration;
+
+/// How many times a unit is sent out before the coordinator gives up on it
+const MAX_ATTEMPTS: u32 = 3;
+/// How long the coordinator waits for a worker's reply before treating it as failed
+const WORKER_TIMEOUT: Duration = Duration::from_secs(600);
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+/// The largest frame either side will read, so a bad length can't exhaust memory
+const MAX_FRAME_SIZE: u64 = 256 * 1024 * 1024;
+/// The largest token frame, read before the peer is known to be a worker
+const MAX_TOKEN_SIZE: u64 = 1024;
+/// How long the coordinator waits for a new connection's token
+const AUTH_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The analysis settings the coordinator sends to each worker
+#[derive(Debug, PartialEq)]
+pub struct WorkerConfig {
+    pub min_elo: u64,
+    pub log_analyses: Vec<LogAnalysis>,
+    /// Duplicates are skipped by the coordinator, before battles are sent out
+    pub filter: BattleFilter,
+}
+
+impl WorkerConfig {
+    fn encode(&self) -> String {
+        format!(
+            "min_elo\t{}\nlog_analyses\t{}\ntimer\t{}\ninclude_unrated\t{}\nexclude_guests\t{}\nexclude_abandoned\t{}\nexcluded_players\t{}\nsuspected_bots\t{}\nopponent_species\t{}\nformat_kind\t{}\nalts\t{}\n{}",
+            self.min_elo,
+            self.log_analyses.iter().join(","),
+            self.filter.timer.map(|t| t.to_string()).unwrap_or_default(),
+            self.filter.include_unrated,
+            self.filter.exclude_guests,
+            self.filter.exclude_abandoned,
+            self.filter.excluded_players.iter().join(","),
+            self.filter.suspected_bots.iter().join(","),
+            self.filter.opponent_species.as_deref().unwrap_or_default(),
+            self.filter.format_kind,
+            self.filter
+                .alts
+                .iter()
+                .map(|(alt, main)| format!("{}={}", alt, main))
+                .join(","),
+            [("only_players", &self.filter.only_players), ("roster", &self.filter.roster)]
+                .iter()
+                .filter_map(|(key, players)| players
+                    .as_ref()
+                    .map(|p| format!("{}\t{}\n", key, p.iter().join(","))))
+                .join(""),
+        )
+    }
+
+    fn decode(config: &str) -> Result<Self, String> {
+        let mut min_elo = None;
+        let mut log_analyses = vec![];
+        let mut filter = BattleFilter::default();
+        for line in config.lines() {
+            match line.split_once('\t') {
+                Some(("min_elo", value)) => {
+                    min_elo = Some(value.parse::<u64>().map_err(|e| e.to_string())?)
+                }
+                Some(("log_analyses", ""))
+                | Some(("timer", ""))
+                | Some(("excluded_players", ""))
+                | Some(("suspected_bots", ""))
+                | Some(("opponent_species", ""))
+                | Some(("alts", "")) => {}
+                Some(("log_analyses", value)) => {
+                    log_analyses = value
+                        .split(',')
+                        .map(|a| a.parse::<LogAnalysis>())
+                        .collect::<Result<_, _>>()?
+                }
+                Some(("timer", value)) => filter.timer = Some(value.parse::<Timer>()?),
+                Some(("include_unrated", value)) => {
+                    filter.include_unrated = value.parse::<bool>().map_err(|e| e.to_string())?
+                }
+                Some(("exclude_guests", value)) => {
+                    filter.exclude_guests = value.parse::<bool>().map_err(|e| e.to_string())?
+                }
+                Some(("exclude_abandoned", value)) => {
+                    filter.exclude_abandoned = value.parse::<bool>().map_err(|e| e.to_string())?
+                }
+                Some(("excluded_players", value)) => {
+                    filter.excluded_players = value.split(',').map(String::from).collect()
+                }
+                Some(("opponent_species", value)) => {
+                    filter.opponent_species = Some(value.to_string())
+                }
+                Some(("format_kind", value)) => filter.format_kind = value.parse::<FormatKind>()?,
+                Some(("alts", value)) => {
+                    for pair in value.split(',') {
+                        let (alt, main) = pair
+                            .split_once('=')
+                            .ok_or_else(|| format!("invalid alt '{}'", pair))?;
+                        filter.alts.insert(alt.to_string(), main.to_string());
+                    }
+                }
+                Some(("suspected_bots", value)) => {
+                    filter.suspected_bots = value.split(',').map(String::from).collect()
+                }
+                Some((key @ "only_players", value)) | Some((key @ "roster", value)) => {
+                    let players = value
+                        .split(',')
+                        .filter(|id| !id.is_empty())
+                        .map(String::from)
+                        .collect();
+                    if key == "roster" {
+                        filter.roster = Some(players);
+                    } else {
+                        filter.only_players = Some(players);
+                    }
+                }
+                _ => return Err(format!("unexpected config line '{}'", line)),
+            }
+        }
+        Ok(Self {
+            min_elo: min_elo.ok_or("config is missing min_elo")?,
+            log_analyses,
+            filter,
+        })
+    }
+}
+
+/// A batch of input files, read the first time it's handed out
+struct WorkUnit {
+    files: Vec<PathBuf>,
+    battles: Option<Vec<String>>,
+    attempts: u32,
+}
+
+fn write_frame(stream: &mut impl Write, bytes: &[u8]) -> io::Result<()> {
+    stream.write_all(&(bytes.len() as u64).to_le_bytes())?;
+    stream.write_all(bytes)
+}
+
+fn read_frame(stream: &mut impl Read) -> io::Result<Vec<u8>> {
+    read_frame_up_to(stream, MAX_FRAME_SIZE)
+}
+
+fn read_frame_up_to(stream: &mut impl Read, max_size: u64) -> io::Result<Vec<u8>> {
+    let mut len = [0; 8];
+    stream.read_exact(&mut len)?;
+    let len = u64::from_le_bytes(len);
+    if len > max_size {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("frame of {} bytes is over the limit", len),
+        ));
+    }
+    let mut bytes = vec![0; len as usize];
+    stream.read_exact(&mut bytes)?;
+    Ok(bytes)
+}
+
+fn read_string(stream: &mut impl Read) -> io::Result<String> {
+    String::from_utf8(read_frame(stream)?)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+/// Compares tokens in time that doesn't depend on where they first differ
+fn tokens_match(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+fn send_unit(stream: &mut TcpStream, battles: &[String]) -> io::Result<()> {
+    write_frame(stream, &(battles.len() as u64).to_le_bytes())?;
+    for battle in battles {
+        write_frame(stream, battle.as_bytes())?;
+    }
+    stream.flush()
+}
+
+/// Hands out units to whichever workers connect to `listener` with `token` until every unit
+/// is analyzed, then returns the combined stats
+pub fn coordinate(
+    listener: TcpListener,
+    config: &WorkerConfig,
+    token: &str,
+    files: Vec<PathBuf>,
+    batch_size: usize,
+) -> Result<Stats, StatsError> {
+    let queue: VecDeque<WorkUnit> = files
+        .chunks(batch_size.max(1))
+        .map(|files| WorkUnit {
+            files: files.to_vec(),
+            battles: None,
+            attempts: 0,
+        })
+        .collect();
+    let remaining = AtomicUsize::new(queue.len());
+    let queue = Mutex::new(queue);
+    let failure = Mutex::new(None);
+    let seen_battles = Mutex::new(HashSet::new());
+    let mut stats = Stats::new();
+    stats.log_stats = LogStats::new(&config.log_analyses);
+    let stats = Mutex::new(stats);
+    let encoded_config = config.encode();
+
+    // the next unit to send, reading its files if this is its first time out
+    let next_unit = || -> Option<WorkUnit> {
+        let mut unit = queue.lock().unwrap().pop_front()?;
+        if unit.battles.is_none() {
+            let mut battles = vec![];
+            for path in &unit.files {
+                let json = match validate::read_battle(path) {
+                    Ok(json) => json,
+                    Err(e) => {
+                        stats
+                            .lock()
+                            .unwrap()
+                            .skip_malformed(path.display(), e.into());
+                        continue;
+                    }
+                };
+                if config.filter.skip_duplicates {
+                    let mut hasher = Fnv1a::default();
+                    hasher.write(json.as_bytes());
+                    if !seen_battles.lock().unwrap().insert(hasher.finish()) {
+                        stats.lock().unwrap().count("Duplicate battles skipped");
+                        continue;
+                    }
+                }
+                battles.push(json);
+            }
+            unit.battles = Some(battles);
+        }
+        Some(unit)
+    };
+
+    let serve = |mut stream: TcpStream| -> Result<(), StatsError> {
+        let peer = stream
+            .peer_addr()
+            .map(|a| a.to_string())
+            .unwrap_or_default();
+        stream.set_nonblocking(false)?;
+        stream.set_read_timeout(Some(AUTH_TIMEOUT))?;
+        match read_frame_up_to(&mut stream, MAX_TOKEN_SIZE) {
+            Ok(given) if tokens_match(&given, token.as_bytes()) => {}
+            Ok(_) => {
+                status!("Worker {} rejected: wrong token", peer);
+                return Ok(());
+            }
+            Err(e) => {
+                status!("Worker {} rejected: {}", peer, e);
+                return Ok(());
+            }
+        }
+        stream.set_read_timeout(Some(WORKER_TIMEOUT))?;
+        if let Err(e) = write_frame(&mut stream, encoded_config.as_bytes()) {
+            status!("Worker {} failed: {}", peer, e);
+            return Ok(());
+        }
+        status!("Worker {} connected", peer);
+
+        loop {
+            let mut unit = match next_unit() {
+                Some(unit) => unit,
+                None if failure.lock().unwrap().is_some() => return Ok(()),
+                None if remaining.load(Ordering::SeqCst) == 0 => {
+                    // the worker may already be gone, and there's nothing left for it anyway
+                    let _ = send_unit(&mut stream, &[]);
+                    return Ok(());
+                }
+                None => {
+                    // other workers' units may still fail and come back to the queue
+                    thread::sleep(POLL_INTERVAL);
+                    continue;
+                }
+            };
+
+            unit.attempts += 1;
+            let battles = unit.battles.as_deref().unwrap_or_default();
+            let reply = send_unit(&mut stream, battles).and_then(|_| read_string(&mut stream));
+            let partial = match reply {
+                Ok(state) => Stats::from_state(&state),
+                Err(e) => Err(StatsError::IO(e)),
+            };
+            match partial {
+                Ok(partial) => {
+                    stats.lock().unwrap().merge(partial);
+                    remaining.fetch_sub(1, Ordering::SeqCst);
+                }
+                Err(e) => {
+                    status!(
+                        "Worker {} failed: {:?}; requeueing {} battles",
+                        peer,
+                        e,
+                        battles.len()
+                    );
+                    if unit.attempts >= MAX_ATTEMPTS {
+                        return Err(StatsError::Worker(format!(
+                            "a unit of {} files failed on {} workers (first file: {})",
+                            unit.files.len(),
+                            MAX_ATTEMPTS,
+                            unit.files[0].display()
+                        )));
+                    }
+                    queue.lock().unwrap().push_back(unit);
+                    return Ok(());
+                }
+            }
+        }
+    };
+
+    listener.set_nonblocking(true)?;
+    thread::scope(|scope| -> Result<(), StatsError> {
+        let serve = &serve;
+        let failure = &failure;
+        while remaining.load(Ordering::SeqCst) > 0 && failure.lock().unwrap().is_none() {
+            match listener.accept() {
+                Ok((stream, _)) => {
+                    scope.spawn(move || {
+                        if let Err(e) = serve(stream) {
+                            *failure.lock().unwrap() = Some(e);
+                        }
+                    });
+                }
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => thread::sleep(POLL_INTERVAL),
+                Err(e) => return Err(e.into()),
+            }
+        }
+        Ok(())
+    })?;
+
+    if let Some(e) = failure.into_inner().unwrap() {
+        return Err(e);
+    }
+    Ok(stats.into_inner().unwrap())
+}
+
+/// Connects to a coordinator with `token` and analyzes units until it says there are none left
+pub fn work(coordinator: impl ToSocketAddrs, token: &str) -> Result<(), StatsError> {
+    let mut stream = TcpStream::connect(coordinator)?;
+    write_frame(&mut stream, token.as_bytes())?;
+    stream.flush()?;
+    let config = match read_string(&mut stream) {
+        Ok(config) => WorkerConfig::decode(&config)?,
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => {
+            return Err(StatsError::Worker(String::from(
+                "the coordinator hung up; check that --token matches its own",
+            )))
+        }
+        Err(e) => return Err(e.into()),
+    };
+
+    let mut units = 0;
+    loop {
+        let count = read_frame(&mut stream)?
+            .try_into()
+            .map(u64::from_le_bytes)
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "malformed unit header"))?;
+        if count == 0 {
+            break;
+        }
+
+        let mut stats = Stats::new();
+        stats.log_stats = LogStats::new(&config.log_analyses);
+        for idx in 0..count {
+            let json = read_string(&mut stream)?;
+            match Stats::process_battle(&json, config.min_elo, &config.log_analyses, &config.filter)
+            {
+                Ok(battle) => stats.add_battle(battle),
+                Err(e) => {
+                    stats.skip_malformed(format!("battle {} of unit {}", idx + 1, units + 1), e)
+                }
+            }
+        }
+        write_frame(&mut stream, stats.to_state().as_bytes())?;
+        stream.flush()?;
+        units += 1;
+    }
+
+    status!("Analyzed {} work units", units);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stats::Output;
+
+    #[test]
+    fn test_config_roundtrip() {
+        let config = WorkerConfig {
+            min_elo: 1300,
+            log_analyses: vec![LogAnalysis::Leads, LogAnalysis::KOs],
+            filter: BattleFilter {
+                timer: Some(Timer::Blitz),
+                include_unrated: true,
+                exclude_guests: true,
+                exclude_abandoned: true,
+                excluded_players: ["bot1", "bot2"].iter().map(|s| s.to_string()).collect(),
+                only_players: Some(["annika"].iter().map(|s| s.to_string()).collect()),
+                suspected_bots: ["bot3"].iter().map(|s| s.to_string()).collect(),
+                roster: Some(["annika", "marty"].iter().map(|s| s.to_string()).collect()),
+                opponent_species: Some(String::from("Kingambit")),
+                format_kind: FormatKind::Standard,
+                alts: [("annikaalt", "annika"), ("marty2", "marty")]
+                    .iter()
+                    .map(|(alt, main)| (alt.to_string(), main.to_string()))
+                    .collect(),
+                ..BattleFilter::default()
+            },
+        };
+        assert_eq!(WorkerConfig::decode(&config.encode()), Ok(config));
+
+        let config = WorkerConfig {
+            min_elo: 0,
+            log_analyses: vec![],
+            filter: BattleFilter::default(),
+        };
+        assert_eq!(WorkerConfig::decode(&config.encode()), Ok(config));
+    }
+
+    #[test]
+    fn test_frames() {
+        let mut buf = vec![];
+        write_frame(&mut buf, b"hello").unwrap();
+        write_frame(&mut buf, b"").unwrap();
+        let mut reader = &buf[..];
+        assert_eq!(read_frame(&mut reader).unwrap(), b"hello");
+        assert_eq!(read_frame(&mut reader).unwrap(), b"");
+        assert!(read_frame(&mut reader).is_err());
+
+        let oversized = (MAX_FRAME_SIZE + 1).to_le_bytes();
+        assert_eq!(
+            read_frame(&mut &oversized[..]).unwrap_err().kind(),
+            io::ErrorKind::InvalidData
+        );
+    }
+
+    #[test]
+    fn test_coordinate_with_failed_worker() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let config = WorkerConfig {
+            min_elo: 1050,
+            log_analyses: vec![],
+            filter: BattleFilter::default(),
+        };
+        let mut files = vec![PathBuf::from("src/benchmark-data.json"); 10];
+        files.push(PathBuf::from("src/missing-battle.json"));
+        let coordinator =
+            thread::spawn(move || coordinate(listener, &config, "secret", files, 3).unwrap());
+
+        // never gets the config, let alone any battles
+        assert!(matches!(work(address, "guess"), Err(StatsError::Worker(_))));
+
+        // takes a unit, then disconnects without replying
+        let mut flaky = TcpStream::connect(address).unwrap();
+        write_frame(&mut flaky, b"secret").unwrap();
+        read_frame(&mut flaky).unwrap();
+        read_frame(&mut flaky).unwrap();
+        drop(flaky);
+
+        work(address, "secret").unwrap();
+        let mut stats = coordinator.join().unwrap();
+        assert_eq!(stats.battles, 10);
+        assert_eq!(stats.counter("Unreadable files skipped"), Some(1));
+        assert!(stats
+            .to_csv()
+            .starts_with("species,games,wins,winrate,deviations\nRotom-Fan,10,10,"));
+    }
+}
@@ -0,0 +1,137 @@
+/// "Clutch factor": how much better each species does in long games than in short ones
+///
+/// A positive factor suggests a late-game win condition; a negative one suggests a species
+/// that relies on early momentum.
+use crate::stats::{format_float, Output, Stats, LONG_GAME_TURNS};
+use crate::table::{self, Block, Table};
+use itertools::Itertools;
+
+struct ClutchRow {
+    species: String,
+    /// `species` as shown in the human-readable output
+    display_name: String,
+    /// winrates as percentages
+    short_winrate: f64,
+    long_winrate: f64,
+    long_games: u32,
+}
+
+impl ClutchRow {
+    fn clutch_factor(&self) -> f64 {
+        self.long_winrate - self.short_winrate
+    }
+}
+
+pub struct Clutch {
+    rows: Vec<ClutchRow>,
+}
+
+impl Clutch {
+    pub fn new(stats: &Stats) -> Self {
+        let mut rows = stats
+            .records()
+            .filter_map(|(species, s)| {
+                let short_games = s.games - s.long_games;
+                if short_games == 0 || s.long_games == 0 {
+                    return None;
+                }
+                Some(ClutchRow {
+                    species: species.to_string(),
+                    display_name: stats.display_name(species).to_string(),
+                    short_winrate: (s.wins - s.long_wins) as f64 / short_games as f64 * 100.0,
+                    long_winrate: s.long_wins as f64 / s.long_games as f64 * 100.0,
+                    long_games: s.long_games,
+                })
+            })
+            .collect::<Vec<_>>();
+
+        rows.sort_by(|a, b| {
+            b.clutch_factor()
+                .partial_cmp(&a.clutch_factor())
+                .unwrap()
+                .then_with(|| a.species.cmp(&b.species))
+        });
+
+        Self { rows }
+    }
+}
+
+impl Output for Clutch {
+    fn to_csv(&mut self) -> String {
+        Itertools::intersperse(
+            self.rows.iter().map(|row| {
+                [
+                    row.species.clone(),
+                    format_float(row.short_winrate),
+                    format_float(row.long_winrate),
+                    format_float(row.clutch_factor()),
+                    row.long_games.to_string(),
+                ]
+                .join(",")
+            }),
+            String::from("\n"),
+        )
+        .collect()
+    }
+
+    fn to_human_readable(&mut self) -> String {
+        table::blocks_to_text(&self.to_blocks())
+    }
+
+    fn to_blocks(&mut self) -> Vec<Block> {
+        let short_title = format!("Winrate (<= {} turns)", LONG_GAME_TURNS);
+        let long_title = format!("Winrate (> {} turns)", LONG_GAME_TURNS);
+        let mut table = Table::new(&[
+            "Pokemon",
+            &short_title,
+            &long_title,
+            "Clutch factor",
+            "Long games",
+        ]);
+
+        for row in &self.rows {
+            table.add_row(vec![
+                row.display_name.clone(),
+                format!("{}%", format_float(row.short_winrate)),
+                format!("{}%", format_float(row.long_winrate)),
+                format_float(row.clutch_factor()),
+                row.long_games.to_string(),
+            ]);
+        }
+
+        vec![Block::Table(table)]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stats::GameResult;
+
+    #[test]
+    fn test_clutch() {
+        let mut stats = Stats::new();
+        for (species, won, turns) in [
+            ("Blissey", true, 40),
+            ("Blissey", false, 5),
+            ("Blissey", true, 30),
+            ("Dragapult", false, 35),
+            ("Dragapult", true, 8),
+            ("Mew", true, 8),
+        ]
+        .iter()
+        {
+            stats.add_game_results(vec![GameResult {
+                species: species.to_string(),
+                won: *won,
+                turns: *turns,
+                rating: None,
+            }]);
+        }
+
+        assert_eq!(
+            Clutch::new(&stats).to_csv(),
+            "Blissey,0.000000,100.000000,100.000000,2\nDragapult,100.000000,0.000000,-100.000000,1"
+        );
+    }
+}
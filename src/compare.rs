@@ -0,0 +1,160 @@
+/// Comparison of species performance between two formats
+///
+/// Species that appear in both formats are ranked by how significant the difference between
+/// their two winrates is (a two-proportion z-test), so the species that depend on the format's
+/// rules (e.g. the blitz timer) float to the top.
+use crate::stats::{format_float, Output, Stats};
+use crate::table::{self, Block, Table};
+use itertools::Itertools;
+
+/// |z| at or above which a difference is flagged as notable (roughly p < 0.003)
+const NOTABLE_Z: f64 = 3.0;
+
+struct ComparisonRow {
+    species: String,
+    /// winrates as percentages
+    winrate_a: f64,
+    winrate_b: f64,
+    z: f64,
+}
+
+pub struct Comparison {
+    label_a: String,
+    label_b: String,
+    rows: Vec<ComparisonRow>,
+}
+
+impl Comparison {
+    pub fn new(label_a: &str, a: &Stats, label_b: &str, b: &Stats) -> Self {
+        let mut rows = vec![];
+        for (species, stats_a) in a.records() {
+            let stats_b = match b.get(species) {
+                Some(s) => s,
+                None => continue,
+            };
+
+            let (games_a, wins_a) = (stats_a.games as f64, stats_a.wins as f64);
+            let (games_b, wins_b) = (stats_b.games as f64, stats_b.wins as f64);
+            let rate_a = wins_a / games_a;
+            let rate_b = wins_b / games_b;
+            let pooled = (wins_a + wins_b) / (games_a + games_b);
+            let standard_error = (pooled * (1.0 - pooled) * (1.0 / games_a + 1.0 / games_b)).sqrt();
+            let z = if standard_error == 0.0 {
+                0.0
+            } else {
+                (rate_a - rate_b) / standard_error
+            };
+
+            rows.push(ComparisonRow {
+                species: species.to_string(),
+                winrate_a: rate_a * 100.0,
+                winrate_b: rate_b * 100.0,
+                z,
+            });
+        }
+
+        rows.sort_by(|x, y| {
+            y.z.abs()
+                .partial_cmp(&x.z.abs())
+                .unwrap()
+                .then_with(|| x.species.cmp(&y.species))
+        });
+
+        Self {
+            label_a: label_a.to_string(),
+            label_b: label_b.to_string(),
+            rows,
+        }
+    }
+}
+
+impl Output for Comparison {
+    fn to_csv(&mut self) -> String {
+        Itertools::intersperse(
+            self.rows.iter().map(|row| {
+                [
+                    row.species.clone(),
+                    format_float(row.winrate_a),
+                    format_float(row.winrate_b),
+                    format_float(row.winrate_a - row.winrate_b),
+                    format_float(row.z),
+                ]
+                .join(",")
+            }),
+            String::from("\n"),
+        )
+        .collect()
+    }
+
+    fn to_human_readable(&mut self) -> String {
+        table::blocks_to_text(&self.to_blocks())
+    }
+
+    fn to_blocks(&mut self) -> Vec<Block> {
+        let winrate_a = format!("{} winrate", self.label_a);
+        let winrate_b = format!("{} winrate", self.label_b);
+        let mut table = Table::new(&[
+            "Pokemon",
+            &winrate_a,
+            &winrate_b,
+            "Difference",
+            "Z",
+            "Notable",
+        ]);
+
+        for row in &self.rows {
+            table.add_row(vec![
+                row.species.clone(),
+                format!("{}%", format_float(row.winrate_a)),
+                format!("{}%", format_float(row.winrate_b)),
+                format_float(row.winrate_a - row.winrate_b),
+                format_float(row.z),
+                String::from(if row.z.abs() >= NOTABLE_Z { "*" } else { "" }),
+            ]);
+        }
+
+        vec![Block::Table(table)]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stats::GameResult;
+
+    fn stats_with(records: &[(&str, u32, u32)]) -> Stats {
+        let mut stats = Stats::new();
+        for (species, games, wins) in records {
+            for game in 0..*games {
+                stats.add_game_results(vec![GameResult {
+                    species: species.to_string(),
+                    won: game < *wins,
+                    turns: 1,
+                    rating: None,
+                }]);
+            }
+        }
+        stats
+    }
+
+    #[test]
+    fn test_comparison() {
+        let standard = stats_with(&[
+            ("Dragapult", 1000, 500),
+            ("Slowking", 1000, 600),
+            ("Mew", 10, 5),
+        ]);
+        let blitz = stats_with(&[("Dragapult", 1000, 600), ("Slowking", 1000, 590)]);
+        let mut comparison = Comparison::new("standard", &standard, "blitz", &blitz);
+
+        let csv = comparison.to_csv();
+        let lines = csv.lines().collect::<Vec<_>>();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].starts_with("Dragapult,50.000000,60.000000,-10.000000,-4.494666"));
+        assert!(lines[1].starts_with("Slowking,"));
+
+        let human = comparison.to_human_readable();
+        assert!(human.contains("| standard winrate | blitz winrate |"));
+        assert!(human.contains("| *       |"));
+    }
+}
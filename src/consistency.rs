@@ -0,0 +1,196 @@
+/// How much each species' winrate varies from day to day (`--consistency-output`)
+///
+/// Two species with the same monthly winrate can differ a lot: one wins a little more than
+/// half its games every day, while the other swings between great and terrible days. This
+/// gives the standard deviation of each species' daily winrates, and a consistency score: the
+/// share of days its winrate was on the same side of 50% as its overall winrate.
+///
+/// Days where a species played fewer than `MIN_DAY_GAMES` games are left out, since their
+/// winrates are mostly noise.
+use crate::filter::BattleFilter;
+use crate::pass;
+use crate::stats::{format_float, Output};
+use crate::table::{self, Block, Table};
+use indexmap::IndexMap;
+use itertools::Itertools;
+use std::io;
+use std::path::PathBuf;
+
+/// A species' day only counts if it played at least this many games that day
+const MIN_DAY_GAMES: u32 = 10;
+
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+struct Record {
+    games: u32,
+    wins: u32,
+}
+
+impl Record {
+    fn winrate(&self) -> f64 {
+        self.wins as f64 / self.games as f64 * 100.0
+    }
+}
+
+struct ConsistencyRow {
+    species: String,
+    /// Days with at least `MIN_DAY_GAMES` games
+    days: usize,
+    /// Over those days
+    winrate: f64,
+    /// Standard deviation of the daily winrates, in percentage points
+    daily_deviation: f64,
+    /// Share of days on the same side of 50% as `winrate`, as a percentage
+    consistency: f64,
+}
+
+impl ConsistencyRow {
+    /// None unless the species has at least two days to compare
+    fn new(species: String, days: &[Record]) -> Option<Self> {
+        let days = days
+            .iter()
+            .filter(|day| day.games >= MIN_DAY_GAMES)
+            .collect::<Vec<_>>();
+        if days.len() < 2 {
+            return None;
+        }
+
+        let games = days.iter().map(|day| day.games).sum::<u32>();
+        let wins = days.iter().map(|day| day.wins).sum::<u32>();
+        let winrate = Record { games, wins }.winrate();
+
+        let daily = days.iter().map(|day| day.winrate()).collect::<Vec<_>>();
+        let mean = daily.iter().sum::<f64>() / daily.len() as f64;
+        let variance =
+            daily.iter().map(|w| (w - mean).powi(2)).sum::<f64>() / (daily.len() - 1) as f64;
+
+        let same_side = daily
+            .iter()
+            .filter(|w| (**w >= 50.0) == (winrate >= 50.0))
+            .count();
+        Some(ConsistencyRow {
+            species,
+            days: days.len(),
+            winrate,
+            daily_deviation: variance.sqrt(),
+            consistency: same_side as f64 / days.len() as f64 * 100.0,
+        })
+    }
+}
+
+pub struct ConsistencyReport {
+    rows: Vec<ConsistencyRow>,
+}
+
+impl ConsistencyReport {
+    pub fn new<'a>(
+        days: impl Iterator<Item = &'a [PathBuf]>,
+        min_elo: u64,
+        filter: &BattleFilter,
+    ) -> io::Result<Self> {
+        // species:record for each day, in day order
+        let mut records: IndexMap<String, Vec<Record>> = IndexMap::new();
+        let mut scan = pass::Scan::new(min_elo, filter);
+        for (day, files) in days.enumerate() {
+            let battles = scan.day(files, pass::sides)?;
+            for (_, won, species) in battles.into_iter().flatten() {
+                for species in species {
+                    let days = records.entry(species).or_default();
+                    days.resize(day + 1, Record::default());
+                    days[day].games += 1;
+                    days[day].wins += won as u32;
+                }
+            }
+        }
+        Ok(Self::from_records(records))
+    }
+
+    /// Most consistent species first
+    fn from_records(records: IndexMap<String, Vec<Record>>) -> Self {
+        let mut rows = records
+            .into_iter()
+            .filter_map(|(species, days)| ConsistencyRow::new(species, &days))
+            .collect::<Vec<_>>();
+        rows.sort_by(|a, b| {
+            b.consistency
+                .partial_cmp(&a.consistency)
+                .unwrap()
+                .then_with(|| a.daily_deviation.partial_cmp(&b.daily_deviation).unwrap())
+                .then_with(|| a.species.cmp(&b.species))
+        });
+        ConsistencyReport { rows }
+    }
+}
+
+impl Output for ConsistencyReport {
+    fn to_csv(&mut self) -> String {
+        Itertools::intersperse(
+            self.rows.iter().map(|row| {
+                [
+                    row.species.clone(),
+                    row.days.to_string(),
+                    format_float(row.winrate),
+                    format_float(row.daily_deviation),
+                    format_float(row.consistency),
+                ]
+                .join(",")
+            }),
+            String::from("\n"),
+        )
+        .collect()
+    }
+
+    fn to_human_readable(&mut self) -> String {
+        table::blocks_to_text(&self.to_blocks())
+    }
+
+    fn to_blocks(&mut self) -> Vec<Block> {
+        let days_title = format!("Days (>= {} games)", MIN_DAY_GAMES);
+        let mut table = Table::new(&[
+            "Pokemon",
+            "Consistency",
+            "Daily winrate std. dev.",
+            "Winrate",
+            &days_title,
+        ]);
+        for row in &self.rows {
+            table.add_row(vec![
+                row.species.clone(),
+                format!("{}%", format_float(row.consistency)),
+                format_float(row.daily_deviation),
+                format!("{}%", format_float(row.winrate)),
+                row.days.to_string(),
+            ]);
+        }
+        vec![Block::Table(table)]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_consistency() {
+        let day = |games, wins| Record { games, wins };
+        let mut records = IndexMap::new();
+        // 60% every day
+        records.insert(
+            String::from("Blissey"),
+            vec![day(10, 6), day(20, 12), day(10, 6)],
+        );
+        // 55% overall, but only from one great day; the 5-game day doesn't count
+        records.insert(
+            String::from("Dragapult"),
+            vec![day(10, 4), day(20, 14), day(10, 4), day(5, 5)],
+        );
+        // only one day with enough games
+        records.insert(String::from("Mew"), vec![day(10, 10), day(1, 0)]);
+
+        let mut report = ConsistencyReport::from_records(records);
+        assert_eq!(
+            report.to_csv(),
+            "Blissey,3,60.000000,0.000000,100.000000\n\
+             Dragapult,3,55.000000,17.320508,33.333333"
+        );
+    }
+}
@@ -0,0 +1,137 @@
+/// Adversarial battles the parser has to survive
+///
+/// Every case runs through the full per-battle path (`Stats::process_battle` with every log
+/// analysis enabled); each must either be analyzed or rejected with a `BattleError`, never
+/// panic. Add new cases here whenever a malformed battle turns up in real logs.
+use crate::filter::BattleFilter;
+use crate::log::LogAnalysis;
+use crate::stats::{Stats, StatsError};
+use crate::validate::BattleError;
+
+const ALL_ANALYSES: [LogAnalysis; 4] = [
+    LogAnalysis::Leads,
+    LogAnalysis::KOs,
+    LogAnalysis::Tera,
+    LogAnalysis::Hazards,
+];
+
+/// (description, battle JSON, expected error, if it should be rejected)
+const CASES: [(&str, &str, Option<BattleError>); 12] = [
+    ("empty file", "", Some(BattleError::InvalidJson)),
+    ("whitespace", "  \n", Some(BattleError::InvalidJson)),
+    (
+        "not an object",
+        r#""p1team""#,
+        Some(BattleError::NotAnObject),
+    ),
+    (
+        "truncated",
+        r#"{"winner":"A","p1":"A","p2":"B","p1team":[{"species":"Me"#,
+        Some(BattleError::InvalidJson),
+    ),
+    (
+        "nickname closing the object",
+        r#"{"winner":"A","p1":"A","p2":"B","p1team":[{"name":"\"},{\"","species":"Mew"}],"p2team":[{"species":"Ditto"}],"log":["|switch|p1a: \"},{\"|Mew|100/100"]}"#,
+        None,
+    ),
+    (
+        "unescaped nickname",
+        r#"{"winner":"A","p1":"A","p2":"B","p1team":[{"name":""},{"","species":"Mew"}],"p2team":[{"species":"Ditto"}]}"#,
+        Some(BattleError::InvalidJson),
+    ),
+    (
+        "huge unicode name",
+        concat!(
+            r#"{"winner":"ÅÅÅÅÅÅÅÅÅÅÅÅÅÅÅÅÅÅÅÅÅÅÅÅÅÅÅÅÅÅÅÅÅÅÅÅÅÅÅÅÅÅÅÅÅÅÅÅÅÅ🦀🦀🦀🦀🦀🦀🦀🦀🦀🦀","#,
+            r#""p1":"ÅÅÅÅÅÅÅÅÅÅÅÅÅÅÅÅÅÅÅÅÅÅÅÅÅÅÅÅÅÅÅÅÅÅÅÅÅÅÅÅÅÅÅÅÅÅÅÅÅÅ🦀🦀🦀🦀🦀🦀🦀🦀🦀🦀","#,
+            r#""p2":"\u0000\ud83e","p1team":[{"species":"Ｍｅｗ"}],"p2team":[{"species":"🦀"}]}"#,
+        ),
+        None,
+    ),
+    (
+        "no teams",
+        r#"{"winner":"A","p1":"A","p2":"B"}"#,
+        Some(BattleError::NoTeams),
+    ),
+    (
+        "team of strings",
+        r#"{"p1team":["Mew"],"p2team":[{"species":"Ditto"}]}"#,
+        Some(BattleError::InvalidTeam("p1")),
+    ),
+    (
+        "numeric species",
+        r#"{"p1team":[{"species":151}],"p2team":[{"species":"Ditto"}]}"#,
+        Some(BattleError::InvalidTeam("p1")),
+    ),
+    (
+        "log as a string",
+        r#"{"p1team":[{"species":"Mew"}],"p2team":[{"species":"Ditto"}],"log":"|win|A"}"#,
+        Some(BattleError::InvalidField("log")),
+    ),
+    (
+        "malformed log lines",
+        r#"{"winner":"A","p1":"A","p2":"B","p1team":[{"species":"Mew"}],"p2team":[{"species":"Ditto"}],"turns":3,"log":["|switch|","|switch|p9z: X|","|faint|p1a","|move||||","|-terastallize|","|-sidestart|p5|","|turn|x",7,null]}"#,
+        None,
+    ),
+];
+
+fn process(json: &str) -> Result<(), BattleError> {
+    match Stats::process_battle(json, 0, &ALL_ANALYSES, &BattleFilter::default()) {
+        Ok(_) => Ok(()),
+        Err(StatsError::Battle(e)) => Err(e),
+        Err(e) => panic!("unexpected error {:?}", e),
+    }
+}
+
+#[test]
+fn test_corpus() {
+    for (description, json, expected) in CASES.iter() {
+        let expected = match expected {
+            Some(e) => Err(e.clone()),
+            None => Ok(()),
+        };
+        assert_eq!(process(json), expected, "{}", description);
+    }
+}
+
+/// Every prefix of a real battle, as left by a write that was cut off
+#[test]
+fn test_truncations() {
+    let json = include_str!("benchmark-data.json");
+    let mut end = 0;
+    while end < json.len() {
+        process(&json[..end]).ok();
+        end += 1;
+        while !json.is_char_boundary(end) {
+            end += 1;
+        }
+    }
+}
+
+/// A real battle with random bytes overwritten by JSON punctuation
+#[test]
+fn test_mutations() {
+    let json = include_str!("benchmark-data.json");
+    let punctuation = b"{}[]\",:\\0 ";
+    // xorshift, so failures are reproducible
+    let mut state: u64 = 0x2545_f491_4f6c_dd1d;
+    let mut next = || {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        state as usize
+    };
+
+    for _ in 0..2_000 {
+        let mut bytes = json.as_bytes().to_vec();
+        for _ in 0..1 + next() % 4 {
+            let idx = next() % bytes.len();
+            if bytes[idx].is_ascii() {
+                bytes[idx] = punctuation[next() % punctuation.len()];
+            }
+        }
+        if let Ok(mutated) = String::from_utf8(bytes) {
+            process(&mutated).ok();
+        }
+    }
+}
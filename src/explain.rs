@@ -0,0 +1,189 @@
+/// How the outputs' numbers are computed (`explain-metrics`)
+///
+/// Published winrates are only as trustworthy as readers' understanding of them, so this prints
+/// each formula with the parameters the given options would use. Every formula ends with a
+/// worked example that calls the same code the outputs do, so the explanation can't drift from
+/// the numbers.
+use crate::filter::Sample;
+use crate::publish::{wilson_interval, Z};
+use crate::recency::HalfLife;
+use crate::stats::{format_float, PokemonStats, LONG_GAME_TURNS};
+
+/// Games and wins in the worked examples
+const EXAMPLE_GAMES: u32 = 1000;
+const EXAMPLE_WINS: u32 = 540;
+/// An older day and the newest one, for the recency weighting example
+const EXAMPLE_DAYS: [&str; 2] = ["2024-01-01", "2024-01-08"];
+
+/// The options that change how metrics are computed
+pub struct MetricsConfig {
+    pub min_elo: u64,
+    pub include_unrated: bool,
+    pub recency_half_life: Option<HalfLife>,
+    pub provisional_below: Option<u32>,
+    pub sample: Option<Sample>,
+}
+
+impl MetricsConfig {
+    pub fn explain(&self) -> String {
+        let example = PokemonStats {
+            games: EXAMPLE_GAMES,
+            wins: EXAMPLE_WINS,
+            weighted_games: EXAMPLE_GAMES as f64,
+            weighted_wins: EXAMPLE_WINS as f64,
+            ..PokemonStats::default()
+        };
+        let final_stats = example.final_stats();
+        let (lower, upper) = wilson_interval(example.weighted_wins, example.weighted_games);
+
+        let mut sections = vec![];
+
+        let mut counting = vec![
+            String::from("Each team counts one game for every species on it, and a win for each if the team won."),
+            format!(
+                "Battles where any player is rated below {} are skipped; unrated players count as 0.",
+                self.min_elo
+            ),
+        ];
+        counting.push(if self.include_unrated {
+            String::from("Battles where no player is rated are counted anyway.")
+        } else {
+            String::from("Battles where no player is rated are skipped.")
+        });
+        if let Some(sample) = self.sample {
+            counting.push(format!(
+                "Only {} are read; counts cover the sample, not the full input.",
+                sample
+            ));
+        }
+        sections.push(("Games and wins", counting));
+
+        sections.push((
+            "Weighting",
+            match self.recency_half_life {
+                Some(half_life) => {
+                    let weights = half_life.day_weights(EXAMPLE_DAYS.iter().cloned());
+                    vec![
+                        format!(
+                            "weight = 0.5^(age / {}), where age is how long before the newest day in the input a battle's day is",
+                            half_life
+                        ),
+                        String::from("Winrates and deviations use weighted games and wins; game and win counts are raw."),
+                        format!(
+                            "Example: a battle on {} when the newest day is {} has weight {}",
+                            EXAMPLE_DAYS[0],
+                            EXAMPLE_DAYS[1],
+                            format_float(weights[0])
+                        ),
+                    ]
+                }
+                None => vec![String::from(
+                    "Every game has weight 1, so weighted games and wins are the raw counts.",
+                )],
+            },
+        ));
+
+        sections.push((
+            "Winrate",
+            vec![
+                String::from("winrate = 100 * weighted wins / weighted games"),
+                format!(
+                    "Example: {} wins in {} games gives {}%",
+                    EXAMPLE_WINS,
+                    EXAMPLE_GAMES,
+                    format_float(final_stats.winrate)
+                ),
+            ],
+        ));
+
+        sections.push((
+            "Deviations",
+            vec![
+                String::from("deviations = (winrate - 50) * sqrt(weighted games) / 50"),
+                String::from("How many standard deviations the winrate is from 50%, if every game were a coin flip."),
+                format!(
+                    "Example: {} wins in {} games gives {}",
+                    EXAMPLE_WINS,
+                    EXAMPLE_GAMES,
+                    format_float(final_stats.deviations)
+                ),
+            ],
+        ));
+
+        sections.push((
+            "Wilson bound (--publish-output)",
+            vec![
+                format!("With p = weighted wins / weighted games, n = weighted games, and z = {}:", Z),
+                String::from("center = (p + z^2 / 2n) / (1 + z^2 / n)"),
+                String::from("margin = z / (1 + z^2 / n) * sqrt(p(1 - p) / n + z^2 / 4n^2)"),
+                String::from("The 95% interval is center +/- margin; only species whose interval excludes 50% are published."),
+                format!(
+                    "Example: {} wins in {} games gives {}% to {}%",
+                    EXAMPLE_WINS,
+                    EXAMPLE_GAMES,
+                    format_float(lower),
+                    format_float(upper)
+                ),
+            ],
+        ));
+
+        sections.push((
+            "Shrinkage",
+            match self.provisional_below {
+                Some(games) => vec![
+                    String::from("Winrates aren't shrunk towards 50%."),
+                    format!(
+                        "Species with fewer than {} games are listed separately as provisional.",
+                        games
+                    ),
+                ],
+                None => vec![String::from(
+                    "Winrates aren't shrunk towards 50%; see --provisional-below to set low-sample species apart.",
+                )],
+            },
+        ));
+
+        sections.push((
+            "Long games (--clutch-output)",
+            vec![format!(
+                "Games lasting more than {} turns are long games.",
+                LONG_GAME_TURNS
+            )],
+        ));
+
+        sections
+            .into_iter()
+            .map(|(title, lines)| {
+                let mut section = format!("{}\n", title);
+                for line in lines {
+                    section.push_str(&format!("  {}\n", line));
+                }
+                section
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_explain() {
+        let config = MetricsConfig {
+            min_elo: 1300,
+            include_unrated: false,
+            recency_half_life: Some("7d".parse().unwrap()),
+            provisional_below: None,
+            sample: None,
+        };
+        let explanation = config.explain();
+        assert!(explanation.contains("rated below 1300 are skipped"));
+        assert!(explanation.contains("weight = 0.5^(age / 7 days)"));
+        assert!(explanation.contains("has weight 0.500000\n"));
+        assert!(explanation.contains("gives 54.000000%\n"));
+        assert!(explanation.contains("gives 2.529822\n"));
+        assert!(explanation.contains("gives 50.901472% to 57.067913%\n"));
+    }
+}
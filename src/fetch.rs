@@ -0,0 +1,226 @@
+/// Downloading public replays into the input layout (`fetch-logs`)
+///
+/// Replays are found with the replay server's search API, newest first, and converted into the
+/// battle JSON this tool reads, one file per battle under a directory per day. Replays don't
+/// include teams, so each team is the Pokémon that were sent out during the battle, and ratings
+/// come from the `|player|` lines. Requests are made with `curl`, which has to be installed.
+use crate::stats::{format_timestamp, parse_day, StatsError};
+use std::fs;
+use std::path::Path;
+use std::process;
+use std::thread;
+use std::time::Duration;
+
+const REPLAY_SERVER: &str = "https://replay.pokemonshowdown.com";
+/// The search API returns this many replays when there are more to fetch
+const PAGE_SIZE: usize = 51;
+/// Pause between requests, to go easy on the replay server
+const REQUEST_INTERVAL: Duration = Duration::from_millis(200);
+
+fn get(url: &str) -> Result<String, StatsError> {
+    thread::sleep(REQUEST_INTERVAL);
+    let output = process::Command::new("curl")
+        .args(["--silent", "--show-error", "--fail", "--location", url])
+        .output()?;
+    if !output.status.success() {
+        return Err(StatsError::Fetch(format!(
+            "{}: {}",
+            url,
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    String::from_utf8(output.stdout)
+        .map_err(|_| StatsError::Fetch(format!("{}: response isn't UTF-8", url)))
+}
+
+/// Quotes and escapes a string for JSON
+pub(crate) fn json_string(s: &str) -> String {
+    let mut quoted = String::with_capacity(s.len() + 2);
+    quoted.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            c if (c as u32) < 0x20 => quoted.push_str(&format!("\\u{:04x}", c as u32)),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// Converts a replay's protocol log into battle JSON, or `None` if the battle never finished
+pub fn battle_from_log(log: &str, format: &str) -> Option<String> {
+    let mut players = vec![];
+    let mut ratings = vec![];
+    let mut teams: Vec<Vec<&str>> = vec![];
+    let mut winner = None;
+    let mut turns = 0;
+
+    for line in log.lines() {
+        let mut parts = line.split('|').skip(1);
+        match parts.next() {
+            Some("player") => {
+                let side = parts.next().unwrap_or("");
+                let name = parts.next().unwrap_or("");
+                if name.is_empty() || players.iter().any(|(s, _)| *s == side) {
+                    continue;
+                }
+                players.push((side, name));
+                ratings.push(parts.nth(1).and_then(|r| r.parse::<u32>().ok()));
+                teams.push(vec![]);
+            }
+            Some("switch") | Some("drag") | Some("replace") => {
+                // |switch|p1a: Nickname|Species, L84, F|100/100
+                let side = parts.next().unwrap_or("").get(..2).unwrap_or("");
+                let species = parts.next().unwrap_or("").split(", ").next().unwrap_or("");
+                if let Some(idx) = players.iter().position(|(s, _)| *s == side) {
+                    if !species.is_empty() && !teams[idx].contains(&species) {
+                        teams[idx].push(species);
+                    }
+                }
+            }
+            Some("turn") => {
+                if let Some(turn) = parts.next().and_then(|t| t.parse().ok()) {
+                    turns = turn;
+                }
+            }
+            Some("win") => winner = Some(parts.next().unwrap_or("")),
+            Some("tie") => winner = Some(""),
+            _ => {}
+        }
+    }
+
+    let winner = winner?;
+    let mut fields = vec![
+        format!("\"winner\":{}", json_string(winner)),
+        format!("\"turns\":{}", turns),
+        format!("\"format\":{}", json_string(format)),
+    ];
+    for (((side, name), rating), team) in players.iter().zip(&ratings).zip(&teams) {
+        fields.push(format!("\"{}\":{}", side, json_string(name)));
+        let team = team
+            .iter()
+            .map(|species| format!("{{\"species\":{}}}", json_string(species)))
+            .collect::<Vec<_>>();
+        fields.push(format!("\"{}team\":[{}]", side, team.join(",")));
+        if let Some(rating) = rating {
+            fields.push(format!("\"{}rating\":{{\"elo\":{}}}", side, rating));
+        }
+    }
+    let log = log
+        .lines()
+        .filter(|line| !line.is_empty())
+        .map(json_string)
+        .collect::<Vec<_>>();
+    fields.push(format!("\"log\":[{}]", log.join(",")));
+
+    Some(format!("{{{}}}", fields.join(",")))
+}
+
+/// Downloads every public replay of `format` uploaded since the start of `since` (YYYY-MM-DD)
+/// into `out/<day>/<replay id>.json`, skipping replays that were already downloaded
+pub fn fetch_logs(format: &str, since: &str, out: &Path) -> Result<(), StatsError> {
+    let since = parse_day(since)
+        .ok_or_else(|| StatsError::Fetch(format!("'{}' isn't a day (YYYY-MM-DD)", since)))?;
+
+    let (mut fetched, mut skipped) = (0, 0);
+    let mut before = None;
+    loop {
+        let mut url = format!("{}/search.json?format={}", REPLAY_SERVER, format);
+        if let Some(before) = before {
+            url += &format!("&before={}", before);
+        }
+        let page = get(&url)?;
+        let page = gjson::parse(&page);
+        let replays = page.array();
+
+        for replay in replays.iter().take(PAGE_SIZE - 1) {
+            let uploaded = replay.get("uploadtime").u64();
+            if uploaded < since {
+                println!("Fetched {} replays ({} already present)", fetched, skipped);
+                return Ok(());
+            }
+            before = Some(uploaded);
+            let id = replay.get("id").str().to_string();
+            if replay.get("private").u64() != 0 || id.contains(['/', '\\']) {
+                continue;
+            }
+
+            let day_dir = out.join(&format_timestamp(uploaded)[..10]);
+            let path = day_dir.join(format!("{}.json", id));
+            if path.exists() {
+                skipped += 1;
+                continue;
+            }
+            let replay_json = get(&format!("{}/{}.json", REPLAY_SERVER, id))?;
+            match battle_from_log(gjson::get(&replay_json, "log").str(), format) {
+                Some(battle) => {
+                    fs::create_dir_all(&day_dir)?;
+                    fs::write(&path, battle)?;
+                    fetched += 1;
+                }
+                None => eprintln!("Skipping unfinished replay {}", id),
+            }
+        }
+
+        if replays.len() < PAGE_SIZE {
+            println!("Fetched {} replays ({} already present)", fetched, skipped);
+            return Ok(());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::filter::BattleFilter;
+    use crate::stats::Stats;
+
+    #[test]
+    fn test_battle_from_log() {
+        let log = "|j|☆Annika\n|player|p1|Annika|cynthia|1400\n|player|p2|Rust \"Hater\"|2|1300\n\
+                   |start\n|switch|p1a: Rotom|Rotom-Fan|100/100\n|switch|p2a: Tusk|Great Tusk, L78|100/100\n\
+                   |turn|1\n|drag|p2a: Pult|Dragapult, L80, F|100/100\n|switch|p1a: Rotom|Rotom-Fan|100/100\n\
+                   |turn|2\n|win|Annika";
+        let battle = battle_from_log(log, "gen9randombattle").unwrap();
+        assert_eq!(
+            battle,
+            concat!(
+                r#"{"winner":"Annika","turns":2,"format":"gen9randombattle","#,
+                r#""p1":"Annika","p1team":[{"species":"Rotom-Fan"}],"p1rating":{"elo":1400},"#,
+                r#""p2":"Rust \"Hater\"","p2team":[{"species":"Great Tusk"},{"species":"Dragapult"}],"p2rating":{"elo":1300},"#,
+                r#""log":["|j|☆Annika","|player|p1|Annika|cynthia|1400","|player|p2|Rust \"Hater\"|2|1300","#,
+                r#""|start","|switch|p1a: Rotom|Rotom-Fan|100/100","|switch|p2a: Tusk|Great Tusk, L78|100/100","#,
+                r#""|turn|1","|drag|p2a: Pult|Dragapult, L80, F|100/100","|switch|p1a: Rotom|Rotom-Fan|100/100","#,
+                r#""|turn|2","|win|Annika"]}"#,
+            )
+        );
+
+        let results = Stats::process_battle(&battle, 1000, &[], &BattleFilter::default()).unwrap();
+        let won = results
+            .results
+            .iter()
+            .map(|r| (r.species.as_str(), r.won))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            won,
+            vec![
+                ("Rotom-Fan", true),
+                ("Great Tusk", false),
+                ("Dragapult", false)
+            ]
+        );
+
+        // unrated players get no rating, and unfinished battles are skipped
+        let unrated = battle_from_log("|player|p1|A||\n|win|A", "gen9randombattle").unwrap();
+        assert!(!unrated.contains("p1rating"));
+        assert_eq!(
+            battle_from_log("|player|p1|A||\n|turn|1", "gen9randombattle"),
+            None
+        );
+    }
+}
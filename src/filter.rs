@@ -0,0 +1,601 @@
+/// Battle-level filters applied before a battle's results are counted
+use crate::annotations::Annotations;
+use crate::hash::Fnv1a;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::hash::Hasher;
+use std::io;
+use std::path::Path;
+use std::str::FromStr;
+
+/// One of `count` deterministic slices of the input files, for splitting a run across machines
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Shard {
+    /// 1-based
+    index: u64,
+    count: u64,
+}
+
+impl FromStr for Shard {
+    type Err = String;
+
+    /// Parses `k/n`, e.g. `2/8` for the second of eight shards
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let error = || format!("invalid shard '{}' (expected k/n with 1 <= k <= n)", s);
+        let (index, count) = s.split_once('/').ok_or_else(error)?;
+        let index = index.parse::<u64>().map_err(|_| error())?;
+        let count = count.parse::<u64>().map_err(|_| error())?;
+        if index == 0 || index > count {
+            return Err(error());
+        }
+        Ok(Self { index, count })
+    }
+}
+
+impl std::fmt::Display for Shard {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}/{}", self.index, self.count)
+    }
+}
+
+impl Shard {
+    /// Whether a file (given by its path relative to the format directory) belongs to this shard
+    pub fn includes(&self, relative_path: &Path) -> bool {
+        let mut hasher = Fnv1a::default();
+        hasher.write(relative_path.to_string_lossy().as_bytes());
+        hasher.finish() % self.count == self.index - 1
+    }
+}
+
+/// A deterministic fraction of the input files, for quick estimates
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Sample {
+    /// In (0, 1]
+    pub fraction: f64,
+}
+
+impl FromStr for Sample {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.parse::<f64>() {
+            Ok(fraction) if fraction > 0.0 && fraction <= 1.0 => Ok(Self { fraction }),
+            _ => Err(format!(
+                "invalid sample '{}' (expected a fraction greater than 0 and at most 1)",
+                s
+            )),
+        }
+    }
+}
+
+impl std::fmt::Display for Sample {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{:.2}% of files", self.fraction * 100.0)
+    }
+}
+
+impl Sample {
+    /// Whether a file (given by its path relative to the format directory) is in the sample
+    pub fn includes(&self, relative_path: &Path) -> bool {
+        let mut hasher = Fnv1a::default();
+        // salted, so the sample doesn't line up with the shards
+        hasher.write(b"sample:");
+        hasher.write(relative_path.to_string_lossy().as_bytes());
+        (hasher.finish() % 1_000_000) as f64 / 1_000_000.0 < self.fraction
+    }
+}
+
+/// Which file names in a day directory are battles, as a glob where `*` matches any run of
+/// characters and `?` matches one (e.g. `*.json*` for mirrors that store `.json.log` files)
+#[derive(Clone, Debug, PartialEq)]
+pub struct FilePattern {
+    pattern: Vec<char>,
+}
+
+impl Default for FilePattern {
+    fn default() -> Self {
+        "*.json".parse().unwrap()
+    }
+}
+
+impl FromStr for FilePattern {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.is_empty() || s.contains('/') {
+            return Err(format!(
+                "invalid file pattern '{}' (expected a file name glob like *.json)",
+                s
+            ));
+        }
+        Ok(Self {
+            pattern: s.chars().collect(),
+        })
+    }
+}
+
+impl std::fmt::Display for FilePattern {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.pattern.iter().collect::<String>())
+    }
+}
+
+impl FilePattern {
+    /// Whether a file name (without its directory) matches the pattern
+    pub fn matches(&self, name: &str) -> bool {
+        let name = name.chars().collect::<Vec<_>>();
+        // matched[j]: whether the pattern so far matches the first j characters of the name
+        let mut matched = vec![false; name.len() + 1];
+        matched[0] = true;
+        for &p in &self.pattern {
+            if p == '*' {
+                for j in 1..=name.len() {
+                    matched[j] |= matched[j - 1];
+                }
+            } else {
+                for j in (1..=name.len()).rev() {
+                    matched[j] = matched[j - 1] && (p == '?' || p == name[j - 1]);
+                }
+                matched[0] = false;
+            }
+        }
+        matched[name.len()]
+    }
+}
+
+/// The kind of battle timer a game was played with
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Timer {
+    /// The standard ladder timer
+    Standard,
+    /// The super-fast 'Blitz' timer (10 seconds per turn)
+    Blitz,
+}
+
+impl FromStr for Timer {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "standard" => Ok(Timer::Standard),
+            "blitz" => Ok(Timer::Blitz),
+            _ => Err(format!(
+                "unknown timer '{}' (expected standard or blitz)",
+                s
+            )),
+        }
+    }
+}
+
+impl std::fmt::Display for Timer {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str(match self {
+            Timer::Standard => "standard",
+            Timer::Blitz => "blitz",
+        })
+    }
+}
+
+impl Timer {
+    /// Works out the timer from the format ID or, for battles in a shared directory,
+    /// from the `|rule|Blitz: ...` line the server logs for blitz formats
+    pub fn of_battle(json: &str) -> Self {
+        if gjson::get(json, "format").str().ends_with("blitz") {
+            return Timer::Blitz;
+        }
+
+        let log = gjson::get(json, "log");
+        let is_blitz = log
+            .array()
+            .iter()
+            .any(|line| line.str().starts_with("|rule|Blitz"));
+        if is_blitz {
+            Timer::Blitz
+        } else {
+            Timer::Standard
+        }
+    }
+}
+
+/// What kind of format the battles are from, which decides which team members are counted
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub enum FormatKind {
+    /// Random formats: every generated team member counts
+    #[default]
+    Random,
+    /// Standard formats: only team members that were sent out count, since with team preview
+    /// (e.g. bring 6, pick 4) the rest of the team never played
+    Standard,
+}
+
+impl FromStr for FormatKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "random" => Ok(FormatKind::Random),
+            "standard" => Ok(FormatKind::Standard),
+            _ => Err(format!(
+                "unknown format kind '{}' (expected random or standard)",
+                s
+            )),
+        }
+    }
+}
+
+impl std::fmt::Display for FormatKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str(match self {
+            FormatKind::Random => "random",
+            FormatKind::Standard => "standard",
+        })
+    }
+}
+
+/// Battles that end by forfeit or inactivity within this many turns count as abandoned early
+pub const EARLY_ABANDON_TURNS: u32 = 5;
+
+/// Whether a battle ended early because a player forfeited or disconnected (lost to the
+/// inactivity timer), rather than being played out
+pub fn is_abandoned_early(json: &str) -> bool {
+    // most battles record their length, so the log only has to be read for short ones
+    let turns = gjson::get(json, "turns");
+    if turns.exists() && turns.u32() > EARLY_ABANDON_TURNS {
+        return false;
+    }
+    if gjson::get(json, "endType").str() == "forfeit" {
+        return true;
+    }
+
+    let mut log_turns = 0;
+    let mut abandoned = false;
+    gjson::get(json, "log").each(|_, line| {
+        let line = line.str();
+        if line.starts_with("|turn|") {
+            log_turns += 1;
+        } else if (line.starts_with("|-message|") || line.starts_with("|raw|"))
+            && (line.ends_with(" forfeited.") || line.ends_with(" lost due to inactivity."))
+        {
+            abandoned = true;
+        }
+        // without a `turns` field, stop once the log shows the battle went on long enough
+        turns.exists() || log_turns <= EARLY_ABANDON_TURNS
+    });
+    abandoned && (turns.exists() || log_turns <= EARLY_ABANDON_TURNS)
+}
+
+/// Whether a player name is an unregistered guest account, like `Guest 12345678`
+pub fn is_guest(name: &str) -> bool {
+    match name.strip_prefix("Guest ") {
+        Some(number) => !number.is_empty() && number.chars().all(|c| c.is_ascii_digit()),
+        None => false,
+    }
+}
+
+/// Converts a username to its Showdown ID: lowercase, with everything but letters and digits removed
+pub fn to_id(name: &str) -> String {
+    name.chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .map(|c| c.to_ascii_lowercase())
+        .collect()
+}
+
+/// Reads a file of usernames, one per line, ignoring blank lines and `#` comments
+pub fn read_player_list(path: &Path) -> io::Result<HashSet<String>> {
+    Ok(fs::read_to_string(path)?
+        .lines()
+        .filter(|line| !line.trim_start().starts_with('#'))
+        .map(to_id)
+        .filter(|id| !id.is_empty())
+        .collect())
+}
+
+/// Reads a JSON object mapping alt accounts' usernames to their main accounts' usernames, as
+/// alt ID:main ID
+pub fn read_alts(path: &Path) -> io::Result<HashMap<String, String>> {
+    let json = fs::read_to_string(path)?;
+    let value = gjson::parse(&json);
+    if !gjson::valid(&json) || value.kind() != gjson::Kind::Object {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "{} isn't a JSON object of alt:main usernames",
+                path.display()
+            ),
+        ));
+    }
+    let mut alts = HashMap::new();
+    value.each(|alt, main| {
+        alts.insert(to_id(alt.str()), to_id(main.str()));
+        true
+    });
+    Ok(alts)
+}
+
+/// Conditions a battle must meet to be analyzed
+#[derive(Debug, Default, PartialEq)]
+pub struct BattleFilter {
+    /// Only analyze battles played with this timer
+    pub timer: Option<Timer>,
+    /// Skip battles whose contents exactly match an earlier one
+    pub skip_duplicates: bool,
+    /// Analyze battles where no side has a rating, regardless of the minimum elo
+    pub include_unrated: bool,
+    /// Skip battles where any player is a guest account
+    pub exclude_guests: bool,
+    /// Skip battles that were forfeited or abandoned within the first few turns
+    pub exclude_abandoned: bool,
+    /// Skip battles involving any of these players (by ID)
+    pub excluded_players: HashSet<String>,
+    /// Only analyze battles involving at least one of these players (by ID)
+    pub only_players: Option<HashSet<String>>,
+    /// Skip battles involving any of these players (by ID), as found by `bots::find_suspected_bots`
+    pub suspected_bots: HashSet<String>,
+    /// Only analyze battles where every player is on this roster (by ID), as in a tournament
+    pub roster: Option<HashSet<String>>,
+    /// Only count sides whose opponent had this (normalized) species
+    pub opponent_species: Option<String>,
+    /// Which team members are counted
+    pub format_kind: FormatKind,
+    /// Alt account ID:main account ID, so a person's accounts count as one player
+    pub alts: HashMap<String, String>,
+    /// Known changes to species, which their stats are split at
+    pub annotations: Annotations,
+}
+
+impl BattleFilter {
+    pub fn accepts(&self, json: &str) -> bool {
+        match self.timer {
+            Some(timer) => Timer::of_battle(json) == timer,
+            None => true,
+        }
+    }
+
+    /// A player's ID, or their main account's ID if this is a known alt
+    pub fn player_id(&self, name: &str) -> String {
+        let id = to_id(name);
+        self.alts.get(&id).cloned().unwrap_or(id)
+    }
+
+    /// Maps any alts in the player lists to their main accounts, so that listing either
+    /// account covers both
+    pub fn resolve_alts(&mut self) {
+        let alts = &self.alts;
+        let resolve = |ids: &mut HashSet<String>| {
+            *ids = ids
+                .drain()
+                .map(|id| alts.get(&id).cloned().unwrap_or(id))
+                .collect();
+        };
+        resolve(&mut self.excluded_players);
+        resolve(&mut self.suspected_bots);
+        if let Some(ids) = &mut self.only_players {
+            resolve(ids);
+        }
+        if let Some(ids) = &mut self.roster {
+            resolve(ids);
+        }
+    }
+
+    /// Whether a battle between these players passes the player lists
+    pub fn accepts_players(&self, players: &[&str]) -> bool {
+        let ids = players
+            .iter()
+            .map(|p| self.player_id(p))
+            .collect::<Vec<_>>();
+        if ids.iter().any(|id| self.excluded_players.contains(id)) {
+            return false;
+        }
+        if let Some(roster) = &self.roster {
+            if !ids.iter().all(|id| roster.contains(id)) {
+                return false;
+            }
+        }
+        match &self.only_players {
+            Some(only) => ids.iter().any(|id| only.contains(id)),
+            None => true,
+        }
+    }
+
+    pub fn involves_suspected_bot(&self, players: &[&str]) -> bool {
+        !self.suspected_bots.is_empty()
+            && players
+                .iter()
+                .any(|p| self.suspected_bots.contains(&self.player_id(p)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_is_guest() {
+        assert!(is_guest("Guest 12345678"));
+        assert!(is_guest("Guest 1"));
+        assert!(!is_guest("Guest"));
+        assert!(!is_guest("Guest Star"));
+        assert!(!is_guest("Annika"));
+    }
+
+    #[test]
+    fn test_player_lists() {
+        assert_eq!(to_id("Annika (alt)"), "annikaalt");
+
+        let filter = BattleFilter {
+            excluded_players: ["badbot"].iter().map(|s| s.to_string()).collect(),
+            ..BattleFilter::default()
+        };
+        assert!(filter.accepts_players(&["Annika", "Marty"]));
+        assert!(!filter.accepts_players(&["Annika", "Bad Bot"]));
+
+        let filter = BattleFilter {
+            only_players: Some(["annika"].iter().map(|s| s.to_string()).collect()),
+            ..BattleFilter::default()
+        };
+        assert!(filter.accepts_players(&["ANNIKA", "Marty"]));
+        assert!(!filter.accepts_players(&["The Immortal", "Marty"]));
+
+        let filter = BattleFilter {
+            roster: Some(["annika", "marty"].iter().map(|s| s.to_string()).collect()),
+            ..BattleFilter::default()
+        };
+        assert!(filter.accepts_players(&["Annika", "Marty"]));
+        assert!(!filter.accepts_players(&["Annika", "The Immortal"]));
+    }
+
+    #[test]
+    fn test_alts() {
+        let path = std::env::temp_dir().join(format!(
+            "randbats-winrates-alts-test-{}.json",
+            std::process::id()
+        ));
+        fs::write(&path, r#"{"Annika (alt)": "Annika", "marty2": "Marty"}"#).unwrap();
+        let alts = read_alts(&path).unwrap();
+        fs::write(&path, r#"["annika"]"#).unwrap();
+        assert!(read_alts(&path).is_err());
+        fs::remove_file(&path).unwrap();
+
+        let mut filter = BattleFilter {
+            excluded_players: ["marty2"].iter().map(|s| s.to_string()).collect(),
+            only_players: Some(["annika"].iter().map(|s| s.to_string()).collect()),
+            alts,
+            ..BattleFilter::default()
+        };
+        filter.resolve_alts();
+        assert_eq!(filter.player_id("Annika (alt)"), "annika");
+        assert_eq!(filter.player_id("The Immortal"), "theimmortal");
+        // listing either account covers both
+        assert!(filter.accepts_players(&["Annika (alt)", "The Immortal"]));
+        assert!(!filter.accepts_players(&["Annika", "Marty"]));
+
+        filter.suspected_bots = ["marty"].iter().map(|s| s.to_string()).collect();
+        assert!(filter.involves_suspected_bot(&["The Immortal", "marty2"]));
+        filter.suspected_bots = ["annikaalt"].iter().map(|s| s.to_string()).collect();
+        filter.resolve_alts();
+        assert!(filter.involves_suspected_bot(&["Annika", "The Immortal"]));
+        assert!(!filter.involves_suspected_bot(&["Marty", "The Immortal"]));
+    }
+
+    #[test]
+    fn test_timer() {
+        assert_eq!(
+            Timer::of_battle(r#"{"format":"gen9randombattleblitz","log":[]}"#),
+            Timer::Blitz
+        );
+        assert_eq!(
+            Timer::of_battle(
+                r#"{"format":"gen9randombattle","log":["|gen|9","|rule|Blitz: Super-fast 'Blitz' timer giving 30 second Team Preview and 10 seconds per turn"]}"#
+            ),
+            Timer::Blitz
+        );
+        assert_eq!(
+            Timer::of_battle(r#"{"format":"gen9randombattle","log":["|gen|9"]}"#),
+            Timer::Standard
+        );
+    }
+
+    #[test]
+    fn test_sample() {
+        assert_eq!("0.1".parse::<Sample>(), Ok(Sample { fraction: 0.1 }));
+        assert!("0".parse::<Sample>().is_err());
+        assert!("1.5".parse::<Sample>().is_err());
+        assert_eq!(Sample { fraction: 0.25 }.to_string(), "25.00% of files");
+        assert_eq!(Sample { fraction: 0.07 }.to_string(), "7.00% of files");
+        assert_eq!(Sample { fraction: 0.0005 }.to_string(), "0.05% of files");
+
+        let paths = (0..1000)
+            .map(|n| PathBuf::from(format!("2024-01-01/{}.json", n)))
+            .collect::<Vec<_>>();
+        let tenth = Sample { fraction: 0.1 };
+        let sampled = paths.iter().filter(|p| tenth.includes(p)).count();
+        assert!((50..150).contains(&sampled));
+        // a larger sample contains every file of a smaller one
+        let half = Sample { fraction: 0.5 };
+        assert!(paths.iter().all(|p| !tenth.includes(p) || half.includes(p)));
+        assert!(paths.iter().all(|p| Sample { fraction: 1.0 }.includes(p)));
+    }
+
+    #[test]
+    fn test_file_pattern() {
+        let default = FilePattern::default();
+        assert_eq!(default.to_string(), "*.json");
+        assert!(default.matches("12345.json"));
+        assert!(!default.matches("12345.json.log"));
+        assert!(!default.matches("12345"));
+
+        let pattern = "*.json*".parse::<FilePattern>().unwrap();
+        assert!(pattern.matches("12345.json"));
+        assert!(pattern.matches("12345.json.log"));
+        assert!(!pattern.matches("12345.log"));
+
+        let pattern = "battle-??.*".parse::<FilePattern>().unwrap();
+        assert!(pattern.matches("battle-01.json"));
+        assert!(!pattern.matches("battle-1.json"));
+        assert!("*".parse::<FilePattern>().unwrap().matches("12345"));
+
+        assert!("".parse::<FilePattern>().is_err());
+        assert!("day/*.json".parse::<FilePattern>().is_err());
+    }
+
+    #[test]
+    fn test_shard() {
+        assert_eq!("2/8".parse::<Shard>(), Ok(Shard { index: 2, count: 8 }));
+        assert!("0/8".parse::<Shard>().is_err());
+        assert!("9/8".parse::<Shard>().is_err());
+        assert!("two".parse::<Shard>().is_err());
+
+        // every file lands in exactly one shard
+        let shards = (1..=3)
+            .map(|k| format!("{}/3", k).parse::<Shard>().unwrap())
+            .collect::<Vec<_>>();
+        for i in 0..100 {
+            let path = format!("2021-05-01/{}.json", i);
+            let owners = shards
+                .iter()
+                .filter(|s| s.includes(Path::new(&path)))
+                .count();
+            assert_eq!(owners, 1);
+        }
+    }
+
+    #[test]
+    fn test_filter() {
+        let blitz = r#"{"format":"gen9randombattleblitz","log":[]}"#;
+        assert!(BattleFilter::default().accepts(blitz));
+        assert!(BattleFilter {
+            timer: Some(Timer::Blitz),
+            ..BattleFilter::default()
+        }
+        .accepts(blitz));
+        assert!(!BattleFilter {
+            timer: Some(Timer::Standard),
+            ..BattleFilter::default()
+        }
+        .accepts(blitz));
+    }
+
+    #[test]
+    fn test_is_abandoned_early() {
+        assert!(is_abandoned_early(
+            r#"{"turns":1,"log":["|turn|1","|-message|Annika forfeited.","|win|Marty"]}"#
+        ));
+        assert!(is_abandoned_early(
+            r#"{"log":["|turn|1","|turn|2","|-message|Annika lost due to inactivity.","|win|Marty"]}"#
+        ));
+        assert!(is_abandoned_early(
+            r#"{"turns":3,"endType":"forfeit","log":[]}"#
+        ));
+        // played out, or forfeited after a real game
+        assert!(!is_abandoned_early(
+            r#"{"turns":2,"endType":"normal","log":["|turn|2","|win|Marty"]}"#
+        ));
+        assert!(!is_abandoned_early(
+            r#"{"turns":30,"log":["|turn|30","|-message|Annika forfeited.","|win|Marty"]}"#
+        ));
+        assert!(!is_abandoned_early(
+            r#"{"log":["|turn|1","|turn|2","|turn|3","|turn|4","|turn|5","|turn|6","|-message|Annika forfeited."]}"#
+        ));
+    }
+}
@@ -0,0 +1,186 @@
+/// Mean rating gained per game by the players who received each species (`--rating-gain-output`)
+///
+/// Winrate treats a win over a much higher-rated opponent the same as a win over a much
+/// lower-rated one. The ladder doesn't, so this estimates the rating change each game implied
+/// from the two players' ratings, as Showdown's ladder would compute it, and averages it over
+/// the games each species was in. Only rated two-player battles with a winner count.
+use crate::calibration::expected_winrate;
+use crate::filter::BattleFilter;
+use crate::pass;
+use crate::stats::{format_float, Output, Stats};
+use crate::table::{self, Block, Table};
+use indexmap::IndexMap;
+use itertools::Itertools;
+use std::io;
+use std::path::PathBuf;
+
+/// Showdown's ladder K-factor at a rating (ignoring its smaller losses below 1200)
+fn k_factor(elo: f64) -> f64 {
+    if elo > 1600.0 {
+        32.0
+    } else if elo > 1350.0 {
+        40.0
+    } else {
+        50.0
+    }
+}
+
+/// The rating change for a player rated `elo` who won or lost against one rated `opponent`
+fn rating_change(elo: f64, opponent: f64, won: bool) -> f64 {
+    let expected = expected_winrate(elo - opponent) / 100.0;
+    let score = if won { 1.0 } else { 0.0 };
+    k_factor(elo) * (score - expected)
+}
+
+/// (species, won, rating change) for every species in the battle
+fn species_gains(json: &str) -> Vec<(String, bool, f64)> {
+    let sides = Stats::results_by_side(0, json);
+    let (p1, p2) = match sides.as_slice() {
+        [p1, p2] if !p1.is_empty() && !p2.is_empty() => (p1, p2),
+        _ => return vec![],
+    };
+    let (r1, r2) = match (p1[0].rating, p2[0].rating) {
+        (Some(r1), Some(r2)) => (r1, r2),
+        _ => return vec![],
+    };
+    if p1[0].won == p2[0].won {
+        return vec![];
+    }
+    let (gain1, gain2) = (
+        rating_change(r1, r2, p1[0].won),
+        rating_change(r2, r1, p2[0].won),
+    );
+    p1.iter()
+        .map(|result| (result.species.clone(), result.won, gain1))
+        .chain(
+            p2.iter()
+                .map(|result| (result.species.clone(), result.won, gain2)),
+        )
+        .collect()
+}
+
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+struct Record {
+    games: u32,
+    wins: u32,
+    gain_sum: f64,
+}
+
+impl Record {
+    fn winrate(&self) -> f64 {
+        self.wins as f64 / self.games as f64 * 100.0
+    }
+
+    fn mean_gain(&self) -> f64 {
+        self.gain_sum / self.games as f64
+    }
+}
+
+pub struct GainReport {
+    /// Species:record
+    rows: IndexMap<String, Record>,
+}
+
+impl GainReport {
+    pub fn new<'a>(
+        days: impl Iterator<Item = &'a [PathBuf]>,
+        min_elo: u64,
+        filter: &BattleFilter,
+    ) -> io::Result<Self> {
+        let mut report = GainReport {
+            rows: IndexMap::new(),
+        };
+        let mut scan = pass::Scan::new(min_elo, filter);
+        for files in days {
+            for (species, won, gain) in scan.day(files, species_gains)?.into_iter().flatten() {
+                report.add(species, won, gain);
+            }
+        }
+        report.sort();
+        Ok(report)
+    }
+
+    fn add(&mut self, species: String, won: bool, gain: f64) {
+        let record = self.rows.entry(species).or_default();
+        record.games += 1;
+        record.wins += won as u32;
+        record.gain_sum += gain;
+    }
+
+    /// Most rating gained per game first
+    fn sort(&mut self) {
+        self.rows.sort_by(|a_species, a, b_species, b| {
+            b.mean_gain()
+                .partial_cmp(&a.mean_gain())
+                .unwrap()
+                .then_with(|| a_species.cmp(b_species))
+        });
+    }
+}
+
+impl Output for GainReport {
+    fn to_csv(&mut self) -> String {
+        Itertools::intersperse(
+            self.rows.iter().map(|(species, record)| {
+                [
+                    species.clone(),
+                    record.games.to_string(),
+                    format_float(record.winrate()),
+                    format_float(record.mean_gain()),
+                ]
+                .join(",")
+            }),
+            String::from("\n"),
+        )
+        .collect()
+    }
+
+    fn to_human_readable(&mut self) -> String {
+        table::blocks_to_text(&self.to_blocks())
+    }
+
+    fn to_blocks(&mut self) -> Vec<Block> {
+        let mut table = Table::new(&["Pokemon", "Mean rating gain", "Winrate", "Games"]);
+        for (species, record) in &self.rows {
+            table.add_row(vec![
+                species.clone(),
+                format!("{:+.2}", record.mean_gain()),
+                format!("{}%", format_float(record.winrate())),
+                record.games.to_string(),
+            ]);
+        }
+        vec![Block::Table(table)]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rating_gain() {
+        // an upset: the 1300 player beats the 1420 player
+        let json = r#"{"winner":"A","p1":"A","p2":"B","p1team":[{"species":"Mew"},{"species":"Ditto"}],"p2team":[{"species":"Ditto"}],"p1rating":{"elo":1300},"p2rating":{"elo":1420}}"#;
+        let gains = species_gains(json);
+        assert_eq!(gains.len(), 3);
+        assert_eq!((gains[0].0.as_str(), gains[0].1), ("Mew", true));
+        assert!((gains[0].2 - 33.306971).abs() < 1e-6);
+        assert!((gains[2].2 + 26.645577).abs() < 1e-6);
+        let unrated = r#"{"winner":"A","p1":"A","p2":"B","p1team":[{"species":"Mew"}],"p2team":[{"species":"Ditto"}],"p1rating":{"elo":1300}}"#;
+        assert!(species_gains(unrated).is_empty());
+
+        let mut report = GainReport {
+            rows: IndexMap::new(),
+        };
+        for (species, won, gain) in gains {
+            report.add(species, won, gain);
+        }
+        report.add(String::from("Mew"), false, -10.0);
+        report.sort();
+        assert_eq!(
+            report.to_csv(),
+            "Mew,2,50.000000,11.653486\n\
+             Ditto,2,50.000000,3.330697"
+        );
+    }
+}
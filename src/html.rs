@@ -0,0 +1,175 @@
+/// HTML output (`--html-output`)
+///
+/// A standalone page with the human-readable output's rankings as a table that sorts by any
+/// column when its header is clicked, so the monthly report can be published as-is. Species
+/// names are translated like the human-readable output, and the page needs nothing but itself.
+use crate::stats::{format_float, PokemonStats, Stats};
+
+const STYLE: &str = "body { font-family: sans-serif; margin: 2em; }
+table { border-collapse: collapse; }
+th, td { border: 1px solid #ccc; padding: 0.25em 0.75em; text-align: right; }
+th { background: #eee; cursor: pointer; user-select: none; }
+td:nth-child(2), th:nth-child(2) { text-align: left; }";
+
+/// Sorts a table by the clicked column's `data-sort` values, toggling the direction on each click
+const SCRIPT: &str = "document.querySelectorAll('table.rankings').forEach(table => {
+  table.querySelectorAll('th').forEach((th, column) => {
+    th.addEventListener('click', () => {
+      const ascending = th.dataset.order !== 'ascending';
+      th.dataset.order = ascending ? 'ascending' : 'descending';
+      const body = table.tBodies[0];
+      const value = row => row.cells[column].dataset.sort;
+      const rows = Array.from(body.rows).sort((a, b) => {
+        const [x, y] = [value(a), value(b)];
+        const order = isNaN(x) || isNaN(y) ? x.localeCompare(y) : x - y;
+        return ascending ? order : -order;
+      });
+      rows.forEach(row => body.appendChild(row));
+    });
+  });
+});";
+
+pub(crate) fn escape_html(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// A table cell showing `text` that sorts by `value`
+fn cell(text: &str, value: &str) -> String {
+    format!(
+        "<td data-sort=\"{}\">{}</td>",
+        escape_html(value),
+        escape_html(text)
+    )
+}
+
+impl Stats {
+    fn html_table(&self, pokemon: &[(&String, &PokemonStats)]) -> String {
+        let mut lines = vec![
+            String::from("<table class=\"rankings\">"),
+            String::from(
+                "<thead><tr><th>Rank</th><th>Pokémon</th><th>Winrate</th><th>Deviations</th>\
+                 <th>Games</th><th>Wins</th></tr></thead>",
+            ),
+            String::from("<tbody>"),
+        ];
+        for ((species, stats), rank) in pokemon.iter().zip(self.ranks(pokemon)) {
+            let fstats = stats.final_stats();
+            let name = self.display_name(species);
+            let (winrate, deviations) = (
+                format_float(fstats.winrate),
+                format_float(fstats.deviations),
+            );
+            lines.push(format!(
+                "<tr>{}{}{}{}{}{}</tr>",
+                cell(&rank.to_string(), &rank.to_string()),
+                cell(name, name),
+                cell(&format!("{}%", winrate), &winrate),
+                cell(&deviations, &deviations),
+                cell(
+                    &self.number_format.format(stats.games),
+                    &stats.games.to_string()
+                ),
+                cell(
+                    &self.number_format.format(stats.wins),
+                    &stats.wins.to_string()
+                ),
+            ));
+        }
+        lines.push(String::from("</tbody>"));
+        lines.push(String::from("</table>"));
+        lines.join("\n")
+    }
+
+    pub fn to_html(&mut self) -> String {
+        self.sort();
+
+        let (provisional, established): (Vec<_>, Vec<_>) = self
+            .pokemon
+            .iter()
+            .partition(|(_, stats)| self.is_provisional(stats));
+
+        let mut body = vec![String::from("<h1>Random Battle winrates</h1>")];
+        let mut details = self
+            .metadata
+            .iter()
+            .flat_map(|metadata| metadata.lines())
+            .map(|(label, value)| (label.to_string(), value))
+            .collect::<Vec<_>>();
+        if let Some(sample) = self.sample {
+            details.push((String::from("Sampled"), sample.to_string()));
+        }
+        let summary = self
+            .summary_lines()
+            .into_iter()
+            .map(|(label, value)| (label.to_string(), value))
+            .collect::<Vec<_>>();
+        let list = |items: &[(String, String)]| {
+            let mut list = vec![String::from("<ul>")];
+            for (label, value) in items {
+                list.push(format!(
+                    "<li>{}: {}</li>",
+                    escape_html(label),
+                    escape_html(value)
+                ));
+            }
+            list.push(String::from("</ul>"));
+            list.join("\n")
+        };
+
+        if !details.is_empty() {
+            body.push(list(&details));
+        }
+        body.push(self.html_table(&established));
+        if !provisional.is_empty() {
+            body.push(format!(
+                "<h2>Provisional (fewer than {} games)</h2>",
+                self.provisional_below.unwrap_or(0)
+            ));
+            body.push(self.html_table(&provisional));
+        }
+        if !summary.is_empty() {
+            body.push(list(&summary));
+        }
+
+        format!(
+            "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n\
+             <title>Random Battle winrates</title>\n<style>\n{}\n</style>\n</head>\n<body>\n{}\n\
+             <script>\n{}\n</script>\n</body>\n</html>\n",
+            STYLE,
+            body.join("\n"),
+            SCRIPT
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::stats::{GameResult, Stats};
+
+    #[test]
+    fn test_html() {
+        let result = |species: &str, won| GameResult {
+            species: species.to_string(),
+            won,
+            turns: 10,
+            rating: None,
+        };
+        let mut stats = Stats::new();
+        stats.add_game_results(vec![result("Mew", true), result("<Ditto>", false)]);
+        stats.add_game_results(vec![result("Mew", true)]);
+        stats.provisional_below = Some(2);
+
+        let html = stats.to_html();
+        assert!(html.starts_with("<!DOCTYPE html>"));
+        assert!(html.contains(
+            "<tr><td data-sort=\"1\">1</td><td data-sort=\"Mew\">Mew</td>\
+             <td data-sort=\"100.000000\">100.000000%</td><td data-sort=\"1.414214\">1.414214</td>\
+             <td data-sort=\"2\">2</td><td data-sort=\"2\">2</td></tr>"
+        ));
+        assert!(html.contains("<h2>Provisional (fewer than 2 games)</h2>"));
+        assert!(html.contains("<td data-sort=\"&lt;Ditto&gt;\">&lt;Ditto&gt;</td>"));
+    }
+}
@@ -0,0 +1,68 @@
+/// JSON output (`--json-output`)
+///
+/// The same per-species numbers as the CSV output, as an array of objects, for web frontends
+/// that would rather not parse CSV. Species are in the same order as the other outputs.
+use crate::fetch::json_string;
+use crate::stats::{format_float, Stats};
+use itertools::Itertools;
+
+impl Stats {
+    /// The `"key":value` fields of each species' object, in order
+    pub(crate) fn json_fields(&mut self) -> Vec<Vec<String>> {
+        self.sort();
+
+        self.pokemon
+            .iter()
+            .map(|(species, stats)| {
+                let fstats = stats.final_stats();
+                let mut fields = vec![
+                    format!("\"species\":{}", json_string(species)),
+                    format!("\"games\":{}", stats.games),
+                    format!("\"wins\":{}", stats.wins),
+                    format!("\"winrate\":{}", format_float(fstats.winrate)),
+                    format!("\"deviations\":{}", format_float(fstats.deviations)),
+                ];
+                if self.provisional_below.is_some() {
+                    fields.push(format!("\"provisional\":{}", self.is_provisional(stats)));
+                }
+                fields
+            })
+            .collect()
+    }
+
+    pub fn to_json(&mut self) -> String {
+        let objects = self
+            .json_fields()
+            .into_iter()
+            .map(|fields| format!("{{{}}}", fields.join(",")));
+        format!("[{}]\n", objects.format(",\n "))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::stats::{GameResult, Stats};
+
+    #[test]
+    fn test_json() {
+        let result = |species: &str, won| GameResult {
+            species: species.to_string(),
+            won,
+            turns: 10,
+            rating: None,
+        };
+        let mut stats = Stats::new();
+        stats.add_game_results(vec![result("Mew", true), result("Type: \"Null\"", false)]);
+        stats.add_game_results(vec![result("Mew", true)]);
+        assert_eq!(
+            stats.to_json(),
+            "[{\"species\":\"Mew\",\"games\":2,\"wins\":2,\"winrate\":100.000000,\"deviations\":1.414214},\n \
+             {\"species\":\"Type: \\\"Null\\\"\",\"games\":1,\"wins\":0,\"winrate\":0.000000,\"deviations\":-1.000000}]\n"
+        );
+
+        stats.provisional_below = Some(2);
+        assert!(stats
+            .to_json()
+            .contains("\"deviations\":-1.000000,\"provisional\":true}"));
+    }
+}
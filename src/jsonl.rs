@@ -0,0 +1,241 @@
+/// JSON Lines outputs (`--jsonl-output` and `--species-jsonl-output`)
+///
+/// `--jsonl-output` writes one JSON object per line, each with a `section` naming what it's a
+/// row of: the per-species rankings (the same fields as the JSON output), then the rows of
+/// every other requested report, keyed by their column headers. Each section is written and
+/// flushed as soon as it's computed, so stream processors can start on the rankings while the
+/// slower breakdowns that take another pass over the input are still running.
+///
+/// `--species-jsonl-output` is only the rankings, for loading into Elasticsearch or BigQuery:
+/// every line is self-contained, repeating which format, elo cutoff and days it describes, so
+/// months can be appended to one index or table and still be told apart.
+use crate::fetch::json_string;
+use crate::stats::Stats;
+use crate::table::Block;
+use std::io::{self, Write};
+
+/// A column header as a JSON key, e.g. "Mean rating gain" as `mean_rating_gain`
+fn key(header: &str) -> String {
+    header
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        .collect::<Vec<_>>()
+        .join("_")
+}
+
+/// A cell as a JSON value: a number if it is one (ignoring a leading `+` or a trailing `%`),
+/// and a string otherwise
+fn value(cell: &str) -> String {
+    let number = cell.trim_start_matches('+').trim_end_matches('%');
+    let is_number = number.parse::<f64>().is_ok()
+        && number
+            .chars()
+            .all(|c| c.is_ascii_digit() || matches!(c, '-' | '.' | 'e' | 'E'));
+    if is_number && !number.starts_with('.') && !number.ends_with('.') {
+        number.to_string()
+    } else {
+        json_string(cell)
+    }
+}
+
+/// Details of the run repeated on every line of `--species-jsonl-output`
+pub struct RunFields {
+    /// The name of the input directory, e.g. gen9randombattle
+    pub format: Option<String>,
+    pub min_elo: u64,
+    /// The first and last day analyzed, by name; unknown for merged states
+    pub days: Option<(String, String)>,
+}
+
+impl RunFields {
+    fn to_json_fields(&self) -> String {
+        let string_or_null =
+            |s: Option<&String>| s.map_or(String::from("null"), |s| json_string(s));
+        format!(
+            "\"format\":{},\"min_elo\":{},\"first_day\":{},\"last_day\":{}",
+            string_or_null(self.format.as_ref()),
+            self.min_elo,
+            string_or_null(self.days.as_ref().map(|(first, _)| first)),
+            string_or_null(self.days.as_ref().map(|(_, last)| last)),
+        )
+    }
+}
+
+impl Stats {
+    pub fn to_species_jsonl(&mut self, run: &RunFields) -> String {
+        let run = run.to_json_fields();
+        self.json_fields()
+            .into_iter()
+            .map(|fields| format!("{{{},{}}}\n", run, fields.join(",")))
+            .collect()
+    }
+}
+
+pub struct JsonlWriter<W> {
+    out: W,
+    /// The reader stopped reading (e.g. `head`), so there's no point writing more
+    closed: bool,
+}
+
+impl<W: Write> JsonlWriter<W> {
+    pub fn new(out: W) -> Self {
+        JsonlWriter { out, closed: false }
+    }
+
+    /// The output, to be finished once every section is written
+    pub fn into_inner(self) -> W {
+        self.out
+    }
+
+    fn write_lines(&mut self, mut lines: impl Iterator<Item = String>) -> io::Result<()> {
+        if self.closed {
+            return Ok(());
+        }
+        let result = lines
+            .try_for_each(|line| writeln!(self.out, "{}", line))
+            .and_then(|_| self.out.flush());
+        match result {
+            Err(e) if e.kind() == io::ErrorKind::BrokenPipe => {
+                self.closed = true;
+                Ok(())
+            }
+            result => result,
+        }
+    }
+
+    /// Writes a line for each species in the rankings
+    pub fn write_rankings(&mut self, stats: &mut Stats) -> io::Result<()> {
+        let lines = stats
+            .json_fields()
+            .into_iter()
+            .map(|fields| format!("{{\"section\":\"rankings\",{}}}", fields.join(",")));
+        self.write_lines(lines)
+    }
+
+    /// Writes a line for each row of the tables in a report, keyed by their column titles
+    pub fn write_tables(&mut self, section: &str, blocks: &[Block]) -> io::Result<()> {
+        let mut lines = vec![];
+        for block in blocks {
+            let table = match block {
+                Block::Table(table) => table,
+                _ => continue,
+            };
+            let keys = table.titles().iter().map(|t| key(t)).collect::<Vec<_>>();
+            for row in table.rows() {
+                let mut fields = vec![format!("\"section\":{}", json_string(section))];
+                fields.extend(
+                    keys.iter()
+                        .zip(row)
+                        .map(|(key, cell)| format!("{}:{}", json_string(key), value(cell))),
+                );
+                lines.push(format!("{{{}}}", fields.join(",")));
+            }
+        }
+        self.write_lines(lines.into_iter())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stats::GameResult;
+    use crate::table::Table;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    /// A writer whose contents can be read after it's been boxed
+    #[derive(Clone, Default)]
+    struct Shared(Rc<RefCell<Vec<u8>>>);
+
+    impl Write for Shared {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.borrow_mut().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_species_jsonl() {
+        let mut stats = Stats::new();
+        for species in ["Mew", "Ditto"] {
+            stats.add_game_results(vec![GameResult {
+                species: species.to_string(),
+                won: species == "Mew",
+                turns: 1,
+                rating: None,
+            }]);
+        }
+        let run = RunFields {
+            format: Some(String::from("gen9randombattle")),
+            min_elo: 1500,
+            days: Some((String::from("2024-01-01"), String::from("2024-01-31"))),
+        };
+        assert_eq!(
+            stats.to_species_jsonl(&run),
+            "{\"format\":\"gen9randombattle\",\"min_elo\":1500,\"first_day\":\"2024-01-01\",\
+             \"last_day\":\"2024-01-31\",\"species\":\"Mew\",\"games\":1,\"wins\":1,\
+             \"winrate\":100.000000,\"deviations\":1.000000}\n\
+             {\"format\":\"gen9randombattle\",\"min_elo\":1500,\"first_day\":\"2024-01-01\",\
+             \"last_day\":\"2024-01-31\",\"species\":\"Ditto\",\"games\":1,\"wins\":0,\
+             \"winrate\":0.000000,\"deviations\":-1.000000}\n"
+        );
+
+        let merged = RunFields {
+            format: None,
+            min_elo: 0,
+            days: None,
+        };
+        assert!(stats
+            .to_species_jsonl(&merged)
+            .starts_with("{\"format\":null,\"min_elo\":0,\"first_day\":null,\"last_day\":null,"));
+    }
+
+    #[test]
+    fn test_jsonl() {
+        assert_eq!(key("Higher-rated winrate"), "higher_rated_winrate");
+        assert_eq!(
+            (value("+6.99"), value("50.0%"), value("1,234"), value("-")),
+            (
+                String::from("6.99"),
+                String::from("50.0"),
+                String::from("\"1,234\""),
+                String::from("\"-\"")
+            )
+        );
+
+        let buffer = Shared::default();
+        let mut writer = JsonlWriter::new(Box::new(buffer.clone()));
+        let mut stats = Stats::new();
+        stats.add_game_results(vec![GameResult {
+            species: String::from("Mew"),
+            won: true,
+            turns: 1,
+            rating: None,
+        }]);
+        writer.write_rankings(&mut stats).unwrap();
+        assert_eq!(
+            String::from_utf8(buffer.0.borrow().clone()).unwrap(),
+            "{\"section\":\"rankings\",\"species\":\"Mew\",\"games\":1,\"wins\":1,\
+             \"winrate\":100.000000,\"deviations\":1.000000}\n"
+        );
+
+        buffer.0.borrow_mut().clear();
+        let mut table = Table::new(&["Pokemon", "Mean rating gain"]);
+        table.add_row(vec![String::from("Mew"), String::from("+6.99")]);
+        table.add_row(vec![String::from("Mew | banned"), String::from("-1.00")]);
+        let blocks = vec![
+            Block::Text(String::from("Some title | with a bar")),
+            Block::Table(table),
+        ];
+        writer.write_tables("rating_gain", &blocks).unwrap();
+        assert_eq!(
+            String::from_utf8(buffer.0.borrow().clone()).unwrap(),
+            "{\"section\":\"rating_gain\",\"pokemon\":\"Mew\",\"mean_rating_gain\":6.99}\n\
+             {\"section\":\"rating_gain\",\"pokemon\":\"Mew | banned\",\"mean_rating_gain\":-1.00}\n"
+        );
+    }
+}
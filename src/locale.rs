@@ -0,0 +1,78 @@
+/// Translated species names for human-facing outputs (`--translations` and `--locale`)
+///
+/// The translations file is CSV with a header row naming the locales, e.g.
+/// `species,ja,fr,de`, then one row per species keyed by its canonical English name.
+/// Only human-readable outputs are translated; CSV, state, and other machine-readable outputs
+/// keep the English names so they can still be joined across locales.
+use std::collections::HashMap;
+
+#[derive(Debug, Default, PartialEq)]
+pub struct Translations {
+    /// English name:translated name, for one locale
+    names: HashMap<String, String>,
+}
+
+impl Translations {
+    /// Reads the names for `locale` from a translations file
+    pub fn parse(file: &str, locale: &str) -> Result<Self, String> {
+        let mut lines = file.lines().filter(|line| !line.trim().is_empty());
+        let header = lines.next().ok_or("the translations file is empty")?;
+        let column = header
+            .split(',')
+            .skip(1)
+            .position(|l| l.trim() == locale)
+            .ok_or_else(|| format!("the translations file has no '{}' column", locale))?
+            + 1;
+
+        let mut names = HashMap::new();
+        for (idx, line) in lines.enumerate() {
+            let fields = line.split(',').map(str::trim).collect::<Vec<_>>();
+            match fields.get(column) {
+                Some(name) if !name.is_empty() => {
+                    names.insert(fields[0].to_string(), name.to_string());
+                }
+                Some(_) => {}
+                None => {
+                    return Err(format!(
+                        "translations row {} has {} fields; expected at least {}",
+                        idx + 2,
+                        fields.len(),
+                        column + 1
+                    ))
+                }
+            }
+        }
+        Ok(Self { names })
+    }
+
+    /// The translated name of `species`, or its English name if it has none
+    pub fn name<'a>(&'a self, species: &'a str) -> &'a str {
+        self.names.get(species).map_or(species, String::as_str)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_translations() {
+        let file = "species,ja,fr\n\
+                    Pikachu,ピカチュウ,Pikachu\n\
+                    Bulbasaur,フシギダネ,Bulbizarre\n\
+                    Mew,,\n";
+        let french = Translations::parse(file, "fr").unwrap();
+        assert_eq!(french.name("Bulbasaur"), "Bulbizarre");
+        // untranslated species keep their English names
+        assert_eq!(french.name("Mew"), "Mew");
+        assert_eq!(french.name("Ditto"), "Ditto");
+        assert_eq!(
+            Translations::parse(file, "ja").unwrap().name("Pikachu"),
+            "ピカチュウ"
+        );
+
+        assert!(Translations::parse(file, "de").is_err());
+        assert!(Translations::parse("species,ja\nPikachu", "ja").is_err());
+        assert!(Translations::parse("", "ja").is_err());
+    }
+}
@@ -0,0 +1,445 @@
+/// Battle log analysis
+///
+/// The `log` field of a battle is walked exactly once: each line is tokenized into a
+/// `LogEvent` (borrowing from the line, so nothing is allocated per event) and handed to
+/// every registered `LogAnalyzer`. Enabling more analyses adds dispatch cost, not parse cost.
+use crate::stats::{Output, Stats, MAX_SIDES};
+use crate::table::Table;
+use indexmap::IndexMap;
+use itertools::Itertools;
+use std::collections::HashSet;
+use std::fmt;
+use std::str::FromStr;
+
+/// The most active slots per side a battle can have (triples)
+const MAX_SLOTS: usize = 3;
+
+/// Side conditions that count as entry hazards
+const HAZARDS: [&str; 4] = ["Stealth Rock", "Spikes", "Toxic Spikes", "Sticky Web"];
+
+/// A spot on the field, e.g. `p2a` is side 1, slot 0
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Position {
+    side: usize,
+    slot: usize,
+}
+
+impl Position {
+    /// Parses the start of a Pokémon identifier like `p1a: Nickname`.
+    /// A bare side identifier (`p1: Player`) refers to slot 0.
+    fn parse(ident: &str) -> Option<Self> {
+        let bytes = ident.as_bytes();
+        if bytes.len() < 2 || bytes[0] != b'p' {
+            return None;
+        }
+        let side = (bytes[1] as char).to_digit(10)? as usize;
+        if side == 0 || side > MAX_SIDES {
+            return None;
+        }
+        let slot = match bytes.get(2) {
+            Some(letter) if letter.is_ascii_lowercase() => (letter - b'a') as usize,
+            _ => 0,
+        };
+        if slot >= MAX_SLOTS {
+            return None;
+        }
+        Some(Self {
+            side: side - 1,
+            slot,
+        })
+    }
+}
+
+/// A single protocol message from the battle log that an analyzer may care about
+#[derive(Debug, PartialEq)]
+pub enum LogEvent<'a> {
+    /// `|start|`: team preview is over and the leads are about to be sent out
+    Start,
+    Turn(u32),
+    /// A Pokémon entering the field (`switch`, `drag`, or `replace`)
+    Switch {
+        position: Position,
+        species: &'a str,
+    },
+    Move(Position),
+    Faint(Position),
+    Terastallize(Position),
+    /// A side condition (such as a hazard) being set up on the given side
+    SideStart {
+        side: usize,
+        condition: &'a str,
+    },
+}
+
+impl<'a> LogEvent<'a> {
+    /// Tokenizes one log line; lines that no analyzer uses yield `None`
+    pub fn parse(line: &'a str) -> Option<Self> {
+        let mut parts = line.split('|');
+        if !parts.next()?.is_empty() {
+            return None;
+        }
+
+        match parts.next()? {
+            "start" => Some(LogEvent::Start),
+            "turn" => parts.next()?.parse().ok().map(LogEvent::Turn),
+            "switch" | "drag" | "replace" => {
+                let position = Position::parse(parts.next()?)?;
+                // details look like `Rotom-Fan, L84, M`
+                let species = parts.next()?.split(',').next()?;
+                Some(LogEvent::Switch { position, species })
+            }
+            "move" => Position::parse(parts.next()?).map(LogEvent::Move),
+            "faint" => Position::parse(parts.next()?).map(LogEvent::Faint),
+            "-terastallize" => Position::parse(parts.next()?).map(LogEvent::Terastallize),
+            "-sidestart" => {
+                let side = Position::parse(parts.next()?)?.side;
+                let condition = parts.next()?;
+                let condition = condition.strip_prefix("move: ").unwrap_or(condition);
+                Some(LogEvent::SideStart { side, condition })
+            }
+            _ => None,
+        }
+    }
+}
+
+/// What the analyzers know about the battle so far, shared between all of them
+#[derive(Default)]
+pub struct BattleState<'a> {
+    active: [[Option<&'a str>; MAX_SLOTS]; MAX_SIDES],
+    /// The Pokémon that most recently used a move
+    last_mover: Option<Position>,
+}
+
+impl<'a> BattleState<'a> {
+    pub fn species_at(&self, position: Position) -> Option<&'a str> {
+        self.active[position.side][position.slot]
+    }
+
+    /// The species that last used a move, provided it isn't on `side`
+    pub fn opposing_mover(&self, side: usize) -> Option<&'a str> {
+        match self.last_mover {
+            Some(position) if position.side != side => self.species_at(position),
+            _ => None,
+        }
+    }
+
+    fn update(&mut self, event: &LogEvent<'a>) {
+        match *event {
+            LogEvent::Switch { position, species } => {
+                self.active[position.side][position.slot] = Some(species);
+            }
+            LogEvent::Move(position) => self.last_mover = Some(position),
+            LogEvent::Turn(_) => self.last_mover = None,
+            _ => {}
+        }
+    }
+}
+
+/// A log-derived statistic that can be enabled from the command line
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum LogAnalysis {
+    /// Times each species was sent out first
+    Leads,
+    /// Opposing Pokémon fainted by each species' moves
+    KOs,
+    /// Times each species terastallized
+    Tera,
+    /// Entry hazards each species set up
+    Hazards,
+}
+
+impl FromStr for LogAnalysis {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "leads" => Ok(LogAnalysis::Leads),
+            "kos" => Ok(LogAnalysis::KOs),
+            "tera" => Ok(LogAnalysis::Tera),
+            "hazards" => Ok(LogAnalysis::Hazards),
+            _ => Err(format!(
+                "unknown log analysis '{}' (expected leads, kos, tera, or hazards)",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for LogAnalysis {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            LogAnalysis::Leads => "leads",
+            LogAnalysis::KOs => "kos",
+            LogAnalysis::Tera => "tera",
+            LogAnalysis::Hazards => "hazards",
+        })
+    }
+}
+
+/// One occurrence of a log statistic for a species
+#[derive(Debug, PartialEq)]
+pub struct LogTally {
+    species: String,
+    analysis: LogAnalysis,
+}
+
+/// Receives every event of a battle log in order
+pub trait LogAnalyzer {
+    /// `state` reflects everything before `event`
+    fn handle<'a>(&mut self, event: &LogEvent<'a>, state: &BattleState<'a>) -> Option<&'a str>;
+}
+
+#[derive(Default)]
+struct LeadAnalyzer {
+    turns_started: bool,
+}
+
+impl LogAnalyzer for LeadAnalyzer {
+    fn handle<'a>(&mut self, event: &LogEvent<'a>, _: &BattleState<'a>) -> Option<&'a str> {
+        match *event {
+            LogEvent::Turn(_) => {
+                self.turns_started = true;
+                None
+            }
+            LogEvent::Switch { species, .. } if !self.turns_started => Some(species),
+            _ => None,
+        }
+    }
+}
+
+struct KOAnalyzer;
+
+impl LogAnalyzer for KOAnalyzer {
+    fn handle<'a>(&mut self, event: &LogEvent<'a>, state: &BattleState<'a>) -> Option<&'a str> {
+        match *event {
+            LogEvent::Faint(position) => state.opposing_mover(position.side),
+            _ => None,
+        }
+    }
+}
+
+struct TeraAnalyzer;
+
+impl LogAnalyzer for TeraAnalyzer {
+    fn handle<'a>(&mut self, event: &LogEvent<'a>, state: &BattleState<'a>) -> Option<&'a str> {
+        match *event {
+            LogEvent::Terastallize(position) => state.species_at(position),
+            _ => None,
+        }
+    }
+}
+
+struct HazardAnalyzer;
+
+impl LogAnalyzer for HazardAnalyzer {
+    fn handle<'a>(&mut self, event: &LogEvent<'a>, state: &BattleState<'a>) -> Option<&'a str> {
+        match *event {
+            LogEvent::SideStart { side, condition } if HAZARDS.contains(&condition) => {
+                state.opposing_mover(side)
+            }
+            _ => None,
+        }
+    }
+}
+
+impl LogAnalysis {
+    fn analyzer(&self) -> Box<dyn LogAnalyzer> {
+        match self {
+            LogAnalysis::Leads => Box::new(LeadAnalyzer::default()),
+            LogAnalysis::KOs => Box::new(KOAnalyzer),
+            LogAnalysis::Tera => Box::new(TeraAnalyzer),
+            LogAnalysis::Hazards => Box::new(HazardAnalyzer),
+        }
+    }
+}
+
+/// Runs the given analyses over a battle's log in a single pass
+pub fn analyze_log(json: &str, analyses: &[LogAnalysis]) -> Vec<LogTally> {
+    if analyses.is_empty() {
+        return vec![];
+    }
+
+    let mut analyzers: Vec<(LogAnalysis, Box<dyn LogAnalyzer>)> =
+        analyses.iter().map(|a| (*a, a.analyzer())).collect();
+    let mut state = BattleState::default();
+    let mut tallies = vec![];
+
+    let log = gjson::get(json, "log");
+    let lines = log.array();
+    for line in &lines {
+        let event = match LogEvent::parse(line.str()) {
+            Some(e) => e,
+            None => continue,
+        };
+        for (analysis, analyzer) in analyzers.iter_mut() {
+            if let Some(species) = analyzer.handle(&event, &state) {
+                tallies.push(LogTally {
+                    species: Stats::normalize_species(species),
+                    analysis: *analysis,
+                });
+            }
+        }
+        state.update(&event);
+    }
+
+    tallies
+}
+
+/// The (normalized) species each side sent out, indexed by side, or `None` if the battle has
+/// no log to tell
+pub fn sent_out(json: &str) -> Option<Vec<HashSet<String>>> {
+    let log = gjson::get(json, "log");
+    let lines = log.array();
+    if lines.is_empty() {
+        return None;
+    }
+
+    let mut species = vec![HashSet::new(); MAX_SIDES];
+    for line in &lines {
+        if let Some(LogEvent::Switch {
+            position,
+            species: s,
+        }) = LogEvent::parse(line.str())
+        {
+            species[position.side].insert(Stats::normalize_species(s));
+        }
+    }
+    Some(species)
+}
+
+/// Per-species counts for each enabled log analysis
+#[derive(Debug, Default)]
+pub struct LogStats {
+    pub(crate) analyses: Vec<LogAnalysis>,
+    /// species:counts map, with counts in the same order as `analyses`
+    pub(crate) counts: IndexMap<String, Vec<u32>>,
+}
+
+impl LogStats {
+    pub fn new(analyses: &[LogAnalysis]) -> Self {
+        Self {
+            analyses: analyses.to_vec(),
+            counts: IndexMap::new(),
+        }
+    }
+
+    pub fn add_tallies(&mut self, tallies: Vec<LogTally>) {
+        for tally in tallies {
+            let idx = match self.analyses.iter().position(|a| *a == tally.analysis) {
+                Some(i) => i,
+                None => continue,
+            };
+            let num_analyses = self.analyses.len();
+            self.counts
+                .entry(tally.species)
+                .or_insert_with(|| vec![0; num_analyses])[idx] += 1;
+        }
+    }
+
+    pub fn merge(&mut self, other: LogStats) {
+        if self.analyses.is_empty() {
+            *self = other;
+            return;
+        }
+
+        for (species, counts) in other.counts {
+            let num_analyses = self.analyses.len();
+            let own_counts = self
+                .counts
+                .entry(species)
+                .or_insert_with(|| vec![0; num_analyses]);
+            for (analysis, count) in other.analyses.iter().zip(counts) {
+                if let Some(idx) = self.analyses.iter().position(|a| a == analysis) {
+                    own_counts[idx] += count;
+                }
+            }
+        }
+    }
+}
+
+impl Output for LogStats {
+    fn to_csv(&mut self) -> String {
+        self.counts.sort_keys();
+
+        Itertools::intersperse(
+            self.counts.iter().map(|(species, counts)| {
+                std::iter::once(species.to_string())
+                    .chain(counts.iter().map(|c| c.to_string()))
+                    .join(",")
+            }),
+            String::from("\n"),
+        )
+        .collect()
+    }
+
+    fn to_human_readable(&mut self) -> String {
+        self.counts.sort_keys();
+
+        let titles = self
+            .analyses
+            .iter()
+            .map(|a| a.to_string())
+            .collect::<Vec<_>>();
+        let mut table = Table::new(
+            &std::iter::once("Pokemon")
+                .chain(titles.iter().map(|t| t.as_str()))
+                .collect::<Vec<_>>(),
+        );
+        for (species, counts) in &self.counts {
+            table.add_row(
+                std::iter::once(species.to_string())
+                    .chain(counts.iter().map(|c| c.to_string()))
+                    .collect(),
+            );
+        }
+
+        table.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_LOG_JSON: &str = r#"{"log":["|player|p1|Annika|cynthia|1400","|player|p2|Rust Hater|cynthia|1100","|start","|switch|p1a: Fan|Rotom-Fan, L84|100/100","|switch|p2a: Drednaw|Drednaw, L84, F|100/100","|turn|1","|move|p1a: Fan|Air Slash|p2a: Drednaw","|move|p2a: Drednaw|Stealth Rock|p1a: Fan","|-sidestart|p1: Annika|move: Stealth Rock","|turn|2","|-terastallize|p1a: Fan|Flying","|move|p1a: Fan|Air Slash|p2a: Drednaw","|faint|p2a: Drednaw","|switch|p2a: Pikachu|Pikachu-Sinnoh, L92|100/100","|turn|3"]}"#;
+
+    #[test]
+    fn test_parse_events() {
+        assert_eq!(
+            LogEvent::parse("|switch|p2b: Pika|Pikachu-Sinnoh, L92|100/100"),
+            Some(LogEvent::Switch {
+                position: Position { side: 1, slot: 1 },
+                species: "Pikachu-Sinnoh"
+            })
+        );
+        assert_eq!(
+            LogEvent::parse("|-sidestart|p1: Annika|move: Spikes"),
+            Some(LogEvent::SideStart {
+                side: 0,
+                condition: "Spikes"
+            })
+        );
+        assert_eq!(LogEvent::parse("|turn|12"), Some(LogEvent::Turn(12)));
+        assert_eq!(LogEvent::parse("|j|☆Annika"), None);
+        assert_eq!(LogEvent::parse("|faint|nonsense"), None);
+    }
+
+    #[test]
+    fn test_analyze_log() {
+        let analyses = [
+            LogAnalysis::Leads,
+            LogAnalysis::KOs,
+            LogAnalysis::Tera,
+            LogAnalysis::Hazards,
+        ];
+        let mut stats = LogStats::new(&analyses);
+        stats.add_tallies(analyze_log(SAMPLE_LOG_JSON, &analyses));
+
+        assert_eq!(stats.to_csv(), "Drednaw,1,0,0,1\nRotom-Fan,1,1,1,0");
+    }
+
+    #[test]
+    fn test_analyze_log_disabled() {
+        assert!(analyze_log(SAMPLE_LOG_JSON, &[]).is_empty());
+    }
+}
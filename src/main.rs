@@ -1,109 +1,2232 @@
-#![feature(test)]
+#![cfg_attr(test, feature(test))]
 
 /// Program to generate winrates for Pokémon Showdown Random Battles
 ///
 /// Written by Annika
 /// Adapted from The Immortal's JavaScript winrate program, improved by Marty
+#[cfg(test)]
 extern crate test;
+mod abandon;
+mod ace;
+mod annotations;
+mod archetype;
+mod artifact;
+mod balance;
+mod bots;
+mod calibration;
+mod chart;
+mod chat;
+mod cluster;
+mod clutch;
+mod compare;
+mod consistency;
+#[cfg(test)]
+mod corpus;
+mod explain;
+mod fetch;
+mod filter;
+mod gain;
+mod hash;
+mod html;
+mod json;
+mod jsonl;
+mod locale;
+mod log;
+mod matchup;
+mod memory;
+mod normalization;
+mod observations;
+mod ots;
+mod parquet;
+mod pass;
+mod publish;
+mod rarity;
+mod rating;
+mod recency;
+mod regression;
+mod remote;
+mod rollup;
+mod schema;
+mod seen;
+mod selftest;
+mod side;
+mod spread;
+mod state;
 mod stats;
-use rayon::prelude::*;
+mod status;
+mod streak;
+mod synergy;
+mod table;
+mod tally;
+mod trajectory;
+mod validate;
+mod xlsx;
+use crossbeam_channel::bounded;
+use filter::{BattleFilter, FilePattern, FormatKind, Sample, Shard, Timer};
+use itertools::Itertools;
+use locale::Translations;
+use log::{LogAnalysis, LogStats};
+use recency::HalfLife;
 pub use stats::*;
+use status::status;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::fs;
-use std::path::PathBuf;
+use std::hash::Hasher;
+use std::io::Write;
+use std::num::NonZeroUsize;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Mutex;
+use std::thread;
+use std::time::{SystemTime, UNIX_EPOCH};
+use structopt::clap::{Error as ClapError, ErrorKind};
 use structopt::StructOpt;
+use table::{Block, NumberFormat, Paging};
+use trajectory::PlayerRatings;
+use validate::BattleError;
 
 #[derive(StructOpt)]
 #[structopt(rename_all = "kebab-case")]
 struct Options {
+    /// Required unless a subcommand is given
     #[structopt(long = "minimum-elo")]
-    min_elo: u64,
+    min_elo: Option<u64>,
 
+    /// Required unless a subcommand is given
     #[structopt(short = "i", long = "input")]
     #[structopt(parse(from_os_str))]
-    format_dir: PathBuf,
+    format_dir: Option<PathBuf>,
 
+    /// Where to write each species' games, wins, winrate and deviations as CSV; this and the
+    /// other outputs can be given more than once to write to several places, can be s3:// or
+    /// https:// URLs to upload to (with curl), and can be '-' to print to stdout instead, which
+    /// also implies --quiet
     #[structopt(short = "o", long = "csv-output")]
-    #[structopt(parse(from_os_str))]
-    csv_output_path: Option<PathBuf>,
+    #[structopt(parse(from_os_str), number_of_values = 1)]
+    csv_output_paths: Vec<PathBuf>,
 
+    /// Where to write the rankings as tables ('-' for stdout)
     #[structopt(short = "h", long = "human-output")]
+    #[structopt(parse(from_os_str), number_of_values = 1)]
+    human_readable_output_paths: Vec<PathBuf>,
+
+    /// Where to write the human-readable output as GitHub-flavored Markdown, for pasting into
+    /// GitHub issues and Smogon posts
+    #[structopt(long = "markdown-output")]
+    #[structopt(parse(from_os_str), number_of_values = 1)]
+    markdown_output_paths: Vec<PathBuf>,
+
+    /// Where to write the human-readable output as BBCode, with its tables as [TABLE]s, for
+    /// posting on Smogon's forums
+    #[structopt(long = "bbcode-output")]
+    #[structopt(parse(from_os_str), number_of_values = 1)]
+    bbcode_output_paths: Vec<PathBuf>,
+
+    /// Where to write the top of the rankings as a compact `!code` block for pasting into
+    /// Pokémon Showdown chat, headed by the battles analyzed and the elo cutoff
+    #[structopt(long = "code-output")]
+    #[structopt(parse(from_os_str), number_of_values = 1)]
+    code_output_paths: Vec<PathBuf>,
+
+    /// How many species to show in --code-output
+    #[structopt(long = "code-rows", default_value = "20")]
+    code_rows: NonZeroUsize,
+
+    /// Where to write a standalone HTML page with the rankings as a table that sorts by any
+    /// column
+    #[structopt(long = "html-output")]
+    #[structopt(parse(from_os_str), number_of_values = 1)]
+    html_output_paths: Vec<PathBuf>,
+
+    /// Where to write each species' games, wins, winrate and deviations as a JSON array
+    #[structopt(long = "json-output")]
+    #[structopt(parse(from_os_str), number_of_values = 1)]
+    json_output_paths: Vec<PathBuf>,
+
+    /// Where to write one JSON object per line: each species' games, wins, winrate and
+    /// deviations, then the rows of every other requested report, each section written as soon
+    /// as it's computed
+    #[structopt(long = "jsonl-output")]
+    #[structopt(parse(from_os_str), number_of_values = 1)]
+    jsonl_output_paths: Vec<PathBuf>,
+
+    /// Where to write one JSON object per species per line, each also naming the format, elo
+    /// cutoff and first and last day analyzed, for loading into Elasticsearch or BigQuery
+    #[structopt(long = "species-jsonl-output")]
+    #[structopt(parse(from_os_str), number_of_values = 1)]
+    species_jsonl_output_paths: Vec<PathBuf>,
+
+    /// Where to write each species' games, wins, winrate and deviations as an Apache Parquet
+    /// file, for loading into dataframe libraries
+    #[structopt(long = "parquet-output")]
+    #[structopt(parse(from_os_str), number_of_values = 1)]
+    parquet_output_paths: Vec<PathBuf>,
+
+    /// Where to write the rankings as an Excel spreadsheet, with winrates as percentages
+    #[structopt(long = "xlsx-output")]
+    #[structopt(parse(from_os_str), number_of_values = 1)]
+    xlsx_output_paths: Vec<PathBuf>,
+
+    /// Where to write a Vega-Lite spec for a bar chart of the deviations of the --chart-top
+    /// species at each end of the rankings
+    #[structopt(long = "chart-output")]
+    #[structopt(parse(from_os_str), number_of_values = 1)]
+    chart_output_paths: Vec<PathBuf>,
+
+    /// Where to write an SVG bar chart of the winrates of the --chart-top species at each end
+    /// of the rankings
+    #[structopt(long = "svg-output")]
+    #[structopt(parse(from_os_str), number_of_values = 1)]
+    svg_output_paths: Vec<PathBuf>,
+
+    /// How many species at the top, and at the bottom, of the rankings the charts show
+    #[structopt(long = "chart-top", default_value = "10")]
+    chart_top: usize,
+
+    /// How to write game and win counts in human-readable output (plain or grouped)
+    #[structopt(long = "number-format", default_value = "plain")]
+    number_format: NumberFormat,
+
+    /// Show at most this many species in the human-readable output, followed by a line saying
+    /// which page they are, e.g. to fit a terminal under `watch`
+    #[structopt(long = "max-rows")]
+    max_rows: Option<NonZeroUsize>,
+
+    /// Which page of --max-rows species to show in the human-readable output, starting at 1
+    #[structopt(long = "page", requires = "max-rows")]
+    page: Option<NonZeroUsize>,
+
+    /// Cut species names in the human-readable output to this many columns, ending them with "…"
+    #[structopt(long = "name-width")]
+    name_width: Option<NonZeroUsize>,
+
+    /// A CSV file of translated species names, with a header row naming each column's locale
+    /// (e.g. species,ja,fr,de)
+    #[structopt(long = "translations", requires = "locale")]
+    #[structopt(parse(from_os_str))]
+    translations_path: Option<PathBuf>,
+
+    /// Which locale in the --translations file to show species names in, in human-readable
+    /// outputs; CSV and state outputs keep the English names
+    #[structopt(long = "locale", requires = "translations-path")]
+    locale: Option<String>,
+
+    /// How to rank species with identical deviations (competition or dense)
+    #[structopt(long = "ranking", default_value = "competition")]
+    ranking: Ranking,
+
+    /// Weight each battle's games by 0.5^(age / half-life) in winrates and deviations, where age
+    /// is measured from the newest day in the input, e.g. 14d, 2w, or 36h (not available with
+    /// `coordinate`)
+    #[structopt(long = "recency-half-life")]
+    recency_half_life: Option<HalfLife>,
+
+    /// Mark species with fewer than this many games as provisional: they're listed separately
+    /// in human-readable output, and CSV output gains a true/false column for it
+    #[structopt(long = "provisional-below")]
+    provisional_below: Option<u32>,
+
+    /// End each output with a summary of the run (battles, species, elo cutoff, timestamp)
+    #[structopt(long = "summary")]
+    summary: bool,
+
+    /// Start each output with the tool version, git commit, options, an input fingerprint, and
+    /// the day directories analyzed with their file counts
+    #[structopt(long = "metadata")]
+    metadata: bool,
+
+    /// Where to write each species' winrate in long vs short games
+    #[structopt(long = "clutch-output")]
+    #[structopt(parse(from_os_str), number_of_values = 1)]
+    clutch_output_paths: Vec<PathBuf>,
+
+    /// Where to write the mean rating of the players who received each species
+    #[structopt(long = "rating-output")]
+    #[structopt(parse(from_os_str), number_of_values = 1)]
+    rating_output_paths: Vec<PathBuf>,
+
+    /// The random sets JSON the generator draws from (e.g. data/random-battles/gen9/sets.json),
+    /// whose species are the pool for --rarity-output
+    #[structopt(long = "sets")]
     #[structopt(parse(from_os_str))]
-    human_readable_output_path: Option<PathBuf>,
+    sets_path: Option<PathBuf>,
+
+    /// Where to write the species in the --sets pool that never appeared or appeared far less
+    /// than their share, and those that appeared without being in the pool
+    #[structopt(long = "rarity-output")]
+    #[structopt(parse(from_os_str), number_of_values = 1)]
+    rarity_output_paths: Vec<PathBuf>,
+
+    /// How often --rarity-output expects each species in the pool to appear (uniform, or sets
+    /// to weight each by how many sets it has)
+    #[structopt(long = "usage-model", default_value = "uniform")]
+    usage_model: rarity::UsageModel,
+
+    /// Experimental: where to write the winrates of team archetypes, found by clustering teams
+    /// by their species (takes a second pass over the input)
+    #[structopt(long = "archetype-output")]
+    #[structopt(parse(from_os_str), number_of_values = 1)]
+    archetype_output_paths: Vec<PathBuf>,
+
+    /// How many archetypes to cluster teams into
+    #[structopt(long = "archetypes", default_value = "8")]
+    archetypes: usize,
+
+    /// Where to write winrates by species, nature, and EV/IV spread, for standard formats (takes
+    /// a second pass over the input)
+    #[structopt(long = "spread-output")]
+    #[structopt(parse(from_os_str), number_of_values = 1)]
+    spread_output_paths: Vec<PathBuf>,
+
+    /// Where to write each species' winrate in games that were played out and in games that were
+    /// forfeited or abandoned early (takes a second pass over the input)
+    #[structopt(long = "abandoned-output")]
+    #[structopt(parse(from_os_str), number_of_values = 1)]
+    abandoned_output_paths: Vec<PathBuf>,
+
+    /// Where to write how much each species' winrate varies from day to day (takes a second pass
+    /// over the input)
+    #[structopt(long = "consistency-output")]
+    #[structopt(parse(from_os_str), number_of_values = 1)]
+    consistency_output_paths: Vec<PathBuf>,
+
+    /// Where to write species coefficients from a logistic model of each battle's result, which
+    /// controls for teammates, opponents, and the players' rating difference (takes a second pass
+    /// over the input; only rated two-player battles are used)
+    #[structopt(long = "regression-output")]
+    #[structopt(parse(from_os_str), number_of_values = 1)]
+    regression_output_paths: Vec<PathBuf>,
+
+    /// Where to write one CSV row per side of every counted battle (battle, day, side, player,
+    /// rating, won, turns, and species), for fitting other models (takes a second pass over the
+    /// input)
+    #[structopt(long = "observations-output")]
+    #[structopt(parse(from_os_str), number_of_values = 1)]
+    observations_output_paths: Vec<PathBuf>,
+
+    /// Where to write the first and last day each species was seen, to spot species added to or
+    /// removed from the pool partway through the input (takes a second pass over the input)
+    #[structopt(long = "seen-output")]
+    #[structopt(parse(from_os_str), number_of_values = 1)]
+    seen_output_paths: Vec<PathBuf>,
+
+    /// Where to write p1 and p2 winrates, overall and for each species, to sanity-check the data
+    /// (takes a second pass over the input)
+    #[structopt(long = "side-output")]
+    #[structopt(parse(from_os_str), number_of_values = 1)]
+    side_output_paths: Vec<PathBuf>,
+
+    /// Where to write how often the higher-rated player wins at each rating gap, next to the Elo
+    /// formula's expectation (takes a second pass over the input)
+    #[structopt(long = "calibration-output")]
+    #[structopt(parse(from_os_str), number_of_values = 1)]
+    calibration_output_paths: Vec<PathBuf>,
+
+    /// Where to write the mean rating each species' players gained per game, estimated from
+    /// both players' ratings as the ladder would (takes a second pass over the input)
+    #[structopt(long = "rating-gain-output")]
+    #[structopt(parse(from_os_str), number_of_values = 1)]
+    rating_gain_output_paths: Vec<PathBuf>,
+
+    /// Where to write team winrates grouped by each team's ace, its highest-level member unless
+    /// --ace-species is given (takes a second pass over the input)
+    #[structopt(long = "ace-output")]
+    #[structopt(parse(from_os_str), number_of_values = 1)]
+    ace_output_paths: Vec<PathBuf>,
+
+    /// A list of species, one per line and most important first; a team's ace is the first of
+    /// them on the team
+    #[structopt(long = "ace-species", requires = "ace-output-paths")]
+    #[structopt(parse(from_os_str))]
+    ace_species_path: Option<PathBuf>,
+
+    /// Where to write a GraphViz (DOT) graph joining teammates that win more together than their
+    /// own winrates would suggest, e.g. for Gephi (takes a second pass over the input)
+    #[structopt(long = "synergy-output")]
+    #[structopt(parse(from_os_str), number_of_values = 1)]
+    synergy_output_paths: Vec<PathBuf>,
+
+    /// Where to write every teammate pair's winrate next to what its members' own winrates
+    /// would add up to, and the difference (lift) (takes a second pass over the input)
+    #[structopt(long = "pairs-output")]
+    #[structopt(parse(from_os_str), number_of_values = 1)]
+    pairs_output_paths: Vec<PathBuf>,
+
+    /// How to order the pairs output (lift, winrate, or games)
+    #[structopt(long = "pairs-sort", default_value = "lift")]
+    pairs_sort: synergy::PairSort,
+
+    /// Where to write an SVG heatmap of how each species does against each other species, with
+    /// wins in blue and losses in red (takes a second pass over the input)
+    #[structopt(long = "matchup-heatmap")]
+    #[structopt(parse(from_os_str), number_of_values = 1)]
+    matchup_heatmap_paths: Vec<PathBuf>,
+
+    /// How many of the most used species the matchup heatmap includes; only matchups between
+    /// them are counted, so this also bounds its memory use
+    #[structopt(long = "matchup-top", default_value = "30")]
+    matchup_top: usize,
+
+    /// Where to write every raw species string in the input, what it was counted as, and how many
+    /// games it appeared in, to spot new formes that should be merged (takes a second pass over
+    /// the input)
+    #[structopt(long = "normalization-report")]
+    #[structopt(parse(from_os_str), number_of_values = 1)]
+    normalization_report_paths: Vec<PathBuf>,
+
+    /// Where to write the forum post listing the --publish-top best and worst species
+    #[structopt(long = "publish-output", requires = "publish-top")]
+    #[structopt(parse(from_os_str), number_of_values = 1)]
+    publish_output_paths: Vec<PathBuf>,
+
+    /// How many of the best and worst species to list in the forum post; only species whose
+    /// 95% confidence interval excludes 50% are eligible
+    #[structopt(long = "publish-top", requires = "publish-output-paths")]
+    publish_top: Option<usize>,
+
+    /// Where to write a compact binary copy of the stats with a species index, for the lookup
+    /// subcommand
+    #[structopt(long = "artifact-output")]
+    #[structopt(parse(from_os_str), number_of_values = 1)]
+    artifact_output_paths: Vec<PathBuf>,
+
+    /// Where to write suggested level and set changes for species far from a 50% winrate
+    /// (takes a second pass over the input)
+    #[structopt(long = "balance-output")]
+    #[structopt(parse(from_os_str), number_of_values = 1)]
+    balance_output_paths: Vec<PathBuf>,
+
+    /// Species with fewer games than this get no balance suggestions
+    #[structopt(long = "balance-min-games", default_value = "1000")]
+    balance_min_games: u32,
+
+    /// How many standard deviations from 50% a species must be to get a balance suggestion
+    #[structopt(long = "balance-deviations", default_value = "3")]
+    balance_deviations: f64,
+
+    /// Where to write each species' winrate when its player was on a winning streak, a losing
+    /// streak, or neither (takes a second pass over the input, in time order)
+    #[structopt(long = "streak-output")]
+    #[structopt(parse(from_os_str), number_of_values = 1)]
+    streak_output_paths: Vec<PathBuf>,
+
+    /// Where to write each player's mean rating on each day they played, as CSV rows of
+    /// player, day, rated games, and mean rating (not available with `coordinate`)
+    #[structopt(long = "player-ratings-output")]
+    #[structopt(parse(from_os_str), number_of_values = 1)]
+    player_ratings_output_paths: Vec<PathBuf>,
+
+    /// What kind of format the input is (random or standard); in standard formats, only team
+    /// members that were sent out are counted, since team preview formats leave some unused
+    #[structopt(long = "format-kind", default_value = "random")]
+    format_kind: FormatKind,
+
+    /// Only analyze battles played with this timer (standard or blitz)
+    #[structopt(long = "timer")]
+    timer: Option<Timer>,
+
+    /// Skip battles whose contents exactly match one already analyzed
+    #[structopt(long = "skip-duplicates")]
+    skip_duplicates: bool,
+
+    /// Analyze battles where no player has a rating (as on some private servers), regardless of
+    /// --minimum-elo
+    #[structopt(long = "include-unrated")]
+    include_unrated: bool,
+
+    /// Skip battles involving guest accounts (`Guest ########`)
+    #[structopt(long = "exclude-guests")]
+    exclude_guests: bool,
+
+    /// Skip battles that were forfeited or lost to inactivity within the first few turns, which
+    /// say little about the teams
+    #[structopt(long = "exclude-abandoned")]
+    exclude_abandoned: bool,
+
+    /// Skip battles involving any player listed in this file (one username per line)
+    #[structopt(long = "exclude-players")]
+    #[structopt(parse(from_os_str))]
+    excluded_players_path: Option<PathBuf>,
+
+    /// Only analyze battles involving a player listed in this file (one username per line)
+    #[structopt(long = "only-players")]
+    #[structopt(parse(from_os_str))]
+    only_players_path: Option<PathBuf>,
+
+    /// A JSON object mapping alt accounts to main accounts (e.g. {"annika alt": "annika"}), so
+    /// player filters and player-level outputs treat a person's accounts as one player
+    #[structopt(long = "alts")]
+    #[structopt(parse(from_os_str))]
+    alts_path: Option<PathBuf>,
+
+    /// A file of known changes to species, one species,day,label line each (e.g.
+    /// Mew,2024-03-01,level 84 to 82); each annotated species is counted separately before and
+    /// after each of its changes (not available with `coordinate`)
+    #[structopt(long = "annotations")]
+    #[structopt(parse(from_os_str))]
+    annotations_path: Option<PathBuf>,
+
+    /// Scan the input for accounts with implausible game volume or near-constant turn times, and
+    /// skip their battles
+    #[structopt(long = "exclude-suspected-bots")]
+    exclude_suspected_bots: bool,
+
+    /// A second format directory (e.g. the blitz variant) to compare against the input
+    #[structopt(long = "compare-input")]
+    #[structopt(parse(from_os_str))]
+    compare_dir: Option<PathBuf>,
+
+    /// Where to write the comparison between --input and --compare-input
+    #[structopt(long = "comparison-output")]
+    #[structopt(parse(from_os_str), number_of_values = 1)]
+    comparison_output_paths: Vec<PathBuf>,
+
+    /// Only analyze this slice of the input files (k/n), for splitting a run across machines
+    #[structopt(long = "shard")]
+    shard: Option<Shard>,
+
+    /// Only analyze this fraction of the input files (e.g. 0.1), chosen deterministically by
+    /// their paths, for quick estimates; outputs are marked as sampled
+    #[structopt(long = "sample")]
+    sample: Option<Sample>,
+
+    /// Save the aggregated state, to be combined with other shards' using `merge`
+    #[structopt(long = "state-output")]
+    #[structopt(parse(from_os_str), number_of_values = 1)]
+    state_output_paths: Vec<PathBuf>,
+
+    /// Print which days and files would be analyzed, then exit without parsing them
+    #[structopt(long = "dry-run")]
+    dry_run: bool,
+
+    /// Analyze a small bundled dataset and check the outputs against known-good ones, to validate
+    /// this build
+    #[structopt(long = "self-test")]
+    self_test: bool,
+
+    /// With --self-test, rewrite the golden files in selftest/ instead of checking them
+    #[structopt(long = "self-test-update", requires = "self-test")]
+    self_test_update: bool,
+
+    /// Don't print progress messages, only warnings, errors, and requested output
+    #[structopt(short = "q", long = "quiet")]
+    quiet: bool,
+
+    /// Print the peak memory used by each stage of the run
+    #[structopt(long = "report-memory")]
+    report_memory: bool,
+
+    /// How many files each pipeline task (or each unit sent to a worker) handles at once
+    #[structopt(long = "batch-size", default_value = "64")]
+    batch_size: usize,
+
+    /// Merge results in input order, so repeated runs over the same input write byte-identical
+    /// outputs regardless of thread timing (slightly slower; not available with `coordinate`)
+    #[structopt(long = "deterministic")]
+    deterministic: bool,
+
+    /// Read empty and truncated battle files again at the end of the run, in case a log sync
+    /// was still writing them; those still incomplete are skipped and counted in the summary
+    #[structopt(long = "retry-incomplete")]
+    retry_incomplete: bool,
 
     #[structopt(long = "exclude")]
     exclusion: Option<String>,
+
+    /// Which files in each day directory are battles, as a glob (e.g. '*.json*' to also read
+    /// .json.log files, or '*' to read every file)
+    #[structopt(long = "file-pattern", default_value = "*.json")]
+    file_pattern: FilePattern,
+
+    /// How many directories below --input each day's directory is, for layouts like
+    /// format/month/day/hour/*.json (2); battles are read from any depth beneath it, and the day
+    /// is named after its directory
+    #[structopt(long = "day-depth", default_value = "1")]
+    day_depth: NonZeroUsize,
+
+    /// Battle log analyses to run (comma-separated: leads, kos, tera, hazards)
+    #[structopt(long = "log-analyses", use_delimiter = true)]
+    log_analyses: Vec<LogAnalysis>,
+
+    /// Where to write the log analyses' per-species results
+    #[structopt(long = "log-output")]
+    #[structopt(parse(from_os_str), number_of_values = 1)]
+    log_output_paths: Vec<PathBuf>,
+
+    #[structopt(subcommand)]
+    command: Option<Command>,
 }
 
-fn handle_directory(
-    min_elo: u64,
-    format_dir: &PathBuf,
-    exclusion: Option<String>,
-) -> Result<stats::Stats, stats::StatsError> {
-    let mut stats = Stats::new();
-    let stats_mutex = Mutex::new(&mut stats);
+impl Options {
+    /// The days and files in `dir` that these options select
+    fn input_plan(&self, dir: &Path) -> std::io::Result<InputPlan> {
+        let mut plan = InputPlan::new(
+            dir,
+            &self.exclusion,
+            &self.layout(),
+            self.shard,
+            self.sample,
+        )?;
+        if let Some(Command::Tournament { from, to, .. }) = &self.command {
+            plan.restrict_to_days(from, to);
+        }
+        Ok(plan)
+    }
+
+    /// Every output option's label and path
+    fn output_paths(&self) -> Vec<(&'static str, &'static str, &[PathBuf])> {
+        vec![
+            ("--csv-output", "CSV output", &self.csv_output_paths),
+            (
+                "--human-output",
+                "Human-readable output",
+                &self.human_readable_output_paths,
+            ),
+            (
+                "--markdown-output",
+                "Markdown output",
+                &self.markdown_output_paths,
+            ),
+            (
+                "--bbcode-output",
+                "BBCode output",
+                &self.bbcode_output_paths,
+            ),
+            ("--code-output", "Chat code output", &self.code_output_paths),
+            ("--html-output", "HTML output", &self.html_output_paths),
+            ("--json-output", "JSON output", &self.json_output_paths),
+            (
+                "--jsonl-output",
+                "JSON Lines output",
+                &self.jsonl_output_paths,
+            ),
+            (
+                "--species-jsonl-output",
+                "Species JSON Lines output",
+                &self.species_jsonl_output_paths,
+            ),
+            (
+                "--parquet-output",
+                "Parquet output",
+                &self.parquet_output_paths,
+            ),
+            ("--xlsx-output", "Excel output", &self.xlsx_output_paths),
+            ("--chart-output", "Chart output", &self.chart_output_paths),
+            ("--svg-output", "SVG output", &self.svg_output_paths),
+            ("--log-output", "Log output", &self.log_output_paths),
+            (
+                "--clutch-output",
+                "Clutch output",
+                &self.clutch_output_paths,
+            ),
+            (
+                "--rating-output",
+                "Rating output",
+                &self.rating_output_paths,
+            ),
+            (
+                "--rarity-output",
+                "Rarity output",
+                &self.rarity_output_paths,
+            ),
+            (
+                "--player-ratings-output",
+                "Player ratings output",
+                &self.player_ratings_output_paths,
+            ),
+            (
+                "--streak-output",
+                "Streak output",
+                &self.streak_output_paths,
+            ),
+            (
+                "--archetype-output",
+                "Archetype output",
+                &self.archetype_output_paths,
+            ),
+            (
+                "--publish-output",
+                "Publish output",
+                &self.publish_output_paths,
+            ),
+            (
+                "--artifact-output",
+                "Artifact output",
+                &self.artifact_output_paths,
+            ),
+            (
+                "--normalization-report",
+                "Normalization report",
+                &self.normalization_report_paths,
+            ),
+            (
+                "--abandoned-output",
+                "Abandoned output",
+                &self.abandoned_output_paths,
+            ),
+            (
+                "--consistency-output",
+                "Consistency output",
+                &self.consistency_output_paths,
+            ),
+            (
+                "--regression-output",
+                "Regression output",
+                &self.regression_output_paths,
+            ),
+            (
+                "--observations-output",
+                "Observations output",
+                &self.observations_output_paths,
+            ),
+            ("--seen-output", "Seen output", &self.seen_output_paths),
+            ("--side-output", "Side output", &self.side_output_paths),
+            (
+                "--calibration-output",
+                "Calibration output",
+                &self.calibration_output_paths,
+            ),
+            (
+                "--rating-gain-output",
+                "Rating gain output",
+                &self.rating_gain_output_paths,
+            ),
+            ("--ace-output", "Ace output", &self.ace_output_paths),
+            (
+                "--synergy-output",
+                "Synergy output",
+                &self.synergy_output_paths,
+            ),
+            ("--pairs-output", "Pairs output", &self.pairs_output_paths),
+            (
+                "--matchup-heatmap",
+                "Matchup heatmap",
+                &self.matchup_heatmap_paths,
+            ),
+            (
+                "--spread-output",
+                "Spread output",
+                &self.spread_output_paths,
+            ),
+            (
+                "--balance-output",
+                "Balance output",
+                &self.balance_output_paths,
+            ),
+            (
+                "--comparison-output",
+                "Comparison output",
+                &self.comparison_output_paths,
+            ),
+            ("--state-output", "State output", &self.state_output_paths),
+        ]
+    }
+
+    /// Outputs written by reading the battles again after the main analysis
+    fn second_pass_output_paths(&self) -> Vec<(&'static str, &[PathBuf])> {
+        vec![
+            ("--streak-output", &self.streak_output_paths),
+            ("--archetype-output", &self.archetype_output_paths),
+            ("--normalization-report", &self.normalization_report_paths),
+            ("--abandoned-output", &self.abandoned_output_paths),
+            ("--consistency-output", &self.consistency_output_paths),
+            ("--regression-output", &self.regression_output_paths),
+            ("--observations-output", &self.observations_output_paths),
+            ("--seen-output", &self.seen_output_paths),
+            ("--side-output", &self.side_output_paths),
+            ("--calibration-output", &self.calibration_output_paths),
+            ("--rating-gain-output", &self.rating_gain_output_paths),
+            ("--ace-output", &self.ace_output_paths),
+            ("--synergy-output", &self.synergy_output_paths),
+            ("--pairs-output", &self.pairs_output_paths),
+            ("--matchup-heatmap", &self.matchup_heatmap_paths),
+            ("--spread-output", &self.spread_output_paths),
+            ("--balance-output", &self.balance_output_paths),
+            ("--comparison-output", &self.comparison_output_paths),
+        ]
+    }
+
+    /// The subcommand, and the options given that it can't honor, which would otherwise be
+    /// ignored without a word
+    fn unsupported_options(&self) -> Option<(&'static str, Vec<&'static str>)> {
+        let (command, mut unsupported) = match &self.command {
+            Some(Command::Coordinate { .. }) => (
+                "coordinate",
+                // workers aren't told which day each battle is from
+                vec![
+                    ("--recency-half-life", self.recency_half_life.is_some()),
+                    ("--annotations", self.annotations_path.is_some()),
+                    (
+                        "--player-ratings-output",
+                        !self.player_ratings_output_paths.is_empty(),
+                    ),
+                ],
+            ),
+            Some(Command::Merge { .. }) => ("merge", vec![]),
+            Some(Command::Rollup { .. }) => ("rollup", vec![]),
+            _ => return None,
+        };
+        // these only have the combined results, not the battles
+        unsupported.extend(
+            self.second_pass_output_paths()
+                .into_iter()
+                .map(|(flag, paths)| (flag, !paths.is_empty())),
+        );
+        Some((
+            command,
+            unsupported
+                .into_iter()
+                .filter(|(_, given)| *given)
+                .map(|(flag, _)| flag)
+                .collect(),
+        ))
+    }
+
+    fn layout(&self) -> Layout {
+        Layout {
+            file_pattern: self.file_pattern.clone(),
+            day_depth: self.day_depth.get(),
+        }
+    }
+
+    fn pipeline(&self) -> Pipeline {
+        Pipeline {
+            batch_size: self.batch_size,
+            deterministic: self.deterministic,
+            retry_incomplete: self.retry_incomplete,
+        }
+    }
+
+    /// The filter for these options, which scans `format_dir` first when excluding suspected bots
+    fn battle_filter(&self, format_dir: &Path) -> std::io::Result<BattleFilter> {
+        let tournament = match &self.command {
+            Some(Command::Tournament { roster, .. }) => Some(roster),
+            _ => None,
+        };
+        let suspected_bots = if self.exclude_suspected_bots {
+            status!("Looking for suspected bots...");
+            let plan = InputPlan::new(format_dir, &self.exclusion, &self.layout(), None, None)?;
+            bots::find_suspected_bots(plan.days.iter().map(|day| day.files.as_slice()))
+        } else {
+            HashSet::new()
+        };
+
+        let mut filter = BattleFilter {
+            timer: self.timer,
+            format_kind: self.format_kind,
+            skip_duplicates: self.skip_duplicates,
+            // tournament games aren't rated
+            include_unrated: self.include_unrated || tournament.is_some(),
+            exclude_guests: self.exclude_guests,
+            exclude_abandoned: self.exclude_abandoned,
+            excluded_players: match &self.excluded_players_path {
+                Some(path) => filter::read_player_list(path)?,
+                None => HashSet::new(),
+            },
+            only_players: match &self.only_players_path {
+                Some(path) => Some(filter::read_player_list(path)?),
+                None => None,
+            },
+            suspected_bots,
+            roster: match tournament {
+                Some(path) => Some(filter::read_player_list(path)?),
+                None => None,
+            },
+            opponent_species: match &self.command {
+                Some(Command::Query { vs }) => Some(Stats::normalize_species(vs)),
+                _ => None,
+            },
+            alts: match &self.alts_path {
+                Some(path) => filter::read_alts(path)?,
+                None => HashMap::new(),
+            },
+            annotations: match &self.annotations_path {
+                Some(path) => annotations::Annotations::read(path)?,
+                None => annotations::Annotations::default(),
+            },
+        };
+        filter.resolve_alts();
+        Ok(filter)
+    }
+}
+
+#[derive(StructOpt)]
+enum Command {
+    /// Combine state files saved with --state-output (e.g. from sharded runs) and write the
+    /// usual outputs, which are given before the subcommand
+    Merge {
+        #[structopt(parse(from_os_str), required = true)]
+        states: Vec<PathBuf>,
+    },
+    /// Combine several months (state files, or input directories analyzed with the other
+    /// options) into one quarter or season: the combined state is saved, the usual outputs given
+    /// before the subcommand are written, and each month's winrates can be kept as columns
+    Rollup {
+        /// The months' state files or directories, in order
+        #[structopt(long = "inputs", parse(from_os_str), required = true)]
+        inputs: Vec<PathBuf>,
+
+        /// Where to save the combined state
+        #[structopt(long = "out", parse(from_os_str))]
+        out: PathBuf,
+
+        /// Where to write each species' overall winrate and its winrate in each month
+        #[structopt(long = "trend-output", parse(from_os_str))]
+        trend_output: Option<PathBuf>,
+    },
+    /// Hand out the input's battles to workers over TCP, then write the usual outputs from
+    /// their combined results
+    Coordinate {
+        /// Address to listen for workers on (e.g. 0.0.0.0:7878 to accept workers on other
+        /// machines)
+        #[structopt(long = "listen", default_value = "127.0.0.1:7878")]
+        listen: String,
+
+        /// Secret that workers must present before they're sent any battles
+        #[structopt(
+            long = "token",
+            env = "RANDBATS_WINRATES_TOKEN",
+            hide_env_values = true
+        )]
+        token: String,
+    },
+    /// Write the usual outputs over only the games between a tournament's players, played within
+    /// a range of days
+    Tournament {
+        /// File listing the tournament's players, one username per line
+        #[structopt(long = "roster", parse(from_os_str))]
+        roster: PathBuf,
 
-    for entry in fs::read_dir(format_dir)? {
-        let path = entry?.path();
-        if path.is_dir() {
-            let name = path.file_name().unwrap().to_str().unwrap_or("");
+        /// First day of the tournament, as named in the input directory (e.g. 2024-03-01)
+        #[structopt(long = "from")]
+        from: String,
+
+        /// Last day of the tournament
+        #[structopt(long = "to")]
+        to: String,
+    },
+    /// Write the usual outputs counting only teams that faced a given species, giving each
+    /// species' winrate against it
+    Query {
+        /// The opposing species, e.g. Kingambit
+        #[structopt(long = "vs")]
+        vs: String,
+    },
+    /// Analyze battles handed out by a coordinator (no other options are needed)
+    Worker {
+        /// The coordinator's address
+        coordinator: String,
+
+        /// The secret given to the coordinator's --token
+        #[structopt(
+            long = "token",
+            env = "RANDBATS_WINRATES_TOKEN",
+            hide_env_values = true
+        )]
+        token: String,
+    },
+    /// Download public replays into the directory layout this tool reads, one directory per day
+    /// (no other options are needed; requires curl)
+    FetchLogs {
+        /// Format to download, e.g. gen9randombattle
+        #[structopt(long = "format")]
+        format: String,
+
+        /// Earliest day to download, e.g. 2024-06-01
+        #[structopt(long = "since")]
+        since: String,
+
+        /// Directory to download into (existing replays are skipped)
+        #[structopt(long = "out", parse(from_os_str))]
+        out: PathBuf,
+    },
+    /// Print how winrates, deviations and the other metrics are computed, with the parameters
+    /// the other options given would use (no input is read)
+    ExplainMetrics,
+    /// Print one species' stats from a file written with --artifact-output, without reading the
+    /// rest of it (no other options are needed)
+    Lookup {
+        /// The artifact file
+        #[structopt(parse(from_os_str))]
+        artifact: PathBuf,
+
+        /// The species, e.g. Kingambit
+        species: String,
+    },
+}
+
+/// Where an output named `-` is written instead of a file
+const STDOUT_PATH: &str = "-";
+
+/// Somewhere an output is written
+enum Destination {
+    Stdout(std::io::StdoutLock<'static>),
+    File(fs::File),
+    /// Uploaded when it's finished
+    Remote(remote::RemoteWriter),
+    /// Several destinations at once
+    Tee(Tee),
+}
+
+impl Write for Destination {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        match self {
+            Destination::Stdout(out) => out.write(buf),
+            Destination::File(out) => out.write(buf),
+            Destination::Remote(out) => out.write(buf),
+            Destination::Tee(out) => out.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        match self {
+            Destination::Stdout(out) => out.flush(),
+            Destination::File(out) => out.flush(),
+            Destination::Remote(out) => out.flush(),
+            Destination::Tee(out) => out.flush(),
+        }
+    }
+}
+
+impl Destination {
+    /// Flushes everything written, and uploads it if the destination is remote; a destination
+    /// that's dropped without being finished is never uploaded
+    fn finish(self) -> std::io::Result<()> {
+        match self {
+            Destination::Remote(out) => out.finish(),
+            Destination::Tee(Tee(outs)) => {
+                outs.into_iter().try_for_each(|out| match out.finish() {
+                    Err(e) if e.kind() == std::io::ErrorKind::BrokenPipe => Ok(()),
+                    result => result,
+                })
+            }
+            mut out => out.flush(),
+        }
+    }
+}
+
+/// Opens an output file for writing, or stdout if the path is `-`, or a remote output (uploaded
+/// when finished) if the path is a URL
+fn create_output(path: &Path) -> std::io::Result<Destination> {
+    Ok(if path == Path::new(STDOUT_PATH) {
+        Destination::Stdout(std::io::stdout().lock())
+    } else if let Some(url) = remote::url(path) {
+        Destination::Remote(remote::RemoteWriter::new(url))
+    } else {
+        Destination::File(fs::File::create(path)?)
+    })
+}
+
+/// Writes everything written to it to each of several outputs
+struct Tee(Vec<Destination>);
+
+impl Write for Tee {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let mut idx = 0;
+        while idx < self.0.len() {
+            match self.0[idx].write_all(buf) {
+                // one reader stopping early shouldn't cut the other outputs short
+                Err(e) if e.kind() == std::io::ErrorKind::BrokenPipe => {
+                    self.0.remove(idx);
+                }
+                result => {
+                    result?;
+                    idx += 1;
+                }
+            }
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.0.iter_mut().try_for_each(|out| out.flush())
+    }
+}
+
+/// Opens every destination given for an output option; writes go to all of them
+fn create_outputs(paths: &[PathBuf]) -> std::io::Result<Destination> {
+    match paths {
+        [path] => create_output(path),
+        _ => Ok(Destination::Tee(Tee(paths
+            .iter()
+            .map(|path| create_output(path))
+            .collect::<Result<_, _>>()?))),
+    }
+}
+
+/// An output option's destinations, if it was given at all
+fn destinations(paths: &[PathBuf]) -> Option<&[PathBuf]> {
+    if paths.is_empty() {
+        None
+    } else {
+        Some(paths)
+    }
+}
+
+/// Like `fs::write` to each path, but writes to stdout for a path of `-` and uploads to URLs
+fn write_output(paths: &[PathBuf], contents: impl AsRef<[u8]>) -> std::io::Result<()> {
+    for path in paths {
+        let mut out = create_output(path)?;
+        match out.write_all(contents.as_ref()).and_then(|_| out.finish()) {
+            // the reader (e.g. `head`) has all it wanted
+            Err(e) if e.kind() == std::io::ErrorKind::BrokenPipe => {}
+            result => result?,
+        }
+    }
+    Ok(())
+}
+
+/// Writes a report's human-readable output, and its tables to --jsonl-output if requested
+fn write_report(
+    paths: &[PathBuf],
+    section: &str,
+    blocks: Vec<Block>,
+    jsonl: &mut Option<jsonl::JsonlWriter<Destination>>,
+) -> std::io::Result<()> {
+    if let Some(jsonl) = jsonl {
+        jsonl.write_tables(section, &blocks)?;
+    }
+    write_output(paths, table::blocks_to_text(&blocks))
+}
+
+/// Finishes the --jsonl-output writer, if there is one, uploading it if it's remote
+fn finish_jsonl(jsonl: Option<jsonl::JsonlWriter<Destination>>) -> std::io::Result<()> {
+    match jsonl.map(|jsonl| jsonl.into_inner().finish()) {
+        Some(Err(e)) if e.kind() != std::io::ErrorKind::BrokenPipe => Err(e),
+        _ => Ok(()),
+    }
+}
+
+/// Hashes the relative path and size of every file under `dir`, in a stable order
+fn fingerprint_directory(dir: &Path) -> std::io::Result<String> {
+    fn visit(dir: &Path, root: &Path, hasher: &mut hash::Fnv1a) -> std::io::Result<()> {
+        let mut entries = fs::read_dir(dir)?
+            .map(|entry| entry.map(|e| e.path()))
+            .collect::<Result<Vec<_>, _>>()?;
+        entries.sort();
+
+        for path in entries {
+            if path.is_dir() {
+                visit(&path, root, hasher)?;
+            } else {
+                let relative = path.strip_prefix(root).unwrap_or(&path);
+                hasher.write(relative.to_string_lossy().as_bytes());
+                hasher.write(&fs::metadata(&path)?.len().to_le_bytes());
+            }
+        }
+        Ok(())
+    }
+
+    let mut hasher = hash::Fnv1a::default();
+    visit(dir, dir, &mut hasher)?;
+    Ok(format!("{:016x}", hasher.finish()))
+}
+
+/// The battle files in one day directory that will be analyzed
+struct DayPlan {
+    name: String,
+    files: Vec<PathBuf>,
+}
+
+/// Everything a run would read, worked out before any JSON is parsed
+struct InputPlan {
+    days: Vec<DayPlan>,
+    /// Names of day directories skipped by `--exclude`
+    ignored: Vec<String>,
+}
+
+/// Where battles are found in the input directory
+#[derive(Clone, Debug, PartialEq)]
+struct Layout {
+    /// Which files are battles
+    file_pattern: FilePattern,
+    /// How many directories below the input each day's directory is; 1 for `format/day/*.json`
+    day_depth: usize,
+}
+
+impl Default for Layout {
+    fn default() -> Self {
+        Layout {
+            file_pattern: FilePattern::default(),
+            day_depth: 1,
+        }
+    }
+}
+
+impl Layout {
+    /// Directories `depth` levels below `dir`, in path order
+    fn day_dirs(dir: &Path, depth: usize) -> std::io::Result<Vec<PathBuf>> {
+        let mut entries = fs::read_dir(dir)?
+            .map(|entry| entry.map(|e| e.path()))
+            .collect::<Result<Vec<_>, _>>()?;
+        entries.sort();
+
+        let mut dirs = vec![];
+        for path in entries.into_iter().filter(|path| path.is_dir()) {
+            if depth <= 1 {
+                dirs.push(path);
+            } else {
+                dirs.extend(Layout::day_dirs(&path, depth - 1)?);
+            }
+        }
+        Ok(dirs)
+    }
+
+    /// Every file anywhere under `dir`, sorted into those matching the pattern and the rest
+    fn battle_files(
+        &self,
+        dir: &Path,
+        matched: &mut Vec<PathBuf>,
+        unmatched: &mut usize,
+    ) -> std::io::Result<()> {
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+            if path.is_dir() {
+                self.battle_files(&path, matched, unmatched)?;
+            } else if self
+                .file_pattern
+                .matches(path.file_name().unwrap().to_str().unwrap_or(""))
+            {
+                matched.push(path);
+            } else {
+                *unmatched += 1;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl InputPlan {
+    fn new(
+        format_dir: &Path,
+        exclusion: &Option<String>,
+        layout: &Layout,
+        shard: Option<Shard>,
+        sample: Option<Sample>,
+    ) -> std::io::Result<Self> {
+        let mut days: Vec<DayPlan> = vec![];
+        let mut ignored = vec![];
+
+        for path in Layout::day_dirs(format_dir, layout.day_depth)? {
+            let name = path.file_name().unwrap().to_str().unwrap_or("").to_string();
             let should_ignore = match exclusion {
                 Some(ref x) => name.contains(x),
                 None => false,
             };
             if should_ignore {
-                println!("Ignoring {}", name);
+                if !ignored.contains(&name) {
+                    ignored.push(name);
+                }
                 continue;
             }
 
-            println!("Analyzing {}...", name);
-            fs::read_dir(path)?
-                .collect::<Vec<_>>()
-                .par_iter()
-                .filter_map(|file| {
-                    let battle_json_path = file
-                        .as_ref()
-                        .expect(format!("error opening file").as_str())
-                        .path();
-                    let filename = battle_json_path.to_str().unwrap_or("");
-                    if !filename.ends_with(".json") {
-                        return None;
+            let (mut matched, mut unmatched) = (vec![], 0);
+            layout.battle_files(&path, &mut matched, &mut unmatched)?;
+            if matched.is_empty() && unmatched > 0 {
+                eprintln!(
+                    "Warning: none of the {} files in {} match --file-pattern '{}'",
+                    unmatched,
+                    path.display(),
+                    layout.file_pattern
+                );
+            }
+            let files = matched.into_iter().filter(|file| {
+                let relative = file.strip_prefix(format_dir).unwrap_or(file);
+                shard.is_none_or(|shard| shard.includes(relative))
+                    && sample.is_none_or(|sample| sample.includes(relative))
+            });
+
+            // deeper layouts can have a day's directory under more than one parent (e.g. a day
+            // that spans two month directories), which all count as the same day
+            match days.iter_mut().find(|day| day.name == name) {
+                Some(day) => day.files.extend(files),
+                None => days.push(DayPlan {
+                    name,
+                    files: files.collect(),
+                }),
+            }
+        }
+
+        Ok(Self { days, ignored })
+    }
+
+    /// Ignores days whose names fall outside `from..=to` (e.g. `2024-03-01` to `2024-03-03`)
+    fn restrict_to_days(&mut self, from: &str, to: &str) {
+        let (inside, outside) = std::mem::take(&mut self.days)
+            .into_iter()
+            .partition::<Vec<_>, _>(|day| from <= day.name.as_str() && day.name.as_str() <= to);
+        self.days = inside;
+        self.ignored.extend(outside.into_iter().map(|day| day.name));
+    }
+
+    /// Each day's name and how many files will be read from it
+    fn day_counts(&self) -> Vec<(String, usize)> {
+        self.days
+            .iter()
+            .map(|day| (day.name.clone(), day.files.len()))
+            .collect()
+    }
+
+    fn num_files(&self) -> usize {
+        self.days.iter().map(|day| day.files.len()).sum()
+    }
+}
+
+/// How many items may wait between pipeline stages before the earlier stage blocks
+const PIPELINE_CAPACITY: usize = 1024;
+/// Threads reading battle files from disk
+const READER_THREADS: usize = 4;
+
+/// How `handle_directory` splits up its work
+#[derive(Copy, Clone, Debug, PartialEq)]
+struct Pipeline {
+    /// Files per pipeline task
+    batch_size: usize,
+    /// Merge each batch's results in input order, so floating-point sums (and the order of
+    /// species tied in the rankings) don't depend on thread timing
+    deterministic: bool,
+    /// Read empty and truncated files again once everything else is done, in case they were
+    /// still being written
+    retry_incomplete: bool,
+}
+
+impl Default for Pipeline {
+    fn default() -> Self {
+        Pipeline {
+            batch_size: 64,
+            deterministic: false,
+            retry_incomplete: false,
+        }
+    }
+}
+
+/// Analyzes every battle in `plan`
+///
+/// Work flows through a pipeline of bounded channels spanning all days: one thread lists files
+/// in batches of `batch_size`, readers load them from disk, and parsers process them. Each stage
+/// runs at its own pace, and blocks when the next one falls behind. Parsers aggregate into their
+/// own `Stats`, which are merged once every battle is done, so days are analyzed in parallel
+/// without contending on a lock. In deterministic mode, parsers instead send each batch's
+/// `Stats` back to be merged in the order the batches were listed.
+fn handle_directory(
+    min_elo: u64,
+    plan: &InputPlan,
+    log_analyses: &[LogAnalysis],
+    filter: &BattleFilter,
+    pipeline: Pipeline,
+    track_player_ratings: bool,
+    recency_half_life: Option<HalfLife>,
+) -> Result<stats::Stats, stats::StatsError> {
+    let new_stats = || {
+        let mut stats = Stats::new();
+        stats.log_stats = LogStats::new(log_analyses);
+        if track_player_ratings {
+            stats.player_ratings = Some(PlayerRatings::default());
+        }
+        stats
+    };
+    let mut stats = Stats::new();
+    stats.log_stats = LogStats::new(log_analyses);
+    // content hashes of every battle seen so far, when skipping duplicates
+    let seen_battles = Mutex::new(HashSet::new());
+    // (day, path) of every empty or truncated file, when they're retried at the end
+    let incomplete = Mutex::new(vec![]);
+
+    for name in &plan.ignored {
+        status!("Ignoring {}", name);
+    }
+    let day_weights = match recency_half_life {
+        Some(half_life) => half_life.day_weights(plan.days.iter().map(|day| day.name.as_str())),
+        None => vec![1.0; plan.days.len()],
+    };
+    // files left to process in each day, for reporting when it's done
+    let remaining = plan
+        .days
+        .iter()
+        .map(|day| AtomicUsize::new(day.files.len()))
+        .collect::<Vec<_>>();
+    let finished_days = AtomicUsize::new(0);
+    let finish_day = |day: usize| {
+        let finished = finished_days.fetch_add(1, Ordering::SeqCst) + 1;
+        status!(
+            "Finished {} ({}/{} days)",
+            plan.days[day].name,
+            finished,
+            plan.days.len()
+        );
+    };
+
+    let add_batch = |stats: &mut Stats,
+                     day: usize,
+                     paths: &[PathBuf],
+                     battles: Vec<Result<String, BattleError>>| {
+        for (path, json) in paths.iter().zip(battles) {
+            let json = match json {
+                Ok(json) => json,
+                Err(BattleError::Empty | BattleError::Truncated) if pipeline.retry_incomplete => {
+                    incomplete.lock().unwrap().push((day, path.clone()));
+                    continue;
+                }
+                Err(e) => {
+                    stats.skip_malformed(path.display(), e.into());
+                    continue;
+                }
+            };
+            let is_duplicate = filter.skip_duplicates && {
+                let mut hasher = hash::Fnv1a::default();
+                hasher.write(json.as_bytes());
+                !seen_battles.lock().unwrap().insert(hasher.finish())
+            };
+            if is_duplicate {
+                stats.count("Duplicate battles skipped");
+            } else {
+                match Stats::process_battle(&json, min_elo, log_analyses, filter) {
+                    Ok(mut battle) => {
+                        let name = &plan.days[day].name;
+                        filter.annotations.apply(name, &mut battle.results);
+                        stats.add_battle_on(name, day_weights[day], battle)
                     }
+                    Err(e) => stats.skip_malformed(path.display(), e),
+                }
+            }
+        }
+    };
+
+    // batches carry their position in the listing, for deterministic mode
+    let (path_tx, path_rx) = bounded::<(usize, usize, &[PathBuf])>(PIPELINE_CAPACITY);
+    let (json_tx, json_rx) =
+        bounded::<(usize, usize, &[PathBuf], Vec<Result<String, BattleError>>)>(PIPELINE_CAPACITY);
+    let (batch_tx, batch_rx) = bounded::<(usize, Stats)>(PIPELINE_CAPACITY);
+    let parser_threads = thread::available_parallelism().map_or(1, |n| n.get());
+
+    thread::scope(|scope| {
+        let days = &plan.days;
+        let finish_day = &finish_day;
+        scope.spawn(move || {
+            let mut position = 0;
+            for (idx, day) in days.iter().enumerate() {
+                status!("Analyzing {}...", day.name);
+                if day.files.is_empty() {
+                    finish_day(idx);
+                }
+                for batch in day.files.chunks(pipeline.batch_size.max(1)) {
+                    path_tx.send((position, idx, batch)).unwrap();
+                    position += 1;
+                }
+            }
+        });
 
-                    let json = fs::read_to_string(&battle_json_path)
-                        .expect(format!("error reading file {}", filename).as_str());
-                    Some(
-                        Stats::process_json(min_elo, &json)
-                            .expect(format!("error processing JSON in {}", filename).as_str()),
-                    )
+        for _ in 0..READER_THREADS {
+            let path_rx = path_rx.clone();
+            let json_tx = json_tx.clone();
+            scope.spawn(move || {
+                for (position, day, paths) in path_rx {
+                    let battles = paths
+                        .iter()
+                        .map(|path| validate::read_battle(path))
+                        .collect::<Vec<_>>();
+                    json_tx.send((position, day, paths, battles)).unwrap();
+                }
+            });
+        }
+        drop(json_tx);
+
+        let parsers = (0..parser_threads)
+            .map(|_| {
+                let json_rx = json_rx.clone();
+                let batch_tx = batch_tx.clone();
+                let remaining = &remaining;
+                let (new_stats, add_batch) = (&new_stats, &add_batch);
+                scope.spawn(move || {
+                    let mut stats = new_stats();
+                    for (position, day, paths, battles) in json_rx {
+                        if pipeline.deterministic {
+                            let mut batch_stats = new_stats();
+                            add_batch(&mut batch_stats, day, paths, battles);
+                            batch_tx.send((position, batch_stats)).unwrap();
+                        } else {
+                            add_batch(&mut stats, day, paths, battles);
+                        }
+                        if remaining[day].fetch_sub(paths.len(), Ordering::SeqCst) == paths.len() {
+                            finish_day(day);
+                        }
+                    }
+                    stats
                 })
-                .for_each(|res| {
-                    stats_mutex.lock().unwrap().add_game_results(res);
-                });
+            })
+            .collect::<Vec<_>>();
+        drop(batch_tx);
+
+        // batches that finished before an earlier one, by position
+        let mut waiting = BTreeMap::new();
+        let mut next = 0;
+        for (position, batch_stats) in batch_rx {
+            waiting.insert(position, batch_stats);
+            while let Some(batch_stats) = waiting.remove(&next) {
+                stats.merge(batch_stats);
+                next += 1;
+            }
+        }
+
+        for parser in parsers {
+            stats.merge(parser.join().unwrap());
+        }
+    });
+
+    let mut incomplete = std::mem::take(&mut *incomplete.lock().unwrap());
+    if !incomplete.is_empty() {
+        status!("Retrying {} empty or truncated files...", incomplete.len());
+        // in listing order, so deterministic runs stay deterministic
+        incomplete.sort();
+        for (day, path) in incomplete {
+            match validate::read_battle(&path) {
+                Ok(json) => {
+                    stats.count("Incomplete files read on retry");
+                    add_batch(&mut stats, day, std::slice::from_ref(&path), vec![Ok(json)]);
+                }
+                Err(e) => stats.skip_malformed(path.display(), e.into()),
+            }
+        }
+    }
+
+    if let Some(duplicates) = stats.counter("Duplicate battles skipped") {
+        status!("Skipped {} duplicate battles", duplicates);
+    }
+    if let Some(malformed) = stats.counter("Malformed battles skipped") {
+        status!("Skipped {} malformed battles", malformed);
+    }
+    for (label, what) in [
+        ("Empty files skipped", "empty files"),
+        ("Truncated files skipped", "truncated files"),
+        ("Unreadable files skipped", "unreadable files"),
+    ] {
+        if let Some(skipped) = stats.counter(label) {
+            status!("Skipped {} {}", skipped, what);
         }
     }
+    if let Some(unrated) = stats.counter("Unrated battles excluded") {
+        status!(
+            "Excluded {} unrated battles (use --include-unrated to analyze them)",
+            unrated
+        );
+    }
+    for warning in stats.schema.warnings() {
+        eprintln!("{}", warning);
+    }
 
     Ok(stats)
 }
 
+/// Prints what a run with these options would do, without parsing any battles
+fn print_dry_run(options: &Options, format_dir: &Path, min_elo: u64) -> std::io::Result<()> {
+    let plan = options.input_plan(format_dir)?;
+
+    println!("Input directory: {}", format_dir.display());
+    println!("Minimum elo: {}", min_elo);
+    if options.file_pattern != FilePattern::default() {
+        println!("File pattern: {}", options.file_pattern);
+    }
+    if options.day_depth.get() > 1 {
+        println!(
+            "Day directories: {} levels below the input",
+            options.day_depth
+        );
+    }
+    if let Some(shard) = options.shard {
+        println!("Shard: {}", shard);
+    }
+    if let Some(sample) = options.sample {
+        println!("Sample: {}", sample);
+    }
+    if let Some(Command::Query { vs }) = &options.command {
+        println!("Only counting teams that faced: {}", vs);
+    }
+    if let Some(Command::Tournament { roster, from, to }) = &options.command {
+        println!("Tournament roster: {}", roster.display());
+        println!("Tournament days: {} to {}", from, to);
+    }
+    if options.format_kind != FormatKind::Random {
+        println!("Format kind: {}", options.format_kind);
+    }
+    if let Some(timer) = options.timer {
+        println!("Timer: {:?}", timer);
+    }
+    if options.include_unrated {
+        println!("Including unrated battles");
+    }
+    if options.exclude_guests {
+        println!("Excluding battles with guest players");
+    }
+    if options.exclude_abandoned {
+        println!("Excluding battles abandoned early");
+    }
+    if options.exclude_suspected_bots {
+        println!("Excluding suspected bots");
+    }
+    if let Some(path) = &options.excluded_players_path {
+        println!("Excluding players listed in: {}", path.display());
+    }
+    if let Some(path) = &options.only_players_path {
+        println!("Only including players listed in: {}", path.display());
+    }
+    if let Some(path) = &options.alts_path {
+        println!("Merging alt accounts listed in: {}", path.display());
+    }
+    if let Some(path) = &options.annotations_path {
+        println!(
+            "Splitting species at the changes listed in: {}",
+            path.display()
+        );
+    }
+    if let Some(exclusion) = &options.exclusion {
+        println!("Excluding days containing: {}", exclusion);
+    }
+    if !options.log_analyses.is_empty() {
+        println!(
+            "Log analyses: {}",
+            options
+                .log_analyses
+                .iter()
+                .map(|a| a.to_string())
+                .join(", ")
+        );
+    }
+    for (_, label, paths) in options.output_paths() {
+        for path in paths {
+            println!("{}: {}", label, path.display());
+        }
+    }
+
+    println!();
+    for name in &plan.ignored {
+        println!("Would ignore {}", name);
+    }
+    for day in &plan.days {
+        println!("Would analyze {} ({} files)", day.name, day.files.len());
+    }
+    println!(
+        "Total: {} files in {} days",
+        plan.num_files(),
+        plan.days.len()
+    );
+
+    Ok(())
+}
+
+/// Fills in the summary and metadata requested by the options, then writes every requested output,
+/// returning the --jsonl-output writer for later reports
+fn write_outputs(
+    mut stats: Stats,
+    options: &Options,
+    input_days: Vec<(String, usize)>,
+    input_fingerprint: impl FnOnce() -> std::io::Result<String>,
+) -> Result<(Stats, Option<jsonl::JsonlWriter<Destination>>), StatsError> {
+    stats.number_format = options.number_format;
+    stats.ranking = options.ranking;
+    stats.provisional_below = options.provisional_below;
+    stats.sample = options.sample;
+    stats.paging = options.max_rows.map(|max_rows| Paging {
+        max_rows: max_rows.get(),
+        page: options.page.map_or(1, NonZeroUsize::get),
+    });
+    stats.name_width = options.name_width.map(NonZeroUsize::get);
+    if let (Some(translations_path), Some(locale)) = (&options.translations_path, &options.locale) {
+        let file = fs::read_to_string(translations_path)?;
+        stats.translations =
+            Some(Translations::parse(&file, locale).map_err(StatsError::Translations)?);
+    }
+    if options.summary {
+        stats.summary = Some(RunInfo {
+            min_elo: options.min_elo.unwrap_or(0),
+            generated_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or(0),
+        });
+    }
+
+    let run_fields = jsonl::RunFields {
+        format: options
+            .format_dir
+            .as_ref()
+            .and_then(|dir| dir.file_name())
+            .map(|name| name.to_string_lossy().to_string()),
+        min_elo: options.min_elo.unwrap_or(0),
+        days: input_days
+            .iter()
+            .map(|(day, _)| day.clone())
+            .minmax()
+            .into_option(),
+    };
+
+    if options.metadata {
+        stats.metadata = Some(Metadata {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            git_commit: env!("GIT_COMMIT").to_string(),
+            command_line: std::env::args().collect::<Vec<_>>().join(" "),
+            input_fingerprint: input_fingerprint()?,
+            days: input_days,
+        });
+    }
+
+    if let Some(state_paths) = destinations(&options.state_output_paths) {
+        write_output(state_paths, stats.to_state())?;
+    }
+
+    if let Some(csv_paths) = destinations(&options.csv_output_paths) {
+        write_output(csv_paths, stats.to_csv())?;
+    }
+
+    if let Some(human_paths) = destinations(&options.human_readable_output_paths) {
+        write_output(human_paths, stats.to_human_readable())?;
+    }
+
+    if let Some(markdown_paths) = destinations(&options.markdown_output_paths) {
+        write_output(markdown_paths, stats.to_markdown())?;
+    }
+
+    if let Some(bbcode_paths) = destinations(&options.bbcode_output_paths) {
+        write_output(bbcode_paths, stats.to_bbcode())?;
+    }
+
+    if let Some(code_paths) = destinations(&options.code_output_paths) {
+        write_output(
+            code_paths,
+            stats.to_chat_code(options.code_rows.get(), options.min_elo.unwrap_or(0)),
+        )?;
+    }
+
+    if let Some(html_paths) = destinations(&options.html_output_paths) {
+        write_output(html_paths, stats.to_html())?;
+    }
+
+    if let Some(json_paths) = destinations(&options.json_output_paths) {
+        write_output(json_paths, stats.to_json())?;
+    }
+
+    if let Some(species_jsonl_paths) = destinations(&options.species_jsonl_output_paths) {
+        write_output(species_jsonl_paths, stats.to_species_jsonl(&run_fields))?;
+    }
+
+    let mut jsonl = match destinations(&options.jsonl_output_paths) {
+        Some(jsonl_paths) => Some(jsonl::JsonlWriter::new(create_outputs(jsonl_paths)?)),
+        None => None,
+    };
+    if let Some(jsonl) = &mut jsonl {
+        jsonl.write_rankings(&mut stats)?;
+    }
+
+    if let Some(parquet_paths) = destinations(&options.parquet_output_paths) {
+        write_output(parquet_paths, stats.to_parquet())?;
+    }
+
+    if let Some(xlsx_paths) = destinations(&options.xlsx_output_paths) {
+        write_output(xlsx_paths, stats.to_xlsx())?;
+    }
+
+    if let Some(chart_paths) = destinations(&options.chart_output_paths) {
+        write_output(chart_paths, stats.to_vega_lite(options.chart_top))?;
+    }
+
+    if let Some(svg_paths) = destinations(&options.svg_output_paths) {
+        write_output(svg_paths, stats.to_svg_chart(options.chart_top))?;
+    }
+
+    if let Some(clutch_paths) = destinations(&options.clutch_output_paths) {
+        let blocks = clutch::Clutch::new(&stats).to_blocks();
+        write_report(clutch_paths, "clutch", blocks, &mut jsonl)?;
+    }
+
+    if let Some(rating_paths) = destinations(&options.rating_output_paths) {
+        let blocks = rating::RatingReport::new(&stats).to_blocks();
+        write_report(rating_paths, "rating", blocks, &mut jsonl)?;
+    }
+
+    if let (Some(rarity_paths), Some(sets_path)) = (
+        destinations(&options.rarity_output_paths),
+        &options.sets_path,
+    ) {
+        let pool = rarity::read_pool(sets_path)?;
+        let blocks = rarity::RarityReport::new(&pool, &stats, options.usage_model).to_blocks();
+        write_report(rarity_paths, "rarity", blocks, &mut jsonl)?;
+    }
+
+    if let (Some(publish_paths), Some(k)) = (
+        destinations(&options.publish_output_paths),
+        options.publish_top,
+    ) {
+        write_output(
+            publish_paths,
+            publish::Publication::new(&stats, k).to_forum_post(),
+        )?;
+    }
+
+    if let Some(artifact_paths) = destinations(&options.artifact_output_paths) {
+        let mut artifact = vec![];
+        artifact::write(&stats, &mut artifact)?;
+        write_output(artifact_paths, artifact)?;
+    }
+
+    if let Some(log_paths) = destinations(&options.log_output_paths) {
+        write_output(log_paths, stats.log_stats.to_csv())?;
+    }
+
+    if let Some(player_ratings_paths) = destinations(&options.player_ratings_output_paths) {
+        if let Some(player_ratings) = &mut stats.player_ratings {
+            write_output(player_ratings_paths, player_ratings.to_csv())?;
+        }
+    }
+
+    Ok((stats, jsonl))
+}
+
+/// Combines saved states into one set of outputs
+fn merge_states(options: &Options, state_paths: &[PathBuf]) -> Result<(), StatsError> {
+    let mut stats = Stats::new();
+    let mut hasher = hash::Fnv1a::default();
+    for path in state_paths {
+        let state = fs::read_to_string(path)?;
+        hasher.write(state.as_bytes());
+        stats.merge(Stats::from_state(&state)?);
+    }
+
+    // states don't record which days they came from
+    let (_, jsonl) = write_outputs(stats, options, vec![], || {
+        Ok(format!("{:016x}", hasher.finish()))
+    })?;
+    finish_jsonl(jsonl)?;
+    Ok(())
+}
+
+fn rollup_months(
+    options: &Options,
+    inputs: &[PathBuf],
+    out: &Path,
+    trend_path: &Option<PathBuf>,
+) -> Result<(), StatsError> {
+    let mut stats = Stats::new();
+    let mut rollup = rollup::Rollup::default();
+    let mut input_days = vec![];
+    let mut hasher = hash::Fnv1a::default();
+    for input in inputs {
+        let (label, month) = if input.is_dir() {
+            let min_elo = match options.min_elo {
+                Some(min_elo) => min_elo,
+                None => ClapError::with_description(
+                    "--minimum-elo is required to roll up input directories",
+                    ErrorKind::MissingRequiredArgument,
+                )
+                .exit(),
+            };
+            status!("Rolling up {}...", input.display());
+            let plan = options.input_plan(input)?;
+            let month = handle_directory(
+                min_elo,
+                &plan,
+                &options.log_analyses,
+                &options.battle_filter(input)?,
+                options.pipeline(),
+                !options.player_ratings_output_paths.is_empty(),
+                options.recency_half_life,
+            )?;
+            input_days.extend(plan.day_counts());
+            hasher.write(fingerprint_directory(input)?.as_bytes());
+            (input.file_name(), month)
+        } else {
+            let state = fs::read_to_string(input)?;
+            hasher.write(state.as_bytes());
+            (input.file_stem(), Stats::from_state(&state)?)
+        };
+        rollup.add_month(&label.unwrap_or_default().to_string_lossy(), &month);
+        stats.merge(month);
+    }
+
+    write_output(&[out.to_path_buf()], stats.to_state())?;
+    if let Some(trend_path) = trend_path {
+        write_output(std::slice::from_ref(trend_path), rollup.to_human_readable())?;
+    }
+    let (_, jsonl) = write_outputs(stats, options, input_days, || {
+        Ok(format!("{:016x}", hasher.finish()))
+    })?;
+    finish_jsonl(jsonl)?;
+    Ok(())
+}
+
 fn main() -> Result<(), StatsError> {
     let options = Options::from_args();
+    let stdout_outputs = options
+        .output_paths()
+        .into_iter()
+        .flat_map(|(_, _, paths)| paths)
+        .filter(|path| path.as_path() == Path::new(STDOUT_PATH))
+        .count();
+    if stdout_outputs > 1 {
+        eprintln!("Error: only one output can be written to stdout ('-')");
+        return Ok(());
+    }
+    // progress messages would be mixed into the output
+    status::set_quiet(options.quiet || stdout_outputs > 0);
+
+    if options.self_test {
+        return selftest::run(options.self_test_update);
+    }
+
+    if let Some(Command::Coordinate { token, .. } | Command::Worker { token, .. }) =
+        &options.command
+    {
+        if token.is_empty() {
+            ClapError::with_description("--token can't be empty", ErrorKind::EmptyValue).exit();
+        }
+    }
+    if let Some(Command::Worker { coordinator, token }) = &options.command {
+        return cluster::work(coordinator.as_str(), token);
+    }
+    if let Some(Command::FetchLogs { format, since, out }) = &options.command {
+        return fetch::fetch_logs(format, since, out);
+    }
+    if let Some(Command::ExplainMetrics) = &options.command {
+        let config = explain::MetricsConfig {
+            min_elo: options.min_elo.unwrap_or(0),
+            include_unrated: options.include_unrated,
+            recency_half_life: options.recency_half_life,
+            provisional_below: options.provisional_below,
+            sample: options.sample,
+        };
+        print!("{}", config.explain());
+        return Ok(());
+    }
+    if let Some(Command::Lookup { artifact, species }) = &options.command {
+        return Ok(artifact::print_lookup(artifact, species)?);
+    }
+    if let Some((command, unsupported)) = options.unsupported_options() {
+        if !unsupported.is_empty() {
+            ClapError::with_description(
+                &format!("{} can't be used with {}", unsupported.join(", "), command),
+                ErrorKind::ArgumentConflict,
+            )
+            .exit();
+        }
+    }
+    if let Some(Command::Rollup {
+        inputs,
+        out,
+        trend_output,
+    }) = &options.command
+    {
+        return rollup_months(&options, inputs, out, trend_output);
+    }
+
+    let outputs = options.output_paths();
+    if outputs.iter().all(|(_, _, paths)| paths.is_empty()) && !options.dry_run {
+        eprintln!(
+            "Error: You must specify at least one of {}",
+            outputs.iter().map(|(flag, _, _)| flag).join(", ")
+        );
+        return Ok(());
+    }
+    if !options.log_analyses.is_empty() && options.log_output_paths.is_empty() {
+        eprintln!("Error: --log-analyses requires --log-output");
+        return Ok(());
+    }
+    if options.compare_dir.is_some() != !options.comparison_output_paths.is_empty() {
+        eprintln!("Error: --compare-input and --comparison-output must be used together");
+        return Ok(());
+    }
+    if !options.rarity_output_paths.is_empty() && options.sets_path.is_none() {
+        eprintln!("Error: --rarity-output requires --sets");
+        return Ok(());
+    }
+
+    if let Some(Command::Merge { states }) = &options.command {
+        return merge_states(&options, states);
+    }
+
+    let min_elo = match &options.command {
+        // tournament games are unrated, so there's no sensible default for other subcommands
+        Some(Command::Tournament { .. }) => options.min_elo.or(Some(0)),
+        _ => options.min_elo,
+    };
+    let (min_elo, format_dir) = match (min_elo, options.format_dir.clone()) {
+        (Some(min_elo), Some(format_dir)) => (min_elo, format_dir),
+        _ => ClapError::with_description(
+            "--minimum-elo and --input are required unless a subcommand is given",
+            ErrorKind::MissingRequiredArgument,
+        )
+        .exit(),
+    };
+
+    if options.dry_run {
+        print_dry_run(&options, &format_dir, min_elo)?;
+        return Ok(());
+    }
 
-    if options.csv_output_path.is_none() && options.human_readable_output_path.is_none() {
-        eprintln!("Error: You must specify at least one of --csv-output or --human-output");
+    let mut memory = memory::MemoryReport::new(options.report_memory);
+
+    if let Some(Command::Coordinate { listen, token }) = &options.command {
+        let plan = options.input_plan(&format_dir)?;
+        for name in &plan.ignored {
+            status!("Ignoring {}", name);
+        }
+        let input_days = plan.day_counts();
+        let files = plan.days.into_iter().flat_map(|day| day.files).collect();
+        let config = cluster::WorkerConfig {
+            min_elo,
+            log_analyses: options.log_analyses.clone(),
+            filter: options.battle_filter(&format_dir)?,
+        };
+        status!("Waiting for workers on {}...", listen);
+        let listener = std::net::TcpListener::bind(listen.as_str())?;
+        let stats = cluster::coordinate(listener, &config, token, files, options.batch_size)?;
+        for warning in stats.schema.warnings() {
+            eprintln!("{}", warning);
+        }
+        memory.end_stage("Coordination");
+        let (_, jsonl) = write_outputs(stats, &options, input_days, || {
+            fingerprint_directory(&format_dir)
+        })?;
+        finish_jsonl(jsonl)?;
+        memory.end_stage("Outputs");
+        memory.print();
         return Ok(());
     }
 
-    let mut stats = handle_directory(options.min_elo, &options.format_dir, options.exclusion)?;
+    let filter = options.battle_filter(&format_dir)?;
+    if options.exclude_suspected_bots {
+        memory.end_stage("Bot detection");
+    }
+    let plan = options.input_plan(&format_dir)?;
+    let stats = handle_directory(
+        min_elo,
+        &plan,
+        &options.log_analyses,
+        &filter,
+        options.pipeline(),
+        !options.player_ratings_output_paths.is_empty(),
+        options.recency_half_life,
+    )?;
+    memory.end_stage("Analysis");
+    let (stats, mut jsonl) = write_outputs(stats, &options, plan.day_counts(), || {
+        fingerprint_directory(&format_dir)
+    })?;
+    memory.end_stage("Outputs");
+
+    if let Some(streak_paths) = destinations(&options.streak_output_paths) {
+        status!("Analyzing streaks...");
+        let days = plan.days.iter().map(|day| day.files.as_slice());
+        let mut report = streak::StreakReport::new(days, min_elo, &filter)?;
+        write_report(streak_paths, "streaks", report.to_blocks(), &mut jsonl)?;
+        memory.end_stage("Streaks");
+    }
+
+    if let Some(archetype_paths) = destinations(&options.archetype_output_paths) {
+        status!("Clustering teams...");
+        let days = plan.days.iter().map(|day| day.files.as_slice());
+        let mut report =
+            archetype::ArchetypeReport::new(days, min_elo, &filter, options.archetypes)?;
+        write_report(
+            archetype_paths,
+            "archetypes",
+            report.to_blocks(),
+            &mut jsonl,
+        )?;
+        memory.end_stage("Archetypes");
+    }
+
+    if let Some(normalization_paths) = destinations(&options.normalization_report_paths) {
+        status!("Collecting species names...");
+        let days = plan.days.iter().map(|day| day.files.as_slice());
+        let mut report = normalization::NormalizationReport::new(days, min_elo, &filter)?;
+        write_report(
+            normalization_paths,
+            "normalization",
+            report.to_blocks(),
+            &mut jsonl,
+        )?;
+        memory.end_stage("Normalization");
+    }
+
+    if let Some(abandoned_paths) = destinations(&options.abandoned_output_paths) {
+        status!("Finding abandoned games...");
+        let days = plan.days.iter().map(|day| day.files.as_slice());
+        let mut report = abandon::AbandonReport::new(days, min_elo, &filter)?;
+        write_report(abandoned_paths, "abandoned", report.to_blocks(), &mut jsonl)?;
+        memory.end_stage("Abandoned games");
+    }
+
+    if let Some(consistency_paths) = destinations(&options.consistency_output_paths) {
+        status!("Comparing daily winrates...");
+        let days = plan.days.iter().map(|day| day.files.as_slice());
+        let mut report = consistency::ConsistencyReport::new(days, min_elo, &filter)?;
+        write_report(
+            consistency_paths,
+            "consistency",
+            report.to_blocks(),
+            &mut jsonl,
+        )?;
+        memory.end_stage("Consistency");
+    }
+
+    if let Some(regression_paths) = destinations(&options.regression_output_paths) {
+        status!("Fitting regression model...");
+        let days = plan.days.iter().map(|day| day.files.as_slice());
+        let mut report = regression::RegressionReport::new(days, min_elo, &filter)?;
+        write_report(
+            regression_paths,
+            "regression",
+            report.to_blocks(),
+            &mut jsonl,
+        )?;
+        memory.end_stage("Regression");
+    }
+
+    if let Some(observations_paths) = destinations(&options.observations_output_paths) {
+        status!("Exporting observations...");
+        let days = plan
+            .days
+            .iter()
+            .map(|day| (day.name.as_str(), day.files.as_slice()));
+        let mut out = std::io::BufWriter::new(create_outputs(observations_paths)?);
+        observations::write(days, min_elo, &filter, &mut out)?;
+        out.into_inner().map_err(|e| e.into_error())?.finish()?;
+        memory.end_stage("Observations");
+    }
 
-    if let Some(csv_path) = options.csv_output_path {
-        fs::write(csv_path, stats.to_csv())?;
+    if let Some(seen_paths) = destinations(&options.seen_output_paths) {
+        status!("Finding when species were seen...");
+        let days = plan
+            .days
+            .iter()
+            .map(|day| (day.name.as_str(), day.files.as_slice()));
+        let mut report = seen::SeenReport::new(days, min_elo, &filter)?;
+        write_report(seen_paths, "seen", report.to_blocks(), &mut jsonl)?;
+        memory.end_stage("First and last seen");
     }
 
-    if let Some(human_path) = options.human_readable_output_path {
-        fs::write(human_path, stats.to_human_readable())?;
+    if let Some(side_paths) = destinations(&options.side_output_paths) {
+        status!("Comparing sides...");
+        let days = plan.days.iter().map(|day| day.files.as_slice());
+        let mut report = side::SideReport::new(days, min_elo, &filter)?;
+        write_report(side_paths, "sides", report.to_blocks(), &mut jsonl)?;
+        memory.end_stage("Sides");
     }
 
+    if let Some(calibration_paths) = destinations(&options.calibration_output_paths) {
+        status!("Checking rating calibration...");
+        let days = plan.days.iter().map(|day| day.files.as_slice());
+        let mut report = calibration::CalibrationReport::new(days, min_elo, &filter)?;
+        write_report(
+            calibration_paths,
+            "calibration",
+            report.to_blocks(),
+            &mut jsonl,
+        )?;
+        memory.end_stage("Calibration");
+    }
+
+    if let Some(rating_gain_paths) = destinations(&options.rating_gain_output_paths) {
+        status!("Estimating rating gains...");
+        let days = plan.days.iter().map(|day| day.files.as_slice());
+        let mut report = gain::GainReport::new(days, min_elo, &filter)?;
+        write_report(
+            rating_gain_paths,
+            "rating_gain",
+            report.to_blocks(),
+            &mut jsonl,
+        )?;
+        memory.end_stage("Rating gains");
+    }
+
+    if let Some(ace_paths) = destinations(&options.ace_output_paths) {
+        status!("Finding each team's ace...");
+        let rule = match &options.ace_species_path {
+            Some(path) => ace::AceRule::read_species(path)?,
+            None => ace::AceRule::HighestLevel,
+        };
+        let days = plan.days.iter().map(|day| day.files.as_slice());
+        let mut report = ace::AceReport::new(days, min_elo, &filter, &rule)?;
+        write_report(ace_paths, "aces", report.to_blocks(), &mut jsonl)?;
+        memory.end_stage("Aces");
+    }
+
+    if !options.synergy_output_paths.is_empty() || !options.pairs_output_paths.is_empty() {
+        status!("Counting teammate pairs...");
+        let days = plan.days.iter().map(|day| day.files.as_slice());
+        let graph = synergy::SynergyGraph::new(days, min_elo, &filter)?;
+        if let Some(synergy_paths) = destinations(&options.synergy_output_paths) {
+            write_output(synergy_paths, graph.to_dot())?;
+        }
+        if let Some(pairs_paths) = destinations(&options.pairs_output_paths) {
+            let mut report = graph.pairs_report(options.pairs_sort);
+            write_report(pairs_paths, "pairs", report.to_blocks(), &mut jsonl)?;
+        }
+        memory.end_stage("Synergy");
+    }
+
+    if let Some(heatmap_paths) = destinations(&options.matchup_heatmap_paths) {
+        status!("Counting matchups...");
+        let days = plan.days.iter().map(|day| day.files.as_slice());
+        let species = stats.most_used(options.matchup_top);
+        let matrix = matchup::MatchupMatrix::new(days, min_elo, &filter, &species)?;
+        write_output(heatmap_paths, matrix.to_svg())?;
+        memory.end_stage("Matchups");
+    }
+
+    if let Some(spread_paths) = destinations(&options.spread_output_paths) {
+        status!("Collecting spreads...");
+        let days = plan.days.iter().map(|day| day.files.as_slice());
+        let mut report = spread::SpreadReport::new(days, min_elo, &filter)?;
+        write_report(spread_paths, "spreads", report.to_blocks(), &mut jsonl)?;
+        memory.end_stage("Spreads");
+    }
+
+    if let Some(balance_paths) = destinations(&options.balance_output_paths) {
+        status!("Finding balance suggestions...");
+        let days = plan.days.iter().map(|day| day.files.as_slice());
+        let thresholds = balance::Thresholds {
+            min_games: options.balance_min_games,
+            deviations: options.balance_deviations,
+        };
+        let mut report = balance::BalanceReport::new(days, min_elo, &filter, thresholds)?;
+        write_report(balance_paths, "balance", report.to_blocks(), &mut jsonl)?;
+        memory.end_stage("Balance");
+    }
+
+    if let (Some(compare_dir), Some(comparison_paths)) = (
+        &options.compare_dir,
+        destinations(&options.comparison_output_paths),
+    ) {
+        let other_stats = handle_directory(
+            min_elo,
+            &options.input_plan(compare_dir)?,
+            &[],
+            &filter,
+            options.pipeline(),
+            false,
+            None,
+        )?;
+        let label = |dir: &Path| {
+            dir.file_name()
+                .unwrap_or_default()
+                .to_string_lossy()
+                .to_string()
+        };
+        let mut comparison = compare::Comparison::new(
+            &label(&format_dir),
+            &stats,
+            &label(compare_dir),
+            &other_stats,
+        );
+        write_report(
+            comparison_paths,
+            "comparison",
+            comparison.to_blocks(),
+            &mut jsonl,
+        )?;
+        memory.end_stage("Comparison");
+    }
+
+    finish_jsonl(jsonl)?;
+    memory.print();
     Ok(())
 }
 
@@ -116,77 +2239,392 @@ mod tests {
 
     lazy_static! {
         static ref TEST_DIR: PathBuf = PathBuf::from("target/test/day1");
+        /// Tests run in parallel; don't let one rewrite files another is reading
+        static ref TEST_DIR_LOCK: Mutex<()> = Mutex::new(());
     }
 
     fn build_test_dir(num_files: u32) -> std::io::Result<()> {
+        let _guard = TEST_DIR_LOCK.lock().unwrap();
         let src_file = &PathBuf::from("src/benchmark-data.json");
-        fs::create_dir_all(&TEST_DIR.clone())?;
+        fs::create_dir_all(TEST_DIR.clone())?;
         for i in 0..num_files {
             let mut file = TEST_DIR.clone();
             file.push(format!("{}.json", i));
-            fs::copy(src_file, file)?;
+            if !file.exists() {
+                fs::copy(src_file, file)?;
+            }
         }
         Ok(())
     }
 
+    fn bench_batch_size(b: &mut Bencher, batch_size: usize) {
+        build_test_dir(1_000).unwrap();
+
+        let plan = InputPlan::new(
+            TEST_DIR.parent().unwrap(),
+            &None,
+            &Layout::default(),
+            None,
+            None,
+        )
+        .unwrap();
+        b.iter(|| {
+            handle_directory(
+                1050,
+                &plan,
+                &[],
+                &BattleFilter::default(),
+                Pipeline {
+                    batch_size,
+                    ..Pipeline::default()
+                },
+                false,
+                None,
+            )
+            .unwrap()
+        });
+    }
+
     #[bench]
     fn bench_handle_directory_1k(b: &mut Bencher) {
+        bench_batch_size(b, 64);
+    }
+
+    #[bench]
+    fn bench_handle_directory_1k_unbatched(b: &mut Bencher) {
+        bench_batch_size(b, 1);
+    }
+
+    #[bench]
+    fn bench_handle_directory_1k_batch_512(b: &mut Bencher) {
+        bench_batch_size(b, 512);
+    }
+
+    #[test]
+    fn test_input_plan() {
+        build_test_dir(10).unwrap();
+        let format_dir = &TEST_DIR.parent().unwrap().to_owned();
+
+        let plan = InputPlan::new(format_dir, &None, &Layout::default(), None, None).unwrap();
+        assert_eq!(plan.days.len(), 1);
+        assert!(plan.num_files() >= 10);
+
+        // only 0.json through 9.json, however many files other tests have added
+        let layout = Layout {
+            file_pattern: "?.json".parse().unwrap(),
+            ..Layout::default()
+        };
+        let plan = InputPlan::new(format_dir, &None, &layout, None, None).unwrap();
+        assert_eq!(plan.num_files(), 10);
+        let layout = Layout {
+            file_pattern: "*.json.log".parse().unwrap(),
+            ..Layout::default()
+        };
+        let plan = InputPlan::new(format_dir, &None, &layout, None, None).unwrap();
+        assert_eq!(plan.num_files(), 0);
+
+        let plan = InputPlan::new(
+            format_dir,
+            &Some(String::from("day")),
+            &Layout::default(),
+            None,
+            None,
+        )
+        .unwrap();
+        assert!(plan.days.is_empty());
+        assert_eq!(plan.ignored, vec![String::from("day1")]);
+
+        let mut plan = InputPlan::new(format_dir, &None, &Layout::default(), None, None).unwrap();
+        plan.restrict_to_days("day1", "day9");
+        assert_eq!(plan.days.len(), 1);
+        plan.restrict_to_days("day2", "day9");
+        assert!(plan.days.is_empty());
+        assert_eq!(plan.ignored, vec![String::from("day1")]);
+    }
+
+    #[test]
+    fn test_nested_layout() {
+        let format_dir = PathBuf::from("target/test-nested/gen9randombattle");
+        let battle = fs::read("src/benchmark-data.json").unwrap();
+        for (hour, name) in [
+            ("2024-01/2024-01-31/23", "1.json"),
+            ("2024-02/2024-01-31/00", "2.json"),
+            ("2024-02/2024-02-01/00", "3.json"),
+            ("2024-02/2024-02-01/01", "4.json"),
+        ] {
+            let dir = format_dir.join(hour);
+            fs::create_dir_all(&dir).unwrap();
+            fs::write(dir.join(name), &battle).unwrap();
+        }
+
+        let layout = Layout {
+            day_depth: 2,
+            ..Layout::default()
+        };
+        let plan = InputPlan::new(&format_dir, &None, &layout, None, None).unwrap();
+        assert_eq!(
+            plan.day_counts(),
+            vec![
+                (String::from("2024-01-31"), 2),
+                (String::from("2024-02-01"), 2)
+            ]
+        );
+
+        // one level up, the months are the days
+        let plan = InputPlan::new(&format_dir, &None, &Layout::default(), None, None).unwrap();
+        assert_eq!(
+            plan.day_counts(),
+            vec![(String::from("2024-01"), 1), (String::from("2024-02"), 3)]
+        );
+    }
+
+    #[test]
+    fn test_skip_duplicates() {
         build_test_dir(1_000).unwrap();
+        let format_dir = &TEST_DIR.parent().unwrap().to_owned();
+        let filter = BattleFilter {
+            skip_duplicates: true,
+            ..BattleFilter::default()
+        };
+        let plan = InputPlan::new(format_dir, &None, &Layout::default(), None, None).unwrap();
+        let mut stats =
+            handle_directory(1050, &plan, &[], &filter, Pipeline::default(), false, None).unwrap();
+
+        // every test file is a copy of the same battle
+        assert!(stats
+            .to_csv()
+            .starts_with("species,games,wins,winrate,deviations\nRotom-Fan,1,1,"));
+        assert_eq!(stats.counter("Duplicate battles skipped"), Some(999));
+    }
+
+    #[test]
+    fn test_second_pass_skips_duplicates() {
+        build_test_dir(1_000).unwrap();
+        let format_dir = &TEST_DIR.parent().unwrap().to_owned();
+        let plan = InputPlan::new(format_dir, &None, &Layout::default(), None, None).unwrap();
+        let count = |filter: &BattleFilter| {
+            let mut scan = pass::Scan::new(1050, filter);
+            plan.days
+                .iter()
+                .map(|day| scan.day(&day.files, pass::sides).unwrap().len())
+                .sum::<usize>()
+        };
+
+        assert_eq!(count(&BattleFilter::default()), 1_000);
+        let filter = BattleFilter {
+            skip_duplicates: true,
+            ..BattleFilter::default()
+        };
+        assert_eq!(count(&filter), 1);
+    }
 
+    #[test]
+    fn test_deterministic() {
+        build_test_dir(1_000).unwrap();
         let format_dir = &TEST_DIR.parent().unwrap().to_owned();
-        b.iter(|| handle_directory(1050, format_dir, None).unwrap());
+        let plan = InputPlan::new(format_dir, &None, &Layout::default(), None, None).unwrap();
+        let run = || {
+            let pipeline = Pipeline {
+                batch_size: 7,
+                deterministic: true,
+                retry_incomplete: false,
+            };
+            handle_directory(
+                1050,
+                &plan,
+                &[],
+                &BattleFilter::default(),
+                pipeline,
+                false,
+                None,
+            )
+            .unwrap()
+            .to_state()
+        };
+
+        // rating sums depend on the order battles are added in, so without deterministic mode
+        // they'd usually differ in the last few digits
+        let state = run();
+        for _ in 0..3 {
+            assert_eq!(state, run());
+        }
+    }
+
+    #[test]
+    fn test_incomplete_files() {
+        let day_dir = PathBuf::from("target/test-incomplete/day1");
+        fs::create_dir_all(&day_dir).unwrap();
+        let battle = fs::read("src/benchmark-data.json").unwrap();
+        fs::write(day_dir.join("complete.json"), &battle).unwrap();
+        fs::write(day_dir.join("empty.json"), "").unwrap();
+        fs::write(day_dir.join("truncated.json"), &battle[..battle.len() / 2]).unwrap();
+        assert!(matches!(
+            validate::read_battle(&day_dir.join("missing.json")),
+            Err(BattleError::Unreadable(_))
+        ));
+
+        let plan = InputPlan::new(
+            day_dir.parent().unwrap(),
+            &None,
+            &Layout::default(),
+            None,
+            None,
+        )
+        .unwrap();
+        for retry_incomplete in [false, true] {
+            let pipeline = Pipeline {
+                retry_incomplete,
+                ..Pipeline::default()
+            };
+            let mut stats = handle_directory(
+                1050,
+                &plan,
+                &[],
+                &BattleFilter::default(),
+                pipeline,
+                false,
+                None,
+            )
+            .unwrap();
+            assert!(stats
+                .to_csv()
+                .starts_with("species,games,wins,winrate,deviations\nRotom-Fan,1,1,"));
+            assert_eq!(stats.counter("Empty files skipped"), Some(1));
+            assert_eq!(stats.counter("Truncated files skipped"), Some(1));
+            assert_eq!(stats.counter("Malformed battles skipped"), None);
+            assert_eq!(stats.counter("Incomplete files read on retry"), None);
+        }
+    }
+
+    #[test]
+    fn test_multiple_destinations() {
+        let options = Options::from_iter(&[
+            "randbats-winrates",
+            "--csv-output",
+            "a.csv",
+            "--csv-output",
+            "backup/a.csv",
+            "--json-output",
+            "a.json",
+            "--minimum-elo",
+            "0",
+        ]);
+        assert_eq!(
+            options.csv_output_paths,
+            vec![PathBuf::from("a.csv"), PathBuf::from("backup/a.csv")]
+        );
+        assert_eq!(options.json_output_paths, vec![PathBuf::from("a.json")]);
+        assert_eq!(destinations(&options.html_output_paths), None);
+
+        let dir = PathBuf::from("target/test-destinations");
+        fs::create_dir_all(&dir).unwrap();
+        let paths = vec![dir.join("a.txt"), dir.join("b.txt")];
+        write_output(&paths, "written").unwrap();
+        assert_eq!(fs::read_to_string(&paths[1]).unwrap(), "written");
+        let mut out = create_outputs(&paths).unwrap();
+        out.write_all(b"streamed").unwrap();
+        out.finish().unwrap();
+        for path in &paths {
+            assert_eq!(fs::read_to_string(path).unwrap(), "streamed");
+        }
+    }
+
+    #[test]
+    fn test_unsupported_options() {
+        let options = |args: &[&str]| {
+            Options::from_iter(
+                ["randbats-winrates", "--csv-output", "a.csv"]
+                    .iter()
+                    .chain(args),
+            )
+        };
+        assert_eq!(
+            options(&["--streak-output", "s.csv"]).unsupported_options(),
+            None
+        );
+        assert_eq!(
+            options(&["--streak-output", "s.csv", "merge", "a.state"]).unsupported_options(),
+            Some(("merge", vec!["--streak-output"]))
+        );
+        assert_eq!(
+            options(&[
+                "--recency-half-life",
+                "7",
+                "--seen-output",
+                "s.csv",
+                "coordinate",
+                "--token",
+                "secret"
+            ])
+            .unsupported_options(),
+            Some(("coordinate", vec!["--recency-half-life", "--seen-output"]))
+        );
+        assert_eq!(
+            options(&["rollup", "--inputs", "a.state", "--out", "b.state"]).unsupported_options(),
+            Some(("rollup", vec![]))
+        );
     }
 
     #[test]
     fn test_handle_directory_1k() {
         build_test_dir(1_000).unwrap();
         let format_dir = &TEST_DIR.parent().unwrap().to_owned();
-        let mut stats = handle_directory(1050, format_dir, None).unwrap();
+        let plan = InputPlan::new(format_dir, &None, &Layout::default(), None, None).unwrap();
+        let mut stats = handle_directory(
+            1050,
+            &plan,
+            &[],
+            &BattleFilter::default(),
+            Pipeline::default(),
+            false,
+            None,
+        )
+        .unwrap();
 
         assert_eq!(
             stats.to_csv(),
-            "Rotom-Fan,1000,1000,100,31.622776
-Regirock,1000,1000,100,31.622776
-Conkeldurr,1000,1000,100,31.622776
-Reuniclus,1000,1000,100,31.622776
-Incineroar,1000,1000,100,31.622776
-Miltank,1000,1000,100,31.622776
-Drednaw,1000,0,0,-31.622776
-Pinsir,1000,0,0,-31.622776
-Pikachu,1000,0,0,-31.622776
-Latios,1000,0,0,-31.622776
-Entei,1000,0,0,-31.622776
-Exeggutor-Alola,1000,0,0,-31.622776"
+            "species,games,wins,winrate,deviations\nRotom-Fan,1000,1000,100.000000,31.622777
+Regirock,1000,1000,100.000000,31.622777
+Conkeldurr,1000,1000,100.000000,31.622777
+Reuniclus,1000,1000,100.000000,31.622777
+Incineroar,1000,1000,100.000000,31.622777
+Miltank,1000,1000,100.000000,31.622777
+Drednaw,1000,0,0.000000,-31.622777
+Pinsir,1000,0,0.000000,-31.622777
+Pikachu,1000,0,0.000000,-31.622777
+Latios,1000,0,0.000000,-31.622777
+Entei,1000,0,0.000000,-31.622777
+Exeggutor-Alola,1000,0,0.000000,-31.622777"
         );
         assert_eq!(
             stats.to_human_readable(),
-            "+------+-----------------+------------+---------+-------+------+
-| Rank | Pokemon         | Deviations | Winrate | Games | Wins |
-+------+-----------------+------------+---------+-------+------+
-| 1    | Rotom-Fan       | 31.622776  | 100%    | 1000  | 1000 |
-+------+-----------------+------------+---------+-------+------+
-| 2    | Regirock        | 31.622776  | 100%    | 1000  | 1000 |
-+------+-----------------+------------+---------+-------+------+
-| 3    | Conkeldurr      | 31.622776  | 100%    | 1000  | 1000 |
-+------+-----------------+------------+---------+-------+------+
-| 4    | Reuniclus       | 31.622776  | 100%    | 1000  | 1000 |
-+------+-----------------+------------+---------+-------+------+
-| 5    | Incineroar      | 31.622776  | 100%    | 1000  | 1000 |
-+------+-----------------+------------+---------+-------+------+
-| 6    | Miltank         | 31.622776  | 100%    | 1000  | 1000 |
-+------+-----------------+------------+---------+-------+------+
-| 7    | Drednaw         | -31.622776 | 0%      | 1000  | 0    |
-+------+-----------------+------------+---------+-------+------+
-| 8    | Pinsir          | -31.622776 | 0%      | 1000  | 0    |
-+------+-----------------+------------+---------+-------+------+
-| 9    | Pikachu         | -31.622776 | 0%      | 1000  | 0    |
-+------+-----------------+------------+---------+-------+------+
-| 10   | Latios          | -31.622776 | 0%      | 1000  | 0    |
-+------+-----------------+------------+---------+-------+------+
-| 11   | Entei           | -31.622776 | 0%      | 1000  | 0    |
-+------+-----------------+------------+---------+-------+------+
-| 12   | Exeggutor-Alola | -31.622776 | 0%      | 1000  | 0    |
-+------+-----------------+------------+---------+-------+------+
+            "+------+-----------------+------------+-------------+-------+------+
+| Rank | Pokemon         | Deviations | Winrate     | Games | Wins |
++------+-----------------+------------+-------------+-------+------+
+| 1    | Rotom-Fan       | 31.622777  | 100.000000% | 1000  | 1000 |
++------+-----------------+------------+-------------+-------+------+
+| 1    | Regirock        | 31.622777  | 100.000000% | 1000  | 1000 |
++------+-----------------+------------+-------------+-------+------+
+| 1    | Conkeldurr      | 31.622777  | 100.000000% | 1000  | 1000 |
++------+-----------------+------------+-------------+-------+------+
+| 1    | Reuniclus       | 31.622777  | 100.000000% | 1000  | 1000 |
++------+-----------------+------------+-------------+-------+------+
+| 1    | Incineroar      | 31.622777  | 100.000000% | 1000  | 1000 |
++------+-----------------+------------+-------------+-------+------+
+| 1    | Miltank         | 31.622777  | 100.000000% | 1000  | 1000 |
++------+-----------------+------------+-------------+-------+------+
+| 7    | Drednaw         | -31.622777 | 0.000000%   | 1000  | 0    |
++------+-----------------+------------+-------------+-------+------+
+| 7    | Pinsir          | -31.622777 | 0.000000%   | 1000  | 0    |
++------+-----------------+------------+-------------+-------+------+
+| 7    | Pikachu         | -31.622777 | 0.000000%   | 1000  | 0    |
++------+-----------------+------------+-------------+-------+------+
+| 7    | Latios          | -31.622777 | 0.000000%   | 1000  | 0    |
++------+-----------------+------------+-------------+-------+------+
+| 7    | Entei           | -31.622777 | 0.000000%   | 1000  | 0    |
++------+-----------------+------------+-------------+-------+------+
+| 7    | Exeggutor-Alola | -31.622777 | 0.000000%   | 1000  | 0    |
++------+-----------------+------------+-------------+-------+------+
 "
         )
     }
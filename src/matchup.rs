@@ -0,0 +1,207 @@
+/// An SVG heatmap of species-vs-species winrates (`--matchup-heatmap`)
+///
+/// Each cell is how often teams with the row's species beat teams with the column's species,
+/// coloured on a diverging scale: red below 50%, white at 50%, and blue above. Only the
+/// `--matchup-top` most used species are drawn, since a matrix of every species is too big to
+/// read, and only their matchups are counted, which keeps the matrix to `--matchup-top` squared
+/// cells however many species the input has. Hovering over a cell shows its winrate and games.
+use crate::filter::BattleFilter;
+use crate::html::escape_html;
+use crate::pass;
+use crate::tally::{Interner, Tally};
+use std::io;
+use std::path::PathBuf;
+
+/// Side length of each cell, in pixels
+const CELL_SIZE: usize = 16;
+/// Room for the species names along the top and left, in pixels
+const LABEL_SIZE: usize = 140;
+/// Cells with no games
+const EMPTY_COLOR: (u8, u8, u8) = (204, 204, 204);
+const LOW_COLOR: (u8, u8, u8) = (215, 48, 39);
+const HIGH_COLOR: (u8, u8, u8) = (69, 117, 180);
+
+/// The cell colour for a winrate, as a hex colour
+pub(crate) fn color(winrate: Option<f64>) -> String {
+    let (r, g, b) = match winrate {
+        None => EMPTY_COLOR,
+        Some(winrate) => {
+            let t = ((winrate - 50.0) / 50.0).clamp(-1.0, 1.0);
+            let target = if t < 0.0 { LOW_COLOR } else { HIGH_COLOR };
+            let mix = |c: u8| (255.0 + (c as f64 - 255.0) * t.abs()).round() as u8;
+            (mix(target.0), mix(target.1), mix(target.2))
+        }
+    };
+    format!("#{:02x}{:02x}{:02x}", r, g, b)
+}
+
+pub struct MatchupMatrix {
+    /// The species being counted; others are ignored
+    species: Interner,
+    /// Games for each species ID
+    usage: Vec<u32>,
+    /// (species ID, opposing species ID):counts for the first species
+    matchups: Tally<(u32, u32)>,
+}
+
+impl MatchupMatrix {
+    /// Counts the matchups between the given species, usually the most used ones
+    pub fn new<'a>(
+        days: impl Iterator<Item = &'a [PathBuf]>,
+        min_elo: u64,
+        filter: &BattleFilter,
+        species: &[&str],
+    ) -> io::Result<Self> {
+        let mut matrix = Self::with_species(species);
+        let mut scan = pass::Scan::new(min_elo, filter);
+        for files in days {
+            for sides in scan.day(files, pass::sides)? {
+                let teams = sides
+                    .into_iter()
+                    .map(|(_, won, team)| (won, team))
+                    .collect::<Vec<_>>();
+                matrix.add_battle(&teams);
+            }
+        }
+        Ok(matrix)
+    }
+
+    fn with_species(species: &[&str]) -> Self {
+        let mut interner = Interner::default();
+        for name in species {
+            interner.id(name);
+        }
+        MatchupMatrix {
+            species: interner,
+            usage: vec![0; species.len()],
+            matchups: Tally::default(),
+        }
+    }
+
+    /// Adds a battle's teams, as (won, species)
+    fn add_battle(&mut self, teams: &[(bool, Vec<String>)]) {
+        let mut ids = vec![];
+        for (_, team) in teams {
+            let mut team_ids = vec![];
+            let interner = &self.species;
+            for id in team.iter().filter_map(|species| interner.get(species)) {
+                self.usage[id as usize] += 1;
+                team_ids.push(id);
+            }
+            ids.push(team_ids);
+        }
+        for (side, (won, _)) in teams.iter().enumerate() {
+            for (other, _) in teams.iter().enumerate().filter(|(other, _)| *other != side) {
+                for a in &ids[side] {
+                    for b in &ids[other] {
+                        self.matchups.add((*a, *b), *won);
+                    }
+                }
+            }
+        }
+    }
+
+    /// The matrix as an SVG heatmap, with the most used species first
+    pub fn to_svg(&self) -> String {
+        let mut species = (0..self.usage.len() as u32)
+            .map(|id| (id, self.species.name(id)))
+            .collect::<Vec<_>>();
+        species.sort_by(|(a, a_name), (b, b_name)| {
+            self.usage[*b as usize]
+                .cmp(&self.usage[*a as usize])
+                .then_with(|| a_name.cmp(b_name))
+        });
+
+        let size = LABEL_SIZE + species.len() * CELL_SIZE;
+        let mut svg = vec![
+            format!(
+                "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{0}\" height=\"{0}\" \
+                 font-family=\"sans-serif\" font-size=\"11\">",
+                size
+            ),
+            format!("<rect width=\"{0}\" height=\"{0}\" fill=\"white\"/>", size),
+        ];
+        for (position, (_, name)) in species.iter().enumerate() {
+            let offset = LABEL_SIZE + position * CELL_SIZE + CELL_SIZE * 3 / 4;
+            let name = escape_html(name);
+            svg.push(format!(
+                "<text x=\"{}\" y=\"{}\" text-anchor=\"end\">{}</text>",
+                LABEL_SIZE - 4,
+                offset,
+                name
+            ));
+            svg.push(format!(
+                "<text transform=\"translate({},{}) rotate(-90)\">{}</text>",
+                offset,
+                LABEL_SIZE - 4,
+                name
+            ));
+        }
+        for (row, (a, a_name)) in species.iter().enumerate() {
+            for (column, (b, b_name)) in species.iter().enumerate() {
+                let record = self.matchups.get(&(*a, *b));
+                let title = match record {
+                    Some(record) => format!(
+                        "{} vs {}: {:.1}% in {} games",
+                        a_name,
+                        b_name,
+                        record.winrate(),
+                        record.games
+                    ),
+                    None => format!("{} vs {}: no games", a_name, b_name),
+                };
+                svg.push(format!(
+                    "<rect x=\"{}\" y=\"{}\" width=\"{2}\" height=\"{2}\" fill=\"{3}\"><title>{4}</title></rect>",
+                    LABEL_SIZE + column * CELL_SIZE,
+                    LABEL_SIZE + row * CELL_SIZE,
+                    CELL_SIZE,
+                    color(record.map(|r| r.winrate())),
+                    escape_html(&title)
+                ));
+            }
+        }
+        svg.push(String::from("</svg>"));
+        svg.join("\n") + "\n"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tally::Counts;
+
+    #[test]
+    fn test_color() {
+        assert_eq!(color(Some(50.0)), "#ffffff");
+        assert_eq!(color(Some(0.0)), "#d73027");
+        assert_eq!(color(Some(100.0)), "#4575b4");
+        assert_eq!(color(Some(75.0)), "#a2bada");
+        assert_eq!(color(None), "#cccccc");
+    }
+
+    #[test]
+    fn test_matchup_heatmap() {
+        let team = |species: &[&str]| species.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+        let mut matrix = MatchupMatrix::with_species(&["Mew", "Blissey"]);
+        matrix.add_battle(&[(true, team(&["Mew", "Ditto"])), (false, team(&["Mew"]))]);
+        matrix.add_battle(&[(false, team(&["Mew"])), (true, team(&["Blissey"]))]);
+
+        assert_eq!(
+            matrix.matchups.get(&(0, 0)),
+            Some(Counts { games: 2, wins: 1 })
+        );
+        assert_eq!(
+            matrix.matchups.get(&(1, 0)),
+            Some(Counts { games: 1, wins: 1 })
+        );
+        // Ditto isn't one of the species being counted
+        assert_eq!(matrix.matchups.rows().count(), 3);
+
+        let svg = matrix.to_svg();
+        assert!(svg.starts_with("<svg "));
+        assert!(svg.contains("<title>Mew vs Mew: 50.0% in 2 games</title>"));
+        assert!(svg.contains("<title>Blissey vs Mew: 100.0% in 1 games</title>"));
+        assert!(!svg.contains("Ditto"));
+        assert_eq!(svg.matches("<rect x=").count(), 4);
+    }
+}
@@ -0,0 +1,123 @@
+/// Peak memory reporting for capacity planning (`--report-memory`)
+///
+/// Memory use is read from `/proc/self/status`, so this only works on Linux. The kernel's
+/// peak (`VmHWM`) is reset after each stage by writing to `/proc/self/clear_refs`, which
+/// makes each stage's peak its own; where that isn't allowed, peaks are since startup.
+use crate::table::Table;
+use std::fs;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+struct Usage {
+    /// Resident set size, in kibibytes
+    current_kb: u64,
+    peak_kb: u64,
+}
+
+impl Usage {
+    /// Parses the `VmRSS` and `VmHWM` lines of `/proc/<pid>/status`
+    fn parse(status: &str) -> Option<Self> {
+        let field = |name: &str| {
+            status
+                .lines()
+                .find_map(|line| line.strip_prefix(name))?
+                .trim()
+                .trim_end_matches("kB")
+                .trim()
+                .parse::<u64>()
+                .ok()
+        };
+        Some(Self {
+            current_kb: field("VmRSS:")?,
+            peak_kb: field("VmHWM:")?,
+        })
+    }
+
+    fn read() -> Option<Self> {
+        Usage::parse(&fs::read_to_string("/proc/self/status").ok()?)
+    }
+}
+
+fn format_kb(kb: u64) -> String {
+    format!("{:.1} MiB", kb as f64 / 1024.0)
+}
+
+pub struct MemoryReport {
+    enabled: bool,
+    /// Whether each stage's peak is its own, rather than since startup
+    per_stage_peaks: bool,
+    stages: Vec<(String, Option<Usage>)>,
+}
+
+impl MemoryReport {
+    /// A report that does nothing unless `enabled`
+    pub fn new(enabled: bool) -> Self {
+        let mut report = Self {
+            enabled,
+            per_stage_peaks: false,
+            stages: vec![],
+        };
+        if enabled {
+            report.per_stage_peaks = MemoryReport::reset_peak();
+        }
+        report
+    }
+
+    fn reset_peak() -> bool {
+        fs::write("/proc/self/clear_refs", "5").is_ok()
+    }
+
+    /// Records memory use for the stage that just finished
+    pub fn end_stage(&mut self, name: &str) {
+        if !self.enabled {
+            return;
+        }
+        self.stages.push((name.to_string(), Usage::read()));
+        if self.per_stage_peaks {
+            MemoryReport::reset_peak();
+        }
+    }
+
+    pub fn print(&self) {
+        if !self.enabled {
+            return;
+        }
+        if self.stages.iter().all(|(_, usage)| usage.is_none()) {
+            println!("Memory usage isn't available on this platform");
+            return;
+        }
+
+        let peak_title = if self.per_stage_peaks {
+            "Peak RSS"
+        } else {
+            "Peak RSS (since start)"
+        };
+        let mut table = Table::new(&["Stage", peak_title, "RSS at end"]);
+        for (name, usage) in &self.stages {
+            let (peak, current) = match usage {
+                Some(usage) => (format_kb(usage.peak_kb), format_kb(usage.current_kb)),
+                None => (String::from("?"), String::from("?")),
+            };
+            table.add_row(vec![name.clone(), peak, current]);
+        }
+        print!("{}", table);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_status() {
+        let status = "Name:\trandbats-winrates\nVmHWM:\t   20480 kB\nVmRSS:\t    1024 kB\n";
+        assert_eq!(
+            Usage::parse(status),
+            Some(Usage {
+                current_kb: 1024,
+                peak_kb: 20480
+            })
+        );
+        assert_eq!(Usage::parse("Name:\tfoo\n"), None);
+        assert_eq!(format_kb(20480), "20.0 MiB");
+    }
+}
@@ -0,0 +1,131 @@
+/// Every raw species string in the input and what it was counted as (`--normalization-report`)
+///
+/// Cosmetic formes are merged into their base species, so a newly added forme that isn't
+/// merged yet splits that species' games. Rows are grouped by base name (the part before the
+/// first hyphen), so a new forme shows up right next to the species it belongs with.
+use crate::filter::BattleFilter;
+use crate::pass;
+use crate::stats::{Output, Stats};
+use crate::table::{self, Block, Table};
+use indexmap::IndexMap;
+use itertools::Itertools;
+use std::io;
+use std::path::PathBuf;
+
+fn base_name(species: &str) -> &str {
+    species.split('-').next().unwrap_or(species)
+}
+
+pub struct NormalizationReport {
+    /// Raw species string:games
+    counts: IndexMap<String, u32>,
+}
+
+impl NormalizationReport {
+    pub fn new<'a>(
+        days: impl Iterator<Item = &'a [PathBuf]>,
+        min_elo: u64,
+        filter: &BattleFilter,
+    ) -> io::Result<Self> {
+        let mut report = NormalizationReport {
+            counts: IndexMap::new(),
+        };
+        let mut scan = pass::Scan::new(min_elo, filter);
+        for files in days {
+            let battles = scan.day(files, |json| {
+                Stats::sides(json)
+                    .iter()
+                    .flat_map(|side| {
+                        let species_path = format!("{}team.#.species", side);
+                        gjson::get(json, &species_path)
+                            .array()
+                            .iter()
+                            .map(|species| species.str().to_string())
+                            .collect::<Vec<_>>()
+                    })
+                    .collect::<Vec<_>>()
+            })?;
+            for species in battles.into_iter().flatten() {
+                report.add(species);
+            }
+        }
+        Ok(report)
+    }
+
+    fn add(&mut self, species: String) {
+        *self.counts.entry(species).or_insert(0) += 1;
+    }
+
+    /// (raw species, normalized species, games), grouped by base name
+    fn rows(&self) -> Vec<(&str, String, u32)> {
+        self.counts
+            .iter()
+            .map(|(raw, games)| (raw.as_str(), Stats::normalize_species(raw), *games))
+            .sorted_by(|a, b| {
+                base_name(a.0)
+                    .cmp(base_name(b.0))
+                    .then_with(|| b.2.cmp(&a.2))
+                    .then_with(|| a.0.cmp(b.0))
+            })
+            .collect()
+    }
+}
+
+impl Output for NormalizationReport {
+    fn to_csv(&mut self) -> String {
+        Itertools::intersperse(
+            self.rows()
+                .into_iter()
+                .map(|(raw, normalized, games)| format!("{},{},{}", raw, normalized, games)),
+            String::from("\n"),
+        )
+        .collect()
+    }
+
+    fn to_human_readable(&mut self) -> String {
+        table::blocks_to_text(&self.to_blocks())
+    }
+
+    fn to_blocks(&mut self) -> Vec<Block> {
+        let mut table = Table::new(&["Raw species", "Counted as", "Games"]);
+        for (raw, normalized, games) in self.rows() {
+            let counted_as = if raw == normalized {
+                String::from("(unchanged)")
+            } else {
+                normalized
+            };
+            table.add_row(vec![raw.to_string(), counted_as, games.to_string()]);
+        }
+        vec![Block::Table(table)]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalization_report() {
+        let mut report = NormalizationReport {
+            counts: IndexMap::new(),
+        };
+        for species in [
+            "Vivillon-Fancy",
+            "Maushold-Four",
+            "Vivillon",
+            "Vivillon-Fancy",
+            "Maushold",
+            "Maushold",
+            "Maushold",
+        ] {
+            report.add(species.to_string());
+        }
+        assert_eq!(
+            report.to_csv(),
+            "Maushold,Maushold,3\n\
+             Maushold-Four,Maushold-Four,1\n\
+             Vivillon-Fancy,Vivillon,2\n\
+             Vivillon,Vivillon,1"
+        );
+    }
+}
@@ -0,0 +1,84 @@
+/// One row per side of every counted battle, for fitting models outside this tool
+/// (`--observations-output`)
+///
+/// Columns, without a header: battle (its file name without extensions), day, side, player
+/// ID, rating (empty if unknown), won (1 or 0), turns, and species (normalized, joined with
+/// `/`). Rows are written a day at a time, so the export doesn't have to fit in memory.
+use crate::filter::{to_id, BattleFilter};
+use crate::pass;
+use crate::stats::Stats;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+/// A battle's rows
+fn observations(path: &Path, day: &str, json: &str) -> Vec<String> {
+    let battle = path
+        .file_name()
+        .map(|name| name.to_string_lossy())
+        .unwrap_or_default();
+    let battle = battle.split('.').next().unwrap_or_default();
+
+    Stats::sides(json)
+        .iter()
+        .zip(Stats::results_by_side(0, json))
+        .filter(|(_, results)| !results.is_empty())
+        .map(|(side, results)| {
+            [
+                battle.to_string(),
+                day.to_string(),
+                side.clone(),
+                to_id(gjson::get(json, side).str()),
+                results[0].rating.map_or(String::new(), |r| r.to_string()),
+                (results[0].won as u8).to_string(),
+                results[0].turns.to_string(),
+                results
+                    .iter()
+                    .map(|r| r.species.as_str())
+                    .collect::<Vec<_>>()
+                    .join("/"),
+            ]
+            .join(",")
+        })
+        .collect()
+}
+
+/// Writes every counted battle's rows, returning how many battles there were
+pub fn write<'a>(
+    days: impl Iterator<Item = (&'a str, &'a [PathBuf])>,
+    min_elo: u64,
+    filter: &BattleFilter,
+    out: &mut impl Write,
+) -> io::Result<usize> {
+    let mut battles = 0;
+    let mut scan = pass::Scan::new(min_elo, filter);
+    for (day, files) in days {
+        let rows = scan.day_files(files, |path, json| observations(path, day, json))?;
+        battles += rows.len();
+        for row in rows.into_iter().flatten() {
+            writeln!(out, "{}", row)?;
+        }
+    }
+    out.flush()?;
+    Ok(battles)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_observations() {
+        let json = r#"{"winner":"Player A","turns":12,"p1":"Player A","p2":"Player B","p1team":[{"species":"Kingambit"},{"species":"Pikachu-Alola"}],"p2team":[{"species":"Garchomp"}],"p1rating":{"elo":1200}}"#;
+        assert_eq!(
+            observations(
+                Path::new("logs/gen9randombattle-1.log.json"),
+                "2024-01-01",
+                json
+            ),
+            vec![
+                "gen9randombattle-1,2024-01-01,p1,playera,1200,1,12,Kingambit/Pikachu",
+                "gen9randombattle-1,2024-01-01,p2,playerb,,0,12,Garchomp",
+            ]
+        );
+    }
+}
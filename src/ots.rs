@@ -0,0 +1,131 @@
+/// Recovering teams from Open Team Sheets
+///
+/// Formats with Open Team Sheets reveal each team in the log as a `|showteam|p1|...` line,
+/// and some of their battles have no `p1team`/`p2team` fields at all. Rather than dropping
+/// those battles, their teams are rebuilt from the team sheets.
+use crate::fetch::json_string;
+use crate::stats::Stats;
+
+/// Converts one Pokémon from a packed team, as in Showdown's `Teams.pack`, to team JSON:
+/// `nickname|species|item|ability|moves|nature|evs|gender|ivs|shiny|level|misc`, where the
+/// species is omitted if it's the same as the nickname, and misc ends with the tera type
+fn packed_member(packed: &str) -> Option<String> {
+    let fields = packed.split('|').collect::<Vec<_>>();
+    let species = match fields.get(1)? {
+        &"" => fields[0],
+        species => species,
+    };
+    if species.is_empty() {
+        return None;
+    }
+
+    let mut member = vec![format!("\"species\":{}", json_string(species))];
+    for (idx, key) in [(2, "item"), (3, "ability"), (5, "nature"), (7, "gender")] {
+        match fields.get(idx) {
+            Some(value) if !value.is_empty() => {
+                member.push(format!("\"{}\":{}", key, json_string(value)))
+            }
+            _ => {}
+        }
+    }
+    if let Some(moves) = fields.get(4).filter(|m| !m.is_empty()) {
+        let moves = moves.split(',').map(json_string).collect::<Vec<_>>();
+        member.push(format!("\"moves\":[{}]", moves.join(",")));
+    }
+    let level = fields
+        .get(10)
+        .and_then(|l| l.parse::<u32>().ok())
+        .unwrap_or(100);
+    member.push(format!("\"level\":{}", level));
+    if let Some(tera) = fields
+        .get(11)
+        .and_then(|misc| misc.split(',').nth(5))
+        .filter(|t| !t.is_empty())
+    {
+        member.push(format!("\"teraType\":{}", json_string(tera)));
+    }
+    Some(format!("{{{}}}", member.join(",")))
+}
+
+/// The battle with teams rebuilt from its team sheets, if it has no teams of its own but its
+/// log has team sheets; `json` must already be a valid JSON object
+pub fn with_team_sheets(json: &str) -> Option<String> {
+    if !Stats::sides(json).is_empty() {
+        return None;
+    }
+
+    let log = gjson::get(json, "log");
+    let mut teams = vec![];
+    for line in log.array() {
+        let line = line.str();
+        let sheet = match line.strip_prefix("|showteam|") {
+            Some(sheet) => sheet,
+            None => continue,
+        };
+        let (side, packed) = match sheet.split_once('|') {
+            Some(parts) => parts,
+            None => continue,
+        };
+        let is_side =
+            side.len() == 2 && side.starts_with('p') && matches!(side.as_bytes()[1], b'1'..=b'4');
+        if !is_side {
+            continue;
+        }
+        let team = packed
+            .split(']')
+            .filter_map(packed_member)
+            .collect::<Vec<_>>();
+        teams.push(format!("\"{}team\":[{}]", side, team.join(",")));
+    }
+    if teams.is_empty() {
+        return None;
+    }
+
+    // the log is there, so the object isn't empty
+    let rest = json.trim_start().strip_prefix('{')?;
+    Some(format!("{{{},{}", teams.join(","), rest))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::filter::BattleFilter;
+
+    #[test]
+    fn test_packed_member() {
+        assert_eq!(
+            packed_member("Pult|Dragapult|Choice Specs|Infiltrator|Shadow Ball,Draco Meteor|Timid||M|||50|,,,,,Ghost").unwrap(),
+            r#"{"species":"Dragapult","item":"Choice Specs","ability":"Infiltrator","nature":"Timid","gender":"M","moves":["Shadow Ball","Draco Meteor"],"level":50,"teraType":"Ghost"}"#
+        );
+        // no species means it's the same as the nickname
+        assert_eq!(
+            packed_member("Amoonguss||||||||||").unwrap(),
+            r#"{"species":"Amoonguss","level":100}"#
+        );
+        assert_eq!(packed_member(""), None);
+    }
+
+    #[test]
+    fn test_with_team_sheets() {
+        let json = r#"{"winner":"A","p1":"A","p2":"B","log":["|showteam|p1|Amoonguss||||||||||50|]Incineroar||||||||||50|","|showteam|p2|Rillaboom||||||||||50|","|win|A"]}"#;
+        let recovered = with_team_sheets(json).unwrap();
+        assert!(gjson::valid(&recovered));
+        assert_eq!(
+            gjson::get(&recovered, "p1team.#.species").json(),
+            r#"["Amoonguss","Incineroar"]"#
+        );
+        assert_eq!(gjson::get(&recovered, "p2team.0.level").u32(), 50);
+        assert_eq!(gjson::get(&recovered, "winner").str(), "A");
+
+        let battle = Stats::process_battle(json, 0, &[], &BattleFilter::default()).unwrap();
+        assert_eq!(battle.results.len(), 3);
+        assert_eq!(battle.counters, vec!["Battles recovered from team sheets"]);
+
+        // battles that already have teams, or have no team sheets, are left alone
+        assert_eq!(with_team_sheets(&recovered), None);
+        assert_eq!(with_team_sheets(r#"{"log":["|win|A"]}"#), None);
+        assert!(gjson::valid(
+            &with_team_sheets(r#"{"log":["|showteam|p1|Mew||||||||||"]}"#).unwrap()
+        ));
+    }
+}
@@ -0,0 +1,330 @@
+/// Apache Parquet output (`--parquet-output`)
+///
+/// The same per-species columns as the JSON output, as a Parquet file for loading into pandas,
+/// polars or DuckDB without parsing text. The table is small, so this writes the simplest valid
+/// file rather than pulling in an Arrow stack: one row group, one uncompressed PLAIN-encoded data
+/// page per column, and no statistics. The footer is Thrift's compact protocol, written by hand.
+use crate::stats::Stats;
+
+const MAGIC: &[u8] = b"PAR1";
+
+/// Thrift compact protocol field types
+const THRIFT_I32: u8 = 5;
+const THRIFT_I64: u8 = 6;
+const THRIFT_BINARY: u8 = 8;
+const THRIFT_LIST: u8 = 9;
+const THRIFT_STRUCT: u8 = 12;
+
+/// Parquet physical types
+#[derive(Copy, Clone)]
+enum Type {
+    Boolean = 0,
+    Int64 = 2,
+    Double = 5,
+    ByteArray = 6,
+}
+
+/// Parquet `Encoding`s
+const PLAIN: i32 = 0;
+const RLE: i32 = 3;
+/// `FieldRepetitionType::REQUIRED`
+const REQUIRED: i32 = 0;
+/// `ConvertedType::UTF8`
+const UTF8: i32 = 0;
+
+fn varint(buf: &mut Vec<u8>, mut n: u64) {
+    while n >= 0x80 {
+        buf.push(n as u8 | 0x80);
+        n >>= 7;
+    }
+    buf.push(n as u8);
+}
+
+fn zigzag(n: i64) -> u64 {
+    ((n << 1) ^ (n >> 63)) as u64
+}
+
+/// A Thrift struct in the compact protocol, with fields added in increasing ID order
+#[derive(Default)]
+struct ThriftStruct {
+    buf: Vec<u8>,
+    last_id: i16,
+}
+
+impl ThriftStruct {
+    fn field(&mut self, id: i16, kind: u8) -> &mut Self {
+        let delta = id - self.last_id;
+        if (1..=15).contains(&delta) {
+            self.buf.push((delta as u8) << 4 | kind);
+        } else {
+            self.buf.push(kind);
+            varint(&mut self.buf, zigzag(id as i64));
+        }
+        self.last_id = id;
+        self
+    }
+
+    fn list_header(&mut self, id: i16, kind: u8, len: usize) {
+        self.field(id, THRIFT_LIST);
+        if len < 15 {
+            self.buf.push((len as u8) << 4 | kind);
+        } else {
+            self.buf.push(0xf0 | kind);
+            varint(&mut self.buf, len as u64);
+        }
+    }
+
+    fn i32(mut self, id: i16, n: i32) -> Self {
+        self.field(id, THRIFT_I32);
+        varint(&mut self.buf, zigzag(n as i64));
+        self
+    }
+
+    fn i64(mut self, id: i16, n: i64) -> Self {
+        self.field(id, THRIFT_I64);
+        varint(&mut self.buf, zigzag(n));
+        self
+    }
+
+    fn string(mut self, id: i16, s: &str) -> Self {
+        self.field(id, THRIFT_BINARY);
+        varint(&mut self.buf, s.len() as u64);
+        self.buf.extend_from_slice(s.as_bytes());
+        self
+    }
+
+    fn structure(mut self, id: i16, inner: ThriftStruct) -> Self {
+        self.field(id, THRIFT_STRUCT);
+        self.buf.extend(inner.finish());
+        self
+    }
+
+    fn i32_list(mut self, id: i16, items: &[i32]) -> Self {
+        self.list_header(id, THRIFT_I32, items.len());
+        for &n in items {
+            varint(&mut self.buf, zigzag(n as i64));
+        }
+        self
+    }
+
+    fn string_list(mut self, id: i16, items: &[&str]) -> Self {
+        self.list_header(id, THRIFT_BINARY, items.len());
+        for s in items {
+            varint(&mut self.buf, s.len() as u64);
+            self.buf.extend_from_slice(s.as_bytes());
+        }
+        self
+    }
+
+    fn struct_list(mut self, id: i16, items: Vec<ThriftStruct>) -> Self {
+        self.list_header(id, THRIFT_STRUCT, items.len());
+        for item in items {
+            self.buf.extend(item.finish());
+        }
+        self
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        self.buf.push(0);
+        self.buf
+    }
+}
+
+/// A required column and its PLAIN-encoded values
+struct Column {
+    name: &'static str,
+    physical_type: Type,
+    values: Vec<u8>,
+}
+
+impl Column {
+    fn new(name: &'static str, physical_type: Type) -> Self {
+        Column {
+            name,
+            physical_type,
+            values: vec![],
+        }
+    }
+
+    /// Writes the column as a single data page, returning its `ColumnChunk`
+    fn write(&self, file: &mut Vec<u8>, num_rows: usize) -> ThriftStruct {
+        let page_header = ThriftStruct::default()
+            .i32(1, 0) // DATA_PAGE
+            .i32(2, self.values.len() as i32)
+            .i32(3, self.values.len() as i32)
+            .structure(
+                5,
+                ThriftStruct::default()
+                    .i32(1, num_rows as i32)
+                    .i32(2, PLAIN)
+                    .i32(3, RLE)
+                    .i32(4, RLE),
+            )
+            .finish();
+        let offset = file.len() as i64;
+        let size = (page_header.len() + self.values.len()) as i64;
+        file.extend(page_header);
+        file.extend_from_slice(&self.values);
+
+        let metadata = ThriftStruct::default()
+            .i32(1, self.physical_type as i32)
+            .i32_list(2, &[PLAIN, RLE])
+            .string_list(3, &[self.name])
+            .i32(4, 0) // UNCOMPRESSED
+            .i64(5, num_rows as i64)
+            .i64(6, size)
+            .i64(7, size)
+            .i64(9, offset);
+        ThriftStruct::default()
+            .i64(2, offset)
+            .structure(3, metadata)
+    }
+
+    fn schema_element(&self) -> ThriftStruct {
+        let element = ThriftStruct::default()
+            .i32(1, self.physical_type as i32)
+            .i32(3, REQUIRED)
+            .string(4, self.name);
+        match self.physical_type {
+            Type::ByteArray => element.i32(6, UTF8),
+            _ => element,
+        }
+    }
+}
+
+/// PLAIN-encoded booleans, packed eight to a byte with the first value in the lowest bit
+fn pack_bools(bools: &[bool]) -> Vec<u8> {
+    bools
+        .chunks(8)
+        .map(|chunk| {
+            chunk
+                .iter()
+                .enumerate()
+                .fold(0, |byte, (bit, &b)| byte | (b as u8) << bit)
+        })
+        .collect()
+}
+
+impl Stats {
+    pub fn to_parquet(&mut self) -> Vec<u8> {
+        self.sort();
+
+        let mut species = Column::new("species", Type::ByteArray);
+        let mut games = Column::new("games", Type::Int64);
+        let mut wins = Column::new("wins", Type::Int64);
+        let mut winrate = Column::new("winrate", Type::Double);
+        let mut deviations = Column::new("deviations", Type::Double);
+        let mut provisional = vec![];
+        for (name, stats) in &self.pokemon {
+            let fstats = stats.final_stats();
+            species
+                .values
+                .extend_from_slice(&(name.len() as u32).to_le_bytes());
+            species.values.extend_from_slice(name.as_bytes());
+            games
+                .values
+                .extend_from_slice(&(stats.games as i64).to_le_bytes());
+            wins.values
+                .extend_from_slice(&(stats.wins as i64).to_le_bytes());
+            winrate
+                .values
+                .extend_from_slice(&fstats.winrate.to_le_bytes());
+            deviations
+                .values
+                .extend_from_slice(&fstats.deviations.to_le_bytes());
+            provisional.push(self.is_provisional(stats));
+        }
+        let mut columns = vec![species, games, wins, winrate, deviations];
+        if self.provisional_below.is_some() {
+            columns.push(Column {
+                name: "provisional",
+                physical_type: Type::Boolean,
+                values: pack_bools(&provisional),
+            });
+        }
+
+        let num_rows = self.pokemon.len();
+        let mut file = MAGIC.to_vec();
+        let chunks = columns
+            .iter()
+            .map(|column| column.write(&mut file, num_rows))
+            .collect();
+        let root = ThriftStruct::default()
+            .string(4, "schema")
+            .i32(5, columns.len() as i32);
+        let schema = std::iter::once(root)
+            .chain(columns.iter().map(Column::schema_element))
+            .collect();
+        let row_group = ThriftStruct::default()
+            .struct_list(1, chunks)
+            .i64(2, (file.len() - MAGIC.len()) as i64)
+            .i64(3, num_rows as i64);
+        let footer = ThriftStruct::default()
+            .i32(1, 1)
+            .struct_list(2, schema)
+            .i64(3, num_rows as i64)
+            .struct_list(4, vec![row_group])
+            .string(
+                6,
+                concat!("randbats-winrates version ", env!("CARGO_PKG_VERSION")),
+            )
+            .finish();
+
+        file.extend_from_slice(&footer);
+        file.extend_from_slice(&(footer.len() as u32).to_le_bytes());
+        file.extend_from_slice(MAGIC);
+        file
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stats::GameResult;
+
+    #[test]
+    fn test_thrift() {
+        let mut buf = vec![];
+        varint(&mut buf, 300);
+        assert_eq!(buf, vec![0xac, 0x02]);
+        assert_eq!((zigzag(0), zigzag(-1), zigzag(1)), (0, 1, 2));
+
+        // field 1 is a short-form header; field 20 needs its ID written out
+        let encoded = ThriftStruct::default()
+            .i32(1, -1)
+            .i64(20, 2)
+            .string_list(21, &["ab"])
+            .finish();
+        assert_eq!(
+            encoded,
+            vec![0x15, 0x01, 0x06, 0x28, 0x04, 0x19, 0x18, 0x02, b'a', b'b', 0x00]
+        );
+        assert_eq!(pack_bools(&[true, false, true]), vec![0b101]);
+    }
+
+    #[test]
+    fn test_parquet() {
+        let result = |species: &str, won| GameResult {
+            species: species.to_string(),
+            won,
+            turns: 10,
+            rating: None,
+        };
+        let mut stats = Stats::new();
+        stats.add_game_results(vec![result("Mew", true), result("Ditto", false)]);
+
+        let file = stats.to_parquet();
+        assert!(file.starts_with(MAGIC) && file.ends_with(MAGIC));
+        let mut footer_len = [0; 4];
+        footer_len.copy_from_slice(&file[file.len() - 8..file.len() - 4]);
+        let footer_start = file.len() - 8 - u32::from_le_bytes(footer_len) as usize;
+        // the species column's page follows its header, right after the leading magic bytes
+        let species_page = b"\x03\x00\x00\x00Mew\x05\x00\x00\x00Ditto";
+        assert!(file[..footer_start]
+            .windows(species_page.len())
+            .any(|window| window == species_page));
+        let footer = &file[footer_start..file.len() - 8];
+        assert!(footer.windows(10).any(|window| window == b"deviations"));
+        assert!(!footer.windows(11).any(|window| window == b"provisional"));
+    }
+}
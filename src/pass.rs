@@ -0,0 +1,180 @@
+/// Extra passes over the input, for reports that need more than the aggregated `Stats`
+///
+/// A pass reads one day at a time, in parallel within the day, and hands every battle the main
+/// analysis would count to an extraction function.
+use crate::filter::{to_id, BattleFilter, FormatKind};
+use crate::log;
+use crate::ots;
+use crate::stats::Stats;
+use crate::{hash, validate};
+use std::collections::HashSet;
+use std::hash::Hasher;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::thread;
+
+/// A counted battle's sides: (player ID, won, species)
+pub type Sides = Vec<(String, bool, Vec<String>)>;
+
+/// One pass over the input, day by day
+pub struct Scan<'a> {
+    min_elo: u64,
+    filter: &'a BattleFilter,
+    /// Content hashes of every battle seen so far, when skipping duplicates
+    seen_battles: HashSet<u64>,
+}
+
+impl<'a> Scan<'a> {
+    pub fn new(min_elo: u64, filter: &'a BattleFilter) -> Self {
+        Scan {
+            min_elo,
+            filter,
+            seen_battles: HashSet::new(),
+        }
+    }
+
+    /// Extracts something from each battle in one day that passes the filters, in file order
+    pub fn day<T: Send>(
+        &mut self,
+        files: &[PathBuf],
+        extract: impl Fn(&str) -> T + Sync,
+    ) -> io::Result<Vec<T>> {
+        self.day_files(files, |_, json| extract(json))
+    }
+
+    /// Like `day`, but also gives `extract` the battle's file
+    pub fn day_files<T: Send>(
+        &mut self,
+        files: &[PathBuf],
+        extract: impl Fn(&Path, &str) -> T + Sync,
+    ) -> io::Result<Vec<T>> {
+        let threads = thread::available_parallelism().map_or(1, |n| n.get());
+        let chunk_size = (files.len() / threads).max(1);
+        let (min_elo, filter, extract) = (self.min_elo, self.filter, &extract);
+
+        // (content hash, extracted) for each counted battle, so duplicates are dropped in file
+        // order whichever thread read them
+        let battles = thread::scope(|scope| {
+            let scanners = files
+                .chunks(chunk_size)
+                .map(|chunk| {
+                    scope.spawn(move || {
+                        let mut battles = vec![];
+                        for path in chunk {
+                            let json = match validate::read_battle(path) {
+                                Ok(json) => json,
+                                Err(_) => continue,
+                            };
+                            let mut hasher = hash::Fnv1a::default();
+                            hasher.write(json.as_bytes());
+                            match Stats::process_battle(&json, min_elo, &[], filter) {
+                                Ok(battle) if !battle.results.is_empty() => {}
+                                _ => continue,
+                            }
+                            let json = ots::with_team_sheets(&json).unwrap_or(json);
+                            battles.push((hasher.finish(), extract(path, &json)));
+                        }
+                        battles
+                    })
+                })
+                .collect::<Vec<_>>();
+
+            scanners
+                .into_iter()
+                .flat_map(|scanner| scanner.join().unwrap())
+                .collect::<Vec<_>>()
+        });
+
+        let skip_duplicates = filter.skip_duplicates;
+        let seen_battles = &mut self.seen_battles;
+        Ok(battles
+            .into_iter()
+            .filter(|(hash, _)| !skip_duplicates || seen_battles.insert(*hash))
+            .map(|(_, battle)| battle)
+            .collect())
+    }
+}
+
+/// The sides of a battle with at least one Pokémon
+pub fn sides(json: &str) -> Sides {
+    Stats::sides(json)
+        .iter()
+        .zip(Stats::results_by_side(0, json))
+        .filter(|(_, results)| !results.is_empty())
+        .map(|(side, results)| {
+            (
+                to_id(gjson::get(json, side).str()),
+                results[0].won,
+                results.into_iter().map(|r| r.species).collect(),
+            )
+        })
+        .collect()
+}
+
+/// The generation a battle was played in, from its format ID (e.g. `gen4randombattle`) or,
+/// failing that, its `|gen|` log line
+pub fn generation(json: &str) -> Option<u32> {
+    let format = gjson::get(json, "format");
+    if let Some(gen) = format.str().strip_prefix("gen") {
+        let digits = gen
+            .chars()
+            .take_while(|c| c.is_ascii_digit())
+            .collect::<String>();
+        if let Ok(gen) = digits.parse() {
+            return Some(gen);
+        }
+    }
+    let log = gjson::get(json, "log");
+    let gen = log
+        .array()
+        .iter()
+        .find_map(|line| line.str().strip_prefix("|gen|")?.parse().ok());
+    gen
+}
+
+/// Every counted Pokémon in a battle, as (species, won, whatever `extract` takes from its team
+/// entry); with `FormatKind::Standard`, only those that were sent out count
+pub fn team_members<T>(
+    json: &str,
+    filter: &BattleFilter,
+    extract: impl Fn(&gjson::Value) -> T,
+) -> Vec<(String, bool, T)> {
+    let sent_out = match filter.format_kind {
+        FormatKind::Standard => log::sent_out(json),
+        FormatKind::Random => None,
+    };
+    let mut members = vec![];
+    for (side, results) in Stats::sides(json)
+        .iter()
+        .zip(Stats::results_by_side(0, json))
+    {
+        let idx = side[1..].parse::<usize>().map_or(0, |n| n - 1);
+        let team_path = format!("{}team", side);
+        let team = gjson::get(json, &team_path);
+        for (result, member) in results.into_iter().zip(team.array()) {
+            if let Some(sent_out) = &sent_out {
+                if !sent_out[idx].contains(&result.species) {
+                    continue;
+                }
+            }
+            members.push((result.species, result.won, extract(&member)));
+        }
+    }
+    members
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_generation() {
+        assert_eq!(generation(r#"{"format":"gen4randombattle"}"#), Some(4));
+        assert_eq!(generation(r#"{"format":"gen10randombattle"}"#), Some(10));
+        assert_eq!(
+            generation(r#"{"log":["|player|p1|A||","|gen|1"]}"#),
+            Some(1)
+        );
+        assert_eq!(generation(r#"{"format":"randombattle","log":[]}"#), None);
+    }
+}
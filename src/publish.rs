@@ -0,0 +1,161 @@
+/// The best and worst species for the monthly forum post (`--publish-top`)
+///
+/// Sorting by deviations alone puts low-sample noise next to real outliers, so only species
+/// whose 95% Wilson score interval excludes 50% are eligible. The best are ranked by the lower
+/// bound of their interval and the worst by the upper bound, so a species needs both a large
+/// effect and enough games to make the list.
+use crate::stats::{PokemonStats, Stats};
+use crate::table::NumberFormat;
+
+/// z-score for a two-sided 95% confidence interval
+pub(crate) const Z: f64 = 1.96;
+
+/// The 95% Wilson score interval of a winrate, as percentages
+pub(crate) fn wilson_interval(wins: f64, games: f64) -> (f64, f64) {
+    let p = wins / games;
+    let z2 = Z * Z;
+    let center = (p + z2 / (2.0 * games)) / (1.0 + z2 / games);
+    let margin =
+        Z / (1.0 + z2 / games) * (p * (1.0 - p) / games + z2 / (4.0 * games * games)).sqrt();
+    ((center - margin) * 100.0, (center + margin) * 100.0)
+}
+
+struct Entry {
+    species: String,
+    games: u32,
+    /// winrate and its confidence interval, as percentages
+    winrate: f64,
+    lower: f64,
+    upper: f64,
+}
+
+impl Entry {
+    fn new(species: &str, stats: &PokemonStats) -> Self {
+        let (lower, upper) = wilson_interval(stats.weighted_wins, stats.weighted_games);
+        Entry {
+            species: species.to_string(),
+            games: stats.games,
+            winrate: stats.weighted_wins / stats.weighted_games * 100.0,
+            lower,
+            upper,
+        }
+    }
+
+    fn to_list_item(&self) -> String {
+        format!(
+            "[*][B]{}[/B]: {:.2}% ({:.2}%–{:.2}%) in {} games",
+            self.species,
+            self.winrate,
+            self.lower,
+            self.upper,
+            NumberFormat::Grouped.format(self.games)
+        )
+    }
+}
+
+pub struct Publication {
+    best: Vec<Entry>,
+    worst: Vec<Entry>,
+}
+
+impl Publication {
+    /// Picks up to `k` species whose winrates are confidently above 50%, and up to `k` below
+    pub fn new(stats: &Stats, k: usize) -> Self {
+        let (mut best, mut worst): (Vec<_>, Vec<_>) = stats
+            .records()
+            .filter(|(_, s)| s.weighted_games > 0.0)
+            .map(|(species, s)| Entry::new(species, s))
+            .filter(|entry| entry.lower > 50.0 || entry.upper < 50.0)
+            .partition(|entry| entry.lower > 50.0);
+
+        best.sort_by(|a, b| {
+            b.lower
+                .partial_cmp(&a.lower)
+                .unwrap()
+                .then_with(|| a.species.cmp(&b.species))
+        });
+        worst.sort_by(|a, b| {
+            a.upper
+                .partial_cmp(&b.upper)
+                .unwrap()
+                .then_with(|| a.species.cmp(&b.species))
+        });
+        best.truncate(k);
+        worst.truncate(k);
+        Self { best, worst }
+    }
+
+    /// BBCode, ready to paste into a forum post
+    pub fn to_forum_post(&self) -> String {
+        let mut post = String::new();
+        for (title, entries) in [
+            ("Best performers", &self.best),
+            ("Worst performers", &self.worst),
+        ] {
+            post.push_str(&format!("[B]{}[/B]\n", title));
+            if entries.is_empty() {
+                post.push_str("None with enough games to be sure.\n\n");
+                continue;
+            }
+            post.push_str("[LIST=1]\n");
+            for entry in entries {
+                post.push_str(&entry.to_list_item());
+                post.push('\n');
+            }
+            post.push_str("[/LIST]\n\n");
+        }
+        post.push_str(
+            "[SIZE=1]Ranges are 95% confidence intervals; \
+             species whose range includes 50% aren't listed.[/SIZE]\n",
+        );
+        post
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stats::GameResult;
+
+    #[test]
+    fn test_publication() {
+        let mut stats = Stats::new();
+        let mut add = |species: &str, games: u32, wins: u32| {
+            for game in 0..games {
+                stats.add_game_results(vec![GameResult {
+                    species: species.to_string(),
+                    won: game < wins,
+                    turns: 10,
+                    rating: None,
+                }]);
+            }
+        };
+        add("Dragapult", 1000, 600);
+        add("Blissey", 1000, 400);
+        add("Chansey", 1000, 450);
+        // a bigger effect than Dragapult's, but too few games to be sure of it
+        add("Mew", 10, 8);
+
+        let publication = Publication::new(&stats, 1);
+        assert_eq!(
+            publication
+                .best
+                .iter()
+                .map(|e| &e.species)
+                .collect::<Vec<_>>(),
+            vec!["Dragapult"]
+        );
+        assert_eq!(
+            publication
+                .worst
+                .iter()
+                .map(|e| &e.species)
+                .collect::<Vec<_>>(),
+            vec!["Blissey"]
+        );
+        assert_eq!(
+            publication.best[0].to_list_item(),
+            "[*][B]Dragapult[/B]: 60.00% (56.93%–62.99%) in 1,000 games"
+        );
+    }
+}
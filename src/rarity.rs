@@ -0,0 +1,417 @@
+/// Species the random pool should have produced but didn't (`--rarity-output`)
+///
+/// Given the random sets JSON the generator uses (its top-level keys are the species IDs in the
+/// pool), every species in the pool is expected to appear about equally often, or in proportion
+/// to how many sets it has with `--usage-model sets`, so one that never appears, or appears far
+/// less than its share, points at a generator bug rather than a balance problem. Each species'
+/// games are tested against its expected games (chi-square, or an exact Poisson test when fewer
+/// than five games are expected), and those that deviate significantly either way are listed.
+/// Species that appear without being in the pool are listed too. Species are matched by ID, so
+/// cosmetic formes that are counted under their base species (e.g. Pikachu's hats) show up as
+/// never seen if the pool lists them separately.
+use crate::filter::to_id;
+use crate::stats::{format_float, Output, Stats};
+use crate::table::{self, Block, Table};
+use itertools::Itertools;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::str::FromStr;
+
+/// Standard deviations below its expected games at which a species is flagged as rare
+const RARE_Z: f64 = -3.0;
+/// Significance level for the whole pool; each species is tested at this over the pool size
+const SIGNIFICANCE: f64 = 0.05;
+/// Expected games below which the chi-square approximation is replaced by an exact test
+const EXACT_BELOW: f64 = 5.0;
+
+/// How often each species in the pool is expected to be generated
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub enum UsageModel {
+    /// Every species equally often
+    #[default]
+    Uniform,
+    /// In proportion to the number of sets the species has in the sets file
+    Sets,
+}
+
+impl FromStr for UsageModel {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "uniform" => Ok(UsageModel::Uniform),
+            "sets" => Ok(UsageModel::Sets),
+            _ => Err(format!(
+                "unknown usage model '{}' (expected uniform or sets)",
+                s
+            )),
+        }
+    }
+}
+
+/// Reads the species IDs in a random sets JSON file, in file order, with how many sets each has
+/// (1 for species without a `sets` list)
+pub fn read_pool(path: &Path) -> io::Result<Vec<(String, usize)>> {
+    let json = fs::read_to_string(path)?;
+    let value = gjson::parse(&json);
+    if !gjson::valid(&json) || value.kind() != gjson::Kind::Object {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "{} isn't a JSON object of species to their sets",
+                path.display()
+            ),
+        ));
+    }
+    let mut pool = vec![];
+    value.each(|species, data| {
+        let sets = data.get("sets");
+        let sets = if sets.kind() == gjson::Kind::Array {
+            sets.array().len().max(1)
+        } else {
+            1
+        };
+        pool.push((to_id(species.str()), sets));
+        true
+    });
+    Ok(pool)
+}
+
+/// The complementary error function, to within 1.2e-7 (Numerical Recipes' `erfcc`)
+fn erfc(x: f64) -> f64 {
+    let t = 1.0 / (1.0 + 0.5 * x.abs());
+    let coefficients = [
+        -1.26551223,
+        1.00002368,
+        0.37409196,
+        0.09678418,
+        -0.18628806,
+        0.27886807,
+        -1.13520398,
+        1.48851587,
+        -0.82215223,
+        0.17087277,
+    ];
+    let poly = coefficients
+        .iter()
+        .rev()
+        .fold(0.0, |acc, coefficient| coefficient + t * acc);
+    let ans = t * (-x * x + poly).exp();
+    if x >= 0.0 {
+        ans
+    } else {
+        2.0 - ans
+    }
+}
+
+/// P(X <= k) for X ~ Poisson(mean)
+fn poisson_cdf(k: u32, mean: f64) -> f64 {
+    let mut term = (-mean).exp();
+    let mut sum = term;
+    for i in 1..=k {
+        term *= mean / i as f64;
+        sum += term;
+    }
+    sum.min(1.0)
+}
+
+/// Two-sided p-value of seeing `games` when `expected` were expected
+fn p_value(games: u32, expected: f64) -> f64 {
+    if expected <= 0.0 {
+        return 1.0;
+    }
+    if expected < EXACT_BELOW {
+        let below = poisson_cdf(games, expected);
+        let above = if games == 0 {
+            1.0
+        } else {
+            1.0 - poisson_cdf(games - 1, expected)
+        };
+        (2.0 * below.min(above)).min(1.0)
+    } else {
+        // the chi-square statistic with one degree of freedom is z^2, so P(chi^2 >= z^2) is
+        // the two-sided normal tail
+        let z = (games as f64 - expected) / expected.sqrt();
+        erfc(z.abs() / std::f64::consts::SQRT_2)
+    }
+}
+
+struct RarityRow {
+    species: String,
+    games: u32,
+    /// Games the species would have under the usage model; only meaningful for species in
+    /// the pool, as are `z` and `p`
+    expected: f64,
+    /// Standard deviations from the expected games
+    z: f64,
+    p: f64,
+    status: &'static str,
+}
+
+pub struct RarityReport {
+    rows: Vec<RarityRow>,
+    pool_size: usize,
+    model: UsageModel,
+    /// Games each species in the pool would have on average
+    expected: f64,
+}
+
+impl RarityReport {
+    pub fn new(pool: &[(String, usize)], stats: &Stats, model: UsageModel) -> Self {
+        let pool = pool
+            .iter()
+            .unique_by(|(id, _)| id)
+            .map(|(id, sets)| {
+                let weight = match model {
+                    UsageModel::Uniform => 1.0,
+                    UsageModel::Sets => *sets as f64,
+                };
+                (id, weight)
+            })
+            .collect::<Vec<_>>();
+        // ID:(name, games) of every species seen
+        let seen = stats
+            .records()
+            .map(|(species, record)| (to_id(species), (species, record.games)))
+            .collect::<HashMap<_, _>>();
+        let pool_games = pool
+            .iter()
+            .filter_map(|(id, _)| seen.get(*id))
+            .map(|(_, games)| *games as f64)
+            .sum::<f64>();
+        let total_weight = pool.iter().map(|(_, weight)| weight).sum::<f64>();
+
+        let mut rows = vec![];
+        for (id, weight) in &pool {
+            let (species, games) = match seen.get(*id) {
+                Some((species, games)) => (species.to_string(), *games),
+                None => (id.to_string(), 0),
+            };
+            let expected = pool_games * weight / total_weight;
+            // appearances are roughly Poisson, so the standard deviation is sqrt(expected)
+            let z = if expected > 0.0 {
+                (games as f64 - expected) / expected.sqrt()
+            } else {
+                0.0
+            };
+            let p = p_value(games, expected);
+            let status = if games == 0 {
+                "never seen"
+            } else if z <= RARE_Z {
+                "rare"
+            } else if p < SIGNIFICANCE / pool.len() as f64 {
+                if z < 0.0 {
+                    "underrepresented"
+                } else {
+                    "overrepresented"
+                }
+            } else {
+                continue;
+            };
+            rows.push(RarityRow {
+                species,
+                games,
+                expected,
+                z,
+                p,
+                status,
+            });
+        }
+        rows.sort_by(|a, b| {
+            a.z.partial_cmp(&b.z)
+                .unwrap()
+                .then_with(|| a.species.cmp(&b.species))
+        });
+
+        let pool_ids = pool.iter().map(|(id, _)| *id).collect::<HashSet<_>>();
+        for (id, (species, games)) in seen.iter().sorted_by_key(|(_, (species, _))| *species) {
+            if !pool_ids.contains(id) {
+                rows.push(RarityRow {
+                    species: species.to_string(),
+                    games: *games,
+                    expected: 0.0,
+                    z: 0.0,
+                    p: 1.0,
+                    status: "not in pool",
+                });
+            }
+        }
+
+        Self {
+            rows,
+            pool_size: pool.len(),
+            model,
+            expected: if pool.is_empty() {
+                0.0
+            } else {
+                pool_games / pool.len() as f64
+            },
+        }
+    }
+}
+
+impl RarityRow {
+    fn test(&self) -> &'static str {
+        if self.expected < EXACT_BELOW {
+            "exact"
+        } else {
+            "chi-square"
+        }
+    }
+}
+
+impl Output for RarityReport {
+    /// species,games,expected,deviations,p,status
+    fn to_csv(&mut self) -> String {
+        Itertools::intersperse(
+            self.rows.iter().map(|row| {
+                format!(
+                    "{},{},{},{},{:.3e},{}",
+                    row.species,
+                    row.games,
+                    format_float(row.expected),
+                    format_float(row.z),
+                    row.p,
+                    row.status
+                )
+            }),
+            String::from("\n"),
+        )
+        .collect()
+    }
+
+    fn to_human_readable(&mut self) -> String {
+        table::blocks_to_text(&self.to_blocks())
+    }
+
+    fn to_blocks(&mut self) -> Vec<Block> {
+        let mut table = Table::new(&[
+            "Pokemon",
+            "Games",
+            "Expected",
+            "Deviations",
+            "p",
+            "Test",
+            "Status",
+        ]);
+        for row in &self.rows {
+            let (expected, z, p, test) = if row.status == "not in pool" {
+                Default::default()
+            } else {
+                (
+                    format!("{:.1}", row.expected),
+                    format_float(row.z),
+                    format!("{:.3e}", row.p),
+                    row.test().to_string(),
+                )
+            };
+            table.add_row(vec![
+                row.species.clone(),
+                row.games.to_string(),
+                expected,
+                z,
+                p,
+                test,
+                row.status.to_string(),
+            ]);
+        }
+        let model = match self.model {
+            UsageModel::Uniform => String::new(),
+            UsageModel::Sets => String::from(" on average, in proportion to their sets"),
+        };
+        vec![
+            Block::Text(format!(
+                "{} species in the pool, expected to appear in about {} games each{}",
+                self.pool_size,
+                format_float(self.expected),
+                model
+            )),
+            Block::Table(table),
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stats::GameResult;
+
+    #[test]
+    fn test_rarity() {
+        let mut stats = Stats::new();
+        let mut add = |species: &str, games: u32| {
+            for _ in 0..games {
+                stats.add_game_results(vec![GameResult {
+                    species: species.to_string(),
+                    won: true,
+                    turns: 1,
+                    rating: None,
+                }]);
+            }
+        };
+        add("Mew", 70);
+        add("Ditto", 70);
+        add("Rotom-Fan", 159);
+        add("Smeargle", 1);
+        add("Missingno.", 5);
+
+        let pool = ["mew", "ditto", "rotomfan", "smeargle", "unown"]
+            .iter()
+            .map(|id| (id.to_string(), 1))
+            .collect::<Vec<_>>();
+        let mut report = RarityReport::new(&pool, &stats, UsageModel::Uniform);
+        // 300 games over 5 species in the pool
+        assert_eq!(report.expected, 60.0);
+        assert_eq!(
+            report.to_csv(),
+            "unown,0,60.000000,-7.745967,9.486e-15,never seen\n\
+             Smeargle,1,60.000000,-7.616867,2.599e-14,rare\n\
+             Rotom-Fan,159,60.000000,12.780845,2.098e-37,overrepresented\n\
+             Missingno.,5,0.000000,0.000000,1.000e0,not in pool"
+        );
+        assert!(report.to_human_readable().starts_with(
+            "5 species in the pool, expected to appear in about 60.000000 games each\n"
+        ));
+
+        // weighted by sets, Rotom-Fan's games are about what its four sets would give
+        let mut pool = pool;
+        pool[2].1 = 4;
+        let mut report = RarityReport::new(&pool, &stats, UsageModel::Sets);
+        assert!(!report.to_csv().contains("Rotom-Fan"));
+        assert!(report.to_csv().contains("Mew,70,37.500000,5.307228"));
+    }
+
+    #[test]
+    fn test_p_value() {
+        assert!((erfc(0.0) - 1.0).abs() < 1e-7);
+        // the two-sided normal tail is 5% at 1.96 standard deviations
+        assert!((erfc(1.959964 / std::f64::consts::SQRT_2) - 0.05).abs() < 1e-6);
+        // 79 games when 60 were expected is 2.45 standard deviations out
+        assert!((p_value(79, 60.0) - 0.014168).abs() < 1e-5);
+        // exact: P(X = 0) for a mean of 3 is e^-3, doubled for both tails
+        assert!((p_value(0, 3.0) - 2.0 * (-3.0f64).exp()).abs() < 1e-12);
+        assert_eq!(p_value(3, 3.0), 1.0);
+    }
+
+    #[test]
+    fn test_read_pool() {
+        let path = std::env::temp_dir().join(format!("rarity-pool-{}.json", std::process::id()));
+        fs::write(
+            &path,
+            r#"{"bulbasaur": {"level": 84, "sets": [{}, {}]}, "Rotom-Fan": {"level": 86}}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            read_pool(&path).unwrap(),
+            vec![
+                (String::from("bulbasaur"), 2),
+                (String::from("rotomfan"), 1)
+            ]
+        );
+        fs::write(&path, "[]").unwrap();
+        assert!(read_pool(&path).is_err());
+        fs::remove_file(&path).unwrap();
+    }
+}
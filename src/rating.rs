@@ -0,0 +1,134 @@
+/// The mean rating of the players who received each species
+///
+/// Random Battles should deal every species evenly across the ladder, so a species whose
+/// players are rated well above (or below) average suggests its raw winrate partly reflects
+/// who was playing it.
+use crate::stats::{format_float, Output, Stats};
+use crate::table::{self, Block, Table};
+use itertools::Itertools;
+
+struct RatingRow {
+    species: String,
+    /// `species` as shown in the human-readable output
+    display_name: String,
+    mean_rating: f64,
+    /// Difference from the mean rating over every species
+    difference: f64,
+    rated_games: u32,
+}
+
+pub struct RatingReport {
+    rows: Vec<RatingRow>,
+}
+
+impl RatingReport {
+    pub fn new(stats: &Stats) -> Self {
+        let (total_games, total_rating) = stats.records().fold((0, 0.0), |(games, sum), (_, s)| {
+            (games + s.rated_games, sum + s.rating_sum)
+        });
+        let overall_mean = if total_games == 0 {
+            0.0
+        } else {
+            total_rating / total_games as f64
+        };
+
+        let mut rows = stats
+            .records()
+            .filter(|(_, s)| s.rated_games > 0)
+            .map(|(species, s)| {
+                let mean_rating = s.rating_sum / s.rated_games as f64;
+                RatingRow {
+                    species: species.to_string(),
+                    display_name: stats.display_name(species).to_string(),
+                    mean_rating,
+                    difference: mean_rating - overall_mean,
+                    rated_games: s.rated_games,
+                }
+            })
+            .collect::<Vec<_>>();
+
+        rows.sort_by(|a, b| {
+            b.difference
+                .partial_cmp(&a.difference)
+                .unwrap()
+                .then_with(|| a.species.cmp(&b.species))
+        });
+
+        Self { rows }
+    }
+}
+
+impl Output for RatingReport {
+    fn to_csv(&mut self) -> String {
+        Itertools::intersperse(
+            self.rows.iter().map(|row| {
+                [
+                    row.species.clone(),
+                    format_float(row.mean_rating),
+                    format_float(row.difference),
+                    row.rated_games.to_string(),
+                ]
+                .join(",")
+            }),
+            String::from("\n"),
+        )
+        .collect()
+    }
+
+    fn to_human_readable(&mut self) -> String {
+        table::blocks_to_text(&self.to_blocks())
+    }
+
+    fn to_blocks(&mut self) -> Vec<Block> {
+        let mut table = Table::new(&[
+            "Pokemon",
+            "Mean player rating",
+            "Difference from average",
+            "Rated games",
+        ]);
+
+        for row in &self.rows {
+            table.add_row(vec![
+                row.display_name.clone(),
+                format_float(row.mean_rating),
+                format_float(row.difference),
+                row.rated_games.to_string(),
+            ]);
+        }
+
+        vec![Block::Table(table)]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stats::GameResult;
+
+    #[test]
+    fn test_rating_report() {
+        let mut stats = Stats::new();
+        for (species, rating) in [
+            ("Blissey", Some(1500.0)),
+            ("Blissey", Some(1300.0)),
+            ("Dragapult", Some(1100.0)),
+            ("Dragapult", None),
+            ("Mew", None),
+        ]
+        .iter()
+        {
+            stats.add_game_results(vec![GameResult {
+                species: species.to_string(),
+                won: true,
+                turns: 1,
+                rating: *rating,
+            }]);
+        }
+
+        // the average over all three rated games is 1300
+        assert_eq!(
+            RatingReport::new(&stats).to_csv(),
+            "Blissey,1400.000000,100.000000,2\nDragapult,1100.000000,-200.000000,1"
+        );
+    }
+}
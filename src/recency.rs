@@ -0,0 +1,86 @@
+/// Down-weighting older battles (`--recency-half-life`)
+///
+/// A battle's games count `0.5^(age / half-life)` times towards winrates and deviations, where
+/// its age is the time between its day directory and the newest day in the input. Game and win
+/// counts in the outputs are still the raw counts.
+use crate::stats::parse_day;
+use std::str::FromStr;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct HalfLife {
+    days: f64,
+}
+
+impl FromStr for HalfLife {
+    type Err = String;
+
+    /// Parses a duration like `14d`, `2w`, or `36h`; a bare number is in days
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (number, days_per_unit) = match s.char_indices().last() {
+            Some((idx, 'd')) => (&s[..idx], 1.0),
+            Some((idx, 'w')) => (&s[..idx], 7.0),
+            Some((idx, 'h')) => (&s[..idx], 1.0 / 24.0),
+            _ => (s, 1.0),
+        };
+        match number.parse::<f64>() {
+            Ok(n) if n > 0.0 && n.is_finite() => Ok(HalfLife {
+                days: n * days_per_unit,
+            }),
+            _ => Err(format!(
+                "invalid half-life '{}' (expected e.g. 14d, 2w, or 36h)",
+                s
+            )),
+        }
+    }
+}
+
+impl std::fmt::Display for HalfLife {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{} days", self.days)
+    }
+}
+
+impl HalfLife {
+    /// The weight of each of the given days, named YYYY-MM-DD, relative to the newest one;
+    /// days with other names aren't down-weighted
+    pub fn day_weights<'a>(&self, days: impl Iterator<Item = &'a str> + Clone) -> Vec<f64> {
+        let newest = days.clone().filter_map(parse_day).max();
+        days.map(|day| match (parse_day(day), newest) {
+            (Some(start), Some(newest)) => {
+                let age = (newest - start) as f64 / 86_400.0;
+                // long ranges with short half-lives would otherwise underflow to 0
+                0.5_f64.powf(age / self.days).max(f64::MIN_POSITIVE)
+            }
+            _ => 1.0,
+        })
+        .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_half_life() {
+        assert_eq!("14d".parse(), Ok(HalfLife { days: 14.0 }));
+        assert_eq!("2w".parse(), Ok(HalfLife { days: 14.0 }));
+        assert_eq!("36h".parse(), Ok(HalfLife { days: 1.5 }));
+        assert_eq!("3".parse(), Ok(HalfLife { days: 3.0 }));
+        assert!("0d".parse::<HalfLife>().is_err());
+        assert!("fortnight".parse::<HalfLife>().is_err());
+
+        let half_life = HalfLife { days: 2.0 };
+        let days = ["2024-01-01", "2024-01-03", "2024-01-05", "misc"];
+        assert_eq!(
+            half_life.day_weights(days.iter().copied()),
+            vec![0.25, 0.5, 1.0, 1.0]
+        );
+
+        let half_life: HalfLife = "1h".parse().unwrap();
+        let days = ["2024-01-01", "2024-02-15"];
+        let weights = half_life.day_weights(days.iter().copied());
+        assert!(weights[0] > 0.0);
+        assert_eq!(weights[1], 1.0);
+    }
+}
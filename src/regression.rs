@@ -0,0 +1,353 @@
+/// Species strength adjusted for opponents and ratings, from a logistic model
+/// (`--regression-output`)
+///
+/// A species' raw winrate mixes in who it was teamed with, who it faced, and how well its
+/// players were rated. This fits `P(p1 wins) = sigmoid(Σ p1 species - Σ p2 species +
+/// rating coefficient × rating difference)` over every rated two-player battle, so each
+/// species' coefficient is its effect on the log-odds of winning with the rest held fixed.
+///
+/// The fit is Newton's method on the likelihood with a small ridge penalty on the species
+/// coefficients. The penalty keeps rarely-seen species near 0, and pins down the coefficients'
+/// overall level, which the likelihood alone can't: adding the same amount to every species
+/// changes nothing when both teams have the same number of Pokémon.
+use crate::filter::BattleFilter;
+use crate::pass;
+use crate::stats::{format_float, GameResult, Output, Stats};
+use crate::table::{self, Block, Table};
+use indexmap::IndexMap;
+use itertools::Itertools;
+use std::io;
+use std::path::PathBuf;
+
+/// Strength of the ridge penalty on species coefficients
+const RIDGE: f64 = 1.0;
+/// Rating differences are measured in units of this many points
+const RATING_SCALE: f64 = 100.0;
+const MAX_ITERATIONS: usize = 50;
+/// Stop once no coefficient changes by more than this in an iteration
+const TOLERANCE: f64 = 1e-8;
+
+fn sigmoid(x: f64) -> f64 {
+    1.0 / (1.0 + (-x).exp())
+}
+
+/// One battle from p1's point of view
+struct Observation {
+    /// (species index, p1's count minus p2's count)
+    species: Vec<(usize, f64)>,
+    /// In units of `RATING_SCALE`
+    rating_diff: f64,
+    won: bool,
+}
+
+/// p1's species, p2's species, p1's rating minus p2's, and whether p1 won, for rated two-player
+/// battles with a winner
+fn battle_teams(json: &str) -> Option<(Vec<String>, Vec<String>, f64, bool)> {
+    let sides = Stats::results_by_side(0, json);
+    let (p1, p2) = match sides.as_slice() {
+        [p1, p2] if !p1.is_empty() && !p2.is_empty() => (p1, p2),
+        _ => return None,
+    };
+    if p1[0].won == p2[0].won {
+        return None;
+    }
+    let rating_diff = p1[0].rating? - p2[0].rating?;
+    let species = |side: &[GameResult]| side.iter().map(|r| r.species.clone()).collect::<Vec<_>>();
+    Some((species(p1), species(p2), rating_diff, p1[0].won))
+}
+
+/// Cholesky decomposition of a symmetric positive definite matrix, as the lower triangle
+fn cholesky(mut a: Vec<Vec<f64>>) -> Option<Vec<Vec<f64>>> {
+    let n = a.len();
+    for j in 0..n {
+        let diagonal = a[j][j] - (0..j).map(|k| a[j][k] * a[j][k]).sum::<f64>();
+        if diagonal <= 0.0 {
+            return None;
+        }
+        a[j][j] = diagonal.sqrt();
+        for i in j + 1..n {
+            let sum = (0..j).map(|k| a[i][k] * a[j][k]).sum::<f64>();
+            a[i][j] = (a[i][j] - sum) / a[j][j];
+        }
+        for value in a[j].iter_mut().skip(j + 1) {
+            *value = 0.0;
+        }
+    }
+    Some(a)
+}
+
+/// Solves `L Lᵀ x = b` given the Cholesky factor `L`
+fn cholesky_solve(l: &[Vec<f64>], b: &[f64]) -> Vec<f64> {
+    let n = b.len();
+    let mut y = vec![0.0; n];
+    for i in 0..n {
+        y[i] = (b[i] - (0..i).map(|k| l[i][k] * y[k]).sum::<f64>()) / l[i][i];
+    }
+    let mut x = vec![0.0; n];
+    for i in (0..n).rev() {
+        x[i] = (y[i] - (i + 1..n).map(|k| l[k][i] * x[k]).sum::<f64>()) / l[i][i];
+    }
+    x
+}
+
+/// Fitted coefficients and their standard errors, with the rating coefficient last
+struct Fit {
+    coefficients: Vec<f64>,
+    standard_errors: Vec<f64>,
+}
+
+fn fit(observations: &[Observation], num_species: usize) -> Option<Fit> {
+    let n = num_species + 1;
+    let mut coefficients = vec![0.0; n];
+    let mut factor = None;
+    for _ in 0..MAX_ITERATIONS {
+        // gradient and Hessian of the negative penalized log-likelihood
+        let mut gradient = vec![0.0; n];
+        let mut hessian = vec![vec![0.0; n]; n];
+        for idx in 0..num_species {
+            gradient[idx] = RIDGE * coefficients[idx];
+            hessian[idx][idx] = RIDGE;
+        }
+        for observation in observations {
+            let features = observation
+                .species
+                .iter()
+                .copied()
+                .chain(std::iter::once((num_species, observation.rating_diff)))
+                .collect::<Vec<_>>();
+            let p = sigmoid(features.iter().map(|(i, x)| coefficients[*i] * x).sum());
+            let residual = p - observation.won as u8 as f64;
+            for (i, x) in &features {
+                gradient[*i] += residual * x;
+                for (j, y) in &features {
+                    hessian[*i][*j] += p * (1.0 - p) * x * y;
+                }
+            }
+        }
+
+        let l = cholesky(hessian)?;
+        let step = cholesky_solve(&l, &gradient);
+        for (coefficient, step) in coefficients.iter_mut().zip(&step) {
+            *coefficient -= step;
+        }
+        factor = Some(l);
+        if step.iter().all(|s| s.abs() < TOLERANCE) {
+            break;
+        }
+    }
+
+    // the inverse Hessian's diagonal gives the variances
+    let l = factor?;
+    let standard_errors = (0..n)
+        .map(|idx| {
+            let mut unit = vec![0.0; n];
+            unit[idx] = 1.0;
+            cholesky_solve(&l, &unit)[idx].sqrt()
+        })
+        .collect();
+    Some(Fit {
+        coefficients,
+        standard_errors,
+    })
+}
+
+struct RegressionRow {
+    species: String,
+    games: u32,
+    wins: u32,
+    coefficient: f64,
+    standard_error: f64,
+}
+
+impl RegressionRow {
+    fn winrate(&self) -> f64 {
+        self.wins as f64 / self.games as f64 * 100.0
+    }
+
+    fn z(&self) -> f64 {
+        self.coefficient / self.standard_error
+    }
+}
+
+pub struct RegressionReport {
+    /// Battles the model was fit on
+    battles: usize,
+    /// Change in p1's log-odds of winning per `RATING_SCALE` points of rating advantage
+    rating_coefficient: f64,
+    rows: Vec<RegressionRow>,
+}
+
+impl RegressionReport {
+    pub fn new<'a>(
+        days: impl Iterator<Item = &'a [PathBuf]>,
+        min_elo: u64,
+        filter: &BattleFilter,
+    ) -> io::Result<Self> {
+        let mut battles = vec![];
+        let mut scan = pass::Scan::new(min_elo, filter);
+        for files in days {
+            battles.extend(scan.day(files, battle_teams)?.into_iter().flatten());
+        }
+        Ok(Self::from_battles(battles))
+    }
+
+    fn from_battles(battles: Vec<(Vec<String>, Vec<String>, f64, bool)>) -> Self {
+        // species:(games, wins), in order of first appearance, which gives each species' index
+        let mut records: IndexMap<String, (u32, u32)> = IndexMap::new();
+        let mut observations = vec![];
+        for (p1, p2, rating_diff, p1_won) in &battles {
+            let mut counts: IndexMap<usize, f64> = IndexMap::new();
+            for (team, sign, won) in [(p1, 1.0, *p1_won), (p2, -1.0, !p1_won)] {
+                for species in team {
+                    let entry = records.entry(species.clone());
+                    let idx = entry.index();
+                    let record = entry.or_default();
+                    record.0 += 1;
+                    record.1 += won as u32;
+                    *counts.entry(idx).or_default() += sign;
+                }
+            }
+            observations.push(Observation {
+                species: counts.into_iter().filter(|(_, c)| *c != 0.0).collect(),
+                rating_diff: rating_diff / RATING_SCALE,
+                won: *p1_won,
+            });
+        }
+
+        let mut report = RegressionReport {
+            battles: observations.len(),
+            rating_coefficient: 0.0,
+            rows: vec![],
+        };
+        // the Hessian is always positive definite thanks to the ridge penalty, unless every
+        // battle had the same rating difference of 0
+        let fit = match fit(&observations, records.len()) {
+            Some(fit) => fit,
+            None => return report,
+        };
+        report.rating_coefficient = fit.coefficients[records.len()];
+        report.rows = records
+            .into_iter()
+            .enumerate()
+            .map(|(idx, (species, (games, wins)))| RegressionRow {
+                species,
+                games,
+                wins,
+                coefficient: fit.coefficients[idx],
+                standard_error: fit.standard_errors[idx],
+            })
+            .collect();
+        report.rows.sort_by(|a, b| {
+            b.coefficient
+                .partial_cmp(&a.coefficient)
+                .unwrap()
+                .then_with(|| a.species.cmp(&b.species))
+        });
+        report
+    }
+}
+
+impl Output for RegressionReport {
+    fn to_csv(&mut self) -> String {
+        Itertools::intersperse(
+            self.rows.iter().map(|row| {
+                [
+                    row.species.clone(),
+                    row.games.to_string(),
+                    format_float(row.winrate()),
+                    format_float(row.coefficient),
+                    format_float(row.standard_error),
+                    format_float(row.z()),
+                ]
+                .join(",")
+            }),
+            String::from("\n"),
+        )
+        .collect()
+    }
+
+    fn to_human_readable(&mut self) -> String {
+        table::blocks_to_text(&self.to_blocks())
+    }
+
+    fn to_blocks(&mut self) -> Vec<Block> {
+        let mut table = Table::new(&[
+            "Pokemon",
+            "Coefficient",
+            "Std. error",
+            "z",
+            "Raw winrate",
+            "Games",
+        ]);
+        for row in &self.rows {
+            table.add_row(vec![
+                row.species.clone(),
+                format_float(row.coefficient),
+                format_float(row.standard_error),
+                format_float(row.z()),
+                format!("{}%", format_float(row.winrate())),
+                row.games.to_string(),
+            ]);
+        }
+        vec![
+            Block::Text(format!(
+                "Fit on {} rated battles; each {} points of rating advantage adds {} to the \
+                 log-odds of winning",
+                self.battles,
+                RATING_SCALE,
+                format_float(self.rating_coefficient)
+            )),
+            Block::Text(String::new()),
+            Block::Table(table),
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cholesky() {
+        let a = vec![
+            vec![4.0, 2.0, 0.4],
+            vec![2.0, 5.0, 1.0],
+            vec![0.4, 1.0, 3.0],
+        ];
+        let l = cholesky(a.clone()).unwrap();
+        let x = cholesky_solve(&l, &[1.0, 2.0, 3.0]);
+        for (row, b) in a.iter().zip([1.0, 2.0, 3.0]) {
+            let product = row.iter().zip(&x).map(|(a, x)| a * x).sum::<f64>();
+            assert!((product - b).abs() < 1e-12);
+        }
+        assert_eq!(cholesky(vec![vec![0.0]]), None);
+    }
+
+    #[test]
+    fn test_regression() {
+        let team = |species: &[&str]| species.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+        let mut battles = vec![];
+        for idx in 0..40 {
+            // Mew beats Ditto three times in four when the players are evenly rated
+            battles.push((team(&["Mew"]), team(&["Ditto"]), 0.0, idx % 4 != 0));
+            // and with the same species, the higher-rated player usually wins
+            battles.push((team(&["Mew"]), team(&["Mew"]), 200.0, idx % 5 != 0));
+        }
+        let mut report = RegressionReport::from_battles(battles);
+        assert_eq!(report.battles, 80);
+        assert!(report.rating_coefficient > 0.0);
+
+        let mew = &report.rows[0];
+        let ditto = &report.rows[1];
+        assert_eq!(
+            (mew.species.as_str(), ditto.species.as_str()),
+            ("Mew", "Ditto")
+        );
+        assert!(mew.coefficient > 0.0 && ditto.coefficient < 0.0);
+        // the ridge penalty splits the difference evenly
+        assert!((mew.coefficient + ditto.coefficient).abs() < 1e-6);
+        assert!(report
+            .to_human_readable()
+            .starts_with("Fit on 80 rated battles"));
+    }
+}
@@ -0,0 +1,312 @@
+/// Writing outputs to remote storage (`s3://` and `https://` output paths)
+///
+/// Any output path can be a URL instead of a file: `https://` (or `http://`) URLs are uploaded
+/// with a PUT, as WebDAV servers and presigned URLs expect, and `s3://bucket/key` URLs are
+/// uploaded to S3, signed with the credentials in the usual `AWS_*` environment variables
+/// (`AWS_ENDPOINT_URL` points them at an S3-compatible service instead). The content type
+/// comes from the path's extension. Uploads are made with `curl`, which has to be installed;
+/// the URL and credentials are passed in its config on stdin, so they don't show up in `ps`.
+use std::env;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process::{self, Stdio};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const DEFAULT_REGION: &str = "us-east-1";
+/// How many names to try for an upload's temporary file before giving up
+const MAX_BODY_FILE_ATTEMPTS: usize = 16;
+
+/// Numbers the temporary files that hold upload bodies, so concurrent uploads don't collide
+static UPLOADS: AtomicUsize = AtomicUsize::new(0);
+
+/// The URL an output path names, if it isn't a local file
+pub fn url(path: &Path) -> Option<&str> {
+    let path = path.to_str()?;
+    ["s3://", "https://", "http://"]
+        .iter()
+        .any(|scheme| path.starts_with(scheme))
+        .then_some(path)
+}
+
+/// The content type for an output, by its extension
+fn content_type(url: &str) -> &'static str {
+    let name = url.rsplit('/').next().unwrap_or(url);
+    let extension = match name.rsplit_once('.') {
+        Some((_, extension)) => extension.to_lowercase(),
+        None => String::new(),
+    };
+    match extension.as_str() {
+        "csv" => "text/csv; charset=utf-8",
+        "json" => "application/json",
+        "jsonl" | "ndjson" => "application/x-ndjson",
+        "md" => "text/markdown; charset=utf-8",
+        "html" | "htm" => "text/html; charset=utf-8",
+        "svg" => "image/svg+xml",
+        "dot" | "gv" => "text/vnd.graphviz",
+        "parquet" => "application/vnd.apache.parquet",
+        "xlsx" => "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet",
+        "txt" | "log" | "" => "text/plain; charset=utf-8",
+        _ => "application/octet-stream",
+    }
+}
+
+/// Quotes a value for a curl config file
+fn quote(value: &str) -> String {
+    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// The curl config lines that upload `body` to `url`, given a way to read the environment
+fn curl_config(
+    url: &str,
+    body: &Path,
+    var: impl Fn(&str) -> Option<String>,
+) -> io::Result<Vec<String>> {
+    let mut config = vec![
+        format!("upload-file = {}", quote(&body.to_string_lossy())),
+        format!(
+            "header = {}",
+            quote(&format!("Content-Type: {}", content_type(url)))
+        ),
+    ];
+
+    let location = match url.strip_prefix("s3://") {
+        None => url.to_string(),
+        Some(location) => {
+            let (bucket, key) = match location.split_once('/') {
+                Some((bucket, key)) if !bucket.is_empty() && !key.is_empty() => (bucket, key),
+                _ => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        format!("{} isn't of the form s3://bucket/key", url),
+                    ))
+                }
+            };
+            let (access_key, secret_key) =
+                match (var("AWS_ACCESS_KEY_ID"), var("AWS_SECRET_ACCESS_KEY")) {
+                    (Some(access_key), Some(secret_key)) => (access_key, secret_key),
+                    _ => {
+                        return Err(io::Error::new(
+                            io::ErrorKind::InvalidInput,
+                            format!(
+                                "writing to {} needs AWS_ACCESS_KEY_ID and AWS_SECRET_ACCESS_KEY",
+                                url
+                            ),
+                        ))
+                    }
+                };
+            let region = var("AWS_REGION")
+                .or_else(|| var("AWS_DEFAULT_REGION"))
+                .unwrap_or_else(|| String::from(DEFAULT_REGION));
+
+            config.push(format!(
+                "aws-sigv4 = {}",
+                quote(&format!("aws:amz:{}:s3", region))
+            ));
+            config.push(format!(
+                "user = {}",
+                quote(&format!("{}:{}", access_key, secret_key))
+            ));
+            if let Some(token) = var("AWS_SESSION_TOKEN") {
+                config.push(format!(
+                    "header = {}",
+                    quote(&format!("x-amz-security-token: {}", token))
+                ));
+            }
+            match var("AWS_ENDPOINT_URL") {
+                // S3-compatible services generally want path-style URLs
+                Some(endpoint) => format!("{}/{}/{}", endpoint.trim_end_matches('/'), bucket, key),
+                None => format!("https://{}.s3.{}.amazonaws.com/{}", bucket, region, key),
+            }
+        }
+    };
+    config.push(format!("url = {}", quote(&location)));
+    Ok(config)
+}
+
+/// Creates a new temporary file that only this user can read, for an upload's body
+///
+/// The file must not exist yet, so a file or symlink someone else planted at the same name in
+/// the shared temporary directory is never written through.
+fn create_body_file() -> io::Result<(PathBuf, fs::File)> {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.subsec_nanos());
+    let mut last_error = None;
+    for _ in 0..MAX_BODY_FILE_ATTEMPTS {
+        let path = env::temp_dir().join(format!(
+            "randbats-winrates-upload-{}-{}-{}",
+            process::id(),
+            nanos,
+            UPLOADS.fetch_add(1, Ordering::Relaxed)
+        ));
+        let mut options = fs::OpenOptions::new();
+        options.write(true).create_new(true);
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+        match options.open(&path) {
+            Ok(file) => return Ok((path, file)),
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => last_error = Some(e),
+            Err(e) => return Err(e),
+        }
+    }
+    Err(last_error.unwrap())
+}
+
+/// Uploads `contents` to `url`
+pub fn upload(url: &str, contents: &[u8]) -> io::Result<()> {
+    let (body, mut file) = create_body_file()?;
+    let result = file.write_all(contents).and_then(|_| curl(url, &body));
+    drop(file);
+    // the upload's own error matters more than a leftover temporary file
+    let _ = fs::remove_file(&body);
+    result
+}
+
+fn curl(url: &str, body: &Path) -> io::Result<()> {
+    let config = curl_config(url, body, |name| env::var(name).ok())?;
+    let mut child = process::Command::new("curl")
+        .args(["--silent", "--show-error", "--fail", "--config", "-"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(config.join("\n").as_bytes())?;
+    }
+    let output = child.wait_with_output()?;
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(io::Error::other(format!(
+            "uploading to {} failed: {}",
+            url,
+            String::from_utf8_lossy(&output.stderr).trim()
+        )))
+    }
+}
+
+/// An output that's collected in memory and uploaded once, when it's finished
+pub struct RemoteWriter {
+    url: String,
+    contents: Vec<u8>,
+}
+
+impl RemoteWriter {
+    pub fn new(url: &str) -> Self {
+        RemoteWriter {
+            url: url.to_string(),
+            contents: vec![],
+        }
+    }
+
+    /// Uploads everything written
+    pub fn finish(self) -> io::Result<()> {
+        upload(&self.url, &self.contents)
+    }
+}
+
+impl Write for RemoteWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.contents.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    /// Does nothing, since outputs like --jsonl-output flush after every section and each upload
+    /// sends the whole output
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_url() {
+        assert_eq!(
+            url(Path::new("s3://bucket/a.csv")),
+            Some("s3://bucket/a.csv")
+        );
+        assert_eq!(
+            url(Path::new("https://example.com/dav/a.csv")),
+            Some("https://example.com/dav/a.csv")
+        );
+        assert_eq!(url(Path::new("s3-backup/a.csv")), None);
+        assert_eq!(url(Path::new("-")), None);
+        assert_eq!(
+            content_type("s3://bucket/2024-01/stats.JSONL"),
+            "application/x-ndjson"
+        );
+        assert_eq!(
+            content_type("https://example.com/rankings"),
+            "text/plain; charset=utf-8"
+        );
+    }
+
+    #[test]
+    fn test_create_body_file() {
+        let (path, mut file) = create_body_file().unwrap();
+        file.write_all(b"body").unwrap();
+        drop(file);
+        assert_eq!(fs::read(&path).unwrap(), b"body");
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+        fs::remove_file(&path).unwrap();
+
+        let (other, _) = create_body_file().unwrap();
+        assert_ne!(path, other);
+        fs::remove_file(&other).unwrap();
+    }
+
+    #[test]
+    fn test_curl_config() {
+        let body = Path::new("/tmp/body");
+        let no_env = |_: &str| None;
+        assert_eq!(
+            curl_config("https://example.com/a.svg", body, no_env).unwrap(),
+            vec![
+                "upload-file = \"/tmp/body\"",
+                "header = \"Content-Type: image/svg+xml\"",
+                "url = \"https://example.com/a.svg\"",
+            ]
+        );
+        assert!(curl_config("s3://bucket/a.csv", body, no_env).is_err());
+
+        let aws = |name: &str| match name {
+            "AWS_ACCESS_KEY_ID" => Some(String::from("AKID")),
+            "AWS_SECRET_ACCESS_KEY" => Some(String::from("se\"cret")),
+            "AWS_REGION" => Some(String::from("eu-west-2")),
+            _ => None,
+        };
+        assert_eq!(
+            curl_config("s3://bucket/2024-01/a.csv", body, aws).unwrap(),
+            vec![
+                "upload-file = \"/tmp/body\"",
+                "header = \"Content-Type: text/csv; charset=utf-8\"",
+                "aws-sigv4 = \"aws:amz:eu-west-2:s3\"",
+                "user = \"AKID:se\\\"cret\"",
+                "url = \"https://bucket.s3.eu-west-2.amazonaws.com/2024-01/a.csv\"",
+            ]
+        );
+        assert!(curl_config("s3://bucket", body, aws).is_err());
+
+        let compatible = |name: &str| match name {
+            "AWS_ENDPOINT_URL" => Some(String::from("http://localhost:9000/")),
+            _ => aws(name),
+        };
+        assert_eq!(
+            curl_config("s3://bucket/a.csv", body, compatible)
+                .unwrap()
+                .last()
+                .unwrap(),
+            "url = \"http://localhost:9000/bucket/a.csv\""
+        );
+    }
+}
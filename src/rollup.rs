@@ -0,0 +1,142 @@
+/// Per-month breakdown of a multi-month rollup (`rollup --trend-output`)
+///
+/// Combining several months into a quarter or season hides how each species moved between
+/// them, so alongside the combined results, this keeps every species' games and wins for each
+/// month and lays the months out as columns, in the order they were given.
+use crate::stats::{format_float, Output, Stats};
+use crate::table::Table;
+use crate::tally::Counts;
+use indexmap::IndexMap;
+use itertools::Itertools;
+
+#[derive(Debug, Default)]
+pub struct Rollup {
+    months: Vec<String>,
+    /// Species:its counts in each month
+    species: IndexMap<String, Vec<Counts>>,
+}
+
+impl Rollup {
+    pub fn add_month(&mut self, label: &str, stats: &Stats) {
+        let month = self.months.len();
+        self.months.push(label.to_string());
+        for (species, record) in stats.records() {
+            let counts = self
+                .species
+                .entry(species.to_string())
+                .or_insert_with(|| vec![Counts::default(); month]);
+            counts.push(Counts {
+                games: record.games,
+                wins: record.wins,
+            });
+        }
+        for counts in self.species.values_mut() {
+            counts.resize(self.months.len(), Counts::default());
+        }
+    }
+
+    /// (species, counts over every month, counts in each month), most games first
+    fn rows(&self) -> Vec<(&str, Counts, &[Counts])> {
+        self.species
+            .iter()
+            .map(|(species, months)| {
+                let total = months
+                    .iter()
+                    .fold(Counts::default(), |total, month| Counts {
+                        games: total.games + month.games,
+                        wins: total.wins + month.wins,
+                    });
+                (species.as_str(), total, months.as_slice())
+            })
+            .sorted_by(|a, b| b.1.games.cmp(&a.1.games).then_with(|| a.0.cmp(b.0)))
+            .collect()
+    }
+}
+
+/// A winrate, unless the counts are for a month without games
+fn winrate(counts: &Counts) -> Option<String> {
+    if counts.games == 0 {
+        None
+    } else {
+        Some(format_float(counts.winrate()))
+    }
+}
+
+impl Output for Rollup {
+    /// species,games,winrate, then games,winrate for each month
+    fn to_csv(&mut self) -> String {
+        Itertools::intersperse(
+            self.rows().into_iter().map(|(species, total, months)| {
+                let mut fields = vec![
+                    species.to_string(),
+                    total.games.to_string(),
+                    winrate(&total).unwrap_or_default(),
+                ];
+                for month in months {
+                    fields.push(month.games.to_string());
+                    fields.push(winrate(month).unwrap_or_default());
+                }
+                fields.join(",")
+            }),
+            String::from("\n"),
+        )
+        .collect()
+    }
+
+    fn to_human_readable(&mut self) -> String {
+        let mut header = vec!["Pokemon", "Games", "Winrate"];
+        header.extend(self.months.iter().map(String::as_str));
+        let mut table = Table::new(&header);
+
+        for (species, total, months) in self.rows() {
+            let mut row = vec![
+                species.to_string(),
+                total.games.to_string(),
+                format!("{}%", winrate(&total).unwrap_or_default()),
+            ];
+            row.extend(months.iter().map(|month| match winrate(month) {
+                Some(winrate) => format!("{}%", winrate),
+                None => String::from("-"),
+            }));
+            table.add_row(row);
+        }
+
+        table.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stats::GameResult;
+
+    fn month(results: &[(&str, bool)]) -> Stats {
+        let mut stats = Stats::new();
+        for (species, won) in results {
+            stats.add_game_results(vec![GameResult {
+                species: species.to_string(),
+                won: *won,
+                turns: 1,
+                rating: None,
+            }]);
+        }
+        stats
+    }
+
+    #[test]
+    fn test_rollup() {
+        let mut rollup = Rollup::default();
+        rollup.add_month("2024-01", &month(&[("Mew", true), ("Mew", true)]));
+        rollup.add_month("2024-02", &month(&[("Ditto", true)]));
+        rollup.add_month("2024-03", &month(&[("Mew", false), ("Ditto", false)]));
+
+        assert_eq!(
+            rollup.to_csv(),
+            "Mew,3,66.666667,2,100.000000,0,,1,0.000000\n\
+             Ditto,2,50.000000,0,,1,100.000000,1,0.000000"
+        );
+        let table = rollup.to_human_readable();
+        assert!(table.contains("2024-02"));
+        assert!(table.contains("-"));
+    }
+}
@@ -0,0 +1,168 @@
+/// Detection of fields Showdown has started writing that this tool doesn't know about
+///
+/// Each battle's `pNrating` objects and team entries are checked against the fields known
+/// when this was written. Unknown fields that show up in a noticeable share of battles are
+/// reported after the run, so a change to the log format is noticed the day it ships rather
+/// than whenever someone next reads the raw logs.
+use indexmap::IndexMap;
+
+/// Fields of `p1rating`, `p2rating`, ...
+const KNOWN_RATING_FIELDS: [&str; 17] = [
+    "entryid",
+    "userid",
+    "w",
+    "l",
+    "t",
+    "gxe",
+    "r",
+    "rd",
+    "rptime",
+    "rpr",
+    "rprd",
+    "elo",
+    "col1",
+    "oldelo",
+    "formatid",
+    "first_played",
+    "last_played",
+];
+
+/// Fields of each Pokémon in `p1team`, `p2team`, ...
+const KNOWN_TEAM_FIELDS: [&str; 17] = [
+    "name",
+    "species",
+    "gender",
+    "shiny",
+    "gigantamax",
+    "level",
+    "moves",
+    "ability",
+    "nature",
+    "evs",
+    "ivs",
+    "item",
+    "teraType",
+    "happiness",
+    "pokeball",
+    "hpType",
+    "dynamaxLevel",
+];
+
+/// Unknown fields in less than this share of battles aren't reported
+const MIN_DRIFT_RATE: f64 = 0.01;
+
+/// The unknown fields in a battle, each once, as `rating.<field>` or `team.<field>`
+pub fn unknown_fields(json: &str) -> Vec<String> {
+    let mut fields = vec![];
+    let mut check = |location: &str, object: &gjson::Value, known: &[&str]| {
+        if object.kind() != gjson::Kind::Object {
+            return;
+        }
+        object.each(|key, _| {
+            let key = key.str();
+            if !known.contains(&key) {
+                let field = format!("{}.{}", location, key);
+                if !fields.contains(&field) {
+                    fields.push(field);
+                }
+            }
+            true
+        });
+    };
+
+    for side in ["p1", "p2", "p3", "p4"].iter() {
+        let rating_path = format!("{}rating", side);
+        check(
+            "rating",
+            &gjson::get(json, &rating_path),
+            &KNOWN_RATING_FIELDS,
+        );
+        let team_path = format!("{}team", side);
+        for member in gjson::get(json, &team_path).array() {
+            check("team", &member, &KNOWN_TEAM_FIELDS);
+        }
+    }
+    fields
+}
+
+/// How often each unknown field was seen
+#[derive(Debug, Default)]
+pub struct SchemaDrift {
+    /// Battles checked for unknown fields
+    pub(crate) battles: u32,
+    /// Field:number of battles it appeared in
+    pub(crate) fields: IndexMap<String, u32>,
+}
+
+impl SchemaDrift {
+    pub fn add(&mut self, unknown_fields: Vec<String>) {
+        self.battles += 1;
+        for field in unknown_fields {
+            *self.fields.entry(field).or_insert(0) += 1;
+        }
+    }
+
+    pub fn merge(&mut self, other: SchemaDrift) {
+        self.battles += other.battles;
+        for (field, count) in other.fields {
+            *self.fields.entry(field).or_insert(0) += count;
+        }
+    }
+
+    /// One line per unknown field seen in a noticeable share of battles, most common first,
+    /// as `key=value` pairs so they can be picked out of logs by monitoring
+    pub fn warnings(&self) -> Vec<String> {
+        let mut fields = self
+            .fields
+            .iter()
+            .filter(|(_, count)| **count as f64 >= self.battles as f64 * MIN_DRIFT_RATE)
+            .collect::<Vec<_>>();
+        fields.sort_by(|a, b| b.1.cmp(a.1).then_with(|| a.0.cmp(b.0)));
+
+        fields
+            .into_iter()
+            .map(|(field, count)| {
+                let (location, name) = field.split_once('.').unwrap_or(("", field));
+                format!(
+                    "schema-drift location={} field={} battles={} total={} rate={:.4}",
+                    location,
+                    name,
+                    count,
+                    self.battles,
+                    *count as f64 / self.battles as f64
+                )
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unknown_fields() {
+        let json = r#"{"p1team":[{"species":"Mew","teraType":"Fire","stellar":true},{"species":"Ditto","stellar":false}],"p2team":[{"species":"Eevee"}],"p1rating":{"elo":1100,"glicko3":5},"p2rating":1200}"#;
+        assert_eq!(unknown_fields(json), vec!["rating.glicko3", "team.stellar"]);
+
+        let mut drift = SchemaDrift::default();
+        drift.add(unknown_fields(json));
+        for _ in 0..98 {
+            drift.add(vec![]);
+        }
+        let mut other = SchemaDrift::default();
+        other.add(vec![String::from("team.stellar")]);
+        drift.merge(other);
+
+        // glicko3 is in 1 of 100 battles, just noticeable; stellar in 2
+        assert_eq!(
+            drift.warnings(),
+            vec![
+                "schema-drift location=team field=stellar battles=2 total=100 rate=0.0200",
+                "schema-drift location=rating field=glicko3 battles=1 total=100 rate=0.0100",
+            ]
+        );
+        drift.add(vec![]);
+        assert_eq!(drift.warnings().len(), 1);
+    }
+}
@@ -0,0 +1,149 @@
+/// The first and last day each species was seen (`--seen-output`)
+///
+/// A species added to (or removed from) the random pool mid-month has far fewer games than its
+/// neighbours, which looks like an anomaly until you see when it appeared. Days are the input's
+/// day directory names, so they sort in date order.
+use crate::filter::BattleFilter;
+use crate::pass;
+use crate::stats::Output;
+use crate::table::{self, Block, Table};
+use indexmap::IndexMap;
+use itertools::Itertools;
+use std::io;
+use std::path::PathBuf;
+
+struct SeenRow {
+    first: String,
+    last: String,
+    /// Days with at least one game
+    days: u32,
+    games: u32,
+}
+
+pub struct SeenReport {
+    /// Species:row
+    rows: IndexMap<String, SeenRow>,
+    /// Days in the input, including ones without battles
+    total_days: usize,
+}
+
+impl SeenReport {
+    pub fn new<'a>(
+        days: impl Iterator<Item = (&'a str, &'a [PathBuf])>,
+        min_elo: u64,
+        filter: &BattleFilter,
+    ) -> io::Result<Self> {
+        let mut report = SeenReport {
+            rows: IndexMap::new(),
+            total_days: 0,
+        };
+        let mut scan = pass::Scan::new(min_elo, filter);
+        for (day, files) in days {
+            let battles = scan.day(files, pass::sides)?;
+            let species = battles
+                .into_iter()
+                .flatten()
+                .flat_map(|(_, _, species)| species);
+            report.add_day(day, species);
+        }
+        report.sort();
+        Ok(report)
+    }
+
+    /// Adds one day's species, with each occurrence being a game; days must be added in order
+    fn add_day(&mut self, day: &str, species: impl Iterator<Item = String>) {
+        self.total_days += 1;
+        for (species, games) in species.counts() {
+            let row = self.rows.entry(species).or_insert_with(|| SeenRow {
+                first: day.to_string(),
+                last: day.to_string(),
+                days: 0,
+                games: 0,
+            });
+            row.last = day.to_string();
+            row.days += 1;
+            row.games += games as u32;
+        }
+    }
+
+    /// Latest first appearance first, then earliest last appearance, so species that weren't
+    /// around for the whole input come first
+    fn sort(&mut self) {
+        self.rows.sort_by(|a_species, a, b_species, b| {
+            b.first
+                .cmp(&a.first)
+                .then_with(|| a.last.cmp(&b.last))
+                .then_with(|| a_species.cmp(b_species))
+        });
+    }
+}
+
+impl Output for SeenReport {
+    fn to_csv(&mut self) -> String {
+        Itertools::intersperse(
+            self.rows.iter().map(|(species, row)| {
+                [
+                    species.clone(),
+                    row.first.clone(),
+                    row.last.clone(),
+                    row.days.to_string(),
+                    row.games.to_string(),
+                ]
+                .join(",")
+            }),
+            String::from("\n"),
+        )
+        .collect()
+    }
+
+    fn to_human_readable(&mut self) -> String {
+        table::blocks_to_text(&self.to_blocks())
+    }
+
+    fn to_blocks(&mut self) -> Vec<Block> {
+        let days_title = format!("Days seen (of {})", self.total_days);
+        let mut table = Table::new(&["Pokemon", "First seen", "Last seen", &days_title, "Games"]);
+        for (species, row) in &self.rows {
+            table.add_row(vec![
+                species.clone(),
+                row.first.clone(),
+                row.last.clone(),
+                row.days.to_string(),
+                row.games.to_string(),
+            ]);
+        }
+        vec![Block::Table(table)]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_seen() {
+        let mut report = SeenReport {
+            rows: IndexMap::new(),
+            total_days: 0,
+        };
+        let species = |names: &[&str]| {
+            names
+                .iter()
+                .map(|s| s.to_string())
+                .collect::<Vec<_>>()
+                .into_iter()
+        };
+        report.add_day("2024-01-01", species(&["Mew", "Ditto", "Mew"]));
+        report.add_day("2024-01-02", species(&["Mew", "Ditto"]));
+        report.add_day("2024-01-03", species(&["Mew", "Ogerpon"]));
+        report.sort();
+
+        assert_eq!(
+            report.to_csv(),
+            "Ogerpon,2024-01-03,2024-01-03,1,1\n\
+             Ditto,2024-01-01,2024-01-02,2,2\n\
+             Mew,2024-01-01,2024-01-03,3,4"
+        );
+        assert!(report.to_human_readable().contains("Days seen (of 3)"));
+    }
+}
@@ -0,0 +1,124 @@
+/// End-to-end check of the whole pipeline against a bundled mini-dataset (`--self-test`)
+///
+/// The dataset in `selftest/` is compiled into the binary, so a deployment can be validated
+/// without real logs or a nightly toolchain: the battles are written to a temporary directory,
+/// analyzed exactly as a normal run would, and the outputs compared to the golden files.
+/// After an intentional change to the outputs, regenerate the golden files with
+/// `--self-test --self-test-update`, run from the repository root.
+use crate::filter::BattleFilter;
+use crate::log::LogAnalysis;
+use crate::stats::{Output, StatsError};
+use crate::{handle_directory, InputPlan, Layout, Pipeline};
+use std::fs;
+use std::path::Path;
+
+const MIN_ELO: u64 = 1050;
+const LOG_ANALYSES: [LogAnalysis; 2] = [LogAnalysis::Leads, LogAnalysis::KOs];
+
+/// (day, file name, contents)
+const BATTLES: [(&str, &str, &str); 6] = [
+    (
+        "2024-01-01",
+        "1.json",
+        include_str!("../selftest/2024-01-01/1.json"),
+    ),
+    (
+        "2024-01-01",
+        "2.json",
+        include_str!("../selftest/2024-01-01/2.json"),
+    ),
+    (
+        "2024-01-01",
+        "3.json",
+        include_str!("../selftest/2024-01-01/3.json"),
+    ),
+    (
+        "2024-01-02",
+        "1.json",
+        include_str!("../selftest/2024-01-02/1.json"),
+    ),
+    (
+        "2024-01-02",
+        "2.json",
+        include_str!("../selftest/2024-01-02/2.json"),
+    ),
+    (
+        "2024-01-02",
+        "3.json",
+        include_str!("../selftest/2024-01-02/3.json"),
+    ),
+];
+
+/// (golden file, expected contents)
+const EXPECTED: [(&str, &str); 2] = [
+    (
+        "selftest/expected.csv",
+        include_str!("../selftest/expected.csv"),
+    ),
+    (
+        "selftest/expected-log.csv",
+        include_str!("../selftest/expected-log.csv"),
+    ),
+];
+
+/// The outputs of analyzing the dataset in `dir`, in the same order as `EXPECTED`
+fn analyze(dir: &Path) -> Result<[String; 2], StatsError> {
+    for (day, name, contents) in BATTLES.iter() {
+        fs::create_dir_all(dir.join(day))?;
+        fs::write(dir.join(day).join(name), contents)?;
+    }
+
+    let plan = InputPlan::new(dir, &None, &Layout::default(), None, None)?;
+    let mut stats = handle_directory(
+        MIN_ELO,
+        &plan,
+        &LOG_ANALYSES,
+        &BattleFilter::default(),
+        Pipeline {
+            batch_size: 2,
+            deterministic: true,
+            retry_incomplete: false,
+        },
+        false,
+        None,
+    )?;
+    Ok([stats.to_csv() + "\n", stats.log_stats.to_csv() + "\n"])
+}
+
+/// Runs the self-test, returning an error describing the first mismatch;
+/// with `update`, rewrites the golden files instead
+pub fn run(update: bool) -> Result<(), StatsError> {
+    let dir = std::env::temp_dir().join(format!(
+        "randbats-winrates-self-test-{}",
+        std::process::id()
+    ));
+    let outputs = analyze(&dir);
+    fs::remove_dir_all(&dir)?;
+
+    for ((path, expected), actual) in EXPECTED.iter().zip(outputs?.iter()) {
+        if update {
+            fs::write(path, actual)?;
+            println!("Updated {}", path);
+        } else if expected != actual {
+            return Err(StatsError::SelfTest(format!(
+                "{} doesn't match:\n--- expected\n{}--- actual\n{}",
+                path, expected, actual
+            )));
+        }
+    }
+
+    if !update {
+        println!("Self-test passed");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_self_test() {
+        run(false).unwrap();
+    }
+}
@@ -0,0 +1,198 @@
+/// Winrates by which side a team was on (`--side-output`)
+///
+/// Sides are assigned at random, so p1 and p2 should each win about half their games, both
+/// overall and for every species. A large gap points at a problem with the data or with how
+/// winners are matched to sides, rather than at anything about the species.
+use crate::filter::BattleFilter;
+use crate::pass;
+use crate::stats::{format_float, Output, Stats};
+use crate::table::{self, Block, Table};
+use indexmap::IndexMap;
+use itertools::Itertools;
+use std::io;
+use std::path::PathBuf;
+
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+struct Record {
+    games: u32,
+    wins: u32,
+}
+
+impl Record {
+    fn winrate(&self) -> Option<f64> {
+        if self.games == 0 {
+            None
+        } else {
+            Some(self.wins as f64 / self.games as f64 * 100.0)
+        }
+    }
+}
+
+/// A battle's sides as (side number starting at 1, won, species)
+fn numbered_sides(json: &str) -> Vec<(usize, bool, Vec<String>)> {
+    Stats::sides(json)
+        .iter()
+        .zip(Stats::results_by_side(0, json))
+        .filter(|(_, results)| !results.is_empty())
+        .filter_map(|(side, results)| {
+            let number = side[1..].parse::<usize>().ok()?;
+            let won = results[0].won;
+            Some((
+                number,
+                won,
+                results.into_iter().map(|r| r.species).collect(),
+            ))
+        })
+        .collect()
+}
+
+pub struct SideReport {
+    /// Record for each side, with p1 first; each team counts once
+    overall: Vec<Record>,
+    /// Species:record for each side
+    rows: IndexMap<String, Vec<Record>>,
+}
+
+impl SideReport {
+    pub fn new<'a>(
+        days: impl Iterator<Item = &'a [PathBuf]>,
+        min_elo: u64,
+        filter: &BattleFilter,
+    ) -> io::Result<Self> {
+        let mut report = SideReport {
+            overall: vec![],
+            rows: IndexMap::new(),
+        };
+        let mut scan = pass::Scan::new(min_elo, filter);
+        for files in days {
+            for sides in scan.day(files, numbered_sides)? {
+                for (number, won, species) in sides {
+                    report.add(number, won, species);
+                }
+            }
+        }
+        report.sort();
+        Ok(report)
+    }
+
+    fn add(&mut self, number: usize, won: bool, species: Vec<String>) {
+        let sides = number.max(self.overall.len());
+        let record = |records: &mut Vec<Record>| {
+            records.resize(sides, Record::default());
+            records[number - 1].games += 1;
+            records[number - 1].wins += won as u32;
+        };
+        record(&mut self.overall);
+        for species in species {
+            record(self.rows.entry(species).or_default());
+        }
+    }
+
+    /// Species with the largest gap between their best and worst side first
+    fn sort(&mut self) {
+        let sides = self.overall.len();
+        let gap = |records: &Vec<Record>| {
+            let winrates = records
+                .iter()
+                .filter_map(|r| r.winrate())
+                .collect::<Vec<_>>();
+            if winrates.len() < 2 {
+                return 0.0;
+            }
+            let max = winrates.iter().cloned().fold(f64::MIN, f64::max);
+            let min = winrates.iter().cloned().fold(f64::MAX, f64::min);
+            max - min
+        };
+        for records in self.rows.values_mut() {
+            records.resize(sides, Record::default());
+        }
+        self.rows.sort_by(|a_species, a, b_species, b| {
+            gap(b)
+                .partial_cmp(&gap(a))
+                .unwrap()
+                .then_with(|| a_species.cmp(b_species))
+        });
+    }
+
+    fn fields(name: &str, records: &[Record]) -> Vec<String> {
+        let mut fields = vec![name.to_string()];
+        for record in records {
+            fields.push(record.games.to_string());
+            fields.push(record.winrate().map_or(String::new(), format_float));
+        }
+        fields
+    }
+}
+
+impl Output for SideReport {
+    /// The first row is every team, as species `(all)`
+    fn to_csv(&mut self) -> String {
+        Itertools::intersperse(
+            std::iter::once(Self::fields("(all)", &self.overall))
+                .chain(
+                    self.rows
+                        .iter()
+                        .map(|(species, records)| Self::fields(species, records)),
+                )
+                .map(|fields| fields.join(",")),
+            String::from("\n"),
+        )
+        .collect()
+    }
+
+    fn to_human_readable(&mut self) -> String {
+        table::blocks_to_text(&self.to_blocks())
+    }
+
+    fn to_blocks(&mut self) -> Vec<Block> {
+        let mut titles = vec![String::from("Pokemon")];
+        for number in 1..=self.overall.len() {
+            titles.push(format!("p{} winrate", number));
+            titles.push(format!("p{} games", number));
+        }
+        let mut table = Table::new(&titles.iter().map(String::as_str).collect::<Vec<_>>());
+        let cells = |name: &str, records: &[Record]| {
+            let mut cells = vec![name.to_string()];
+            for record in records {
+                cells.push(
+                    record
+                        .winrate()
+                        .map_or(String::from("-"), |w| format!("{}%", format_float(w))),
+                );
+                cells.push(record.games.to_string());
+            }
+            cells
+        };
+        table.add_row(cells("(all)", &self.overall));
+        for (species, records) in &self.rows {
+            table.add_row(cells(species, records));
+        }
+        vec![Block::Table(table)]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_side_report() {
+        let json = r#"{"winner":"A","p1":"A","p2":"B","p1team":[{"species":"Mew"},{"species":"Ditto"}],"p2team":[{"species":"Mew"}]}"#;
+        let mut report = SideReport {
+            overall: vec![],
+            rows: IndexMap::new(),
+        };
+        for (number, won, species) in numbered_sides(json) {
+            report.add(number, won, species);
+        }
+        report.add(2, true, vec![String::from("Ditto")]);
+        report.sort();
+
+        assert_eq!(
+            report.to_csv(),
+            "(all),1,100.000000,2,50.000000\n\
+             Mew,1,100.000000,1,0.000000\n\
+             Ditto,1,100.000000,1,100.000000"
+        );
+    }
+}
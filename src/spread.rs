@@ -0,0 +1,197 @@
+/// Winrates by nature and EV/IV spread, for standard formats (`--spread-output`)
+///
+/// Exact spreads rarely repeat, so EVs are bucketed by which stats are invested in (at least
+/// `INVESTED_EVS`), with any stat whose IV is 0 noted too, e.g. `HP/SpD, 0 Atk IV`. Random
+/// Battles sets have no natures and near-uniform EVs, so this is only useful for standard
+/// formats, usually with `--format-kind standard`.
+use crate::filter::BattleFilter;
+use crate::pass;
+use crate::stats::{format_float, Output};
+use crate::table::{self, Block, Table};
+use crate::tally::{Counts, Interner, Tally};
+use itertools::Itertools;
+use std::io;
+use std::path::PathBuf;
+
+/// A stat with at least this many EVs counts as invested in
+const INVESTED_EVS: u32 = 100;
+
+/// (key in the team JSON, name in the output)
+const STATS: [(&str, &str); 6] = [
+    ("hp", "HP"),
+    ("atk", "Atk"),
+    ("def", "Def"),
+    ("spa", "SpA"),
+    ("spd", "SpD"),
+    ("spe", "Spe"),
+];
+
+/// A team member's nature and spread bucket
+fn nature_and_spread(member: &gjson::Value) -> (String, String) {
+    let nature = member.get("nature");
+    let nature = match nature.str() {
+        "" => String::from("(none)"),
+        n => n.to_string(),
+    };
+
+    let evs = member.get("evs");
+    let invested = STATS
+        .iter()
+        .filter(|(key, _)| evs.get(key).u32() >= INVESTED_EVS)
+        .map(|(_, name)| *name)
+        .join("/");
+    let mut spread = if invested.is_empty() {
+        String::from("no investment")
+    } else {
+        invested
+    };
+
+    let ivs = member.get("ivs");
+    for (key, name) in STATS.iter() {
+        let iv = ivs.get(key);
+        if iv.exists() && iv.u32() == 0 {
+            spread.push_str(&format!(", 0 {} IV", name));
+        }
+    }
+    (nature, spread)
+}
+
+pub struct SpreadReport {
+    /// Species, natures and spreads share one set of IDs
+    names: Interner,
+    /// (species, nature, spread) IDs:counts
+    tally: Tally<(u32, u32, u32)>,
+    /// Every row, in output order
+    rows: Vec<((u32, u32, u32), Counts)>,
+}
+
+impl SpreadReport {
+    pub fn new<'a>(
+        days: impl Iterator<Item = &'a [PathBuf]>,
+        min_elo: u64,
+        filter: &BattleFilter,
+    ) -> io::Result<Self> {
+        let mut report = Self::empty();
+        let mut scan = pass::Scan::new(min_elo, filter);
+        for files in days {
+            let battles = scan.day(files, |json| {
+                pass::team_members(json, filter, nature_and_spread)
+            })?;
+            for (species, won, (nature, spread)) in battles.into_iter().flatten() {
+                report.add(&species, &nature, &spread, won);
+            }
+        }
+        report.sort();
+        Ok(report)
+    }
+
+    fn empty() -> Self {
+        SpreadReport {
+            names: Interner::default(),
+            tally: Tally::default(),
+            rows: vec![],
+        }
+    }
+
+    fn add(&mut self, species: &str, nature: &str, spread: &str, won: bool) {
+        let key = (
+            self.names.id(species),
+            self.names.id(nature),
+            self.names.id(spread),
+        );
+        self.tally.add(key, won);
+    }
+
+    /// By species, then most games first
+    fn sort(&mut self) {
+        let names = &self.names;
+        let mut rows = self.tally.rows().collect::<Vec<_>>();
+        rows.sort_by(
+            |((a_species, a_nature, a_spread), a), ((b_species, b_nature, b_spread), b)| {
+                names
+                    .name(*a_species)
+                    .cmp(names.name(*b_species))
+                    .then_with(|| b.games.cmp(&a.games))
+                    .then_with(|| {
+                        (names.name(*a_nature), names.name(*a_spread))
+                            .cmp(&(names.name(*b_nature), names.name(*b_spread)))
+                    })
+            },
+        );
+        self.rows = rows;
+    }
+}
+
+impl Output for SpreadReport {
+    fn to_csv(&mut self) -> String {
+        Itertools::intersperse(
+            self.rows.iter().map(|((species, nature, spread), counts)| {
+                [
+                    self.names.name(*species).to_string(),
+                    self.names.name(*nature).to_string(),
+                    // spreads can contain commas
+                    format!("\"{}\"", self.names.name(*spread)),
+                    counts.games.to_string(),
+                    counts.wins.to_string(),
+                    format_float(counts.winrate()),
+                    format_float(counts.deviations()),
+                ]
+                .join(",")
+            }),
+            String::from("\n"),
+        )
+        .collect()
+    }
+
+    fn to_human_readable(&mut self) -> String {
+        table::blocks_to_text(&self.to_blocks())
+    }
+
+    fn to_blocks(&mut self) -> Vec<Block> {
+        let mut table = Table::new(&[
+            "Pokemon",
+            "Nature",
+            "Spread",
+            "Deviations",
+            "Winrate",
+            "Games",
+            "Wins",
+        ]);
+        for ((species, nature, spread), counts) in &self.rows {
+            table.add_row(vec![
+                self.names.name(*species).to_string(),
+                self.names.name(*nature).to_string(),
+                self.names.name(*spread).to_string(),
+                format_float(counts.deviations()),
+                format!("{}%", format_float(counts.winrate())),
+                counts.games.to_string(),
+                counts.wins.to_string(),
+            ]);
+        }
+        vec![Block::Table(table)]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_spreads() {
+        let json = r#"{"winner":"A","p1":"A","p2":"B","p1team":[{"species":"Garchomp","nature":"Jolly","evs":{"hp":4,"atk":252,"def":0,"spa":0,"spd":0,"spe":252}},{"species":"Blissey","nature":"Bold","evs":{"hp":252,"def":252,"spd":4},"ivs":{"hp":31,"atk":0,"def":31,"spa":31,"spd":31,"spe":31}}],"p2team":[{"species":"Garchomp","nature":"Jolly","evs":{"hp":0,"atk":252,"spe":252}},{"species":"Ditto"}]}"#;
+        let mut report = SpreadReport::empty();
+        for (species, won, (nature, spread)) in
+            pass::team_members(json, &BattleFilter::default(), nature_and_spread)
+        {
+            report.add(&species, &nature, &spread, won);
+        }
+        report.sort();
+
+        assert_eq!(
+            report.to_csv(),
+            "Blissey,Bold,\"HP/Def, 0 Atk IV\",1,1,100.000000,1.000000\n\
+             Ditto,(none),\"no investment\",1,0,0.000000,-1.000000\n\
+             Garchomp,Jolly,\"Atk/Spe\",2,1,50.000000,0.000000"
+        );
+    }
+}
@@ -0,0 +1,298 @@
+/// Saved aggregation state
+///
+/// A state file holds everything needed to produce outputs later, so runs over separate
+/// shards of the input can be combined with `merge`. It's a tab-separated text format:
+/// a version line followed by one record per line.
+use crate::log::{LogAnalysis, LogStats};
+use crate::stats::{PokemonStats, Stats, StatsError};
+use crate::trajectory::{DayRatings, PlayerRatings};
+use itertools::Itertools;
+
+const STATE_HEADER: &str = "randbats-winrates-state\t1";
+
+/// Escapes a text field (a label, species, field name, player or day) so that tabs and newlines
+/// in it can't split the record
+fn escape(field: &str) -> String {
+    let mut escaped = String::with_capacity(field.len());
+    for c in field.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            '\t' => escaped.push_str("\\t"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Reverses `escape`, or returns None for an unknown escape sequence
+fn unescape(field: &str) -> Option<String> {
+    let mut unescaped = String::with_capacity(field.len());
+    let mut chars = field.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+        unescaped.push(match chars.next()? {
+            '\\' => '\\',
+            't' => '\t',
+            'n' => '\n',
+            'r' => '\r',
+            _ => return None,
+        });
+    }
+    Some(unescaped)
+}
+
+impl Stats {
+    pub fn to_state(&self) -> String {
+        let mut lines = vec![
+            STATE_HEADER.to_string(),
+            format!("battles\t{}", self.battles),
+        ];
+        for (label, count) in &self.counters {
+            lines.push(format!("counter\t{}\t{}", escape(label), count));
+        }
+        for (species, s) in &self.pokemon {
+            lines.push(format!(
+                "species\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}",
+                escape(species),
+                s.games,
+                s.wins,
+                s.long_games,
+                s.long_wins,
+                s.rated_games,
+                s.rating_sum,
+                s.weighted_games,
+                s.weighted_wins
+            ));
+        }
+        if !self.log_stats.analyses.is_empty() {
+            lines.push(format!(
+                "log-analyses\t{}",
+                self.log_stats.analyses.iter().join("\t")
+            ));
+            for (species, counts) in &self.log_stats.counts {
+                lines.push(format!(
+                    "log\t{}\t{}",
+                    escape(species),
+                    counts.iter().join("\t")
+                ));
+            }
+        }
+        lines.push(format!("schema-battles\t{}", self.schema.battles));
+        for (field, count) in &self.schema.fields {
+            lines.push(format!("schema-field\t{}\t{}", escape(field), count));
+        }
+        if let Some(player_ratings) = &self.player_ratings {
+            lines.push(String::from("player-ratings"));
+            for (player, days) in &player_ratings.players {
+                for (day, r) in days {
+                    lines.push(format!(
+                        "player-rating\t{}\t{}\t{}\t{}",
+                        escape(player),
+                        escape(day),
+                        r.games,
+                        r.rating_sum
+                    ));
+                }
+            }
+        }
+
+        lines.join("\n") + "\n"
+    }
+
+    pub fn from_state(state: &str) -> Result<Self, StatsError> {
+        let mut lines = state.lines().enumerate();
+        match lines.next() {
+            Some((_, STATE_HEADER)) => {}
+            _ => return Err(StatsError::State(String::from("missing state header"))),
+        }
+
+        let mut stats = Stats::new();
+        for (idx, line) in lines {
+            let error = |message: &str| StatsError::State(format!("line {}: {}", idx + 1, message));
+            let text = |field: Option<&str>, missing: &str| {
+                field
+                    .ok_or_else(|| error(missing))
+                    .and_then(|f| unescape(f).ok_or_else(|| error("invalid escape sequence")))
+            };
+            let number = |field: Option<&str>| {
+                field
+                    .and_then(|f| f.parse::<u32>().ok())
+                    .ok_or_else(|| error("expected a number"))
+            };
+
+            let mut fields = line.split('\t');
+            match fields.next() {
+                Some("battles") => stats.battles = number(fields.next())?,
+                Some("counter") => {
+                    let label = text(fields.next(), "missing label")?;
+                    stats.counters.insert(label, number(fields.next())?);
+                }
+                Some("species") => {
+                    let species = text(fields.next(), "missing species")?;
+                    let float = |field: Option<&str>, default: f64| match field {
+                        Some(f) => f.parse::<f64>().map_err(|_| error("expected a number")),
+                        None => Ok(default),
+                    };
+                    let games = number(fields.next())?;
+                    let wins = number(fields.next())?;
+                    let record = PokemonStats {
+                        games,
+                        wins,
+                        long_games: number(fields.next())?,
+                        long_wins: number(fields.next())?,
+                        // absent from states saved before ratings were tracked
+                        rated_games: fields.next().map_or(Ok(0), |f| number(Some(f)))?,
+                        rating_sum: float(fields.next(), 0.0)?,
+                        // absent from states saved before recency weighting
+                        weighted_games: float(fields.next(), games as f64)?,
+                        weighted_wins: float(fields.next(), wins as f64)?,
+                    };
+                    stats.pokemon.insert(species, record);
+                }
+                Some("log-analyses") => {
+                    let analyses = fields
+                        .map(|f| f.parse::<LogAnalysis>())
+                        .collect::<Result<Vec<_>, _>>()
+                        .map_err(|e| error(&e))?;
+                    stats.log_stats = LogStats::new(&analyses);
+                }
+                Some("log") => {
+                    let species = text(fields.next(), "missing species")?;
+                    let counts = fields
+                        .map(|f| number(Some(f)))
+                        .collect::<Result<Vec<_>, _>>()?;
+                    if counts.len() != stats.log_stats.analyses.len() {
+                        return Err(error("wrong number of log counts"));
+                    }
+                    stats.log_stats.counts.insert(species, counts);
+                }
+                Some("schema-battles") => stats.schema.battles = number(fields.next())?,
+                Some("schema-field") => {
+                    let field = text(fields.next(), "missing field")?;
+                    stats.schema.fields.insert(field, number(fields.next())?);
+                }
+                Some("player-ratings") => stats.player_ratings = Some(PlayerRatings::default()),
+                Some("player-rating") => {
+                    let player = text(fields.next(), "missing player")?;
+                    let day = text(fields.next(), "missing day")?;
+                    let ratings = DayRatings {
+                        games: number(fields.next())?,
+                        rating_sum: fields
+                            .next()
+                            .and_then(|f| f.parse::<f64>().ok())
+                            .ok_or_else(|| error("expected a number"))?,
+                    };
+                    stats
+                        .player_ratings
+                        .get_or_insert_with(PlayerRatings::default)
+                        .players
+                        .entry(player)
+                        .or_default()
+                        .insert(day, ratings);
+                }
+                Some("") | None => {}
+                Some(other) => return Err(error(&format!("unknown record '{}'", other))),
+            }
+        }
+
+        Ok(stats)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stats::{GameResult, Output};
+
+    fn sample_stats() -> Stats {
+        let mut stats = Stats::new();
+        stats.log_stats = LogStats::new(&[LogAnalysis::Leads, LogAnalysis::KOs]);
+        stats.add_game_results(vec![
+            GameResult {
+                species: String::from("Rotom-Fan"),
+                won: true,
+                turns: 25,
+                rating: Some(1234.5),
+            },
+            GameResult {
+                species: String::from("Latios"),
+                won: false,
+                turns: 25,
+                rating: None,
+            },
+        ]);
+        stats.count("Duplicate battles skipped");
+        stats.schema.add(vec![String::from("team.stellar")]);
+        let mut player_ratings = PlayerRatings::default();
+        player_ratings.add("annika", "2024-01-01", 1234.5);
+        stats.player_ratings = Some(player_ratings);
+        stats
+            .log_stats
+            .counts
+            .insert(String::from("Latios"), vec![1, 2]);
+        stats
+    }
+
+    #[test]
+    fn test_state_round_trip() {
+        let mut stats = sample_stats();
+        let state = stats.to_state();
+        let mut restored = Stats::from_state(&state).unwrap();
+
+        assert_eq!(restored.to_state(), state);
+        assert_eq!(restored.to_csv(), stats.to_csv());
+        assert_eq!(restored.log_stats.to_csv(), "Latios,1,2");
+    }
+
+    #[test]
+    fn test_state_escapes_text_fields() {
+        let mut stats = sample_stats();
+        let key = "team.set\tof\nkeys\\";
+        stats.schema.add(vec![String::from(key)]);
+        stats.count("label\twith\ttabs");
+        let state = stats.to_state();
+        assert_eq!(
+            state.lines().count(),
+            sample_stats().to_state().lines().count() + 2
+        );
+
+        let restored = Stats::from_state(&state).unwrap();
+        assert_eq!(restored.schema.fields[key], 1);
+        assert_eq!(restored.counter("label\twith\ttabs"), Some(1));
+        assert_eq!(restored.to_state(), state);
+        assert!(Stats::from_state(&format!("{}\ncounter\tbad\\x\t1", STATE_HEADER)).is_err());
+    }
+
+    #[test]
+    fn test_merge_states() {
+        let mut merged = Stats::from_state(&sample_stats().to_state()).unwrap();
+        merged.merge(Stats::from_state(&sample_stats().to_state()).unwrap());
+
+        assert_eq!(merged.battles, 2);
+        assert_eq!(merged.counter("Duplicate battles skipped"), Some(2));
+        assert!(merged
+            .to_csv()
+            .starts_with("species,games,wins,winrate,deviations\nRotom-Fan,2,2,"));
+        assert_eq!(merged.log_stats.to_csv(), "Latios,2,4");
+        assert_eq!(merged.schema.fields["team.stellar"], 2);
+        assert_eq!(merged.schema.battles, 2);
+        assert_eq!(
+            merged.player_ratings.unwrap().players["annika"]["2024-01-01"],
+            DayRatings {
+                games: 2,
+                rating_sum: 2469.0
+            }
+        );
+    }
+
+    #[test]
+    fn test_invalid_state() {
+        assert!(Stats::from_state("not a state").is_err());
+        assert!(Stats::from_state(&format!("{}\nspecies\tMew\tlots", STATE_HEADER)).is_err());
+    }
+}
@@ -1,25 +1,152 @@
 /// Stats code
-extern crate test;
+use crate::filter::{self, BattleFilter, FormatKind, Sample};
+use crate::locale::Translations;
+use crate::log::{self, LogAnalysis, LogStats, LogTally};
+use crate::ots;
+use crate::schema::{self, SchemaDrift};
+use crate::table::{self, Block, NumberFormat, Paging, Table};
+use crate::trajectory::PlayerRatings;
+use crate::validate::{self, BattleError};
 use indexmap::IndexMap;
-use itertools::Itertools;
-use prettytable::*;
+
+/// The most sides (players) a battle can have, as in free-for-all formats
+pub const MAX_SIDES: usize = 4;
+
+/// Number of decimal places in every formatted float
+const FLOAT_PRECISION: usize = 6;
+
+/// Formats a float with a fixed number of decimal places, so that output is byte-identical
+/// regardless of platform or toolchain (unlike shortest-representation formatting)
+pub fn format_float(value: f64) -> String {
+    // avoid printing "-0.000000"
+    let value = if value == 0.0 { 0.0 } else { value };
+    format!("{:.*}", FLOAT_PRECISION, value)
+}
+
+/// Formats seconds since the Unix epoch as an ISO 8601 UTC timestamp
+pub fn format_timestamp(secs: u64) -> String {
+    let days = (secs / 86_400) as i64;
+    let time = secs % 86_400;
+
+    // civil-from-days conversion (Howard Hinnant's algorithm)
+    let z = days + 719_468;
+    let era = z / 146_097;
+    let day_of_era = z - era * 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        time / 3600,
+        (time / 60) % 60,
+        time % 60
+    )
+}
+
+/// Seconds since the Unix epoch at the start of a day (UTC), the inverse of `format_timestamp`
+pub fn day_start(year: i64, month: i64, day: i64) -> Option<u64> {
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+        return None;
+    }
+
+    // days-from-civil conversion (Howard Hinnant's algorithm)
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let mp = (month + 9) % 12;
+    let day_of_year = (153 * mp + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    let days = era * 146_097 + day_of_era - 719_468;
+    if days < 0 {
+        return None;
+    }
+    Some(days as u64 * 86_400)
+}
+
+/// Seconds since the Unix epoch at the start of a day given as YYYY-MM-DD (UTC)
+pub fn parse_day(day: &str) -> Option<u64> {
+    let mut parts = day.splitn(3, '-').map(|p| p.parse::<i64>().ok());
+    day_start(parts.next()??, parts.next()??, parts.next()??)
+}
 
 pub trait Output {
     fn to_human_readable(&mut self) -> String;
     fn to_csv(&mut self) -> String;
+
+    /// The human-readable output as tables and lines of text, for the formats below; by
+    /// default, the whole output as preformatted text
+    fn to_blocks(&mut self) -> Vec<Block> {
+        vec![Block::Preformatted(self.to_human_readable())]
+    }
+
+    /// GitHub-flavored Markdown, for pasting into GitHub issues and forum posts, with tables as
+    /// pipe tables
+    fn to_markdown(&mut self) -> String {
+        table::blocks_to_markdown(&self.to_blocks())
+    }
+
+    /// BBCode, for posting on Smogon's forums, with tables as `[TABLE]`s
+    fn to_bbcode(&mut self) -> String {
+        table::blocks_to_bbcode(&self.to_blocks())
+    }
+}
+
+/// How ranks are assigned to species with identical deviations
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub enum Ranking {
+    /// Ties share a rank and the following ranks are skipped (1, 2, 2, 4)
+    #[default]
+    Competition,
+    /// Ties share a rank and no ranks are skipped (1, 2, 2, 3)
+    Dense,
+}
+
+impl std::str::FromStr for Ranking {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "competition" => Ok(Ranking::Competition),
+            "dense" => Ok(Ranking::Dense),
+            _ => Err(format!(
+                "unknown ranking '{}' (expected competition or dense)",
+                s
+            )),
+        }
+    }
 }
 
 #[derive(Copy, Clone)]
-struct FinalStats {
+pub(crate) struct FinalStats {
     /// as percentage
-    winrate: f32,
-    deviations: f32,
+    pub(crate) winrate: f64,
+    pub(crate) deviations: f64,
 }
 
 #[derive(Debug)]
 pub enum StatsError {
     Team(String),
     IO(std::io::Error),
+    /// A saved state file couldn't be read
+    State(String),
+    /// A coordinator's work unit failed on every worker that tried it
+    Worker(String),
+    /// The outputs for the bundled self-test dataset didn't match the golden files
+    SelfTest(String),
+    /// A battle was malformed
+    Battle(BattleError),
+    /// Replays couldn't be downloaded
+    Fetch(String),
+    /// The translations file couldn't be read
+    Translations(String),
 }
 
 impl From<String> for StatsError {
@@ -28,6 +155,12 @@ impl From<String> for StatsError {
     }
 }
 
+impl From<BattleError> for StatsError {
+    fn from(err: BattleError) -> StatsError {
+        StatsError::Battle(err)
+    }
+}
+
 impl From<std::io::Error> for StatsError {
     fn from(err: std::io::Error) -> StatsError {
         StatsError::IO(err)
@@ -40,18 +173,63 @@ impl From<std::io::Error> for StatsError {
 //     }
 // }
 
+/// Games that last more than this many turns count as long games
+pub const LONG_GAME_TURNS: u32 = 20;
+
 /// Stores statistics about a pokemon
-#[derive(Copy, Clone, Debug)]
-struct PokemonStats {
-    games: u32,
-    wins: u32,
+#[derive(Copy, Clone, Debug, Default)]
+pub(crate) struct PokemonStats {
+    pub(crate) games: u32,
+    pub(crate) wins: u32,
+    /// Games (and wins) lasting more than `LONG_GAME_TURNS` turns
+    pub(crate) long_games: u32,
+    pub(crate) long_wins: u32,
+    /// Games where the player's rating is known, and the sum of those ratings
+    pub(crate) rated_games: u32,
+    pub(crate) rating_sum: f64,
+    /// Games and wins with each game weighted by its battle's recency; without recency
+    /// weighting, these equal `games` and `wins`
+    pub(crate) weighted_games: f64,
+    pub(crate) weighted_wins: f64,
 }
 
 impl PokemonStats {
+    fn merge(&mut self, other: &PokemonStats) {
+        self.games += other.games;
+        self.wins += other.wins;
+        self.long_games += other.long_games;
+        self.long_wins += other.long_wins;
+        self.rated_games += other.rated_games;
+        self.rating_sum += other.rating_sum;
+        self.weighted_games += other.weighted_games;
+        self.weighted_wins += other.weighted_wins;
+    }
+
+    fn add(&mut self, result: &GameResult, weight: f64) {
+        let wins = if result.won { 1 } else { 0 };
+        self.games += 1;
+        self.wins += wins;
+        self.weighted_games += weight;
+        self.weighted_wins += wins as f64 * weight;
+        if result.turns > LONG_GAME_TURNS {
+            self.long_games += 1;
+            self.long_wins += wins;
+        }
+        if let Some(rating) = result.rating {
+            self.rated_games += 1;
+            self.rating_sum += rating;
+        }
+    }
+
     /// Computes the number of standard deviations from the average
-    fn final_stats(&self) -> FinalStats {
-        let games = self.games as f32;
-        let winrate = (self.wins as f32 / games) * 100.0;
+    pub(crate) fn final_stats(&self) -> FinalStats {
+        // recency weights can be small enough to add up to nothing; fall back to the raw counts
+        let (games, wins) = if self.weighted_games > 0.0 {
+            (self.weighted_games, self.weighted_wins)
+        } else {
+            (self.games as f64, self.wins as f64)
+        };
+        let winrate = (wins / games) * 100.0;
 
         // Standard deviations formula courtesy of pyuk (@pyuk-bot on GitHub)
         let deviations = (winrate - 50.0) * games.sqrt() / 50.0;
@@ -63,18 +241,118 @@ impl PokemonStats {
     }
 }
 
+/// Details about a run that are included in the summary footer
 #[derive(Debug)]
+pub struct RunInfo {
+    pub min_elo: u64,
+    /// Seconds since the Unix epoch
+    pub generated_at: u64,
+}
+
+/// Provenance included at the top of outputs, so published stats can be reproduced
+#[derive(Debug)]
+pub struct Metadata {
+    pub version: String,
+    pub git_commit: String,
+    pub command_line: String,
+    /// Hash of the names and sizes of every input file
+    pub input_fingerprint: String,
+    /// Each analyzed day directory and how many files were read from it; empty when the stats
+    /// come from merged states
+    pub days: Vec<(String, usize)>,
+}
+
+impl Metadata {
+    pub(crate) fn lines(&self) -> Vec<(&'static str, String)> {
+        let mut lines = vec![
+            ("Version", self.version.clone()),
+            ("Git commit", self.git_commit.clone()),
+            ("Command line", self.command_line.clone()),
+            ("Input fingerprint", self.input_fingerprint.clone()),
+        ];
+        for (day, files) in &self.days {
+            lines.push(("Day", format!("{} ({} files)", day, files)));
+        }
+        lines
+    }
+}
+
+/// A side's rating, as read from a battle
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub(crate) enum Rating {
+    Elo(f64),
+    /// The side has no rating at all
+    Unrated,
+    /// The side has a rating, but it isn't a number
+    Unreadable,
+}
+
+/// Where different versions of the server have stored a side's elo, relative to the side ID
+const RATING_PATHS: [&str; 3] = ["rating.elo", "rating", "elo"];
+
+/// What processing one battle produced
+#[derive(Debug, Default)]
+pub struct ProcessedBattle {
+    pub(crate) results: Vec<GameResult>,
+    pub(crate) tallies: Vec<LogTally>,
+    /// Labels of counters to increment, e.g. for problems with the battle's data
+    pub(crate) counters: Vec<&'static str>,
+    /// Fields in the battle that aren't known, see `schema::unknown_fields`
+    pub(crate) unknown_fields: Vec<String>,
+    /// IDs and ratings of the rated players in a battle that contributed results
+    pub(crate) ratings: Vec<(String, f64)>,
+}
+
+#[derive(Clone, Debug)]
 pub struct GameResult {
-    species: String,
-    won: bool,
+    pub(crate) species: String,
+    pub(crate) won: bool,
+    /// Length of the battle
+    pub(crate) turns: u32,
+    /// Elo of the player who had the species
+    pub(crate) rating: Option<f64>,
 }
 
 /// Stores overall statistics
 #[derive(Debug)]
 pub struct Stats {
     /// Pokemon:statistics map
-    pokemon: IndexMap<String, PokemonStats>,
+    pub(crate) pokemon: IndexMap<String, PokemonStats>,
     is_sorted: bool,
+    /// Number of battles that contributed results
+    pub(crate) battles: u32,
+    /// Named counts of battles that were skipped or treated specially, for the summary
+    pub(crate) counters: IndexMap<String, u32>,
+    /// If set, outputs end with a summary of the run
+    pub summary: Option<RunInfo>,
+    /// If set, outputs start with provenance metadata
+    pub metadata: Option<Metadata>,
+    /// Counts from the enabled battle log analyses
+    pub log_stats: LogStats,
+    /// Unknown fields seen in the battles
+    pub(crate) schema: SchemaDrift,
+    /// If set, each player's ratings by day
+    pub player_ratings: Option<PlayerRatings>,
+    /// How games and wins are written in the human-readable output
+    pub number_format: NumberFormat,
+    /// How tied species are ranked in the human-readable output
+    pub ranking: Ranking,
+    /// If set, species with fewer games than this are marked as provisional
+    pub provisional_below: Option<u32>,
+    /// If set, species names in the human-readable output are translated
+    pub translations: Option<Translations>,
+    /// If set, only this sample of the input was analyzed, and outputs say so
+    pub sample: Option<Sample>,
+    /// If set, the human-readable output shows only this page of species
+    pub paging: Option<Paging>,
+    /// If set, species names in the human-readable output are cut to this width
+    pub name_width: Option<usize>,
+}
+
+impl Default for Stats {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Stats {
@@ -82,6 +360,20 @@ impl Stats {
         Self {
             pokemon: IndexMap::new(),
             is_sorted: false,
+            battles: 0,
+            counters: IndexMap::new(),
+            summary: None,
+            metadata: None,
+            log_stats: LogStats::default(),
+            schema: SchemaDrift::default(),
+            player_ratings: None,
+            number_format: NumberFormat::default(),
+            ranking: Ranking::default(),
+            provisional_below: None,
+            translations: None,
+            sample: None,
+            paging: None,
+            name_width: None,
         }
     }
 
@@ -90,67 +382,476 @@ impl Stats {
             self.pokemon.sort_by(|_, a, _, b| {
                 b.final_stats()
                     .deviations
-                    .partial_cmp(&a.final_stats().deviations)
-                    .unwrap()
+                    .total_cmp(&a.final_stats().deviations)
             });
         }
     }
 
-    pub fn process_json(
-        min_elo: u64,
-        json: &str,
-    ) -> Result<Vec<GameResult>, StatsError> {
+    /// The `n` species with the most games, most used first
+    pub(crate) fn most_used(&self, n: usize) -> Vec<&str> {
+        let mut species = self.pokemon.iter().collect::<Vec<_>>();
+        species.sort_by(|(a_species, a), (b_species, b)| {
+            b.games.cmp(&a.games).then_with(|| a_species.cmp(b_species))
+        });
+        species
+            .into_iter()
+            .take(n)
+            .map(|(species, _)| species.as_str())
+            .collect()
+    }
+
+    /// The sides present in a battle: `p1` and `p2`, plus `p3` and `p4` in free-for-alls
+    pub(crate) fn sides(json: &str) -> Vec<String> {
+        (1..=MAX_SIDES)
+            .map(|n| format!("p{}", n))
+            .filter(|side| gjson::get(json, &format!("{}team", side)).exists())
+            .collect()
+    }
+
+    /// Reads a side's elo, whether it's stored as a number or a numeric string
+    pub(crate) fn rating(json: &str, side: &str) -> Rating {
+        let mut has_rating = false;
+        for path in RATING_PATHS.iter() {
+            let path = format!("{}{}", side, path);
+            let value = gjson::get(json, &path);
+            match value.kind() {
+                gjson::Kind::Number => return Rating::Elo(value.f64()),
+                gjson::Kind::String => match value.str().trim().parse::<f64>() {
+                    Ok(elo) if elo.is_finite() => return Rating::Elo(elo),
+                    _ => has_rating = true,
+                },
+                // a rating object without a usable elo in it
+                gjson::Kind::Object => has_rating = true,
+                _ => {}
+            }
+        }
+        if has_rating {
+            Rating::Unreadable
+        } else {
+            Rating::Unrated
+        }
+    }
+
+    pub fn process_json(min_elo: u64, json: &str) -> Result<Vec<GameResult>, StatsError> {
+        validate::check_battle(json)?;
+        Ok(Stats::results_by_side(min_elo, json)
+            .into_iter()
+            .flatten()
+            .collect())
+    }
+
+    /// Each side's results, in side order
+    pub(crate) fn results_by_side(min_elo: u64, json: &str) -> Vec<Vec<GameResult>> {
+        let sides = Stats::sides(json);
+
+        let ratings = sides
+            .iter()
+            .map(|side| match Stats::rating(json, side) {
+                Rating::Elo(elo) => Some(elo),
+                Rating::Unrated | Rating::Unreadable => None,
+            })
+            .collect::<Vec<_>>();
+
         // ELO check
-        for elo_property in ["p1rating.elo", "p2rating.elo"].iter() {
-            if (gjson::get(json, elo_property).f32() as u64) < min_elo {
+        for rating in &ratings {
+            if (rating.unwrap_or(0.0) as u64) < min_elo {
                 // ignore
-                return Ok(vec![]);
+                return vec![];
             }
         }
 
         let mut results = vec![];
+        let winner = gjson::get(json, "winner");
+        let turns = gjson::get(json, "turns").u32();
+
+        // the winning side's species each get a win; every other side's get a loss
+        for (side, rating) in sides.iter().zip(ratings) {
+            let won = gjson::get(json, side) == winner;
+
+            let species_path = format!("{}team.#.species", side);
+            let species_list = gjson::get(json, &species_path);
+            let side_results = species_list
+                .array()
+                .iter()
+                .map(|species| GameResult {
+                    species: Stats::normalize_species(species.str()),
+                    won,
+                    turns,
+                    rating,
+                })
+                .collect();
+            results.push(side_results);
+        }
+        results
+    }
+
+    /// Filters and parses one battle, running the given log analyses on it
+    pub fn process_battle(
+        json: &str,
+        min_elo: u64,
+        log_analyses: &[LogAnalysis],
+        filter: &BattleFilter,
+    ) -> Result<ProcessedBattle, StatsError> {
+        match validate::check_battle(json) {
+            Ok(()) => {}
+            Err(BattleError::NoTeams) => match ots::with_team_sheets(json) {
+                Some(recovered) => {
+                    let mut battle =
+                        Stats::process_battle(&recovered, min_elo, log_analyses, filter)?;
+                    battle.counters.push("Battles recovered from team sheets");
+                    return Ok(battle);
+                }
+                None => return Err(BattleError::NoTeams.into()),
+            },
+            Err(e) => return Err(e.into()),
+        }
+        let mut battle = ProcessedBattle {
+            unknown_fields: schema::unknown_fields(json),
+            ..ProcessedBattle::default()
+        };
+        if !filter.accepts(json) {
+            return Ok(battle);
+        }
+        let sides = Stats::sides(json);
+        let player_values = sides
+            .iter()
+            .map(|side| gjson::get(json, side))
+            .collect::<Vec<_>>();
+        let players = player_values.iter().map(|p| p.str()).collect::<Vec<_>>();
+        if !filter.accepts_players(&players) {
+            battle.counters.push("Battles excluded by player lists");
+            return Ok(battle);
+        }
+        if filter.involves_suspected_bot(&players) {
+            battle.counters.push("Battles with suspected bots excluded");
+            return Ok(battle);
+        }
+        let has_guest = players.iter().any(|player| filter::is_guest(player));
+        if has_guest {
+            battle.counters.push("Battles with guest players");
+            if filter.exclude_guests {
+                battle.counters.push("Guest battles excluded");
+                return Ok(battle);
+            }
+        }
+
+        if filter::is_abandoned_early(json) {
+            battle.counters.push("Battles abandoned early");
+            if filter.exclude_abandoned {
+                battle.counters.push("Abandoned battles excluded");
+                return Ok(battle);
+            }
+        }
+
+        let ratings = sides
+            .iter()
+            .map(|side| Stats::rating(json, side))
+            .collect::<Vec<_>>();
+        if ratings.contains(&Rating::Unreadable) {
+            battle.counters.push("Battles with unreadable ratings");
+        }
+        let unrated = !ratings.is_empty() && ratings.iter().all(|r| *r == Rating::Unrated);
+
+        let include_unrated = unrated && filter.include_unrated;
+        let mut results = Stats::results_by_side(if include_unrated { 0 } else { min_elo }, json);
+        if include_unrated {
+            battle.counters.push("Unrated battles included");
+        } else if unrated && results.is_empty() {
+            battle.counters.push("Unrated battles excluded");
+        }
 
-        // (indices of parsed JSON)
-        for (species_list_property, player_property) in [("p1team.#.species", "p1"), ("p2team.#.species", "p2")].iter() {
-            // json[16] = the winner
-            let won = gjson::get(json, player_property) == gjson::get(json, "winner");
+        if filter.format_kind == FormatKind::Standard {
+            // only count the team members that were sent out
+            if let Some(sent_out) = log::sent_out(json) {
+                for (side, side_results) in sides.iter().zip(results.iter_mut()) {
+                    let idx = side[1..].parse::<usize>().map_or(0, |n| n - 1);
+                    side_results.retain(|r| sent_out[idx].contains(&r.species));
+                }
+            }
+        }
+
+        if let Some(opponent_species) = &filter.opponent_species {
+            // only count sides facing a team with the species
+            let has_species = results
+                .iter()
+                .map(|side| side.iter().any(|r| &r.species == opponent_species))
+                .collect::<Vec<_>>();
+            results = results
+                .into_iter()
+                .enumerate()
+                .filter(|(idx, _)| {
+                    has_species
+                        .iter()
+                        .enumerate()
+                        .any(|(other, has)| other != *idx && *has)
+                })
+                .map(|(_, side)| side)
+                .collect();
+        }
+        battle.results = results.into_iter().flatten().collect();
+        // battles filtered out by elo have no results, and shouldn't count towards log stats either
+        if !battle.results.is_empty() {
+            battle.tallies = log::analyze_log(json, log_analyses);
+            for (player, rating) in players.iter().zip(&ratings) {
+                if let Rating::Elo(elo) = rating {
+                    battle.ratings.push((filter.player_id(player), *elo));
+                }
+            }
+        }
+        Ok(battle)
+    }
 
-            let species_list = gjson::get(json, species_list_property);
-            for species in species_list.array() {
-                results.push(GameResult { species: Stats::normalize_species(species.str()), won });
+    /// Adds the output of `process_battle` for a battle from the day directory `day`,
+    /// with its games counting `weight` times towards winrates
+    pub fn add_battle_on(&mut self, day: &str, weight: f64, battle: ProcessedBattle) {
+        if let Some(player_ratings) = &mut self.player_ratings {
+            for (player, rating) in &battle.ratings {
+                player_ratings.add(player, day, *rating);
             }
         }
-        Ok(results)
+        self.add_weighted_battle(battle, weight);
+    }
+
+    /// Adds the output of `process_battle`
+    pub fn add_battle(&mut self, battle: ProcessedBattle) {
+        self.add_weighted_battle(battle, 1.0);
+    }
+
+    fn add_weighted_battle(&mut self, battle: ProcessedBattle, weight: f64) {
+        self.add_weighted_results(battle.results, weight);
+        self.log_stats.add_tallies(battle.tallies);
+        for label in battle.counters {
+            self.count(label);
+        }
+        self.schema.add(battle.unknown_fields);
     }
 
     pub fn add_game_results(&mut self, results: Vec<GameResult>) {
+        self.add_weighted_results(results, 1.0);
+    }
+
+    fn add_weighted_results(&mut self, results: Vec<GameResult>, weight: f64) {
         if results.is_empty() {
             return;
         }
 
         self.is_sorted = false; // we're adding data so it isn't sorted anymore
+        self.battles += 1;
         for result in results {
-            let wins = if result.won { 1 } else { 0 };
             match self.pokemon.get_mut(&result.species) {
-                Some(s) => {
-                    s.wins += wins;
-                    s.games += 1;
-                }
+                Some(s) => s.add(&result, weight),
                 None => {
-                    self.pokemon
-                        .insert(result.species, PokemonStats { games: 1, wins });
+                    let mut stats = PokemonStats::default();
+                    stats.add(&result, weight);
+                    self.pokemon.insert(result.species, stats);
                 }
             };
         }
     }
 
-    fn normalize_species(species: &str) -> String {
+    /// Counts a battle that couldn't be analyzed, saying where it came from and why
+    pub fn skip_malformed(&mut self, source: impl std::fmt::Display, error: StatsError) {
+        let label = match &error {
+            StatsError::Battle(BattleError::Empty) => "Empty files skipped",
+            StatsError::Battle(BattleError::Truncated) => "Truncated files skipped",
+            StatsError::Battle(BattleError::Unreadable(_)) => "Unreadable files skipped",
+            _ => "Malformed battles skipped",
+        };
+        match error {
+            StatsError::Battle(e) => eprintln!("Skipping {}: {}", source, e),
+            e => eprintln!("Skipping {}: {:?}", source, e),
+        }
+        self.count(label);
+    }
+
+    /// Increments a named counter, which will be listed in the summary
+    pub fn count(&mut self, label: &str) {
+        match self.counters.get_mut(label) {
+            Some(count) => *count += 1,
+            None => {
+                self.counters.insert(label.to_string(), 1);
+            }
+        }
+    }
+
+    /// Adds everything from another set of statistics (such as another shard's) into this one
+    pub fn merge(&mut self, other: Stats) {
+        self.is_sorted = false;
+        self.battles += other.battles;
+        for (species, stats) in other.pokemon {
+            self.pokemon.entry(species).or_default().merge(&stats);
+        }
+        for (label, count) in other.counters {
+            *self.counters.entry(label).or_insert(0) += count;
+        }
+        self.log_stats.merge(other.log_stats);
+        self.schema.merge(other.schema);
+        if let Some(player_ratings) = other.player_ratings {
+            self.player_ratings
+                .get_or_insert_with(PlayerRatings::default)
+                .merge(player_ratings);
+        }
+        debug_assert_eq!(self.check_invariants(), Ok(()));
+    }
+
+    /// Checks the invariants that hold however the statistics were built up
+    /// (by adding battles one by one, or by merging shards in any order)
+    pub fn check_invariants(&self) -> Result<(), String> {
+        let mut total_games: u64 = 0;
+        for (species, s) in self.records() {
+            let violated = if s.wins > s.games {
+                "wins > games"
+            } else if s.long_games > s.games {
+                "long games > games"
+            } else if s.long_wins > s.long_games || s.long_wins > s.wins {
+                "long wins > long games or wins"
+            } else if s.rated_games > s.games {
+                "rated games > games"
+            } else if s.weighted_wins > s.weighted_games {
+                "weighted wins > weighted games"
+            } else {
+                total_games += s.games as u64;
+                continue;
+            };
+            return Err(format!("{}: {} ({:?})", species, violated, s));
+        }
+
+        // every counted battle contributed at least one game
+        if total_games < self.battles as u64 {
+            return Err(format!(
+                "{} battles but only {} games",
+                self.battles, total_games
+            ));
+        }
+        Ok(())
+    }
+
+    pub fn counter(&self, label: &str) -> Option<u32> {
+        self.counters.get(label).copied()
+    }
+
+    /// Iterates over each species' statistics, in no particular order
+    pub(crate) fn records(&self) -> impl Iterator<Item = (&str, &PokemonStats)> {
+        self.pokemon
+            .iter()
+            .map(|(species, stats)| (species.as_str(), stats))
+    }
+
+    pub(crate) fn get(&self, species: &str) -> Option<&PokemonStats> {
+        self.pokemon.get(species)
+    }
+
+    /// The name of a species as shown in human-readable outputs
+    pub(crate) fn display_name<'a>(&'a self, species: &'a str) -> &'a str {
+        match &self.translations {
+            Some(translations) => translations.name(species),
+            None => species,
+        }
+    }
+
+    /// Whether a species has too few games for its winrate to be trusted
+    pub(crate) fn is_provisional(&self, stats: &PokemonStats) -> bool {
+        self.provisional_below
+            .is_some_and(|threshold| stats.games < threshold)
+    }
+
+    /// The rank of each of the given (sorted) species among themselves
+    pub(crate) fn ranks(&self, pokemon: &[(&String, &PokemonStats)]) -> Vec<usize> {
+        let mut ranks = vec![];
+        let mut rank = 0;
+        let mut previous_deviations = None;
+        for (idx, (_, stats)) in pokemon.iter().enumerate() {
+            let deviations = stats.final_stats().deviations;
+            if previous_deviations != Some(deviations) {
+                rank = match self.ranking {
+                    Ranking::Competition => idx + 1,
+                    Ranking::Dense => rank + 1,
+                };
+                previous_deviations = Some(deviations);
+            }
+            ranks.push(rank);
+        }
+        ranks
+    }
+
+    /// A table of the given (sorted) species, ranked among themselves
+    fn ranked_table(&self, pokemon: &[(&String, &PokemonStats)]) -> Table {
+        let mut table = Table::new(&["Rank", "Pokemon", "Deviations", "Winrate", "Games", "Wins"]);
+
+        for ((pokemon, stats), rank) in pokemon.iter().zip(self.ranks(pokemon)) {
+            let fstats = stats.final_stats();
+            let deviations = format_float(fstats.deviations);
+            let mut winrate = format_float(fstats.winrate);
+            winrate.push('%');
+
+            table.add_row(vec![
+                rank.to_string(),
+                self.display_name(pokemon).to_string(),
+                deviations,
+                winrate,
+                self.number_format.format(stats.games),
+                self.number_format.format(stats.wins),
+            ]);
+        }
+        if let Some(width) = self.name_width {
+            table.truncate_column(1, width);
+        }
+        table
+    }
+
+    /// Label/value pairs describing the whole run, if a summary was requested
+    pub(crate) fn summary_lines(&self) -> Vec<(&str, String)> {
+        let info = match &self.summary {
+            Some(i) => i,
+            None => return vec![],
+        };
+
+        let (games, wins) = self
+            .pokemon
+            .values()
+            .fold((0, 0), |(g, w), s| (g + s.games as u64, w + s.wins as u64));
+        let average_winrate = if games == 0 {
+            0.0
+        } else {
+            wins as f64 / games as f64 * 100.0
+        };
+
+        let mut lines = vec![
+            ("Total battles", self.battles.to_string()),
+            ("Distinct species", self.pokemon.len().to_string()),
+            (
+                "Average winrate",
+                format!("{}%", format_float(average_winrate)),
+            ),
+            ("Minimum elo", info.min_elo.to_string()),
+            ("Generated at", format_timestamp(info.generated_at)),
+        ];
+        if let Some(sample) = self.sample {
+            lines.push((
+                "Estimated battles in full input",
+                ((self.battles as f64 / sample.fraction).round() as u64).to_string(),
+            ));
+        }
+        lines.extend(
+            self.counters
+                .iter()
+                .map(|(label, count)| (label.as_str(), count.to_string())),
+        );
+        lines
+    }
+
+    pub(crate) fn normalize_species(species: &str) -> String {
         if species.starts_with("Pikachu-") {
             String::from("Pikachu")
         } else if species.starts_with("Unown-") {
             String::from("Unown")
         } else if species == "Gastrodon-East" {
             String::from("Gastrodon")
+        } else if species == "Shellos-East" {
+            String::from("Shellos")
+        } else if species.starts_with("Burmy-") {
+            // Burmy's cloak is cosmetic; Wormadam's formes aren't
+            String::from("Burmy")
         } else if species == "Magearna-Original" {
             String::from("Magearna")
         } else if species == "Genesect-Douse" {
@@ -177,52 +878,97 @@ impl Stats {
     }
 }
 
+/// The CSV output's header row, before any optional columns
+const CSV_COLUMNS: [&str; 5] = ["species", "games", "wins", "winrate", "deviations"];
+
 impl Output for Stats {
     fn to_csv(&mut self) -> String {
         self.sort();
 
-        Itertools::intersperse(
-            self.pokemon.iter().map(|(pokemon, stats)| {
-                let fstats = stats.final_stats();
-                [
-                    pokemon.to_string(),
-                    stats.games.to_string(),
-                    stats.wins.to_string(),
-                    fstats.winrate.to_string(),
-                    fstats.deviations.to_string(),
-                ]
-                .join(",")
-            }),
-            String::from("\n"),
-        )
-        .collect()
+        let mut header = match &self.metadata {
+            Some(metadata) => metadata
+                .lines()
+                .into_iter()
+                .map(|(label, value)| format!("# {},{}\n", label, value))
+                .collect(),
+            None => String::new(),
+        };
+        if let Some(sample) = self.sample {
+            header.push_str(&format!("# Sampled,{}\n", sample));
+        }
+
+        let mut writer = csv::Writer::from_writer(header.into_bytes());
+        let mut columns = CSV_COLUMNS.to_vec();
+        if self.provisional_below.is_some() {
+            columns.push("provisional");
+        }
+        writer.write_record(&columns).unwrap();
+        for (pokemon, stats) in &self.pokemon {
+            let fstats = stats.final_stats();
+            let mut fields = vec![
+                pokemon.to_string(),
+                stats.games.to_string(),
+                stats.wins.to_string(),
+                format_float(fstats.winrate),
+                format_float(fstats.deviations),
+            ];
+            if self.provisional_below.is_some() {
+                fields.push(self.is_provisional(stats).to_string());
+            }
+            writer.write_record(&fields).unwrap();
+        }
+        // writing to a Vec can't fail, and every record is valid UTF-8
+        let mut csv = String::from_utf8(writer.into_inner().unwrap()).unwrap();
+        csv.pop();
+
+        csv + &self
+            .summary_lines()
+            .into_iter()
+            .map(|(label, value)| format!("\n# {},{}", label, value))
+            .collect::<String>()
     }
 
     fn to_human_readable(&mut self) -> String {
-        let mut table = table!(["Rank", "Pokemon", "Deviations", "Winrate", "Games", "Wins"]);
-        let mut cur_rank = 1;
+        table::blocks_to_text(&self.to_blocks())
+    }
 
+    fn to_blocks(&mut self) -> Vec<Block> {
         self.sort();
 
-        for (pokemon, stats) in &self.pokemon {
-            let fstats = stats.final_stats();
+        let (provisional, established): (Vec<_>, Vec<_>) = self
+            .pokemon
+            .iter()
+            .partition(|(_, stats)| self.is_provisional(stats));
 
-            let deviations = fstats.deviations.to_string();
-            let mut winrate = fstats.winrate.to_string();
-            winrate.push('%');
+        let mut blocks = vec![];
+        if let Some(metadata) = &self.metadata {
+            for (label, value) in metadata.lines() {
+                blocks.push(Block::Text(format!("{}: {}", label, value)));
+            }
+        }
+        if let Some(sample) = self.sample {
+            blocks.push(Block::Text(format!("Sampled: {}", sample)));
+        }
 
-            table.add_row(row![
-                cur_rank,
-                pokemon,
-                deviations,
-                winrate,
-                stats.games,
-                stats.wins
-            ]);
-            cur_rank += 1;
+        let mut established = self.ranked_table(&established);
+        let footer = self.paging.map(|paging| established.paginate(paging));
+        blocks.push(Block::Table(established));
+        if let Some(footer) = footer {
+            blocks.push(Block::Text(footer));
+        }
+        if !provisional.is_empty() {
+            blocks.push(Block::Text(String::new()));
+            blocks.push(Block::Text(format!(
+                "Provisional (fewer than {} games):",
+                self.provisional_below.unwrap_or(0)
+            )));
+            blocks.push(Block::Table(self.ranked_table(&provisional)));
         }
 
-        table.to_string()
+        for (label, value) in self.summary_lines() {
+            blocks.push(Block::Text(format!("{}: {}", label, value)));
+        }
+        blocks
     }
 }
 
@@ -243,6 +989,461 @@ mod tests {
         }
     }
 
+    /// Random game results, from a seeded xorshift generator so failures are reproducible
+    fn random_results(seed: u64, battles: usize) -> Vec<Vec<GameResult>> {
+        let mut state = seed | 1;
+        let mut next = || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state as u32
+        };
+        let species = ["Mew", "Ditto", "Blissey", "Dragapult", "Pikachu-Sinnoh"];
+
+        (0..battles)
+            .map(|_| {
+                (0..next() % 4)
+                    .map(|_| GameResult {
+                        species: species[next() as usize % species.len()].to_string(),
+                        won: next() % 2 == 0,
+                        turns: next() % 40,
+                        rating: if next() % 3 == 0 {
+                            None
+                        } else {
+                            Some((1000 + next() % 1000) as f64)
+                        },
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    fn stats_from(results: &[Vec<GameResult>]) -> Stats {
+        let mut stats = Stats::new();
+        for battle in results {
+            stats.add_game_results(battle.clone());
+        }
+        stats
+    }
+
+    /// Every record, sorted by species, in a form that can be compared exactly
+    fn snapshot(stats: &Stats) -> Vec<(String, [u32; 5], f64)> {
+        let mut records = stats
+            .records()
+            .map(|(species, s)| {
+                (
+                    species.to_string(),
+                    [s.games, s.wins, s.long_games, s.long_wins, s.rated_games],
+                    s.rating_sum,
+                )
+            })
+            .collect::<Vec<_>>();
+        records.sort_by(|a, b| a.0.cmp(&b.0));
+        records
+    }
+
+    #[test]
+    fn test_merge_properties() {
+        for seed in 0..200 {
+            let results = random_results(seed, 30);
+            let (a, b) = results.split_at(seed as usize % 30);
+            let whole = stats_from(&results);
+            assert_eq!(whole.check_invariants(), Ok(()));
+
+            // merging shards in either order loses nothing compared to a single pass
+            let mut ab = stats_from(a);
+            ab.merge(stats_from(b));
+            let mut ba = stats_from(b);
+            ba.merge(stats_from(a));
+            assert_eq!(snapshot(&ab), snapshot(&ba), "seed {}", seed);
+            assert_eq!(snapshot(&ab), snapshot(&whole), "seed {}", seed);
+            assert_eq!(ab.battles, whole.battles);
+
+            // merging into an empty Stats is the identity
+            let mut empty = Stats::new();
+            empty.merge(stats_from(&results));
+            assert_eq!(snapshot(&empty), snapshot(&whole));
+        }
+    }
+
+    #[test]
+    fn test_check_invariants() {
+        let mut stats = stats_from(&random_results(1, 10));
+        assert_eq!(stats.check_invariants(), Ok(()));
+        stats.pokemon.values_mut().next().unwrap().wins += 1_000;
+        assert!(stats
+            .check_invariants()
+            .unwrap_err()
+            .contains("wins > games"));
+
+        let mut stats = Stats::new();
+        stats.battles = 1;
+        assert!(stats.check_invariants().is_err());
+    }
+
+    #[test]
+    fn test_format_float() {
+        assert_eq!(format_float(100.0), "100.000000");
+        assert_eq!(format_float(1.0 / 3.0), "0.333333");
+        assert_eq!(format_float(-0.0), "0.000000");
+        assert_eq!(format_float(-31.6227766), "-31.622777");
+    }
+
+    #[test]
+    fn test_process_ffa_json() {
+        let json = r#"{"winner":"C","p1":"A","p2":"B","p3":"C","p4":"D","p1team":[{"species":"Pikachu-Alola"}],"p2team":[{"species":"Mew"}],"p3team":[{"species":"Mewtwo"},{"species":"Ditto"}],"p4team":[{"species":"Eevee"}],"p1rating":{"elo":1100},"p2rating":{"elo":1200},"p3rating":{"elo":1300},"p4rating":{"elo":1400}}"#;
+        let results = Stats::process_json(1050, json).unwrap();
+        let won = results
+            .iter()
+            .map(|r| (r.species.as_str(), r.won))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            won,
+            vec![
+                ("Pikachu", false),
+                ("Mew", false),
+                ("Mewtwo", true),
+                ("Ditto", true),
+                ("Eevee", false)
+            ]
+        );
+
+        assert!(Stats::process_json(1150, json).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_lenient_rating() {
+        let rating = |json: &str| Stats::rating(json, "p1");
+        assert_eq!(rating(r#"{"p1rating":{"elo":1234}}"#), Rating::Elo(1234.0));
+        assert_eq!(
+            rating(r#"{"p1rating":{"elo":"1234.56"}}"#),
+            Rating::Elo(1234.56)
+        );
+        assert_eq!(rating(r#"{"p1rating":"1300"}"#), Rating::Elo(1300.0));
+        assert_eq!(rating(r#"{"p1elo":1400}"#), Rating::Elo(1400.0));
+        assert_eq!(rating(r#"{"p1rating":{"elo":"high"}}"#), Rating::Unreadable);
+        assert_eq!(rating(r#"{"p1rating":{}}"#), Rating::Unreadable);
+        assert_eq!(rating(r#"{"p2rating":{"elo":1234}}"#), Rating::Unrated);
+
+        let json = r#"{"winner":"A","p1":"A","p2":"B","p1team":[{"species":"Mew"}],"p2team":[{"species":"Ditto"}],"p1rating":{"elo":"1200"},"p2rating":{"elo":"?"}}"#;
+        let battle = Stats::process_battle(json, 0, &[], &BattleFilter::default()).unwrap();
+        assert_eq!(battle.results.len(), 2);
+        assert_eq!(battle.counters, vec!["Battles with unreadable ratings"]);
+    }
+
+    #[test]
+    fn test_exclude_guests() {
+        let json = r#"{"winner":"A","p1":"A","p2":"Guest 12345678","p1team":[{"species":"Mew"}],"p2team":[{"species":"Ditto"}]}"#;
+        let battle = Stats::process_battle(json, 0, &[], &BattleFilter::default()).unwrap();
+        assert_eq!(battle.results.len(), 2);
+        assert_eq!(battle.counters, vec!["Battles with guest players"]);
+
+        let filter = BattleFilter {
+            exclude_guests: true,
+            ..BattleFilter::default()
+        };
+        let battle = Stats::process_battle(json, 0, &[], &filter).unwrap();
+        assert!(battle.results.is_empty());
+        assert_eq!(
+            battle.counters,
+            vec!["Battles with guest players", "Guest battles excluded"]
+        );
+    }
+
+    #[test]
+    fn test_opponent_species() {
+        let json = r#"{"winner":"A","p1":"A","p2":"B","p1team":[{"species":"Mew"},{"species":"Eevee"}],"p2team":[{"species":"Ditto"},{"species":"Mew"}]}"#;
+        let filter = |species: &str| BattleFilter {
+            opponent_species: Some(species.to_string()),
+            ..BattleFilter::default()
+        };
+        let species = |battle: ProcessedBattle| {
+            battle
+                .results
+                .into_iter()
+                .map(|r| r.species)
+                .collect::<Vec<_>>()
+        };
+
+        let battle = Stats::process_battle(json, 0, &[], &filter("Ditto")).unwrap();
+        assert_eq!(species(battle), vec!["Mew", "Eevee"]);
+        let battle = Stats::process_battle(json, 0, &[], &filter("Mew")).unwrap();
+        assert_eq!(species(battle), vec!["Mew", "Eevee", "Ditto", "Mew"]);
+        let battle = Stats::process_battle(json, 0, &[], &filter("Pikachu")).unwrap();
+        assert!(battle.results.is_empty());
+    }
+
+    #[test]
+    fn test_normalize_species() {
+        // cosmetic formes from generation 4
+        assert_eq!(Stats::normalize_species("Shellos-East"), "Shellos");
+        assert_eq!(Stats::normalize_species("Gastrodon-East"), "Gastrodon");
+        assert_eq!(Stats::normalize_species("Burmy-Trash"), "Burmy");
+        assert_eq!(Stats::normalize_species("Unown-Question"), "Unown");
+        assert_eq!(Stats::normalize_species("Wormadam-Trash"), "Wormadam-Trash");
+        assert_eq!(Stats::normalize_species("Rotom-Wash"), "Rotom-Wash");
+    }
+
+    #[test]
+    fn test_format_kind() {
+        let json = r#"{"winner":"A","p1":"A","p2":"B","p1team":[{"species":"Mew"},{"species":"Eevee"}],"p2team":[{"species":"Ditto"},{"species":"Pikachu-Alola"}],"log":["|switch|p1a: Mew|Mew|100/100","|switch|p2a: Pika|Pikachu-Alola, L50, M|100/100","|turn|1"]}"#;
+        let species = |kind: FormatKind| {
+            let filter = BattleFilter {
+                format_kind: kind,
+                ..BattleFilter::default()
+            };
+            Stats::process_battle(json, 0, &[], &filter)
+                .unwrap()
+                .results
+                .into_iter()
+                .map(|r| r.species)
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            species(FormatKind::Random),
+            vec!["Mew", "Eevee", "Ditto", "Pikachu"]
+        );
+        assert_eq!(species(FormatKind::Standard), vec!["Mew", "Pikachu"]);
+    }
+
+    #[test]
+    fn test_include_unrated() {
+        let json = r#"{"winner":"A","p1":"A","p2":"B","p1team":[{"species":"Mew"}],"p2team":[{"species":"Ditto"}]}"#;
+        let battle = Stats::process_battle(json, 1050, &[], &BattleFilter::default()).unwrap();
+        assert!(battle.results.is_empty());
+        assert_eq!(battle.counters, vec!["Unrated battles excluded"]);
+
+        let filter = BattleFilter {
+            include_unrated: true,
+            ..BattleFilter::default()
+        };
+        let battle = Stats::process_battle(json, 1050, &[], &filter).unwrap();
+        assert_eq!(battle.results.len(), 2);
+        assert_eq!(battle.counters, vec!["Unrated battles included"]);
+        assert!(battle.ratings.is_empty());
+    }
+
+    #[test]
+    fn test_player_ratings() {
+        let json = r#"{"winner":"A","p1":"Annika","p2":"Zarel","p1team":[{"species":"Mew"}],"p2team":[{"species":"Ditto"}],"p1rating":{"elo":1100},"p2rating":{"elo":1200.5}}"#;
+        let battle = Stats::process_battle(json, 1050, &[], &BattleFilter::default()).unwrap();
+        assert_eq!(
+            battle.ratings,
+            vec![
+                (String::from("annika"), 1100.0),
+                (String::from("zarel"), 1200.5)
+            ]
+        );
+
+        let mut stats = Stats::new();
+        stats.player_ratings = Some(PlayerRatings::default());
+        stats.add_battle_on("2024-01-01", 1.0, battle);
+        assert_eq!(
+            stats.player_ratings.unwrap().to_csv(),
+            "annika,2024-01-01,1,1100.000000\nzarel,2024-01-01,1,1200.500000"
+        );
+    }
+
+    #[test]
+    fn test_parse_day() {
+        assert_eq!(parse_day("1970-01-01"), Some(0));
+        assert_eq!(parse_day("2024-06-01"), Some(1_717_200_000));
+        assert_eq!(
+            &format_timestamp(parse_day("2000-02-29").unwrap())[..10],
+            "2000-02-29"
+        );
+        assert_eq!(parse_day("2024-13-01"), None);
+        assert_eq!(parse_day("yesterday"), None);
+    }
+
+    #[test]
+    fn test_format_timestamp() {
+        assert_eq!(format_timestamp(0), "1970-01-01T00:00:00Z");
+        assert_eq!(format_timestamp(951_782_400), "2000-02-29T00:00:00Z");
+        assert_eq!(format_timestamp(1_632_906_061), "2021-09-29T09:01:01Z");
+    }
+
+    #[test]
+    fn test_summary() {
+        let mut stats = Stats::new();
+        add_records(&mut stats, 3);
+        stats.summary = Some(RunInfo {
+            min_elo: 1050,
+            generated_at: 0,
+        });
+
+        assert!(stats.to_csv().ends_with(
+            "\n# Total battles,3\n# Distinct species,12\n# Average winrate,50.000000%\n# Minimum elo,1050\n# Generated at,1970-01-01T00:00:00Z"
+        ));
+        assert!(stats
+            .to_human_readable()
+            .ends_with("+\nTotal battles: 3\nDistinct species: 12\nAverage winrate: 50.000000%\nMinimum elo: 1050\nGenerated at: 1970-01-01T00:00:00Z\n"));
+    }
+
+    #[test]
+    fn test_sampled() {
+        let mut stats = Stats::new();
+        add_records(&mut stats, 3);
+        stats.sample = Some(Sample { fraction: 0.1 });
+        stats.summary = Some(RunInfo {
+            min_elo: 1050,
+            generated_at: 0,
+        });
+
+        let csv = stats.to_csv();
+        assert!(csv.starts_with(
+            "# Sampled,10.00% of files\nspecies,games,wins,winrate,deviations\nRotom-Fan,"
+        ));
+        assert!(csv.ends_with("\n# Estimated battles in full input,30"));
+        assert!(stats
+            .to_human_readable()
+            .starts_with("Sampled: 10.00% of files\n+"));
+    }
+
+    #[test]
+    fn test_metadata() {
+        let mut stats = Stats::new();
+        add_records(&mut stats, 1);
+        stats.metadata = Some(Metadata {
+            version: String::from("0.3.0"),
+            git_commit: String::from("abc1234"),
+            command_line: String::from("randbats-winrates --minimum-elo 1050"),
+            input_fingerprint: String::from("0123456789abcdef"),
+            days: vec![(String::from("2024-01-01"), 3)],
+        });
+
+        assert!(stats.to_csv().starts_with(
+            "# Version,0.3.0\n# Git commit,abc1234\n# Command line,randbats-winrates --minimum-elo 1050\n# Input fingerprint,0123456789abcdef\n# Day,2024-01-01 (3 files)\nspecies,games,wins,winrate,deviations\nRotom-Fan,"
+        ));
+        assert!(stats.to_human_readable().starts_with("Version: 0.3.0\n"));
+    }
+
+    #[test]
+    fn test_tied_ranks() {
+        let mut stats = Stats::new();
+        for (species, won) in [("A", true), ("B", true), ("C", false), ("D", false)].iter() {
+            stats.add_game_results(vec![GameResult {
+                species: species.to_string(),
+                won: *won,
+                turns: 1,
+                rating: None,
+            }]);
+        }
+        stats.add_game_results(vec![GameResult {
+            species: String::from("D"),
+            won: false,
+            turns: 1,
+            rating: None,
+        }]);
+
+        let ranks = |stats: &mut Stats| {
+            stats
+                .to_human_readable()
+                .lines()
+                .filter(|line| line.starts_with("| ") && !line.starts_with("| Rank"))
+                .map(|line| line.split('|').nth(1).unwrap().trim().to_string())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(ranks(&mut stats), vec!["1", "1", "3", "4"]);
+        stats.ranking = Ranking::Dense;
+        assert_eq!(ranks(&mut stats), vec!["1", "1", "2", "3"]);
+    }
+
+    #[test]
+    fn test_weighted_battles() {
+        let mut stats = Stats::new();
+        for (won, weight) in [(true, 1.0), (false, 0.25), (false, 0.25)].iter() {
+            let battle = ProcessedBattle {
+                results: vec![GameResult {
+                    species: String::from("Mew"),
+                    won: *won,
+                    turns: 1,
+                    rating: None,
+                }],
+                ..ProcessedBattle::default()
+            };
+            stats.add_battle_on("2024-01-01", *weight, battle);
+        }
+
+        // raw counts are unchanged, but the recent win outweighs both older losses
+        assert_eq!(
+            stats.to_csv(),
+            "species,games,wins,winrate,deviations\nMew,3,1,66.666667,0.408248"
+        );
+        assert_eq!(stats.check_invariants(), Ok(()));
+    }
+
+    #[test]
+    fn test_zero_weighted_games() {
+        let mut stats = Stats::new();
+        for (species, won, weight) in [("Mew", true, 1.0), ("Ditto", false, 0.0)].iter() {
+            let battle = ProcessedBattle {
+                results: vec![GameResult {
+                    species: String::from(*species),
+                    won: *won,
+                    turns: 1,
+                    rating: None,
+                }],
+                ..ProcessedBattle::default()
+            };
+            stats.add_battle_on("2024-01-01", *weight, battle);
+        }
+
+        // a species whose games all weigh nothing falls back to its raw counts
+        assert_eq!(
+            stats.to_csv(),
+            "species,games,wins,winrate,deviations\nMew,1,1,100.000000,1.000000\nDitto,1,0,0.000000,-1.000000"
+        );
+    }
+
+    #[test]
+    fn test_csv_escaping() {
+        let mut stats = Stats::new();
+        stats.add_game_results(vec![GameResult {
+            species: String::from("Type: \"Null\", probably"),
+            won: true,
+            turns: 1,
+            rating: None,
+        }]);
+        assert_eq!(
+            stats.to_csv(),
+            "species,games,wins,winrate,deviations\n\
+             \"Type: \"\"Null\"\", probably\",1,1,100.000000,1.000000"
+        );
+    }
+
+    #[test]
+    fn test_provisional() {
+        let mut stats = Stats::new();
+        for (species, won) in [("A", true), ("A", false), ("B", true), ("C", false)].iter() {
+            stats.add_game_results(vec![GameResult {
+                species: species.to_string(),
+                won: *won,
+                turns: 1,
+                rating: None,
+            }]);
+        }
+        stats.provisional_below = Some(2);
+
+        let csv = stats.to_csv();
+        let flags = csv
+            .lines()
+            .map(|line| line.rsplit(',').next().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(flags, vec!["provisional", "true", "false", "true"]);
+
+        // provisional species are ranked separately, after the rest
+        let table = stats.to_human_readable();
+        let (established, provisional) = table
+            .split_once("Provisional (fewer than 2 games):")
+            .unwrap();
+        assert!(established.contains("| 1    | A "));
+        assert!(!established.contains(" B "));
+        assert!(provisional.contains("| 1    | B "));
+        assert!(provisional.contains("| 2    | C "));
+    }
+
     #[bench]
     pub fn bench_process_json(b: &mut Bencher) {
         b.iter(|| Stats::process_json(1050, &SAMPLE_JSON));
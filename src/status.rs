@@ -0,0 +1,26 @@
+/// Progress messages, which `--quiet` turns off
+///
+/// Everything a run prints to stdout besides its requested output ("Analyzing...", "Skipped N
+/// duplicate battles", and so on) goes through `status!`, so scripts that only want the output
+/// files or a piped report can silence it. Warnings and errors still go to stderr.
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static QUIET: AtomicBool = AtomicBool::new(false);
+
+pub fn set_quiet(quiet: bool) {
+    QUIET.store(quiet, Ordering::Relaxed);
+}
+
+pub fn is_quiet() -> bool {
+    QUIET.load(Ordering::Relaxed)
+}
+
+/// Like `println!`, unless `--quiet` was given
+macro_rules! status {
+    ($($arg:tt)*) => {
+        if !$crate::status::is_quiet() {
+            println!($($arg)*);
+        }
+    };
+}
+pub(crate) use status;
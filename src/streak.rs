@@ -0,0 +1,255 @@
+/// Winrates split by whether the player was on a winning or losing streak (`--streak-output`)
+///
+/// Tilted (or confident) players may play a species differently, so comparing species within
+/// the same streak context controls for that. This needs each player's games in order, so it's
+/// a separate pass after the main analysis: days are read in order, each day's battles are
+/// sorted by their `timestamp` (falling back to file order), and every player's streak is
+/// tracked across the whole input.
+use crate::filter::BattleFilter;
+use crate::pass::{self, Sides};
+use crate::stats::{day_start, format_float, Output};
+use crate::table::{self, Block, Table};
+use indexmap::IndexMap;
+use itertools::Itertools;
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::io;
+use std::path::PathBuf;
+
+/// Players who won (or lost) at least this many games in a row are on a streak
+const MIN_STREAK: i32 = 3;
+
+const MONTHS: [&str; 12] = [
+    "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+];
+
+/// The streak a player was on going into a game
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum Context {
+    WinStreak = 0,
+    Neither = 1,
+    LossStreak = 2,
+}
+
+impl Context {
+    /// `streak` is the number of games in a row won (positive) or lost (negative)
+    fn of(streak: i32) -> Self {
+        if streak >= MIN_STREAK {
+            Context::WinStreak
+        } else if streak <= -MIN_STREAK {
+            Context::LossStreak
+        } else {
+            Context::Neither
+        }
+    }
+}
+
+/// Parses a battle's `timestamp`, as written by JavaScript's `Date.prototype.toString`,
+/// e.g. `Wed Nov 1 1970 00:00:01 GMT-0400 (Eastern Daylight Time)`
+fn parse_timestamp(timestamp: &str) -> Option<u64> {
+    let mut words = timestamp.split_whitespace().skip(1);
+    let month = words.next()?;
+    let month = MONTHS.iter().position(|m| *m == month)? as i64 + 1;
+    let day = words.next()?.parse().ok()?;
+    let year = words.next()?.parse().ok()?;
+    let mut time = words.next()?.split(':').map(|t| t.parse::<i64>().ok());
+    let secs = time.next()?? * 3600 + time.next()?? * 60 + time.next()??;
+
+    let offset = words.next()?.strip_prefix("GMT")?;
+    let sign = if offset.starts_with('-') { -1 } else { 1 };
+    let offset = offset.get(1..)?.parse::<i64>().ok()?;
+    let offset_secs = sign * ((offset / 100) * 3600 + (offset % 100) * 60);
+
+    u64::try_from(day_start(year, month, day)? as i64 + secs - offset_secs).ok()
+}
+
+struct StreakRow {
+    species: String,
+    /// (games, wins) in each `Context`
+    counts: [(u32, u32); 3],
+}
+
+impl StreakRow {
+    fn games(&self) -> u32 {
+        self.counts.iter().map(|(games, _)| games).sum()
+    }
+
+    fn winrate(&self, context: Context) -> Option<f64> {
+        let (games, wins) = self.counts[context as usize];
+        if games == 0 {
+            None
+        } else {
+            Some(wins as f64 / games as f64 * 100.0)
+        }
+    }
+}
+
+pub struct StreakReport {
+    /// Species:row
+    rows: IndexMap<String, StreakRow>,
+}
+
+impl StreakReport {
+    /// Reads every day's battles in order, tracking each player's streak
+    pub fn new<'a>(
+        days: impl Iterator<Item = &'a [PathBuf]>,
+        min_elo: u64,
+        filter: &BattleFilter,
+    ) -> io::Result<Self> {
+        let mut report = StreakReport {
+            rows: IndexMap::new(),
+        };
+        let mut streaks = HashMap::new();
+        let mut scan = pass::Scan::new(min_elo, filter);
+        for files in days {
+            let mut battles = scan.day(files, |json| {
+                let sides = pass::sides(json)
+                    .into_iter()
+                    .map(|(player, won, species)| (filter.player_id(&player), won, species))
+                    .collect::<Sides>();
+                (parse_timestamp(gjson::get(json, "timestamp").str()), sides)
+            })?;
+            // stable, so battles without timestamps stay in file order
+            battles.sort_by_key(|(timestamp, _)| *timestamp);
+            for (_, sides) in battles {
+                report.add_battle(&mut streaks, sides);
+            }
+        }
+
+        report.rows.sort_by(|_, a, _, b| {
+            b.games()
+                .cmp(&a.games())
+                .then_with(|| a.species.cmp(&b.species))
+        });
+        Ok(report)
+    }
+
+    /// Counts a battle under each player's streak going into it, then updates their streaks
+    fn add_battle(&mut self, streaks: &mut HashMap<String, i32>, sides: Sides) {
+        for (player, won, species) in &sides {
+            let context = Context::of(streaks.get(player).copied().unwrap_or(0));
+            for species in species {
+                let row = match self.rows.get_mut(species) {
+                    Some(row) => row,
+                    None => self.rows.entry(species.clone()).or_insert(StreakRow {
+                        species: species.clone(),
+                        counts: [(0, 0); 3],
+                    }),
+                };
+                let counts = &mut row.counts[context as usize];
+                counts.0 += 1;
+                counts.1 += *won as u32;
+            }
+        }
+
+        for (player, won, _) in sides {
+            let streak = streaks.entry(player).or_insert(0);
+            *streak = if won {
+                (*streak).max(0) + 1
+            } else {
+                (*streak).min(0) - 1
+            };
+        }
+    }
+}
+
+const CONTEXTS: [Context; 3] = [Context::WinStreak, Context::Neither, Context::LossStreak];
+
+impl Output for StreakReport {
+    fn to_csv(&mut self) -> String {
+        Itertools::intersperse(
+            self.rows.values().map(|row| {
+                let mut fields = vec![row.species.clone()];
+                for context in CONTEXTS.iter() {
+                    fields.push(row.counts[*context as usize].0.to_string());
+                    fields.push(row.winrate(*context).map_or(String::new(), format_float));
+                }
+                fields.join(",")
+            }),
+            String::from("\n"),
+        )
+        .collect()
+    }
+
+    fn to_human_readable(&mut self) -> String {
+        table::blocks_to_text(&self.to_blocks())
+    }
+
+    fn to_blocks(&mut self) -> Vec<Block> {
+        let win_title = format!("Winrate after {}+ wins", MIN_STREAK);
+        let loss_title = format!("Winrate after {}+ losses", MIN_STREAK);
+        let mut table = Table::new(&[
+            "Pokemon",
+            &win_title,
+            "Games",
+            "Winrate otherwise",
+            "Games",
+            &loss_title,
+            "Games",
+        ]);
+
+        for row in self.rows.values() {
+            let mut cells = vec![row.species.clone()];
+            for context in CONTEXTS.iter() {
+                cells.push(
+                    row.winrate(*context)
+                        .map_or(String::from("-"), |w| format!("{}%", format_float(w))),
+                );
+                cells.push(row.counts[*context as usize].0.to_string());
+            }
+            table.add_row(cells);
+        }
+
+        vec![Block::Table(table)]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_timestamp() {
+        assert_eq!(
+            parse_timestamp("Wed Nov 1 1970 00:00:01 GMT-0400 (Eastern Daylight Time)"),
+            Some(26_280_001)
+        );
+        assert_eq!(
+            parse_timestamp("Sat Jun 01 2024 02:30:00 GMT+0230"),
+            Some(1_717_200_000)
+        );
+        assert_eq!(parse_timestamp(""), None);
+        assert_eq!(parse_timestamp("yesterday at noon"), None);
+    }
+
+    #[test]
+    fn test_streaks() {
+        let mut report = StreakReport {
+            rows: IndexMap::new(),
+        };
+        let mut streaks = HashMap::new();
+        let battle = |a_won: bool, a_species: &str| {
+            vec![
+                (String::from("a"), a_won, vec![a_species.to_string()]),
+                (String::from("b"), !a_won, vec![String::from("Ditto")]),
+            ]
+        };
+
+        // a wins three, then plays Mew on a win streak; b plays Ditto on a loss streak
+        for _ in 0..3 {
+            report.add_battle(&mut streaks, battle(true, "Blissey"));
+        }
+        report.add_battle(&mut streaks, battle(false, "Mew"));
+        // the loss ends a's streak
+        report.add_battle(&mut streaks, battle(true, "Mew"));
+
+        assert_eq!(streaks["a"], 1);
+        assert_eq!(streaks["b"], -1);
+        assert_eq!(
+            report.to_csv(),
+            "Blissey,0,,3,100.000000,0,\n\
+             Ditto,0,,4,0.000000,1,100.000000\n\
+             Mew,1,0.000000,1,100.000000,0,"
+        );
+    }
+}
@@ -0,0 +1,310 @@
+/// Teammate pairs (`--pairs-output`) and a GraphViz graph of their synergy (`--synergy-output`)
+///
+/// Synergy, or lift, is how far a pair's winrate beats what their own winrates would add up
+/// to: a pair of a 55% and a 52% species is expected to win 57% of the time, so winning 60% is
+/// 3 points of lift. The pairs table lists it next to each pair's raw winrate. In the graph,
+/// nodes are species and edges join teammates with positive lift; the DOT output can be opened
+/// in Gephi or rendered with GraphViz to look for team cores. Only pairs with at least
+/// `MIN_PAIR_GAMES` games are included in either.
+use crate::filter::BattleFilter;
+use crate::pass;
+use crate::stats::{format_float, Output};
+use crate::table::{self, Block, Table};
+use indexmap::IndexMap;
+use itertools::Itertools;
+use std::collections::HashMap;
+use std::io;
+use std::path::PathBuf;
+
+/// Pairs with fewer games than this are too noisy to draw
+const MIN_PAIR_GAMES: u32 = 100;
+
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+struct Record {
+    games: u32,
+    wins: u32,
+}
+
+impl Record {
+    fn add(&mut self, won: bool) {
+        self.games += 1;
+        self.wins += won as u32;
+    }
+
+    fn winrate(&self) -> f64 {
+        self.wins as f64 / self.games as f64 * 100.0
+    }
+}
+
+/// How the pairs output is ordered
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub enum PairSort {
+    /// Most lift first
+    #[default]
+    Lift,
+    /// Highest raw winrate first
+    Winrate,
+    /// Most games first
+    Games,
+}
+
+impl std::str::FromStr for PairSort {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "lift" => Ok(PairSort::Lift),
+            "winrate" => Ok(PairSort::Winrate),
+            "games" => Ok(PairSort::Games),
+            _ => Err(format!(
+                "unknown pair sort '{}' (expected lift, winrate, or games)",
+                s
+            )),
+        }
+    }
+}
+
+/// One pair of teammates
+#[derive(Clone, Debug, PartialEq)]
+struct Pair<'a> {
+    a: &'a str,
+    b: &'a str,
+    record: Record,
+    /// The winrate the pair's own winrates add up to
+    expected: f64,
+}
+
+impl Pair<'_> {
+    fn lift(&self) -> f64 {
+        self.record.winrate() - self.expected
+    }
+}
+
+/// Escapes a string for a quoted DOT ID
+fn dot_id(s: &str) -> String {
+    format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+pub struct SynergyGraph {
+    /// Species:record, giving each species' index
+    species: IndexMap<String, Record>,
+    /// (lower species index, higher species index):record
+    pairs: HashMap<(usize, usize), Record>,
+}
+
+impl SynergyGraph {
+    pub fn new<'a>(
+        days: impl Iterator<Item = &'a [PathBuf]>,
+        min_elo: u64,
+        filter: &BattleFilter,
+    ) -> io::Result<Self> {
+        let mut graph = Self::empty();
+        let mut scan = pass::Scan::new(min_elo, filter);
+        for files in days {
+            for sides in scan.day(files, pass::sides)? {
+                for (_, won, team) in sides {
+                    graph.add_team(team, won);
+                }
+            }
+        }
+        Ok(graph)
+    }
+
+    fn empty() -> Self {
+        SynergyGraph {
+            species: IndexMap::new(),
+            pairs: HashMap::new(),
+        }
+    }
+
+    fn add_team(&mut self, team: Vec<String>, won: bool) {
+        let mut indices = team
+            .into_iter()
+            .map(|species| {
+                let entry = self.species.entry(species);
+                let idx = entry.index();
+                entry.or_default().add(won);
+                idx
+            })
+            .collect::<Vec<_>>();
+        indices.sort_unstable();
+        indices.dedup();
+        for (i, a) in indices.iter().enumerate() {
+            for b in &indices[i + 1..] {
+                self.pairs.entry((*a, *b)).or_default().add(won);
+            }
+        }
+    }
+
+    /// Pairs with enough games, in no particular order
+    fn pairs(&self) -> Vec<Pair<'_>> {
+        self.pairs
+            .iter()
+            .filter(|(_, pair)| pair.games >= MIN_PAIR_GAMES)
+            .filter_map(|((a, b), record)| {
+                let (a, a_record) = self.species.get_index(*a)?;
+                let (b, b_record) = self.species.get_index(*b)?;
+                Some(Pair {
+                    a,
+                    b,
+                    record: *record,
+                    expected: a_record.winrate() + b_record.winrate() - 50.0,
+                })
+            })
+            .collect()
+    }
+
+    /// Pairs with enough games, ordered by `sort` and then by name
+    fn sorted_pairs(&self, sort: PairSort) -> Vec<Pair<'_>> {
+        let mut pairs = self.pairs();
+        pairs.sort_by(|x, y| {
+            match sort {
+                PairSort::Lift => y.lift().total_cmp(&x.lift()),
+                PairSort::Winrate => y.record.winrate().total_cmp(&x.record.winrate()),
+                PairSort::Games => y.record.games.cmp(&x.record.games),
+            }
+            .then_with(|| (x.a, x.b).cmp(&(y.a, y.b)))
+        });
+        pairs
+    }
+
+    /// The pairs table, ordered by `sort`
+    pub fn pairs_report(&self, sort: PairSort) -> PairsReport<'_> {
+        PairsReport {
+            pairs: self.sorted_pairs(sort),
+        }
+    }
+
+    /// Pairs with enough games and positive synergy, as (a, b, synergy, games), strongest first
+    fn edges(&self) -> Vec<(&str, &str, f64, u32)> {
+        self.sorted_pairs(PairSort::Lift)
+            .into_iter()
+            .filter(|pair| pair.lift() > 0.0)
+            .map(|pair| (pair.a, pair.b, pair.lift(), pair.record.games))
+            .collect()
+    }
+
+    /// The graph in GraphViz DOT format; only species with an edge are included
+    pub fn to_dot(&self) -> String {
+        let edges = self.edges();
+        let mut lines = vec![String::from("graph synergy {")];
+        let mut nodes = edges
+            .iter()
+            .flat_map(|(a, b, _, _)| [*a, *b])
+            .collect::<Vec<_>>();
+        nodes.sort_unstable();
+        nodes.dedup();
+        for node in nodes {
+            let record = self.species[node];
+            lines.push(format!(
+                "  {} [games={}, winrate={}];",
+                dot_id(node),
+                record.games,
+                format_float(record.winrate())
+            ));
+        }
+        for (a, b, synergy, games) in edges {
+            lines.push(format!(
+                "  {} -- {} [weight={}, games={}];",
+                dot_id(a),
+                dot_id(b),
+                format_float(synergy),
+                games
+            ));
+        }
+        lines.push(String::from("}"));
+        lines.join("\n") + "\n"
+    }
+}
+
+/// Every teammate pair with enough games, with its winrate and lift
+pub struct PairsReport<'a> {
+    pairs: Vec<Pair<'a>>,
+}
+
+impl Output for PairsReport<'_> {
+    fn to_csv(&mut self) -> String {
+        std::iter::once(String::from(
+            "species,teammate,games,wins,winrate,expected,lift",
+        ))
+        .chain(self.pairs.iter().map(|pair| {
+            format!(
+                "{},{},{},{},{},{},{}",
+                pair.a,
+                pair.b,
+                pair.record.games,
+                pair.record.wins,
+                format_float(pair.record.winrate()),
+                format_float(pair.expected),
+                format_float(pair.lift())
+            )
+        }))
+        .join("\n")
+    }
+
+    fn to_human_readable(&mut self) -> String {
+        table::blocks_to_text(&self.to_blocks())
+    }
+
+    fn to_blocks(&mut self) -> Vec<Block> {
+        let mut table = Table::new(&[
+            "Pokemon",
+            "Teammate",
+            "Games",
+            "Winrate",
+            "Expected winrate",
+            "Lift",
+        ]);
+        for pair in &self.pairs {
+            table.add_row(vec![
+                pair.a.to_string(),
+                pair.b.to_string(),
+                pair.record.games.to_string(),
+                format!("{}%", format_float(pair.record.winrate())),
+                format!("{}%", format_float(pair.expected)),
+                format_float(pair.lift()),
+            ]);
+        }
+        vec![Block::Table(table)]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_synergy_graph() {
+        let team = |species: &[&str]| species.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+        let mut graph = SynergyGraph::empty();
+        for idx in 0..MIN_PAIR_GAMES {
+            // Mew and Ditto win together, and lose apart
+            graph.add_team(team(&["Mew", "Ditto"]), idx % 4 != 0);
+            graph.add_team(team(&["Mew", "Blissey"]), idx % 4 == 0);
+            graph.add_team(team(&["Ditto", "Blissey"]), idx % 4 == 0);
+        }
+        assert_eq!(
+            graph.to_dot(),
+            "graph synergy {\n  \
+               \"Ditto\" [games=200, winrate=50.000000];\n  \
+               \"Mew\" [games=200, winrate=50.000000];\n  \
+               \"Mew\" -- \"Ditto\" [weight=25.000000, games=100];\n\
+             }\n"
+        );
+        assert_eq!(dot_id("Type: \"Null\""), "\"Type: \\\"Null\\\"\"");
+
+        assert_eq!(
+            graph.pairs_report(PairSort::Lift).to_csv(),
+            "species,teammate,games,wins,winrate,expected,lift\n\
+             Mew,Ditto,100,75,75.000000,50.000000,25.000000\n\
+             Ditto,Blissey,100,25,25.000000,25.000000,0.000000\n\
+             Mew,Blissey,100,25,25.000000,25.000000,0.000000"
+        );
+        assert_eq!(
+            graph.pairs_report(PairSort::Games).to_csv().lines().nth(1),
+            Some("Ditto,Blissey,100,25,25.000000,25.000000,0.000000")
+        );
+        assert_eq!("winrate".parse(), Ok(PairSort::Winrate));
+        assert!("chemistry".parse::<PairSort>().is_err());
+    }
+}
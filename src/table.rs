@@ -0,0 +1,419 @@
+/// Plain-text table rendering for the human-readable outputs
+///
+/// Draws the same bordered layout prettytable's default format used,
+/// without going through its `Table` -> `TableSlice` transmute. Columns are sized by display
+/// width, so wide (e.g. CJK) characters stay aligned in a monospace font.
+use std::fmt;
+use std::str::FromStr;
+use unicode_width::UnicodeWidthStr;
+
+/// How integer counts are written in human-facing output
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub enum NumberFormat {
+    /// `1234567`
+    #[default]
+    Plain,
+    /// `1,234,567`
+    Grouped,
+}
+
+impl FromStr for NumberFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "plain" => Ok(NumberFormat::Plain),
+            "grouped" => Ok(NumberFormat::Grouped),
+            _ => Err(format!(
+                "unknown number format '{}' (expected plain or grouped)",
+                s
+            )),
+        }
+    }
+}
+
+impl NumberFormat {
+    pub fn format(&self, number: u32) -> String {
+        let digits = number.to_string();
+        match self {
+            NumberFormat::Plain => digits,
+            NumberFormat::Grouped => {
+                let mut grouped = String::with_capacity(digits.len() + digits.len() / 3);
+                for (idx, digit) in digits.chars().enumerate() {
+                    if idx > 0 && (digits.len() - idx).is_multiple_of(3) {
+                        grouped.push(',');
+                    }
+                    grouped.push(digit);
+                }
+                grouped
+            }
+        }
+    }
+}
+
+/// Which rows of a long table to show
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Paging {
+    pub max_rows: usize,
+    /// Starting at 1
+    pub page: usize,
+}
+
+/// Shortens `text` to at most `width` columns, ending it with an ellipsis if anything was cut
+fn truncate(text: &str, width: usize) -> String {
+    if text.width() <= width {
+        return text.to_string();
+    }
+    let mut truncated = String::new();
+    let mut used = 0;
+    for c in text.chars() {
+        let c_width = c.to_string().width();
+        if used + c_width + 1 > width {
+            break;
+        }
+        truncated.push(c);
+        used += c_width;
+    }
+    truncated.push('…');
+    truncated
+}
+
+/// A piece of a human-readable output, for formats that draw tables their own way
+pub enum Block {
+    Table(Table),
+    /// A line of text; empty lines only space out the plain-text output
+    Text(String),
+    /// Text laid out for a monospace font, such as a report whose tables are already drawn
+    Preformatted(String),
+}
+
+/// The blocks as the plain text of the human-readable outputs
+pub fn blocks_to_text(blocks: &[Block]) -> String {
+    blocks
+        .iter()
+        .map(|block| match block {
+            Block::Table(table) => table.to_string(),
+            Block::Text(line) => format!("{}\n", line),
+            Block::Preformatted(text) => text.clone(),
+        })
+        .collect()
+}
+
+/// Escapes a Markdown table cell, so a `|` in it doesn't start a new column
+fn escape_markdown_cell(cell: &str) -> String {
+    cell.replace('\\', "\\\\").replace('|', "\\|")
+}
+
+/// The blocks as GitHub-flavored Markdown
+///
+/// Each table becomes a pipe table with its titles as the header, every other non-empty line
+/// becomes its own paragraph, and preformatted text becomes a code block.
+pub fn blocks_to_markdown(blocks: &[Block]) -> String {
+    blocks
+        .iter()
+        .filter_map(|block| match block {
+            Block::Table(table) => {
+                let row = |cells: &[String]| {
+                    let cells = cells
+                        .iter()
+                        .map(|cell| escape_markdown_cell(cell))
+                        .collect::<Vec<_>>();
+                    format!("| {} |\n", cells.join(" | "))
+                };
+                let mut lines = vec![
+                    row(&table.titles),
+                    format!("|{}\n", " --- |".repeat(table.titles.len())),
+                ];
+                lines.extend(table.rows.iter().map(|cells| row(cells)));
+                Some(lines.concat())
+            }
+            Block::Text(line) if line.trim().is_empty() => None,
+            Block::Text(line) => Some(format!("{}\n", line.trim())),
+            Block::Preformatted(text) => Some(format!("```\n{}\n```\n", text.trim_end())),
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// The blocks as BBCode, for Smogon's forums
+///
+/// Each table becomes a `[TABLE]` with its titles as header cells, every other non-empty line
+/// becomes its own paragraph, and preformatted text becomes a `[CODE]` block.
+pub fn blocks_to_bbcode(blocks: &[Block]) -> String {
+    blocks
+        .iter()
+        .filter_map(|block| match block {
+            Block::Table(table) => {
+                let mut bbcode = String::from("[TABLE]\n");
+                let rows = std::iter::once(("TH", &table.titles))
+                    .chain(table.rows.iter().map(|cells| ("TD", cells)));
+                for (tag, cells) in rows {
+                    bbcode.push_str("[TR]");
+                    for cell in cells {
+                        bbcode.push_str(&format!("[{0}]{1}[/{0}]", tag, cell));
+                    }
+                    bbcode.push_str("[/TR]\n");
+                }
+                Some(bbcode + "[/TABLE]\n")
+            }
+            Block::Text(line) if line.trim().is_empty() => None,
+            Block::Text(line) => Some(format!("{}\n", line.trim())),
+            Block::Preformatted(text) => Some(format!("[CODE]{}[/CODE]\n", text.trim_end())),
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+pub struct Table {
+    titles: Vec<String>,
+    rows: Vec<Vec<String>>,
+}
+
+impl Table {
+    pub fn new(titles: &[&str]) -> Self {
+        Self {
+            titles: titles.iter().map(|t| t.to_string()).collect(),
+            rows: vec![],
+        }
+    }
+
+    pub fn add_row(&mut self, row: Vec<String>) {
+        self.rows.push(row);
+    }
+
+    pub fn titles(&self) -> &[String] {
+        &self.titles
+    }
+
+    pub fn rows(&self) -> &[Vec<String>] {
+        &self.rows
+    }
+
+    /// Shortens every cell in the given column to at most `width` columns
+    pub fn truncate_column(&mut self, column: usize, width: usize) {
+        for row in &mut self.rows {
+            if let Some(cell) = row.get_mut(column) {
+                *cell = truncate(cell, width);
+            }
+        }
+    }
+
+    /// Keeps only the rows on the given page, returning a line saying which rows those are
+    pub fn paginate(&mut self, paging: Paging) -> String {
+        let total = self.rows.len();
+        let pages = total.div_ceil(paging.max_rows).max(1);
+        let start = (paging.page - 1).saturating_mul(paging.max_rows).min(total);
+        let end = start.saturating_add(paging.max_rows).min(total);
+        self.rows = self.rows.drain(start..end).collect();
+        if start == end {
+            format!("Page {} of {}, no rows of {}", paging.page, pages, total)
+        } else {
+            format!(
+                "Page {} of {}, rows {}-{} of {}",
+                paging.page,
+                pages,
+                start + 1,
+                end,
+                total
+            )
+        }
+    }
+
+    /// The table without borders: left-aligned columns separated by a space, one line per row,
+    /// with no trailing newline
+    pub fn to_compact(&self) -> String {
+        let widths = self.column_widths();
+        std::iter::once(&self.titles)
+            .chain(&self.rows)
+            .map(|row| {
+                widths
+                    .iter()
+                    .enumerate()
+                    .map(|(idx, width)| {
+                        let cell = row.get(idx).map(|c| c.as_str()).unwrap_or("");
+                        format!("{}{}", cell, " ".repeat(width - cell.width()))
+                    })
+                    .collect::<Vec<_>>()
+                    .join(" ")
+                    .trim_end()
+                    .to_string()
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    fn column_widths(&self) -> Vec<usize> {
+        let mut widths: Vec<usize> = self.titles.iter().map(|t| t.width()).collect();
+        for row in &self.rows {
+            for (idx, cell) in row.iter().enumerate() {
+                let width = cell.width();
+                match widths.get_mut(idx) {
+                    Some(w) if *w < width => *w = width,
+                    Some(_) => {}
+                    None => widths.push(width),
+                }
+            }
+        }
+        widths
+    }
+
+    fn fmt_separator(f: &mut fmt::Formatter, widths: &[usize]) -> fmt::Result {
+        f.write_str("+")?;
+        for width in widths {
+            write!(f, "{}+", "-".repeat(width + 2))?;
+        }
+        f.write_str("\n")
+    }
+
+    fn fmt_row(f: &mut fmt::Formatter, widths: &[usize], row: &[String]) -> fmt::Result {
+        f.write_str("|")?;
+        for (idx, width) in widths.iter().enumerate() {
+            let cell = row.get(idx).map(|c| c.as_str()).unwrap_or("");
+            let padding = width - cell.width();
+            write!(f, " {}{} |", cell, " ".repeat(padding))?;
+        }
+        f.write_str("\n")
+    }
+}
+
+impl fmt::Display for Table {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let widths = self.column_widths();
+
+        Table::fmt_separator(f, &widths)?;
+        Table::fmt_row(f, &widths, &self.titles)?;
+        Table::fmt_separator(f, &widths)?;
+        for row in &self.rows {
+            Table::fmt_row(f, &widths, row)?;
+            Table::fmt_separator(f, &widths)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_number_format() {
+        assert_eq!(NumberFormat::Plain.format(1234567), "1234567");
+        assert_eq!(NumberFormat::Grouped.format(1234567), "1,234,567");
+        assert_eq!(NumberFormat::Grouped.format(123456), "123,456");
+        assert_eq!(NumberFormat::Grouped.format(999), "999");
+        assert_eq!(NumberFormat::Grouped.format(0), "0");
+    }
+
+    #[test]
+    fn test_paginate() {
+        let mut table = Table::new(&["Pokemon"]);
+        for species in ["Mew", "Ditto", "Blissey", "Pikachu", "Kingambit"] {
+            table.add_row(vec![String::from(species)]);
+        }
+        table.truncate_column(0, 5);
+        let paging = Paging {
+            max_rows: 2,
+            page: 2,
+        };
+        assert_eq!(table.paginate(paging), "Page 2 of 3, rows 3-4 of 5");
+        assert_eq!(
+            table.to_string(),
+            "+---------+\n\
+             | Pokemon |\n\
+             +---------+\n\
+             | Blis…   |\n\
+             +---------+\n\
+             | Pika…   |\n\
+             +---------+\n"
+        );
+
+        let paging = Paging {
+            max_rows: 2,
+            page: 4,
+        };
+        assert_eq!(table.paginate(paging), "Page 4 of 1, no rows of 2");
+        assert_eq!(truncate("ピカチュウ", 6), "ピカ…");
+        assert_eq!(truncate("Mew", 3), "Mew");
+    }
+
+    #[test]
+    fn test_blocks_to_markdown() {
+        let mut table = Table::new(&["Pokemon", "Games"]);
+        table.add_row(vec![String::from("Mew"), String::from("1")]);
+        table.add_row(vec![String::from("Flabébé"), String::from("22")]);
+        table.add_row(vec![
+            String::from("Mew (since 2024-03-01: a | b)"),
+            String::from("3"),
+        ]);
+        let blocks = vec![
+            Block::Text(String::from("Version: 1")),
+            Block::Text(String::from("Sampled: 50%")),
+            Block::Table(table),
+            Block::Text(String::new()),
+            Block::Text(String::from("Battles: 2")),
+        ];
+        assert_eq!(
+            blocks_to_markdown(&blocks),
+            "Version: 1\n\n\
+             Sampled: 50%\n\n\
+             | Pokemon | Games |\n\
+             | --- | --- |\n\
+             | Mew | 1 |\n\
+             | Flabébé | 22 |\n\
+             | Mew (since 2024-03-01: a \\| b) | 3 |\n\n\
+             Battles: 2\n"
+        );
+        assert_eq!(blocks_to_text(&blocks[3..]), "\nBattles: 2\n");
+        assert_eq!(
+            blocks_to_markdown(&[Block::Preformatted(String::from("+---+\n| A |\n"))]),
+            "```\n+---+\n| A |\n```\n"
+        );
+    }
+
+    #[test]
+    fn test_blocks_to_bbcode() {
+        let mut table = Table::new(&["Pokemon", "Games"]);
+        table.add_row(vec![String::from("Mew"), String::from("1")]);
+        table.add_row(vec![String::from("Mew (a | b)"), String::from("2")]);
+        let blocks = vec![
+            Block::Text(String::from("Sampled: 50%")),
+            Block::Table(table),
+            Block::Text(String::from("Battles: 1")),
+        ];
+        assert_eq!(
+            blocks_to_bbcode(&blocks),
+            "Sampled: 50%\n\n\
+             [TABLE]\n\
+             [TR][TH]Pokemon[/TH][TH]Games[/TH][/TR]\n\
+             [TR][TD]Mew[/TD][TD]1[/TD][/TR]\n\
+             [TR][TD]Mew (a | b)[/TD][TD]2[/TD][/TR]\n\
+             [/TABLE]\n\n\
+             Battles: 1\n"
+        );
+    }
+
+    #[test]
+    fn test_to_compact() {
+        let mut table = Table::new(&["#", "Pokemon"]);
+        table.add_row(vec![String::from("1"), String::from("Mew")]);
+        table.add_row(vec![String::from("10"), String::from("Rotom-Fan")]);
+        assert_eq!(table.to_compact(), "#  Pokemon\n1  Mew\n10 Rotom-Fan");
+    }
+
+    #[test]
+    fn test_wide_characters() {
+        let mut table = Table::new(&["Pokemon", "Games"]);
+        table.add_row(vec![String::from("ピカチュウ"), String::from("1")]);
+        table.add_row(vec![String::from("Flabébé"), String::from("2")]);
+        assert_eq!(
+            table.to_string(),
+            "+------------+-------+\n\
+             | Pokemon    | Games |\n\
+             +------------+-------+\n\
+             | ピカチュウ | 1     |\n\
+             +------------+-------+\n\
+             | Flabébé    | 2     |\n\
+             +------------+-------+\n"
+        );
+    }
+}
@@ -0,0 +1,134 @@
+/// Compact game and win counts for large breakdowns
+///
+/// Breakdowns like the spread report and the matchup matrix can have millions of rows, and
+/// keying each by its own `String`s costs several times more memory than the counts. Instead,
+/// names are interned once as `u32` IDs, rows are keyed by tuples of IDs, and the counts live
+/// in plain columns indexed by row number, ready to be written out column by column.
+use indexmap::IndexSet;
+use std::hash::Hash;
+
+/// Integer IDs for repeated names, in order of first use
+#[derive(Debug, Default)]
+pub struct Interner {
+    names: IndexSet<String>,
+}
+
+impl Interner {
+    pub fn id(&mut self, name: &str) -> u32 {
+        match self.names.get_index_of(name) {
+            Some(id) => id as u32,
+            None => self.names.insert_full(name.to_string()).0 as u32,
+        }
+    }
+
+    /// The ID of a name that's already been interned
+    pub fn get(&self, name: &str) -> Option<u32> {
+        self.names.get_index_of(name).map(|id| id as u32)
+    }
+
+    pub fn name(&self, id: u32) -> &str {
+        &self.names[id as usize]
+    }
+}
+
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct Counts {
+    pub games: u32,
+    pub wins: u32,
+}
+
+impl Counts {
+    pub fn winrate(&self) -> f64 {
+        self.wins as f64 / self.games as f64 * 100.0
+    }
+
+    pub fn deviations(&self) -> f64 {
+        (self.winrate() - 50.0) * (self.games as f64).sqrt() / 50.0
+    }
+}
+
+/// Games and wins for each key, stored as columns
+#[derive(Debug)]
+pub struct Tally<K> {
+    /// Row number:key
+    keys: IndexSet<K>,
+    games: Vec<u32>,
+    wins: Vec<u32>,
+}
+
+impl<K> Default for Tally<K> {
+    fn default() -> Self {
+        Tally {
+            keys: IndexSet::new(),
+            games: vec![],
+            wins: vec![],
+        }
+    }
+}
+
+impl<K: Copy + Eq + Hash> Tally<K> {
+    pub fn add(&mut self, key: K, won: bool) {
+        let (row, new) = self.keys.insert_full(key);
+        if new {
+            self.games.push(0);
+            self.wins.push(0);
+        }
+        self.games[row] += 1;
+        self.wins[row] += won as u32;
+    }
+
+    pub fn get(&self, key: &K) -> Option<Counts> {
+        let row = self.keys.get_index_of(key)?;
+        Some(Counts {
+            games: self.games[row],
+            wins: self.wins[row],
+        })
+    }
+
+    /// Every key and its counts, in order of first use
+    pub fn rows(&self) -> impl Iterator<Item = (K, Counts)> + '_ {
+        self.keys
+            .iter()
+            .zip(self.games.iter().zip(&self.wins))
+            .map(|(key, (games, wins))| {
+                (
+                    *key,
+                    Counts {
+                        games: *games,
+                        wins: *wins,
+                    },
+                )
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tally() {
+        let mut names = Interner::default();
+        let (mew, ditto) = (names.id("Mew"), names.id("Ditto"));
+        assert_eq!((mew, ditto, names.id("Mew")), (0, 1, 0));
+        assert_eq!(names.name(ditto), "Ditto");
+        assert_eq!(
+            (names.get("Ditto"), names.get("Blissey")),
+            (Some(ditto), None)
+        );
+
+        let mut tally = Tally::default();
+        tally.add((mew, ditto), true);
+        tally.add((ditto, mew), false);
+        tally.add((mew, ditto), false);
+        assert_eq!(tally.get(&(mew, ditto)), Some(Counts { games: 2, wins: 1 }));
+        assert_eq!(tally.get(&(mew, mew)), None);
+        assert_eq!(
+            tally.rows().collect::<Vec<_>>(),
+            vec![
+                ((mew, ditto), Counts { games: 2, wins: 1 }),
+                ((ditto, mew), Counts { games: 1, wins: 0 })
+            ]
+        );
+    }
+}
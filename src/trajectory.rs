@@ -0,0 +1,115 @@
+/// Each player's rating over time (`--player-ratings-output`)
+///
+/// Ratings are grouped by the day directory each battle was read from, giving one point per
+/// player per day they played rated games: the mean of the ratings shown in those battles.
+use crate::stats::{format_float, Output};
+use crate::table::Table;
+use indexmap::IndexMap;
+use itertools::Itertools;
+use std::collections::BTreeMap;
+
+/// Rated games and the sum of their ratings
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub(crate) struct DayRatings {
+    pub(crate) games: u32,
+    pub(crate) rating_sum: f64,
+}
+
+#[derive(Debug, Default)]
+pub struct PlayerRatings {
+    /// Player ID:day:ratings on that day
+    pub(crate) players: IndexMap<String, BTreeMap<String, DayRatings>>,
+}
+
+impl PlayerRatings {
+    pub fn add(&mut self, player: &str, day: &str, rating: f64) {
+        let days = match self.players.get_mut(player) {
+            Some(days) => days,
+            None => self.players.entry(player.to_string()).or_default(),
+        };
+        let ratings = match days.get_mut(day) {
+            Some(ratings) => ratings,
+            None => days.entry(day.to_string()).or_default(),
+        };
+        ratings.games += 1;
+        ratings.rating_sum += rating;
+    }
+
+    pub fn merge(&mut self, other: PlayerRatings) {
+        for (player, days) in other.players {
+            let own_days = self.players.entry(player).or_default();
+            for (day, ratings) in days {
+                let own = own_days.entry(day).or_default();
+                own.games += ratings.games;
+                own.rating_sum += ratings.rating_sum;
+            }
+        }
+    }
+
+    /// (player, day, games, mean rating), by player and then day
+    fn rows(&self) -> Vec<(&str, &str, u32, f64)> {
+        self.players
+            .iter()
+            .sorted_by(|a, b| a.0.cmp(b.0))
+            .flat_map(|(player, days)| {
+                days.iter().map(move |(day, r)| {
+                    (
+                        player.as_str(),
+                        day.as_str(),
+                        r.games,
+                        r.rating_sum / r.games as f64,
+                    )
+                })
+            })
+            .collect()
+    }
+}
+
+impl Output for PlayerRatings {
+    fn to_csv(&mut self) -> String {
+        Itertools::intersperse(
+            self.rows().into_iter().map(|(player, day, games, mean)| {
+                format!("{},{},{},{}", player, day, games, format_float(mean))
+            }),
+            String::from("\n"),
+        )
+        .collect()
+    }
+
+    fn to_human_readable(&mut self) -> String {
+        let mut table = Table::new(&["Player", "Day", "Rated games", "Mean rating"]);
+        for (player, day, games, mean) in self.rows() {
+            table.add_row(vec![
+                player.to_string(),
+                day.to_string(),
+                games.to_string(),
+                format_float(mean),
+            ]);
+        }
+        table.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_player_ratings() {
+        let mut ratings = PlayerRatings::default();
+        ratings.add("zarel", "2024-01-02", 1500.0);
+        ratings.add("annika", "2024-01-02", 1300.0);
+        ratings.add("annika", "2024-01-01", 1200.0);
+
+        let mut other = PlayerRatings::default();
+        other.add("annika", "2024-01-02", 1400.0);
+        ratings.merge(other);
+
+        assert_eq!(
+            ratings.to_csv(),
+            "annika,2024-01-01,1,1200.000000\n\
+             annika,2024-01-02,2,1350.000000\n\
+             zarel,2024-01-02,1,1500.000000"
+        );
+    }
+}
@@ -0,0 +1,146 @@
+/// Structural checks run on every battle before it's parsed
+///
+/// gjson assumes its input is valid JSON, so malformed battles (truncated files, hand-edited
+/// logs, nicknames that break naive escaping) have to be caught up front; after `check_battle`
+/// passes, every path the analyses read is safe to query.
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+/// Why a battle couldn't be analyzed
+#[derive(Clone, Debug, PartialEq)]
+pub enum BattleError {
+    /// The file is empty, or only whitespace
+    Empty,
+    /// The file doesn't end with `}`, e.g. because it's still being written
+    Truncated,
+    /// The file couldn't be read, e.g. because of its permissions
+    Unreadable(String),
+    /// The file isn't UTF-8
+    NotUtf8,
+    /// Not valid JSON, e.g. a truncated file
+    InvalidJson,
+    /// Valid JSON, but not an object
+    NotAnObject,
+    /// No `p1team`, `p2team`, ... arrays
+    NoTeams,
+    /// A team that isn't an array of objects with string species
+    InvalidTeam(&'static str),
+    /// A top-level field with the wrong type
+    InvalidField(&'static str),
+}
+
+impl fmt::Display for BattleError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BattleError::Empty => f.write_str("empty file"),
+            BattleError::Truncated => f.write_str("truncated file"),
+            BattleError::Unreadable(e) => write!(f, "couldn't be read ({})", e),
+            BattleError::NotUtf8 => f.write_str("not valid UTF-8"),
+            BattleError::InvalidJson => f.write_str("not valid JSON"),
+            BattleError::NotAnObject => f.write_str("not a JSON object"),
+            BattleError::NoTeams => f.write_str("no teams"),
+            BattleError::InvalidTeam(side) => write!(f, "{}team isn't a list of Pokémon", side),
+            BattleError::InvalidField(field) => write!(f, "'{}' has the wrong type", field),
+        }
+    }
+}
+
+/// Top-level fields that have to be strings (or absent)
+const STRING_FIELDS: [&str; 6] = ["winner", "p1", "p2", "p3", "p4", "format"];
+
+/// Reads a battle file, catching files that are empty or cut short before they're parsed
+pub fn read_battle(path: &Path) -> Result<String, BattleError> {
+    let bytes = fs::read(path).map_err(|e| BattleError::Unreadable(e.to_string()))?;
+    check_complete(&bytes)?;
+    String::from_utf8(bytes).map_err(|_| BattleError::NotUtf8)
+}
+
+/// Catches files that are empty or cut short (as log syncs sometimes leave them) without
+/// parsing them
+pub fn check_complete(bytes: &[u8]) -> Result<(), BattleError> {
+    match bytes.iter().rev().find(|b| !b.is_ascii_whitespace()) {
+        None => Err(BattleError::Empty),
+        Some(b'}') => Ok(()),
+        Some(_) => Err(BattleError::Truncated),
+    }
+}
+
+pub fn check_battle(json: &str) -> Result<(), BattleError> {
+    if !gjson::valid(json) {
+        return Err(BattleError::InvalidJson);
+    }
+    if gjson::parse(json).kind() != gjson::Kind::Object {
+        return Err(BattleError::NotAnObject);
+    }
+
+    for field in STRING_FIELDS.iter() {
+        let value = gjson::get(json, field);
+        if value.exists() && value.kind() != gjson::Kind::String {
+            return Err(BattleError::InvalidField(field));
+        }
+    }
+    for field in ["turns", "log"].iter() {
+        let value = gjson::get(json, field);
+        let expected = if *field == "log" {
+            gjson::Kind::Array
+        } else {
+            gjson::Kind::Number
+        };
+        if value.exists() && value.kind() != expected {
+            return Err(BattleError::InvalidField(field));
+        }
+    }
+
+    let mut has_team = false;
+    for side in ["p1", "p2", "p3", "p4"].iter() {
+        let team_path = format!("{}team", side);
+        let team = gjson::get(json, &team_path);
+        if !team.exists() {
+            continue;
+        }
+        has_team = true;
+        let valid_team = team.kind() == gjson::Kind::Array
+            && team.array().iter().all(|member| {
+                member.kind() == gjson::Kind::Object
+                    && member.get("species").kind() == gjson::Kind::String
+            });
+        if !valid_team {
+            return Err(BattleError::InvalidTeam(side));
+        }
+    }
+    if !has_team {
+        return Err(BattleError::NoTeams);
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_battle() {
+        let team = r#""p1team":[{"species":"Mew"}],"p2team":[{"species":"Ditto"}]"#;
+        assert_eq!(check_battle(&format!("{{{}}}", team)), Ok(()));
+        assert_eq!(
+            check_battle(&format!(r#"{{"winner":3,{}}}"#, team)),
+            Err(BattleError::InvalidField("winner"))
+        );
+        assert_eq!(check_battle("[1, 2]"), Err(BattleError::NotAnObject));
+        assert_eq!(check_battle(r#"{"p1":"A"}"#), Err(BattleError::NoTeams));
+        assert_eq!(
+            check_battle(r#"{"p1team":[{"species":"Mew"}],"p2team":{"species":"Ditto"}}"#),
+            Err(BattleError::InvalidTeam("p2"))
+        );
+    }
+
+    #[test]
+    fn test_check_complete() {
+        assert_eq!(check_complete(b"{\"p1\":\"A\"}\n"), Ok(()));
+        assert_eq!(check_complete(b""), Err(BattleError::Empty));
+        assert_eq!(check_complete(b" \n"), Err(BattleError::Empty));
+        assert_eq!(check_complete(b"{\"p1\":\"A"), Err(BattleError::Truncated));
+    }
+}
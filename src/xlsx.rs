@@ -0,0 +1,231 @@
+/// Excel output (`--xlsx-output`)
+///
+/// The rankings as a spreadsheet for reviewers who'd rather not open CSV: a bold header row
+/// that stays put while scrolling, and winrates formatted as percentages. An .xlsx file is a zip
+/// archive of XML parts; this writes the handful a one-sheet workbook needs, stored without
+/// compression, with strings inline rather than in a shared string table.
+use crate::hash::crc32;
+use crate::html::escape_html;
+use crate::stats::Stats;
+
+const CONTENT_TYPES: &str = r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>
+<Types xmlns="http://schemas.openxmlformats.org/package/2006/content-types"><Default Extension="rels" ContentType="application/vnd.openxmlformats-package.relationships+xml"/><Default Extension="xml" ContentType="application/xml"/><Override PartName="/xl/workbook.xml" ContentType="application/vnd.openxmlformats-officedocument.spreadsheetml.sheet.main+xml"/><Override PartName="/xl/worksheets/sheet1.xml" ContentType="application/vnd.openxmlformats-officedocument.spreadsheetml.worksheet+xml"/><Override PartName="/xl/styles.xml" ContentType="application/vnd.openxmlformats-officedocument.spreadsheetml.styles+xml"/></Types>"#;
+
+const ROOT_RELS: &str = r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>
+<Relationships xmlns="http://schemas.openxmlformats.org/package/2006/relationships"><Relationship Id="rId1" Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/officeDocument" Target="xl/workbook.xml"/></Relationships>"#;
+
+const WORKBOOK: &str = r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>
+<workbook xmlns="http://schemas.openxmlformats.org/spreadsheetml/2006/main" xmlns:r="http://schemas.openxmlformats.org/officeDocument/2006/relationships"><sheets><sheet name="Winrates" sheetId="1" r:id="rId1"/></sheets></workbook>"#;
+
+const WORKBOOK_RELS: &str = r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>
+<Relationships xmlns="http://schemas.openxmlformats.org/package/2006/relationships"><Relationship Id="rId1" Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/worksheet" Target="worksheets/sheet1.xml"/><Relationship Id="rId2" Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/styles" Target="styles.xml"/></Relationships>"#;
+
+/// Cell formats, referred to by index: 0 is the default, 1 is bold (for the header), 2 is a
+/// percentage with two decimal places (built-in format 10), and 3 is a number with two (2)
+const STYLES: &str = r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>
+<styleSheet xmlns="http://schemas.openxmlformats.org/spreadsheetml/2006/main"><fonts count="2"><font><sz val="11"/><name val="Calibri"/></font><font><b/><sz val="11"/><name val="Calibri"/></font></fonts><fills count="2"><fill><patternFill patternType="none"/></fill><fill><patternFill patternType="gray125"/></fill></fills><borders count="1"><border><left/><right/><top/><bottom/><diagonal/></border></borders><cellStyleXfs count="1"><xf numFmtId="0" fontId="0" fillId="0" borderId="0"/></cellStyleXfs><cellXfs count="4"><xf numFmtId="0" fontId="0" fillId="0" borderId="0" xfId="0"/><xf numFmtId="0" fontId="1" fillId="0" borderId="0" xfId="0" applyFont="1"/><xf numFmtId="10" fontId="0" fillId="0" borderId="0" xfId="0" applyNumberFormat="1"/><xf numFmtId="2" fontId="0" fillId="0" borderId="0" xfId="0" applyNumberFormat="1"/></cellXfs></styleSheet>"#;
+
+const BOLD: u8 = 1;
+const PERCENT: u8 = 2;
+const DECIMAL: u8 = 3;
+
+/// 1980-01-01 00:00 in MS-DOS format, the earliest a zip entry can be dated, so that the same
+/// stats always give the same file
+const DOS_TIME: u16 = 0;
+const DOS_DATE: u16 = (1 << 5) | 1;
+
+/// A zip archive whose entries are stored uncompressed
+#[derive(Default)]
+struct Zip {
+    bytes: Vec<u8>,
+    central_directory: Vec<u8>,
+    entries: u16,
+}
+
+impl Zip {
+    fn add(&mut self, name: &str, data: &[u8]) {
+        let offset = self.bytes.len() as u32;
+        // version needed, flags, method (stored), time, date, CRC-32, compressed and
+        // uncompressed sizes, name length, and extra field length
+        let mut fields = vec![];
+        for half in [20, 0, 0, DOS_TIME, DOS_DATE] {
+            fields.extend_from_slice(&u16::to_le_bytes(half));
+        }
+        for word in [crc32(data), data.len() as u32, data.len() as u32] {
+            fields.extend_from_slice(&word.to_le_bytes());
+        }
+        for half in [name.len() as u16, 0] {
+            fields.extend_from_slice(&half.to_le_bytes());
+        }
+
+        self.bytes.extend_from_slice(&0x0403_4b50u32.to_le_bytes());
+        self.bytes.extend_from_slice(&fields);
+        self.bytes.extend_from_slice(name.as_bytes());
+        self.bytes.extend_from_slice(data);
+
+        // version made by, then the local header's fields, then comment length, disk number,
+        // internal and external attributes, and where the local header is
+        self.central_directory
+            .extend_from_slice(&0x0201_4b50u32.to_le_bytes());
+        self.central_directory
+            .extend_from_slice(&20u16.to_le_bytes());
+        self.central_directory.extend_from_slice(&fields);
+        self.central_directory.extend_from_slice(&[0; 10]);
+        self.central_directory
+            .extend_from_slice(&offset.to_le_bytes());
+        self.central_directory.extend_from_slice(name.as_bytes());
+        self.entries += 1;
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        let offset = self.bytes.len() as u32;
+        self.bytes.extend_from_slice(&self.central_directory);
+        self.bytes.extend_from_slice(&0x0605_4b50u32.to_le_bytes());
+        for half in [0, 0, self.entries, self.entries] {
+            self.bytes.extend_from_slice(&u16::to_le_bytes(half));
+        }
+        self.bytes
+            .extend_from_slice(&(self.central_directory.len() as u32).to_le_bytes());
+        self.bytes.extend_from_slice(&offset.to_le_bytes());
+        self.bytes.extend_from_slice(&0u16.to_le_bytes());
+        self.bytes
+    }
+}
+
+enum Cell {
+    Text(String),
+    /// A number and its cell format
+    Number(f64, u8),
+}
+
+impl Cell {
+    /// The cell's XML, at `column` (0-based, at most 25) of `row` (1-based); `style` is the
+    /// cell format for text
+    fn to_xml(&self, column: usize, row: usize, style: u8) -> String {
+        let reference = format!("{}{}", (b'A' + column as u8) as char, row);
+        match self {
+            Cell::Text(text) => format!(
+                "<c r=\"{}\" s=\"{}\" t=\"inlineStr\"><is><t>{}</t></is></c>",
+                reference,
+                style,
+                escape_html(text)
+            ),
+            Cell::Number(n, format) => {
+                format!("<c r=\"{}\" s=\"{}\"><v>{}</v></c>", reference, format, n)
+            }
+        }
+    }
+}
+
+impl Stats {
+    fn sheet_xml(&mut self) -> String {
+        self.sort();
+
+        let mut header = vec!["Rank", "Pokémon", "Games", "Wins", "Winrate", "Deviations"];
+        if self.provisional_below.is_some() {
+            header.push("Provisional");
+        }
+        let mut rows = vec![header
+            .iter()
+            .map(|name| Cell::Text(name.to_string()))
+            .collect::<Vec<_>>()];
+        let pokemon = self.pokemon.iter().collect::<Vec<_>>();
+        for ((species, stats), rank) in pokemon.iter().zip(self.ranks(&pokemon)) {
+            let fstats = stats.final_stats();
+            let mut row = vec![
+                Cell::Number(rank as f64, 0),
+                Cell::Text(self.display_name(species).to_string()),
+                Cell::Number(stats.games as f64, 0),
+                Cell::Number(stats.wins as f64, 0),
+                Cell::Number(fstats.winrate / 100.0, PERCENT),
+                Cell::Number(fstats.deviations, DECIMAL),
+            ];
+            if self.provisional_below.is_some() {
+                let provisional = if self.is_provisional(stats) {
+                    "yes"
+                } else {
+                    "no"
+                };
+                row.push(Cell::Text(provisional.to_string()));
+            }
+            rows.push(row);
+        }
+
+        let sheet_data = rows
+            .iter()
+            .enumerate()
+            .map(|(idx, cells)| {
+                let style = if idx == 0 { BOLD } else { 0 };
+                let cells = cells
+                    .iter()
+                    .enumerate()
+                    .map(|(column, cell)| cell.to_xml(column, idx + 1, style))
+                    .collect::<String>();
+                format!("<row r=\"{}\">{}</row>", idx + 1, cells)
+            })
+            .collect::<String>();
+        format!(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\" standalone=\"yes\"?>\n\
+             <worksheet xmlns=\"http://schemas.openxmlformats.org/spreadsheetml/2006/main\">\
+             <sheetViews><sheetView workbookViewId=\"0\">\
+             <pane ySplit=\"1\" topLeftCell=\"A2\" activePane=\"bottomLeft\" state=\"frozen\"/>\
+             </sheetView></sheetViews>\
+             <cols><col min=\"2\" max=\"2\" width=\"24\" customWidth=\"1\"/></cols>\
+             <sheetData>{}</sheetData></worksheet>",
+            sheet_data
+        )
+    }
+
+    pub fn to_xlsx(&mut self) -> Vec<u8> {
+        let mut zip = Zip::default();
+        zip.add("[Content_Types].xml", CONTENT_TYPES.as_bytes());
+        zip.add("_rels/.rels", ROOT_RELS.as_bytes());
+        zip.add("xl/workbook.xml", WORKBOOK.as_bytes());
+        zip.add("xl/_rels/workbook.xml.rels", WORKBOOK_RELS.as_bytes());
+        zip.add("xl/styles.xml", STYLES.as_bytes());
+        zip.add("xl/worksheets/sheet1.xml", self.sheet_xml().as_bytes());
+        zip.finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::stats::{GameResult, Stats};
+
+    #[test]
+    fn test_xlsx() {
+        let result = |species: &str, won| GameResult {
+            species: species.to_string(),
+            won,
+            turns: 10,
+            rating: None,
+        };
+        let mut stats = Stats::new();
+        stats.add_game_results(vec![result("Mew", true), result("Ditto", false)]);
+        stats.add_game_results(vec![result("Mew", false), result("Ditto", false)]);
+
+        let sheet = stats.sheet_xml();
+        assert!(sheet.contains("state=\"frozen\""));
+        assert!(sheet.contains(
+            "<row r=\"2\"><c r=\"A2\" s=\"0\"><v>1</v></c>\
+             <c r=\"B2\" s=\"0\" t=\"inlineStr\"><is><t>Mew</t></is></c>\
+             <c r=\"C2\" s=\"0\"><v>2</v></c><c r=\"D2\" s=\"0\"><v>1</v></c>\
+             <c r=\"E2\" s=\"2\"><v>0.5</v></c><c r=\"F2\" s=\"3\"><v>0</v></c></row>"
+        ));
+        assert!(
+            sheet.contains("<c r=\"F1\" s=\"1\" t=\"inlineStr\"><is><t>Deviations</t></is></c>")
+        );
+
+        let file = stats.to_xlsx();
+        assert!(file.starts_with(b"PK\x03\x04"));
+        // the end of central directory record lists all six parts
+        let end = &file[file.len() - 22..];
+        assert!(end.starts_with(b"PK\x05\x06"));
+        assert_eq!(&end[8..12], &[6, 0, 6, 0]);
+        // and the central directory fills the space between the entries and that record
+        let word = |bytes: &[u8]| u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+        let (size, offset) = (word(&end[12..16]) as usize, word(&end[16..20]) as usize);
+        assert!(file[offset..].starts_with(b"PK\x01\x02"));
+        assert_eq!(offset + size, file.len() - end.len());
+    }
+}